@@ -0,0 +1,192 @@
+use super::errors::FxError;
+use super::functions::{shorten_str_including_wide_char, to_proper_size};
+use super::magic_packed;
+
+use crossterm::style::Stylize;
+
+use std::io::Read;
+use std::path::Path;
+
+/// The first bytes read to estimate the entropy.
+const SAMPLE_SIZE: u64 = 64 * 1024;
+/// The first bytes shown in the hex dump.
+const HEX_DUMP_SIZE: usize = 256;
+/// Above this (bits per byte), the data is likely compressed or encrypted.
+const HIGH_ENTROPY: f64 = 7.5;
+
+/// Signatures of the binaries not previewed otherwise: the offset, the bytes and the name.
+const SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (0, b"\x7fELF", "ELF executable"),
+    (0, b"MZ", "PE/DOS executable"),
+    (0, &[0xFE, 0xED, 0xFA, 0xCE], "Mach-O executable"),
+    (0, &[0xFE, 0xED, 0xFA, 0xCF], "Mach-O executable"),
+    (0, &[0xCE, 0xFA, 0xED, 0xFE], "Mach-O executable"),
+    (0, &[0xCF, 0xFA, 0xED, 0xFE], "Mach-O executable"),
+    (
+        0,
+        &[0xCA, 0xFE, 0xBA, 0xBE],
+        "Java class / Mach-O universal binary",
+    ),
+    (0, b"\0asm", "WebAssembly"),
+    (0, b"%PDF", "PDF document"),
+    (0, b"!<arch>\n", "ar archive"),
+    (0, &[0xED, 0xAB, 0xEE, 0xDB], "RPM package"),
+    (0, b"ID3", "MP3 audio"),
+    (0, b"OggS", "Ogg media"),
+    (0, b"fLaC", "FLAC audio"),
+    (8, b"WAVE", "WAV audio"),
+    (8, b"AVI ", "AVI video"),
+    (4, b"ftyp", "MP4/QuickTime media"),
+    (0, &[0x1A, 0x45, 0xDF, 0xA3], "Matroska/WebM video"),
+    (0, b"wOFF", "WOFF font"),
+    (0, b"wOF2", "WOFF2 font"),
+    (0, b"OTTO", "OpenType font"),
+    (0, &[0x00, 0x01, 0x00, 0x00, 0x00], "TrueType font"),
+];
+
+/// Summarize the binary file for the preview: the detected type, the size,
+/// the entropy of the first bytes and the hex dump of them.
+pub fn summarize_binary(p: &Path, width: u16) -> Result<Vec<String>, FxError> {
+    let mut sample = Vec::new();
+    std::fs::File::open(p)?
+        .take(SAMPLE_SIZE)
+        .read_to_end(&mut sample)?;
+    let size = std::fs::metadata(p)?.len();
+
+    let file_type = detect(&sample)
+        .map(|name| name.to_owned())
+        .or_else(|| magic_packed::describe(p))
+        .unwrap_or_else(|| "unknown".to_owned());
+    let bits = entropy(&sample);
+    let mut entropy = format!("{:.2} bits/byte", bits);
+    //A few bytes cannot tell much.
+    if sample.len() >= 1024 && bits > HIGH_ENTROPY {
+        entropy.push_str(" (compressed or encrypted?)");
+    }
+
+    let width = width as usize;
+    let mut lines = vec![
+        format!("Type:    {}", file_type),
+        format!("Size:    {} ({} bytes)", to_proper_size(size), size),
+        format!("Entropy: {}", entropy),
+        String::new(),
+    ]
+    .into_iter()
+    .map(|line| shorten_str_including_wide_char(&line, width))
+    .collect::<Vec<String>>();
+    let dump = &sample[..sample.len().min(HEX_DUMP_SIZE)];
+    lines.extend(
+        hex_dump(dump, width)
+            .into_iter()
+            .map(|line| line.dark_grey().to_string()),
+    );
+    Ok(lines)
+}
+
+fn detect(header: &[u8]) -> Option<&'static str> {
+    SIGNATURES
+        .iter()
+        .find(|(offset, bytes, _)| header.get(*offset..*offset + bytes.len()) == Some(*bytes))
+        .map(|(_, _, name)| *name)
+}
+
+/// Shannon entropy in bits per byte, from 0 (constant) to 8 (random).
+fn entropy(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for b in bytes {
+        counts[*b as usize] += 1;
+    }
+    let len = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Dump like `00000000  7f 45 4c 46 |.ELF|`, with as many bytes per line as fit in the width
+/// (16, 8 or 4).
+fn hex_dump(bytes: &[u8], width: usize) -> Vec<String> {
+    //Each line takes 4 columns per byte and 12 for the offset and the separators.
+    let per_line = [16, 8, 4]
+        .into_iter()
+        .find(|n| n * 4 + 12 <= width)
+        .unwrap_or(4);
+    bytes
+        .chunks(per_line)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex = chunk
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let ascii: String = chunk
+                .iter()
+                .map(|b| {
+                    if b.is_ascii_graphic() || *b == b' ' {
+                        *b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            let line = format!(
+                "{:08x}  {:<hex_width$} |{}|",
+                i * per_line,
+                hex,
+                ascii,
+                hex_width = per_line * 3 - 1
+            );
+            shorten_str_including_wide_char(&line, width)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(b"\x7fELF\x02\x01"), Some("ELF executable"));
+        assert_eq!(detect(b"RIFF\0\0\0\0WAVEfmt "), Some("WAV audio"));
+        assert_eq!(detect(b"\0\0\0\x20ftypisom"), Some("MP4/QuickTime media"));
+        assert_eq!(detect(b"\x7fEL"), None);
+        assert_eq!(detect(b""), None);
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(&[]), 0.0);
+        assert_eq!(entropy(&[0; 100]), 0.0);
+        assert_eq!(entropy(&[0, 1, 0, 1]), 1.0);
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(entropy(&all), 8.0);
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let bytes = b"\x7fELF\x02\x01\x01\0abcdefgh!";
+        assert_eq!(
+            hex_dump(bytes, 80),
+            vec![
+                "00000000  7f 45 4c 46 02 01 01 00 61 62 63 64 65 66 67 68 |.ELF....abcdefgh|",
+                "00000010  21                                              |!|",
+            ]
+        );
+        assert_eq!(
+            hex_dump(&bytes[..9], 50),
+            vec![
+                "00000000  7f 45 4c 46 02 01 01 00 |.ELF....|",
+                "00000008  61                      |a|",
+            ]
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use super::errors::FxError;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The file that the chosen paths are written to in the file-picker mode (`--choose-files`).
+#[derive(Debug, Clone)]
+pub struct Chooser {
+    path: PathBuf,
+    /// Terminate the paths by NUL instead of newline (`--null`).
+    null: bool,
+}
+
+impl Chooser {
+    /// Empty the file first, so that an earlier choice is not taken for this one.
+    pub fn new(path: PathBuf, null: bool) -> Result<Self, FxError> {
+        fs::write(&path, b"")?;
+        Ok(Chooser { path, null })
+    }
+
+    /// Write the paths, each followed by the separator, keeping the names not valid UTF-8.
+    pub fn write(&self, paths: &[&Path]) -> Result<(), FxError> {
+        let separator = if self.null { b'\0' } else { b'\n' };
+        let mut content = Vec::new();
+        for path in paths {
+            content.extend_from_slice(path.as_os_str().as_encoded_bytes());
+            content.push(separator);
+        }
+        fs::write(&self.path, content)?;
+        Ok(())
+    }
+
+    /// Whether any path has been written, which decides the exit status.
+    pub fn chosen(&self) -> bool {
+        self.path
+            .metadata()
+            .is_ok_and(|metadata| metadata.len() > 0)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chooser() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chosen");
+        fs::write(&path, "/old\n").unwrap();
+        let chooser = Chooser::new(path.clone(), false).unwrap();
+        assert!(!chooser.chosen());
+        chooser
+            .write(&[Path::new("/tmp/a b"), Path::new("/tmp/c")])
+            .unwrap();
+        assert!(chooser.chosen());
+        assert_eq!(fs::read(&path).unwrap(), b"/tmp/a b\n/tmp/c\n");
+
+        let chooser = Chooser::new(path.clone(), true).unwrap();
+        chooser.write(&[Path::new("/tmp/line\nbreak")]).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"/tmp/line\nbreak\0");
+    }
+}
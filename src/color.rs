@@ -0,0 +1,62 @@
+use super::config::Colorname;
+
+use crossterm::style::{Color, ResetColor, SetBackgroundColor, SetForegroundColor, Stylize};
+
+pub enum TermColor<'a> {
+    ForeGround(&'a Colorname),
+    BackGround(&'a Colorname),
+}
+
+/// Escape sequence to set the color.
+pub fn color_code(c: &TermColor) -> String {
+    match c {
+        TermColor::ForeGround(c) => SetForegroundColor(to_color(c)).to_string(),
+        TermColor::BackGround(c) => SetBackgroundColor(to_color(c)).to_string(),
+    }
+}
+
+fn to_color(c: &Colorname) -> Color {
+    match c {
+        Colorname::Black => Color::Black,
+        Colorname::Red => Color::DarkRed,
+        Colorname::Green => Color::DarkGreen,
+        Colorname::Yellow => Color::DarkYellow,
+        Colorname::Blue => Color::DarkBlue,
+        Colorname::Magenta => Color::DarkMagenta,
+        Colorname::Cyan => Color::DarkCyan,
+        Colorname::White => Color::Grey,
+        Colorname::LightBlack => Color::DarkGrey,
+        Colorname::LightRed => Color::Red,
+        Colorname::LightGreen => Color::Green,
+        Colorname::LightYellow => Color::Yellow,
+        Colorname::LightBlue => Color::Blue,
+        Colorname::LightMagenta => Color::Magenta,
+        Colorname::LightCyan => Color::Cyan,
+        Colorname::LightWhite => Color::White,
+        Colorname::Rgb(r, g, b) => Color::Rgb {
+            r: *r,
+            g: *g,
+            b: *b,
+        },
+        Colorname::AnsiValue(n) => Color::AnsiValue(*n),
+    }
+}
+
+/// The text in the colors, or reversed if the background is not set.
+pub fn highlighted(text: &str, fg: Option<&Colorname>, bg: Option<&Colorname>) -> String {
+    match bg {
+        Some(bg) => {
+            let fg = fg
+                .map(|fg| color_code(&TermColor::ForeGround(fg)))
+                .unwrap_or_default();
+            format!(
+                "{}{}{}{}",
+                fg,
+                color_code(&TermColor::BackGround(bg)),
+                text,
+                ResetColor
+            )
+        }
+        None => text.negative().to_string(),
+    }
+}
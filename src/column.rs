@@ -0,0 +1,207 @@
+use super::functions::{
+    convert_to_permissions, format_time, group_name, pad_str, shorten_str_including_wide_char,
+    to_proper_size, user_name, TimeFormat, Timezone,
+};
+use super::item::{FileType, ItemInfo};
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+pub const TIME_WIDTH: u16 = 16;
+const SIZE_WIDTH: u16 = 5;
+const PERMISSIONS_WIDTH: u16 = 3;
+const GIT_WIDTH: u16 = 1;
+const OWNER_WIDTH: u16 = 8;
+
+/// Width of the columns set by `column_widths` in the config, instead of the default.
+pub type ColumnWidths = BTreeMap<Column, u16>;
+
+/// Columns printed after the item name.
+/// The item name always comes first and takes the rest of the width.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Column {
+    Mtime,
+    Size,
+    Permissions,
+    Owner,
+    Group,
+    Git,
+}
+
+impl Column {
+    /// Columns shown when not set in the config.
+    pub fn default_columns() -> Vec<Column> {
+        vec![Column::Mtime]
+    }
+
+    pub fn width(&self, widths: &ColumnWidths) -> u16 {
+        if let Some(width) = widths.get(self) {
+            return *width;
+        }
+        match self {
+            Column::Mtime => TIME_WIDTH,
+            Column::Size => SIZE_WIDTH,
+            Column::Permissions => PERMISSIONS_WIDTH,
+            Column::Owner | Column::Group => OWNER_WIDTH,
+            Column::Git => GIT_WIDTH,
+        }
+    }
+
+    /// When the terminal is too narrow, columns with lower priority are hidden first.
+    pub fn priority(&self) -> u8 {
+        match self {
+            Column::Group => 0,
+            Column::Owner => 1,
+            Column::Mtime => 2,
+            Column::Size => 3,
+            Column::Permissions => 4,
+            Column::Git => 5,
+        }
+    }
+
+    /// Render the column of the item, padded or shortened to the width.
+    pub fn render(
+        &self,
+        item: &ItemInfo,
+        widths: &ColumnWidths,
+        time_format: &TimeFormat,
+        timezone: Timezone,
+    ) -> String {
+        let width = self.width(widths) as usize;
+        let (s, right) = match self {
+            Column::Mtime => match &item.modified_string {
+                Some(s) => (s.clone(), false),
+                None => (format_time(item.modified, time_format, timezone), false),
+            },
+            Column::Size => match item.file_type {
+                FileType::Directory => (String::new(), false),
+                _ => (to_proper_size(item.file_size), true),
+            },
+            Column::Permissions => (
+                item.permissions
+                    .map(convert_to_permissions)
+                    .unwrap_or_default(),
+                true,
+            ),
+            Column::Owner => (
+                item.owner
+                    .map(|(uid, _)| user_name(uid))
+                    .unwrap_or_default(),
+                false,
+            ),
+            Column::Group => (
+                item.owner
+                    .map(|(_, gid)| group_name(gid))
+                    .unwrap_or_default(),
+                false,
+            ),
+            Column::Git => (if item.is_dirty { "M" } else { "" }.to_owned(), false),
+        };
+        pad_str(&shorten_str_including_wide_char(&s, width), width, right)
+    }
+}
+
+/// Render the columns, each preceded by a space.
+pub fn render_columns(
+    columns: &[Column],
+    widths: &ColumnWidths,
+    time_format: &TimeFormat,
+    timezone: Timezone,
+    item: &ItemInfo,
+) -> String {
+    columns.iter().fold(String::new(), |mut acc, column| {
+        acc.push(' ');
+        acc.push_str(&column.render(item, widths, time_format, timezone));
+        acc
+    })
+}
+
+/// Sum of the width of the columns, including the separators.
+pub fn columns_width(columns: &[Column], widths: &ColumnWidths) -> u16 {
+    columns.iter().map(|column| column.width(widths) + 1).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_columns() {
+        let item = ItemInfo {
+            file_type: FileType::File,
+            file_size: 2_500,
+            // 2024-01-02 03:04:05 UTC
+            modified: Some(
+                std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_704_164_645),
+            ),
+            permissions: Some(0o100644),
+            is_dirty: true,
+            ..Default::default()
+        };
+        let columns = [
+            Column::Size,
+            Column::Permissions,
+            Column::Git,
+            Column::Mtime,
+        ];
+        let widths = ColumnWidths::new();
+        let time_format = TimeFormat::parse("%Y-%m-%d %H:%M").unwrap();
+        let rendered = render_columns(&columns, &widths, &time_format, Timezone::Local, &item);
+        let mtime = format_time(item.modified, &time_format, Timezone::Local);
+        assert_eq!(rendered, format!("   2KB 644 M {}", mtime));
+        assert_eq!(columns_width(&columns, &widths) as usize, rendered.len());
+
+        let dir = ItemInfo {
+            file_type: FileType::Directory,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_columns(
+                &[Column::Size, Column::Git],
+                &widths,
+                &time_format,
+                Timezone::Local,
+                &dir
+            ),
+            "        "
+        );
+
+        // Shortened or padded to the width.
+        let widths = ColumnWidths::from([(Column::Mtime, 10), (Column::Size, 7)]);
+        let rendered = render_columns(
+            &[Column::Size, Column::Mtime],
+            &widths,
+            &time_format,
+            Timezone::Local,
+            &item,
+        );
+        assert_eq!(rendered, format!("     2KB {}", &mtime[..10]));
+        assert_eq!(columns_width(&[Column::Size, Column::Mtime], &widths), 19);
+
+        let owned = ItemInfo {
+            owner: Some((4_294_967_000, 4_294_967_000)),
+            ..Default::default()
+        };
+        let widths = ColumnWidths::from([(Column::Group, 4)]);
+        assert_eq!(
+            render_columns(
+                &[Column::Owner, Column::Group],
+                &widths,
+                &time_format,
+                Timezone::Local,
+                &owned
+            ),
+            " 42949670 4294"
+        );
+        assert_eq!(
+            render_columns(
+                &[Column::Owner],
+                &widths,
+                &time_format,
+                Timezone::Local,
+                &ItemInfo::default()
+            ),
+            "         "
+        );
+    }
+}
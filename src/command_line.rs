@@ -0,0 +1,137 @@
+use super::term::*;
+
+use felix::errors::FxError;
+use felix::functions::str_width;
+use felix::shell_command::History;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use std::ffi::OsStr;
+use std::io::Write;
+use std::path::Path;
+
+/// Read a command in the info line after the prompt.
+/// Up and Down go through the history. Return None if canceled by Esc.
+pub fn read_command(prompt: &str, history: &History) -> Result<Option<String>, FxError> {
+    let mut command: Vec<char> = Vec::new();
+    let mut pos = 0;
+    // Index in the history, or its length while editing a new command.
+    let mut index = history.entries().len();
+    let mut draft: Vec<char> = Vec::new();
+    show_cursor();
+    loop {
+        print_command_line(prompt, &command, pos);
+        let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = crossterm::event::read()?
+        else {
+            continue;
+        };
+        match (code, modifiers) {
+            (KeyCode::Esc, _) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
+                hide_cursor();
+                return Ok(None);
+            }
+            (KeyCode::Enter, _) => {
+                hide_cursor();
+                return Ok(Some(command.into_iter().collect()));
+            }
+            (KeyCode::Left, _) => pos = pos.saturating_sub(1),
+            (KeyCode::Right, _) => pos = (pos + 1).min(command.len()),
+            (KeyCode::Home, _) | (KeyCode::Char('a'), KeyModifiers::CONTROL) => pos = 0,
+            (KeyCode::End, _) | (KeyCode::Char('e'), KeyModifiers::CONTROL) => pos = command.len(),
+            (KeyCode::Backspace, _) | (KeyCode::Char('h'), KeyModifiers::CONTROL) if pos > 0 => {
+                pos -= 1;
+                command.remove(pos);
+            }
+            (KeyCode::Delete, _) if pos < command.len() => {
+                command.remove(pos);
+            }
+            (KeyCode::Up, _) if index > 0 => {
+                if index == history.entries().len() {
+                    draft = command.clone();
+                }
+                index -= 1;
+                command = history.entries()[index].chars().collect();
+                pos = command.len();
+            }
+            (KeyCode::Down, _) if index < history.entries().len() => {
+                index += 1;
+                command = match history.entries().get(index) {
+                    Some(entry) => entry.chars().collect(),
+                    None => draft.clone(),
+                };
+                pos = command.len();
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                command.insert(pos, c);
+                pos += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Print the prompt and the command, scrolled to show the cursor.
+fn print_command_line(prompt: &str, command: &[char], pos: usize) {
+    let width = terminal_size().map_or(80, |(column, _)| column) as usize;
+    // The info line starts at the 2nd column, and the cursor needs 1 more.
+    let space = width.saturating_sub(str_width(prompt) + 2);
+    let mut start = 0;
+    while start < pos && str_width(&command[start..pos].iter().collect::<String>()) > space {
+        start += 1;
+    }
+    let mut shown = String::new();
+    let mut shown_width = 0;
+    let mut cursor = 0;
+    for (i, c) in command.iter().enumerate().skip(start) {
+        if i == pos {
+            cursor = shown_width;
+        }
+        let c_width = str_width(&c.to_string());
+        if shown_width + c_width > space {
+            break;
+        }
+        shown.push(*c);
+        shown_width += c_width;
+    }
+    if pos == command.len() {
+        cursor = shown_width;
+    }
+    go_to_info_line_and_reset();
+    print!("{}{}", prompt, shown);
+    move_to((2 + str_width(prompt) + cursor) as u16, 2);
+    let _ = std::io::stdout().flush();
+}
+
+/// Run the command by the shell in the directory, handing over the terminal,
+/// and wait for a key after it finishes, to read the output.
+pub fn run_shell_command(command: &OsStr, dir: &Path) -> Result<std::process::ExitStatus, FxError> {
+    let sh = std::env::var_os("SHELL").unwrap_or_else(|| "sh".into());
+    clear_all();
+    move_to(1, 1);
+    leave_raw_mode();
+    let _ = std::io::stdout().flush();
+    let status = run_in_terminal(
+        std::process::Command::new(sh)
+            .arg("-c")
+            .arg(command)
+            .current_dir(dir),
+    );
+    print!("\nPress any key to continue.");
+    let _ = std::io::stdout().flush();
+    enter_raw_mode();
+    let status = status?;
+    loop {
+        if let Event::Key(KeyEvent {
+            kind: KeyEventKind::Press,
+            ..
+        }) = crossterm::event::read()?
+        {
+            break;
+        }
+    }
+    Ok(status)
+}
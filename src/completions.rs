@@ -0,0 +1,295 @@
+use super::errors::FxError;
+use super::help::HELP;
+
+/// An option of the command line.
+struct CliOption {
+    short: Option<char>,
+    long: &'static str,
+    value: Value,
+    help: &'static str,
+}
+
+/// What the option takes after it.
+#[derive(PartialEq)]
+enum Value {
+    None,
+    /// Optional.
+    Directory,
+    File,
+}
+
+const OPTIONS: &[CliOption] = &[
+    CliOption {
+        short: Some('h'),
+        long: "help",
+        value: Value::None,
+        help: "Print help.",
+    },
+    CliOption {
+        short: Some('l'),
+        long: "log",
+        value: Value::Directory,
+        help: "Launch the app, automatically generating a log file.",
+    },
+    CliOption {
+        short: None,
+        long: "init",
+        value: Value::None,
+        help: "Print a shell script that can be sourced for shell integration.",
+    },
+    CliOption {
+        short: None,
+        long: "new-window",
+        value: Value::Directory,
+        help: "Launch fx in a new window of the terminal set by `terminal` in the config.",
+    },
+    CliOption {
+        short: None,
+        long: "config",
+        value: Value::File,
+        help: "Read the config file instead of the default one (same as FELIX_CONFIG).",
+    },
+    CliOption {
+        short: None,
+        long: "choose-files",
+        value: Value::File,
+        help: "Work as a file picker: write the chosen paths to the file on exit, one per line.",
+    },
+    CliOption {
+        short: None,
+        long: "null",
+        value: Value::None,
+        help: "Terminate the paths written by --choose-files by NUL instead of newline.",
+    },
+];
+
+/// Subcommands, with the arguments and the help.
+const SUBCOMMANDS: &[(&str, &str, &str)] = &[
+    (
+        "completions",
+        "<shell>",
+        "Print the completion script for the shell (bash, zsh or fish).",
+    ),
+    ("man", "", "Print the man page."),
+];
+
+const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// Completion script for the shell.
+pub fn completions(shell: &str) -> Result<String, FxError> {
+    match shell {
+        "bash" => Ok(bash_completions()),
+        "zsh" => Ok(zsh_completions()),
+        "fish" => Ok(fish_completions()),
+        _ => Err(FxError::Arg(format!(
+            "Unsupported shell: {} (choose from {})",
+            shell,
+            SHELLS.join(", ")
+        ))),
+    }
+}
+
+fn option_words() -> Vec<String> {
+    let mut words = vec![];
+    for option in OPTIONS {
+        if let Some(short) = option.short {
+            words.push(format!("-{}", short));
+        }
+        words.push(format!("--{}", option.long));
+    }
+    words
+}
+
+fn bash_completions() -> String {
+    let subcommands: Vec<&str> = SUBCOMMANDS.iter().map(|(name, _, _)| *name).collect();
+    format!(
+        r#"_fx() {{
+    local cur prev
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    if [ "$prev" = completions ]; then
+        COMPREPLY=($(compgen -W "{shells}" -- "$cur"))
+        return
+    fi
+    if [ "$prev" = --config ] || [ "$prev" = --choose-files ]; then
+        COMPREPLY=($(compgen -f -- "$cur"))
+        return
+    fi
+    if [ "$COMP_CWORD" -eq 1 ]; then
+        COMPREPLY=($(compgen -W "{options} {subcommands}" -- "$cur") $(compgen -d -- "$cur"))
+        return
+    fi
+    COMPREPLY=($(compgen -d -- "$cur"))
+}}
+complete -o filenames -F _fx fx
+"#,
+        shells = SHELLS.join(" "),
+        options = option_words().join(" "),
+        subcommands = subcommands.join(" ")
+    )
+}
+
+fn zsh_completions() -> String {
+    let mut options = String::new();
+    for option in OPTIONS {
+        let help = option.help.replace('\'', "'\\''");
+        let names = match option.short {
+            Some(short) => format!("{{-{},--{}}}", short, option.long),
+            None => format!("--{}", option.long),
+        };
+        let arg = match option.value {
+            Value::None => "",
+            Value::Directory => "::directory:_files -/",
+            Value::File => ":file:_files",
+        };
+        options.push_str(&format!("    '{}[{}]{}' \\\n", names, help, arg));
+    }
+    let subcommands: Vec<String> = SUBCOMMANDS
+        .iter()
+        .map(|(name, _, help)| format!("'{}:{}'", name, help.replace('\'', "'\\''")))
+        .collect();
+    format!(
+        r#"#compdef fx
+
+_fx() {{
+  if [ "$words[2]" = completions ]; then
+    _values shell {shells}
+    return
+  fi
+  _arguments \
+{options}    '1: :->first'
+  if [ "$state" = first ]; then
+    _describe command '({subcommands})'
+    _files -/
+  fi
+}}
+
+_fx "$@"
+"#,
+        shells = SHELLS.join(" "),
+        options = options,
+        subcommands = subcommands.join(" ")
+    )
+}
+
+fn fish_completions() -> String {
+    let mut s = String::from("complete -c fx -f -a '(__fish_complete_directories)'\n");
+    for option in OPTIONS {
+        s.push_str("complete -c fx");
+        if let Some(short) = option.short {
+            s.push_str(&format!(" -s {}", short));
+        }
+        if option.value == Value::File {
+            s.push_str(" -r -F");
+        }
+        s.push_str(&format!(
+            " -l {} -d '{}'\n",
+            option.long,
+            option.help.replace('\'', "\\'")
+        ));
+    }
+    for (name, _, help) in SUBCOMMANDS {
+        s.push_str(&format!(
+            "complete -c fx -n __fish_use_subcommand -a {} -d '{}'\n",
+            name,
+            help.replace('\'', "\\'")
+        ));
+    }
+    s.push_str(&format!(
+        "complete -c fx -n '__fish_seen_subcommand_from completions' -a '{}'\n",
+        SHELLS.join(" ")
+    ));
+    s
+}
+
+/// Man page in roff, with the key manual of the help.
+pub fn man_page() -> String {
+    let mut s = format!(
+        ".TH FX 1 \"\" \"felix {}\" \"User Commands\"\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    s.push_str(".SH NAME\nfx \\- a simple TUI file manager with vim-like keymapping\n");
+    s.push_str(".SH SYNOPSIS\n.B fx\n[\\fIOPTIONS\\fR] [\\fIDIRECTORY\\fR]\n");
+    for (name, arg, _) in SUBCOMMANDS {
+        s.push_str(&format!(".br\n.B fx {}\n", name));
+        if !arg.is_empty() {
+            s.push_str(&format!("{}\n", roff_escape(arg)));
+        }
+    }
+    s.push_str(
+        ".SH DESCRIPTION\nShow items in the directory, or in the current directory if omitted.\n",
+    );
+    s.push_str(".SH OPTIONS\n");
+    for option in OPTIONS {
+        let names = match option.short {
+            Some(short) => format!("\\fB\\-{}\\fR, \\fB\\-\\-{}\\fR", short, option.long),
+            None => format!("\\fB\\-\\-{}\\fR", option.long),
+        };
+        let arg = match option.value {
+            Value::None => "",
+            Value::Directory => " [\\fIDIRECTORY\\fR]",
+            Value::File => " \\fIFILE\\fR",
+        };
+        s.push_str(&format!(
+            ".TP\n{}{}\n{}\n",
+            names,
+            arg,
+            roff_escape(option.help)
+        ));
+    }
+    s.push_str(".SH COMMANDS\n");
+    for (name, arg, help) in SUBCOMMANDS {
+        s.push_str(&format!(
+            ".TP\n\\fB{}\\fR {}\n{}\n",
+            name,
+            roff_escape(arg),
+            roff_escape(help)
+        ));
+    }
+    if let Some((_, manual)) = HELP.split_once("## Manual\n") {
+        s.push_str(".SH KEYS\n.nf\n");
+        for line in manual.lines() {
+            s.push_str(&roff_escape(line));
+            s.push('\n');
+        }
+        s.push_str(".fi\n");
+    }
+    s
+}
+
+/// Escape the backslashes, and the dots and quotes at the start of the line.
+fn roff_escape(s: &str) -> String {
+    let s = s.replace('\\', "\\e");
+    if s.starts_with('.') || s.starts_with('\'') {
+        format!("\\&{}", s)
+    } else {
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions() {
+        let bash = completions("bash").unwrap();
+        assert!(bash.contains("--new-window"));
+        assert!(bash.contains("[ \"$prev\" = --choose-files ]"));
+        assert!(bash.contains("completions man"));
+        assert!(completions("zsh").unwrap().contains("{-l,--log}"));
+        assert!(completions("fish")
+            .unwrap()
+            .contains("complete -c fx -s h -l help"));
+        assert!(completions("tcsh").is_err());
+    }
+
+    #[test]
+    fn test_man_page() {
+        let man = man_page();
+        assert!(man.starts_with(".TH FX 1"));
+        assert!(man.contains(".SH KEYS"));
+        assert!(!man.lines().any(|line| line.starts_with("..")));
+        assert_eq!(roff_escape(".foo\\d"), "\\&.foo\\ed");
+    }
+}
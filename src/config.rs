@@ -1,4 +1,14 @@
+use crate::column::{Column, ColumnWidths};
+use crate::conflict::OnConflict;
 use crate::errors::FxError;
+use crate::functions::{TimeFormat, Timezone};
+use crate::keymap::{Keymap, SCRIPT_PREFIX};
+use crate::layout::Split;
+use crate::opener::OpenerConfig;
+use crate::script::compile_script;
+use crate::session::NameOrder;
+use crate::template::Template;
+use crate::theme::Theme;
 
 use serde::Deserialize;
 use std::collections::BTreeMap;
@@ -7,6 +17,10 @@ use std::path::{Path, PathBuf};
 
 pub const FELIX: &str = "felix";
 const CONFIG_FILE: &str = "config.yaml";
+/// Path of the config file to be used instead of the default ones.
+pub const CONFIG_ENV: &str = "FELIX_CONFIG";
+/// All the options are commented out, so it is the same as the default.
+const DEFAULT_CONFIG: &str = include_str!("../config.yaml");
 const CONFIG_FILE_ANOTHER_EXT: &str = "config.yml";
 
 #[derive(Debug, Clone)]
@@ -19,9 +33,52 @@ pub struct ConfigWithPath {
 pub struct Config {
     pub default: Option<String>,
     pub match_vim_exit_behavior: Option<bool>,
-    pub exec: Option<BTreeMap<String, Vec<String>>>,
+    pub exec: Option<BTreeMap<String, OpenerConfig>>,
+    pub alt_exec: Option<BTreeMap<String, OpenerConfig>>,
     pub ignore_case: Option<bool>,
     pub color: Option<ConfigColor>,
+    pub ls_colors: Option<bool>,
+    pub theme: Option<String>,
+    pub script: Option<BTreeMap<String, String>>,
+    pub columns: Option<Vec<Column>>,
+    pub column_widths: Option<ColumnWidths>,
+    pub trash_report_threshold: Option<u64>,
+    pub trash_size_limit: Option<u64>,
+    pub open_size_limit: Option<u64>,
+    pub command_size_limits: Option<BTreeMap<String, u64>>,
+    pub name_order: Option<NameOrder>,
+    pub sort_scripts: Option<BTreeMap<String, String>>,
+    pub terminal: Option<String>,
+    pub terminal_here: Option<String>,
+    pub directories_first: Option<bool>,
+    pub preview_split: Option<Split>,
+    pub preview_size: Option<u16>,
+    pub timezone: Option<String>,
+    pub time_format: Option<String>,
+    pub header_format: Option<String>,
+    pub status_format: Option<String>,
+    pub searches: Option<BTreeMap<String, SavedSearch>>,
+    pub auto_refresh: Option<bool>,
+    pub mouse: Option<bool>,
+    pub alternate_screen: Option<bool>,
+    pub preserve_metadata: Option<bool>,
+    pub background_threshold: Option<u64>,
+    pub on_conflict: Option<OnConflict>,
+    pub legacy_trash: Option<bool>,
+    pub use_systemd_run: Option<bool>,
+    pub relative_base: Option<PathBuf>,
+    pub keys: Option<BTreeMap<String, String>>,
+    pub leader: Option<String>,
+    pub filter_presets: Option<BTreeMap<String, String>>,
+}
+
+/// Named query opened by `:search` as a virtual directory.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedSearch {
+    /// Directory to search. If not set, the current directory.
+    pub dir: Option<PathBuf>,
+    /// Same as the argument of `:filter` (e.g. `name:*.log >100M -r`).
+    pub filter: String,
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
@@ -30,6 +87,16 @@ pub struct ConfigColor {
     pub file_fg: Colorname,
     pub symlink_fg: Colorname,
     pub dirty_fg: Colorname,
+    /// FIFOs, sockets and device files. Optional, not to break the existing configs.
+    #[serde(default = "default_other_fg")]
+    pub other_fg: Colorname,
+    /// Colors of the files by the (lowercase) extension, taking precedence over the others.
+    #[serde(default)]
+    pub ext: BTreeMap<String, Colorname>,
+}
+
+fn default_other_fg() -> Colorname {
+    Colorname::LightMagenta
 }
 
 impl Default for ConfigColor {
@@ -39,6 +106,8 @@ impl Default for ConfigColor {
             file_fg: Colorname::LightWhite,
             symlink_fg: Colorname::LightYellow,
             dirty_fg: Colorname::Red,
+            other_fg: default_other_fg(),
+            ext: BTreeMap::new(),
         }
     }
 }
@@ -71,68 +140,206 @@ impl Default for Config {
             default: Default::default(),
             match_vim_exit_behavior: Default::default(),
             exec: Default::default(),
+            alt_exec: Default::default(),
             ignore_case: Some(false),
             color: Some(Default::default()),
+            ls_colors: Default::default(),
+            theme: Default::default(),
+            script: Default::default(),
+            columns: Default::default(),
+            column_widths: Default::default(),
+            trash_report_threshold: Default::default(),
+            trash_size_limit: Default::default(),
+            open_size_limit: Default::default(),
+            command_size_limits: Default::default(),
+            name_order: Default::default(),
+            sort_scripts: Default::default(),
+            terminal: Default::default(),
+            terminal_here: Default::default(),
+            directories_first: Default::default(),
+            preview_split: Default::default(),
+            preview_size: Default::default(),
+            timezone: Default::default(),
+            time_format: Default::default(),
+            header_format: Default::default(),
+            status_format: Default::default(),
+            searches: Default::default(),
+            auto_refresh: Default::default(),
+            mouse: Default::default(),
+            alternate_screen: Default::default(),
+            preserve_metadata: Default::default(),
+            background_threshold: Default::default(),
+            on_conflict: Default::default(),
+            legacy_trash: Default::default(),
+            use_systemd_run: Default::default(),
+            relative_base: Default::default(),
+            keys: Default::default(),
+            leader: Default::default(),
+            filter_presets: Default::default(),
         }
     }
 }
 
 pub fn read_config(p: &Path) -> Result<ConfigWithPath, FxError> {
     let s = read_to_string(p)?;
-    let deserialized: Config = serde_yaml::from_str(&s)?;
+    let config = parse_config(&s)
+        .map_err(|e| FxError::Yaml(format!("Invalid config: {} ({})", e, p.display())))?;
     Ok(ConfigWithPath {
         config_path: Some(p.to_path_buf()),
-        config: deserialized,
+        config,
     })
 }
 
-pub fn read_config_or_default() -> Result<ConfigWithPath, FxError> {
-    //First, declare default config file path.
-    let (config_file_path1, config_file_path2) = {
-        let mut config_path = {
-            let mut path = dirs::config_dir()
-                .ok_or_else(|| FxError::Dirs("Cannot read the config directory.".to_string()))?;
-            path.push(FELIX);
-            path
-        };
-        let mut another = config_path.clone();
-        config_path.push(CONFIG_FILE);
-        another.push(CONFIG_FILE_ANOTHER_EXT);
-        (config_path, another)
+/// Parse the config and check the values that cannot be checked by the types.
+/// The error shows the key, the line and what is expected,
+/// e.g. `ignore_case: invalid type: string "yes", expected a boolean at line 3 column 14`.
+fn parse_config(s: &str) -> Result<Config, String> {
+    let config: Config = serde_yaml::from_str(s).map_err(|e| e.to_string())?;
+    let invalid = |key: &str, e: FxError| match key_line(s, key) {
+        Some(line) => format!("{}: {} at line {}", key, e, line),
+        None => format!("{}: {}", key, e),
+    };
+    // Check here to show the error, as the bindings and the format are ignored in State if invalid.
+    // The leader, the keys and the presets are added one by one to tell which is wrong.
+    let empty = BTreeMap::new();
+    let keys = config.keys.as_ref().unwrap_or(&empty);
+    let presets = config.filter_presets.as_ref().unwrap_or(&empty);
+    let leader = config.leader.as_deref();
+    Keymap::new(&empty, &empty, leader).map_err(|e| invalid("leader", e))?;
+    Keymap::new(keys, &empty, leader).map_err(|e| invalid("keys", e))?;
+    Keymap::new(keys, presets, leader).map_err(|e| invalid("filter_presets", e))?;
+    let scripts = config.script.as_ref().unwrap_or(&empty);
+    if let Some(name) = keys
+        .keys()
+        .filter_map(|name| name.strip_prefix(SCRIPT_PREFIX))
+        .find(|name| !scripts.contains_key(*name))
+    {
+        let e = FxError::Keys(format!("The script `{}` is not defined in `script`.", name));
+        return Err(invalid("keys", e));
+    }
+    for (name, source) in scripts {
+        compile_script(source)
+            .map_err(|e| invalid("script", FxError::Script(format!("{}: {}", name, e))))?;
+    }
+    for (name, source) in config.sort_scripts.as_ref().unwrap_or(&empty) {
+        compile_script(source)
+            .map_err(|e| invalid("sort_scripts", FxError::Script(format!("{}: {}", name, e))))?;
+    }
+    if let Some(format) = &config.time_format {
+        TimeFormat::parse(format).map_err(|e| invalid("time_format", e))?;
+    }
+    if let Some(timezone) = &config.timezone {
+        Timezone::parse(timezone).map_err(|e| invalid("timezone", e))?;
+    }
+    if let Some(format) = &config.header_format {
+        Template::parse(format).map_err(|e| invalid("header_format", e))?;
+    }
+    if let Some(format) = &config.status_format {
+        Template::parse(format).map_err(|e| invalid("status_format", e))?;
+    }
+    Theme::load(config.theme.as_deref()).map_err(|e| invalid("theme", e))?;
+    Ok(config)
+}
+
+/// The line number of the top-level key.
+fn key_line(s: &str, key: &str) -> Option<usize> {
+    s.lines()
+        .position(|line| {
+            line.strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with(':'))
+        })
+        .map(|i| i + 1)
+}
+
+/// Write the commented config (the same as `config.yaml` in the repository) to the path,
+/// setting `default` if given.
+pub fn write_default_config(p: &Path, default: Option<&str>) -> Result<(), FxError> {
+    if let Some(dir) = p.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let config = match default {
+        Some(default) => DEFAULT_CONFIG.replacen(
+            "# default: nvim\n",
+            &format!("default: {}\n", serde_yaml::to_string(default)?.trim_end()),
+            1,
+        ),
+        None => DEFAULT_CONFIG.to_owned(),
     };
+    std::fs::write(p, config)?;
+    Ok(())
+}
 
-    //On macOS, felix looks for 2 paths:
-    //First `$HOME/Library/Application Support/felix/config.yaml(yml)`,
+/// The config file given by `FELIX_CONFIG` (or `--config`), which is read instead of the others.
+pub fn config_override() -> Option<PathBuf> {
+    let path = PathBuf::from(std::env::var_os(CONFIG_ENV).filter(|p| !p.is_empty())?);
+    if path.is_relative() {
+        Some(std::env::current_dir().ok()?.join(path))
+    } else {
+        Some(path)
+    }
+}
+
+/// Directories where the config file is looked for, in order.
+/// Themes are read from them as well.
+pub fn config_dirs() -> Result<Vec<PathBuf>, FxError> {
+    if let Some(path) = config_override() {
+        return Ok(path.parent().map(Path::to_path_buf).into_iter().collect());
+    }
+    let mut dirs = vec![];
+    //`dirs` follows XDG_CONFIG_HOME only on Linux.
+    if !cfg!(target_os = "linux") {
+        if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+        {
+            dirs.push(dir.join(FELIX));
+        }
+    }
+    let dir = dirs::config_dir()
+        .ok_or_else(|| FxError::Dirs("Cannot read the config directory.".to_string()))?;
+    dirs.push(dir.join(FELIX));
+
+    //On macOS, felix looks for
+    //`$HOME/Library/Application Support/felix/config.yaml(yml)`,
     //and if it fails,
     //`$HOME/.config/felix/config.yaml(yml)`.
-    let config_file_paths = if cfg!(target_os = "macos") {
-        let (alt_config_file_path1, alt_config_file_path2) = {
-            let mut config_path = dirs::home_dir()
-                .ok_or_else(|| FxError::Dirs("Cannot read the home directory.".to_string()))?;
-            config_path.push(".config");
-            config_path.push("FELIX");
-            let mut another = config_path.clone();
-            config_path.push(CONFIG_FILE);
-            another.push(CONFIG_FILE_ANOTHER_EXT);
-            (config_path, another)
-        };
-        vec![
-            config_file_path1,
-            config_file_path2,
-            alt_config_file_path1,
-            alt_config_file_path2,
-        ]
-    } else {
-        vec![config_file_path1, config_file_path2]
-    };
+    if cfg!(target_os = "macos") {
+        let home = dirs::home_dir()
+            .ok_or_else(|| FxError::Dirs("Cannot read the home directory.".to_string()))?;
+        dirs.push(home.join(".config").join(FELIX));
+    }
+    let mut unique = vec![];
+    for dir in dirs {
+        if !unique.contains(&dir) {
+            unique.push(dir);
+        }
+    }
+    Ok(unique)
+}
 
-    let mut config_file: Option<PathBuf> = None;
-    for p in config_file_paths {
-        if p.exists() {
-            config_file = Some(p);
-            break;
+/// Paths where the config file is looked for, in order.
+/// The first one is where the config is written on the first run.
+pub fn config_paths() -> Result<Vec<PathBuf>, FxError> {
+    if let Some(path) = config_override() {
+        return Ok(vec![path]);
+    }
+    Ok(config_dirs()?
+        .into_iter()
+        .flat_map(|dir| [dir.join(CONFIG_FILE), dir.join(CONFIG_FILE_ANOTHER_EXT)])
+        .collect())
+}
+
+pub fn read_config_or_default() -> Result<ConfigWithPath, FxError> {
+    if let Some(path) = config_override() {
+        if !path.exists() {
+            return Err(FxError::Arg(format!(
+                "Config file not found: {}",
+                path.display()
+            )));
         }
+        return read_config(&path);
     }
+    let config_file = config_paths()?.into_iter().find(|p| p.exists());
 
     if let Some(config_file) = config_file {
         read_config(&config_file)
@@ -147,6 +354,7 @@ pub fn read_config_or_default() -> Result<ConfigWithPath, FxError> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::opener::WorkingDir;
 
     #[test]
     fn test_read_default_config() {
@@ -154,8 +362,42 @@ mod tests {
         assert_eq!(default_config.default, None);
         assert_eq!(default_config.match_vim_exit_behavior, None);
         assert_eq!(default_config.exec, None);
+        assert_eq!(default_config.alt_exec, None);
         assert_eq!(default_config.ignore_case, None);
         assert_eq!(default_config.color, None);
+        assert_eq!(default_config.ls_colors, None);
+        assert_eq!(default_config.theme, None);
+        assert_eq!(default_config.script, None);
+        assert_eq!(default_config.columns, None);
+        assert_eq!(default_config.column_widths, None);
+        assert_eq!(default_config.trash_report_threshold, None);
+        assert_eq!(default_config.trash_size_limit, None);
+        assert_eq!(default_config.open_size_limit, None);
+        assert_eq!(default_config.command_size_limits, None);
+        assert_eq!(default_config.name_order, None);
+        assert_eq!(default_config.sort_scripts, None);
+        assert_eq!(default_config.terminal, None);
+        assert_eq!(default_config.terminal_here, None);
+        assert_eq!(default_config.directories_first, None);
+        assert_eq!(default_config.preview_split, None);
+        assert_eq!(default_config.preview_size, None);
+        assert_eq!(default_config.timezone, None);
+        assert_eq!(default_config.time_format, None);
+        assert_eq!(default_config.header_format, None);
+        assert_eq!(default_config.status_format, None);
+        assert_eq!(default_config.searches, None);
+        assert_eq!(default_config.auto_refresh, None);
+        assert_eq!(default_config.mouse, None);
+        assert_eq!(default_config.alternate_screen, None);
+        assert_eq!(default_config.preserve_metadata, None);
+        assert_eq!(default_config.background_threshold, None);
+        assert_eq!(default_config.on_conflict, None);
+        assert_eq!(default_config.legacy_trash, None);
+        assert_eq!(default_config.use_systemd_run, None);
+        assert_eq!(default_config.relative_base, None);
+        assert_eq!(default_config.keys, None);
+        assert_eq!(default_config.leader, None);
+        assert_eq!(default_config.filter_presets, None);
     }
 
     #[test]
@@ -169,12 +411,70 @@ exec:
     [pdf]
   'feh -.':
     [jpg, jpeg, png, gif, svg, hdr]
+  'mpv --no-terminal %f':
+    extensions: [mp4]
+    detach: true
+alt_exec:
+  bash:
+    [sh]
 ignore_case: true
 color:
   dir_fg: LightCyan
   file_fg: LightWhite
   symlink_fg: LightYellow
   dirty_fg: Red
+  other_fg: Yellow
+  ext:
+    rs: LightRed
+    md: !AnsiValue 208
+ls_colors: true
+theme: gruvbox
+columns: [Size, Permissions, Owner, Group, Git, Mtime]
+column_widths:
+  Owner: 12
+  Mtime: 10
+trash_report_threshold: 500
+trash_size_limit: 2000
+open_size_limit: 500
+command_size_limits:
+  nvim: 200
+  less: 0
+name_order: Locale
+sort_scripts:
+  by_length: name.len()
+terminal: alacritty -e
+terminal_here: alacritty
+directories_first: false
+preview_split: Horizontal
+preview_size: 40
+timezone: UTC
+time_format: relative
+header_format: " {path} [{sort}]"
+status_format: " {index}/{count} {size} [{selected} selected]"
+auto_refresh: false
+mouse: false
+alternate_screen: false
+preserve_metadata: false
+background_threshold: 200
+on_conflict: Skip
+legacy_trash: true
+use_systemd_run: true
+relative_base: ~/projects
+keys:
+  move_down: <C-n>
+  move_up: <C-p>
+  toggle_hidden: <leader>.
+leader: <Space>
+filter_presets:
+  <F1>: "images: name:*.png,*.jpg,*.webp"
+searches:
+  big-logs:
+    dir: ~/logs
+    filter: name:*.log >100M -r
+  today:
+    filter: <1d
+script:
+  select-logs: 'for name in items() { if name.ends_with(".log") { select(name) } }'
 "#,
         )
         .unwrap();
@@ -182,18 +482,30 @@ color:
         assert_eq!(full_config.match_vim_exit_behavior, Some(true));
         assert_eq!(
             full_config.exec.clone().unwrap().get("zathura"),
-            Some(&vec!["pdf".to_string()])
+            Some(&OpenerConfig::Extensions(vec!["pdf".to_string()]))
+        );
+        assert_eq!(
+            full_config.exec.clone().unwrap().get("mpv --no-terminal %f"),
+            Some(&OpenerConfig::Options {
+                extensions: vec!["mp4".to_string()],
+                detach: true,
+                cwd: WorkingDir::Current,
+            })
         );
         assert_eq!(
             full_config.exec.unwrap().get("feh -."),
-            Some(&vec![
+            Some(&OpenerConfig::Extensions(vec![
                 "jpg".to_string(),
                 "jpeg".to_string(),
                 "png".to_string(),
                 "gif".to_string(),
                 "svg".to_string(),
                 "hdr".to_string()
-            ])
+            ]))
+        );
+        assert_eq!(
+            full_config.alt_exec.unwrap().get("bash"),
+            Some(&OpenerConfig::Extensions(vec!["sh".to_string()]))
         );
         assert_eq!(full_config.ignore_case, Some(true));
         assert_eq!(
@@ -208,6 +520,180 @@ color:
             full_config.color.clone().unwrap().symlink_fg,
             Colorname::LightYellow
         );
-        assert_eq!(full_config.color.unwrap().dirty_fg, Colorname::Red);
+        assert_eq!(full_config.color.clone().unwrap().dirty_fg, Colorname::Red);
+        assert_eq!(
+            full_config.color.clone().unwrap().other_fg,
+            Colorname::Yellow
+        );
+        assert_eq!(
+            full_config.color.unwrap().ext,
+            BTreeMap::from([
+                ("md".to_owned(), Colorname::AnsiValue(208)),
+                ("rs".to_owned(), Colorname::LightRed)
+            ])
+        );
+        assert_eq!(full_config.ls_colors, Some(true));
+        assert_eq!(full_config.theme, Some("gruvbox".to_owned()));
+        assert_eq!(
+            full_config.columns,
+            Some(vec![
+                Column::Size,
+                Column::Permissions,
+                Column::Owner,
+                Column::Group,
+                Column::Git,
+                Column::Mtime
+            ])
+        );
+        assert_eq!(
+            full_config.column_widths,
+            Some(ColumnWidths::from([
+                (Column::Mtime, 10),
+                (Column::Owner, 12)
+            ]))
+        );
+        assert_eq!(full_config.trash_report_threshold, Some(500));
+        assert_eq!(full_config.trash_size_limit, Some(2000));
+        assert_eq!(full_config.open_size_limit, Some(500));
+        assert_eq!(
+            full_config.command_size_limits,
+            Some(BTreeMap::from([
+                ("less".to_owned(), 0),
+                ("nvim".to_owned(), 200)
+            ]))
+        );
+        assert_eq!(full_config.name_order, Some(NameOrder::Locale));
+        assert_eq!(
+            full_config.sort_scripts,
+            Some(BTreeMap::from([(
+                "by_length".to_owned(),
+                "name.len()".to_owned()
+            )]))
+        );
+        assert_eq!(full_config.terminal, Some("alacritty -e".to_string()));
+        assert_eq!(full_config.terminal_here, Some("alacritty".to_string()));
+        assert_eq!(full_config.directories_first, Some(false));
+        assert_eq!(full_config.preview_split, Some(Split::Horizontal));
+        assert_eq!(full_config.preview_size, Some(40));
+        assert_eq!(full_config.timezone, Some("UTC".to_string()));
+        assert_eq!(full_config.time_format, Some("relative".to_string()));
+        assert_eq!(
+            full_config.header_format,
+            Some(" {path} [{sort}]".to_string())
+        );
+        assert_eq!(
+            full_config.status_format,
+            Some(" {index}/{count} {size} [{selected} selected]".to_string())
+        );
+        assert_eq!(full_config.auto_refresh, Some(false));
+        assert_eq!(full_config.mouse, Some(false));
+        assert_eq!(full_config.alternate_screen, Some(false));
+        assert_eq!(full_config.preserve_metadata, Some(false));
+        assert_eq!(full_config.background_threshold, Some(200));
+        assert_eq!(full_config.on_conflict, Some(OnConflict::Skip));
+        assert_eq!(full_config.legacy_trash, Some(true));
+        assert_eq!(full_config.use_systemd_run, Some(true));
+        assert_eq!(full_config.relative_base, Some(PathBuf::from("~/projects")));
+        assert_eq!(
+            full_config.keys,
+            Some(BTreeMap::from([
+                ("move_down".to_owned(), "<C-n>".to_owned()),
+                ("move_up".to_owned(), "<C-p>".to_owned()),
+                ("toggle_hidden".to_owned(), "<leader>.".to_owned())
+            ]))
+        );
+        assert_eq!(full_config.leader, Some("<Space>".to_owned()));
+        assert_eq!(
+            full_config.filter_presets,
+            Some(BTreeMap::from([(
+                "<F1>".to_owned(),
+                "images: name:*.png,*.jpg,*.webp".to_owned()
+            )]))
+        );
+        let searches = full_config.searches.unwrap();
+        assert_eq!(
+            searches.get("big-logs"),
+            Some(&SavedSearch {
+                dir: Some(PathBuf::from("~/logs")),
+                filter: "name:*.log >100M -r".to_string()
+            })
+        );
+        assert_eq!(
+            searches.get("today"),
+            Some(&SavedSearch {
+                dir: None,
+                filter: "<1d".to_string()
+            })
+        );
+        assert_eq!(
+            full_config.script.unwrap().get("select-logs"),
+            Some(
+                &r#"for name in items() { if name.ends_with(".log") { select(name) } }"#
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_config() {
+        assert!(parse_config("").is_ok());
+        assert_eq!(
+            parse_config("default: nvim\nignore_case: [1]\n").unwrap_err(),
+            "ignore_case: invalid type: sequence, expected a boolean at line 2 column 14"
+        );
+        assert!(parse_config("color:\n  dir_fg: Mauve\n")
+            .unwrap_err()
+            .starts_with("color.dir_fg: unknown variant `Mauve`"));
+        assert_eq!(
+            parse_config("default: nvim\ntime_format: \"%Q\"\n").unwrap_err(),
+            "time_format: Invalid time_format `%Q`. at line 2"
+        );
+        assert_eq!(
+            parse_config("status_format: \"{index} {color}\"\n").unwrap_err(),
+            "status_format: Invalid format `{index} {color}`: unknown placeholder `{color}`. at line 1"
+        );
+        assert_eq!(
+            parse_config("timezone: Asia/Tokio\n").unwrap_err(),
+            "timezone: Unknown timezone `Asia/Tokio`. at line 1"
+        );
+        assert!(parse_config("keys:\n  j: no_such_action\n")
+            .unwrap_err()
+            .starts_with("keys: "));
+        assert!(parse_config("keys:\n  script:up: U\nscript:\n  up: 'cd(\"..\")'\n").is_ok());
+        assert_eq!(
+            parse_config("keys:\n  script:none: U\n").unwrap_err(),
+            "keys: Error: Invalid keys -> The script `none` is not defined in `script`. at line 1"
+        );
+        assert!(parse_config("script:\n  up: 'cd(\"..\"'\n")
+            .unwrap_err()
+            .starts_with("script: up: "));
+        assert!(parse_config("sort_scripts:\n  len: 'name.len('\n")
+            .unwrap_err()
+            .starts_with("sort_scripts: len: "));
+        assert!(parse_config("filter_presets:\n  <Nope>: \">1M\"\n")
+            .unwrap_err()
+            .starts_with("filter_presets: "));
+        assert_eq!(key_line("a: 1\nkeys :\n  keys: 2\n", "keys"), Some(2));
+        assert_eq!(key_line("keys_x: 1\n", "keys"), None);
+    }
+
+    #[test]
+    fn test_write_default_config() {
+        assert!(parse_config(DEFAULT_CONFIG).is_ok());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("felix").join(CONFIG_FILE);
+        write_default_config(&path, None).unwrap();
+        assert_eq!(read_config(&path).unwrap().config.default, None);
+        write_default_config(&path, Some("code -w")).unwrap();
+        assert_eq!(
+            read_config(&path).unwrap().config.default,
+            Some("code -w".to_owned())
+        );
+        write_default_config(&path, Some("vim # comment: x")).unwrap();
+        assert_eq!(
+            read_config(&path).unwrap().config.default,
+            Some("vim # comment: x".to_owned())
+        );
     }
 }
@@ -0,0 +1,159 @@
+use super::errors::FxError;
+use super::frontend::Frontend;
+
+use crossterm::event::{KeyCode, KeyModifiers};
+use serde::Deserialize;
+
+/// What to do when a put item has the same name as an existing one.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Ask for each conflict.
+    #[default]
+    Ask,
+    /// Replace the existing item.
+    Overwrite,
+    /// Leave the existing item, and do not put the new one.
+    Skip,
+    /// Put the new one with a suffix like `_1`.
+    Rename,
+    /// Merge the contents of directories with the same name, and ask for each file.
+    Merge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Overwrite,
+    Skip,
+    Rename,
+    /// Only for directories.
+    Merge,
+}
+
+/// Resolve the name conflicts during one put operation.
+/// Once "apply to all" is chosen, the rest are resolved without asking.
+#[derive(Debug)]
+pub struct Resolver {
+    files: Option<Resolution>,
+    /// Conflicts between directories, which can be merged as well.
+    dirs: Option<Resolution>,
+}
+
+impl Resolver {
+    pub fn new(policy: OnConflict) -> Self {
+        let (files, dirs) = match policy {
+            OnConflict::Ask => (None, None),
+            OnConflict::Overwrite => (Some(Resolution::Overwrite), Some(Resolution::Overwrite)),
+            OnConflict::Skip => (Some(Resolution::Skip), Some(Resolution::Skip)),
+            OnConflict::Rename => (Some(Resolution::Rename), Some(Resolution::Rename)),
+            OnConflict::Merge => (None, Some(Resolution::Merge)),
+        };
+        Resolver { files, dirs }
+    }
+
+    /// Decide what to do with the item, asking by the frontend if needed.
+    /// `dirs` is true if both the put item and the existing one are directories.
+    /// Return `FxError::Canceled` if <Esc> or <C-c> is pressed.
+    pub fn resolve(
+        &mut self,
+        ui: &dyn Frontend,
+        name: &str,
+        dirs: bool,
+    ) -> Result<Resolution, FxError> {
+        let policy = if dirs { self.dirs } else { self.files };
+        if let Some(resolution) = policy {
+            return Ok(resolution);
+        }
+        let choices = if dirs {
+            "m: merge, o: overwrite, s: skip, r: rename (M/O/S/R: apply to all)"
+        } else {
+            "o: overwrite, s: skip, r: rename (O/S/R: apply to all)"
+        };
+        let question = format!("{} already exists. {}", name, choices);
+        loop {
+            let key = ui.ask(&question)?;
+            match key.code {
+                KeyCode::Esc => return Err(FxError::Canceled),
+                KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+                    return Err(FxError::Canceled)
+                }
+                KeyCode::Char(c) => {
+                    if let Some((resolution, all)) = to_resolution(c, dirs) {
+                        if all {
+                            self.apply_to_all(resolution);
+                        }
+                        return Ok(resolution);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Merging applies only to directories, and files are still asked.
+    fn apply_to_all(&mut self, resolution: Resolution) {
+        self.dirs = Some(resolution);
+        if resolution != Resolution::Merge {
+            self.files = Some(resolution);
+        }
+    }
+}
+
+/// Map the key to the resolution, and whether to apply it to the rest (upper case).
+fn to_resolution(c: char, dirs: bool) -> Option<(Resolution, bool)> {
+    let resolution = match c.to_ascii_lowercase() {
+        'm' if dirs => Resolution::Merge,
+        'o' => Resolution::Overwrite,
+        's' => Resolution::Skip,
+        'r' => Resolution::Rename,
+        _ => return None,
+    };
+    Some((resolution, c.is_ascii_uppercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::Headless;
+
+    #[test]
+    fn test_resolver() {
+        assert_eq!(
+            to_resolution('o', false),
+            Some((Resolution::Overwrite, false))
+        );
+        assert_eq!(to_resolution('S', false), Some((Resolution::Skip, true)));
+        assert_eq!(to_resolution('m', false), None);
+        assert_eq!(to_resolution('M', true), Some((Resolution::Merge, true)));
+        assert_eq!(to_resolution('x', true), None);
+
+        let ui = Headless::default();
+        let mut resolver = Resolver::new(OnConflict::Skip);
+        assert_eq!(resolver.resolve(&ui, "a", false).unwrap(), Resolution::Skip);
+        assert_eq!(resolver.resolve(&ui, "a", true).unwrap(), Resolution::Skip);
+
+        let mut resolver = Resolver::new(OnConflict::Merge);
+        assert_eq!(resolver.resolve(&ui, "a", true).unwrap(), Resolution::Merge);
+        assert_eq!(resolver.files, None);
+
+        //Keys not for the choices are asked again, and <Esc> cancels.
+        let mut resolver = Resolver::new(OnConflict::Ask);
+        ui.answer("xmR");
+        assert_eq!(
+            resolver.resolve(&ui, "a", false).unwrap(),
+            Resolution::Rename
+        );
+        assert_eq!(resolver.files, Some(Resolution::Rename));
+        let mut resolver = Resolver::new(OnConflict::Ask);
+        assert!(matches!(
+            resolver.resolve(&ui, "a", true),
+            Err(FxError::Canceled)
+        ));
+
+        let mut resolver = Resolver::new(OnConflict::Ask);
+        resolver.apply_to_all(Resolution::Merge);
+        assert_eq!(resolver.files, None);
+        resolver.apply_to_all(Resolution::Rename);
+        assert_eq!(resolver.files, Some(Resolution::Rename));
+        assert_eq!(resolver.dirs, Some(Resolution::Rename));
+    }
+}
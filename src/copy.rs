@@ -0,0 +1,614 @@
+use super::conflict::Resolution;
+use super::errors::FxError;
+use super::functions::{rename_dir, rename_file, tree_workers};
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+#[cfg(target_family = "unix")]
+use nix::sys::stat::{utimensat, UtimensatFlags};
+#[cfg(target_family = "unix")]
+use nix::sys::time::TimeSpec;
+#[cfg(target_family = "unix")]
+use std::os::unix::fs::MetadataExt;
+
+/// Files smaller than this are copied at once, as the progress of them is not worth showing.
+const MIN_SIZE_TO_COPY_IN_CHUNKS: u64 = 64_000_000;
+const CHUNK_SIZE: usize = 1 << 20;
+/// Files of a directory are copied in parallel if each thread gets at least this many.
+const MIN_FILES_PER_WORKER: usize = 8;
+
+/// Copy the file or directory recursively, calling `progress` with the bytes copied.
+/// Symlinks are recreated as symlinks instead of copying their targets.
+/// If `preserve` is true, the permissions and the timestamps are kept as well.
+/// If it fails or `progress` returns `FxError::Canceled`, the partially copied target
+/// is removed, unless it was there before.
+/// Many files are copied in parallel, and then `progress` is called by one thread at a time.
+pub fn copy_tree<F>(from: &Path, to: &Path, preserve: bool, mut progress: F) -> Result<(), FxError>
+where
+    F: FnMut(u64) -> Result<(), FxError> + Send,
+{
+    let existed = fs::symlink_metadata(to).is_ok();
+    let result = copy_entries(from, to, preserve, &mut progress);
+    if result.is_err() && !existed {
+        if fs::symlink_metadata(to).is_ok_and(|metadata| metadata.is_dir()) {
+            let _ = fs::remove_dir_all(to);
+        } else {
+            let _ = fs::remove_file(to);
+        }
+    }
+    result
+}
+
+fn copy_entries<F>(from: &Path, to: &Path, preserve: bool, progress: &mut F) -> Result<(), FxError>
+where
+    F: FnMut(u64) -> Result<(), FxError> + Send,
+{
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(from) {
+        let entry = entry?;
+        let entry_path = entry.path();
+        let target = match entry_path.strip_prefix(from) {
+            Ok(child) if child.as_os_str().is_empty() => to.to_path_buf(),
+            Ok(child) => to.join(child),
+            Err(_) => continue,
+        };
+        if entry.file_type().is_dir() {
+            fs::create_dir(&target)?;
+            if preserve {
+                dirs.push((entry_path.to_path_buf(), target));
+            }
+        } else if !entry.file_type().is_file() && !entry.file_type().is_symlink() {
+            return Err(FxError::SpecialFile(entry_path.to_owned()));
+        } else {
+            files.push((entry_path.to_path_buf(), target));
+        }
+    }
+    copy_files(&files, preserve, progress)?;
+    //Copying the contents updates the mtime of the directory, and a read-only directory
+    //cannot be filled, so set them from the deepest after all.
+    for (from, to) in dirs.iter().rev() {
+        let metadata = fs::metadata(from)?;
+        fs::set_permissions(to, metadata.permissions())?;
+        set_times(&metadata, to)?;
+    }
+    Ok(())
+}
+
+/// Copy the pairs of files into the existing directories.
+/// Each thread takes the next file; when one fails, the others stop after the current file.
+fn copy_files<F>(
+    files: &[(PathBuf, PathBuf)],
+    preserve: bool,
+    progress: &mut F,
+) -> Result<(), FxError>
+where
+    F: FnMut(u64) -> Result<(), FxError> + Send,
+{
+    let workers = tree_workers().min(files.len() / MIN_FILES_PER_WORKER);
+    if workers <= 1 {
+        for (from, to) in files {
+            copy_one(from, to, preserve, &mut *progress)?;
+        }
+        return Ok(());
+    }
+
+    let progress = Mutex::new(progress);
+    let next = AtomicUsize::new(0);
+    let error: Mutex<Option<FxError>> = Mutex::new(None);
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                let report = |bytes| match progress.lock() {
+                    Ok(mut progress) => progress(bytes),
+                    Err(_) => Err(FxError::Canceled),
+                };
+                while let Some((from, to)) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if error.lock().map_or(true, |error| error.is_some()) {
+                        break;
+                    }
+                    if let Err(e) = copy_one(from, to, preserve, report) {
+                        if let Ok(mut error) = error.lock() {
+                            //Canceled by the user takes precedence, to remove the copied ones.
+                            if error.is_none() || matches!(e, FxError::Canceled) {
+                                *error = Some(e);
+                            }
+                        }
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    match error.into_inner() {
+        Ok(Some(e)) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn copy_one<F>(from: &Path, to: &Path, preserve: bool, progress: F) -> Result<(), FxError>
+where
+    F: FnMut(u64) -> Result<(), FxError>,
+{
+    match copy_file(from, to, preserve, progress) {
+        Ok(()) => Ok(()),
+        Err(FxError::Canceled) => Err(FxError::Canceled),
+        Err(_) => Err(FxError::PutItem(from.to_owned())),
+    }
+}
+
+/// Merge the directory into the existing one. Entries not in `to` are copied, and for
+/// existing ones `resolve` is called with the path and whether both are directories,
+/// which can be merged recursively. Existing ones to overwrite are passed to `trash` first,
+/// which moves them out of the way.
+/// The pairs of the source and the created or overwritten path are added to `put`
+/// even if it fails, to undo and redo them.
+pub fn merge_tree<F, R, T>(
+    from: &Path,
+    to: &Path,
+    preserve: bool,
+    progress: &mut F,
+    resolve: &mut R,
+    trash: &mut T,
+    put: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(), FxError>
+where
+    F: FnMut(u64) -> Result<(), FxError> + Send,
+    R: FnMut(&Path, bool) -> Result<Resolution, FxError>,
+    T: FnMut(&Path) -> Result<(), FxError>,
+{
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let src = entry.path();
+        let is_dir = entry.file_type()?.is_dir();
+        let name = entry.file_name();
+        let mut target = to.join(&name);
+        if let Ok(metadata) = fs::symlink_metadata(&target) {
+            match resolve(&target, is_dir && metadata.is_dir())? {
+                Resolution::Merge => {
+                    merge_tree(&src, &target, preserve, progress, resolve, trash, put)?;
+                    continue;
+                }
+                Resolution::Skip => continue,
+                Resolution::Overwrite => trash(&target)?,
+                Resolution::Rename => {
+                    let mut name_set = BTreeSet::new();
+                    for entry in fs::read_dir(to)? {
+                        name_set.insert(entry?.file_name());
+                    }
+                    target = to.join(if is_dir {
+                        rename_dir(&name, &name_set)
+                    } else {
+                        rename_file(&name, &name_set)
+                    });
+                }
+            }
+        }
+        let copied = if is_dir {
+            copy_tree(&src, &target, preserve, &mut *progress)
+        } else {
+            copy_file(&src, &target, preserve, &mut *progress)
+        };
+        match copied {
+            Ok(()) => put.push((src, target)),
+            Err(FxError::Canceled) => return Err(FxError::Canceled),
+            Err(_) => return Err(FxError::PutItem(src)),
+        }
+    }
+    Ok(())
+}
+
+/// Copy a single file, or recreate the symlink.
+/// `fs::copy` keeps the permissions, so `preserve` adds the timestamps.
+/// Reading a FIFO or a device never ends, so they are refused.
+pub fn copy_file<F>(from: &Path, to: &Path, preserve: bool, mut progress: F) -> Result<(), FxError>
+where
+    F: FnMut(u64) -> Result<(), FxError>,
+{
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.file_type().is_symlink() {
+        copy_symlink(from, to)?;
+    } else if metadata.is_file() {
+        if let Err(e) = copy_contents(from, to, &mut progress) {
+            if let FxError::Canceled = e {
+                let _ = fs::remove_file(to);
+            }
+            return Err(e);
+        }
+    } else {
+        return Err(FxError::SpecialFile(from.to_owned()));
+    }
+    if preserve {
+        set_times(&metadata, to)?;
+    }
+    Ok(())
+}
+
+/// Copy the contents and the permissions of the regular file.
+/// On linux, first try to clone it (reflink on btrfs or XFS), which shares the data blocks
+/// and ends instantly even for huge files. Otherwise small files are copied by `fs::copy`,
+/// which already uses `copy_file_range` on linux and clones the file on APFS,
+/// and large ones in chunks to report the progress while copying.
+fn copy_contents<F>(from: &Path, to: &Path, progress: &mut F) -> Result<(), FxError>
+where
+    F: FnMut(u64) -> Result<(), FxError>,
+{
+    let len = fs::metadata(from)?.len();
+    #[cfg(target_os = "linux")]
+    if reflink(from, to).is_ok() {
+        return progress(len);
+    }
+    if len < MIN_SIZE_TO_COPY_IN_CHUNKS {
+        fs::copy(from, to)?;
+        progress(len)
+    } else {
+        copy_in_chunks(from, to, progress)
+    }
+}
+
+fn copy_in_chunks<F>(from: &Path, to: &Path, progress: &mut F) -> Result<(), FxError>
+where
+    F: FnMut(u64) -> Result<(), FxError>,
+{
+    let mut src = fs::File::open(from)?;
+    let permissions = src.metadata()?.permissions();
+    let mut dest = fs::File::create(to)?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let n = match src.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        dest.write_all(&buf[..n])?;
+        progress(n as u64)?;
+    }
+    dest.set_permissions(permissions)?;
+    Ok(())
+}
+
+/// Clone the file by FICLONE. Fails if the filesystem does not support it,
+/// or the files are on different filesystems.
+#[cfg(target_os = "linux")]
+fn reflink(from: &Path, to: &Path) -> std::io::Result<()> {
+    use nix::libc;
+    use std::os::fd::AsRawFd;
+
+    let src = fs::File::open(from)?;
+    let permissions = src.metadata()?.permissions();
+    let dest = fs::File::options().write(true).create_new(true).open(to)?;
+    let result = unsafe { libc::ioctl(dest.as_raw_fd(), libc::FICLONE as _, src.as_raw_fd()) };
+    if result == -1 {
+        let e = std::io::Error::last_os_error();
+        drop(dest);
+        let _ = fs::remove_file(to);
+        return Err(e);
+    }
+    dest.set_permissions(permissions)
+}
+
+#[cfg(target_family = "unix")]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(target_family = "unix"))]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    let link = fs::read_link(from)?;
+    if from.is_dir() {
+        std::os::windows::fs::symlink_dir(link, to)
+    } else {
+        std::os::windows::fs::symlink_file(link, to)
+    }
+}
+
+/// Set the access and modified time of `to`, not following the symlink.
+#[cfg(target_family = "unix")]
+fn set_times(metadata: &fs::Metadata, to: &Path) -> Result<(), FxError> {
+    let atime = TimeSpec::new(metadata.atime(), metadata.atime_nsec());
+    let mtime = TimeSpec::new(metadata.mtime(), metadata.mtime_nsec());
+    //`From<Errno>` is only implemented on linux and netbsd.
+    utimensat(None, to, &atime, &mtime, UtimensatFlags::NoFollowSymlink)
+        .map_err(|e| FxError::Io(e.to_string()))
+}
+
+/// Only the modified time of files is set here.
+#[cfg(not(target_family = "unix"))]
+fn set_times(metadata: &fs::Metadata, to: &Path) -> Result<(), FxError> {
+    if metadata.is_file() {
+        fs::File::options()
+            .write(true)
+            .open(to)?
+            .set_modified(metadata.modified()?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("sub/a.txt"), "a").unwrap();
+        #[cfg(target_family = "unix")]
+        std::os::unix::fs::symlink("sub/a.txt", src.join("link")).unwrap();
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        fs::File::options()
+            .write(true)
+            .open(src.join("sub/a.txt"))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let dest = dir.path().join("dest");
+        let mut copied = 0;
+        copy_tree(&src, &dest, true, |bytes| {
+            copied += bytes;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(dest.join("sub/a.txt")).unwrap(), "a");
+        assert_eq!(copied, 1);
+        assert_eq!(
+            fs::metadata(dest.join("sub/a.txt"))
+                .unwrap()
+                .modified()
+                .unwrap(),
+            old
+        );
+        #[cfg(target_family = "unix")]
+        {
+            assert!(fs::symlink_metadata(dest.join("link"))
+                .unwrap()
+                .file_type()
+                .is_symlink());
+            assert_eq!(
+                fs::read_link(dest.join("link")).unwrap(),
+                Path::new("sub/a.txt")
+            );
+        }
+
+        // Without preserving, the copy gets the current time.
+        copy_file(
+            &src.join("sub/a.txt"),
+            &dir.path().join("b.txt"),
+            false,
+            |_| Ok(()),
+        )
+        .unwrap();
+        assert_ne!(
+            fs::metadata(dir.path().join("b.txt"))
+                .unwrap()
+                .modified()
+                .unwrap(),
+            old
+        );
+        // The destination must not exist.
+        assert!(copy_tree(&src, &dest, true, |_| Ok(())).is_err());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_copy_contents() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("a");
+        fs::write(&from, "contents").unwrap();
+        fs::set_permissions(&from, fs::Permissions::from_mode(0o640)).unwrap();
+
+        // Whether cloned or copied, the result is the same.
+        let to = dir.path().join("b");
+        copy_contents(&from, &to, &mut |_| Ok(())).unwrap();
+        assert_eq!(fs::read_to_string(&to).unwrap(), "contents");
+        assert_eq!(
+            fs::metadata(&to).unwrap().permissions().mode() & 0o777,
+            0o640
+        );
+
+        let to = dir.path().join("c");
+        let mut copied = 0;
+        copy_in_chunks(&from, &to, &mut |bytes| {
+            copied += bytes;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&to).unwrap(), "contents");
+        assert_eq!(copied, 8);
+        assert_eq!(
+            fs::metadata(&to).unwrap().permissions().mode() & 0o777,
+            0o640
+        );
+    }
+
+    #[test]
+    fn test_cancel_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("a.txt"), "a").unwrap();
+        fs::write(src.join("sub/b.txt"), "b").unwrap();
+
+        let dest = dir.path().join("dest");
+        assert!(matches!(
+            copy_tree(&src, &dest, true, |_| Err(FxError::Canceled)),
+            Err(FxError::Canceled)
+        ));
+        assert!(!dest.exists());
+        let dest = dir.path().join("c.txt");
+        assert!(matches!(
+            copy_file(&src.join("a.txt"), &dest, true, |_| Err(FxError::Canceled)),
+            Err(FxError::Canceled)
+        ));
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_copy_many_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        for i in 0..10 {
+            fs::create_dir_all(src.join(i.to_string())).unwrap();
+            for j in 0..20 {
+                fs::write(src.join(format!("{}/{}", i, j)), "ab").unwrap();
+            }
+        }
+
+        let dest = dir.path().join("dest");
+        let mut copied = 0;
+        copy_tree(&src, &dest, true, |bytes| {
+            copied += bytes;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(copied, 400);
+        assert_eq!(fs::read_to_string(dest.join("9/19")).unwrap(), "ab");
+
+        let dest = dir.path().join("canceled");
+        assert!(matches!(
+            copy_tree(&src, &dest, true, |_| Err(FxError::Canceled)),
+            Err(FxError::Canceled)
+        ));
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_merge_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("a.txt"), "new a").unwrap();
+        fs::write(src.join("b.txt"), "new b").unwrap();
+        fs::write(src.join("sub/c.txt"), "new c").unwrap();
+        fs::write(src.join("sub/d.txt"), "new d").unwrap();
+        let dest = dir.path().join("dest");
+        fs::create_dir_all(dest.join("sub")).unwrap();
+        fs::write(dest.join("a.txt"), "old a").unwrap();
+        fs::write(dest.join("b.txt"), "old b").unwrap();
+        fs::write(dest.join("sub/c.txt"), "old c").unwrap();
+        fs::write(dest.join("keep.txt"), "keep").unwrap();
+
+        let mut put = vec![];
+        merge_tree(
+            &src,
+            &dest,
+            true,
+            &mut |_| Ok(()),
+            &mut |path: &Path, dirs| {
+                Ok(match path.file_name().unwrap().to_str().unwrap() {
+                    "sub" if dirs => Resolution::Merge,
+                    "a.txt" => Resolution::Overwrite,
+                    "b.txt" => Resolution::Skip,
+                    _ => Resolution::Rename,
+                })
+            },
+            &mut |path: &Path| {
+                fs::rename(path, path.with_extension("bak"))?;
+                Ok(())
+            },
+            &mut put,
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "new a");
+        assert_eq!(fs::read_to_string(dest.join("a.bak")).unwrap(), "old a");
+        assert_eq!(fs::read_to_string(dest.join("b.txt")).unwrap(), "old b");
+        assert_eq!(fs::read_to_string(dest.join("sub/c.txt")).unwrap(), "old c");
+        assert_eq!(
+            fs::read_to_string(dest.join("sub/c_1.txt")).unwrap(),
+            "new c"
+        );
+        assert_eq!(fs::read_to_string(dest.join("sub/d.txt")).unwrap(), "new d");
+        assert_eq!(fs::read_to_string(dest.join("keep.txt")).unwrap(), "keep");
+        put.sort();
+        assert_eq!(
+            put,
+            vec![
+                (src.join("a.txt"), dest.join("a.txt")),
+                (src.join("sub/c.txt"), dest.join("sub/c_1.txt")),
+                (src.join("sub/d.txt"), dest.join("sub/d.txt"))
+            ]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_merge_non_utf8_name() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dest) = (dir.path().join("src"), dir.path().join("dest"));
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        let name = OsStr::from_bytes(b"caf\xe9.txt");
+        fs::write(src.join(name), "new").unwrap();
+        fs::write(dest.join(name), "old").unwrap();
+        //Another name shown the same lossily does not conflict.
+        fs::write(src.join(OsStr::from_bytes(b"caf\xea.txt")), "other").unwrap();
+
+        let mut put = vec![];
+        merge_tree(
+            &src,
+            &dest,
+            false,
+            &mut |_| Ok(()),
+            &mut |_: &Path, _| Ok(Resolution::Rename),
+            &mut |_: &Path| Ok(()),
+            &mut put,
+        )
+        .unwrap();
+        let mut put: Vec<PathBuf> = put.into_iter().map(|(_, to)| to).collect();
+        put.sort();
+        assert_eq!(
+            put,
+            vec![
+                dest.join(OsStr::from_bytes(b"caf\xe9_1.txt")),
+                dest.join(OsStr::from_bytes(b"caf\xea.txt"))
+            ]
+        );
+        assert_eq!(fs::read_to_string(dest.join(name)).unwrap(), "old");
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_copy_special_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir(&src).unwrap();
+        nix::unistd::mkfifo(&src.join("fifo"), nix::sys::stat::Mode::S_IRWXU).unwrap();
+
+        assert!(matches!(
+            copy_file(
+                &src.join("fifo"),
+                &dir.path().join("fifo"),
+                true,
+                |_| Ok(())
+            ),
+            Err(FxError::SpecialFile(_))
+        ));
+        //The directories created before it is found are removed.
+        fs::create_dir_all(src.join("sub/deep")).unwrap();
+        fs::write(src.join("sub/a.txt"), "a").unwrap();
+        let dest = dir.path().join("dest");
+        assert!(matches!(
+            copy_tree(&src, &dest, true, |_| Ok(())),
+            Err(FxError::SpecialFile(_))
+        ));
+        assert!(!dest.exists());
+
+        //The existing target is left as it is.
+        fs::create_dir(&dest).unwrap();
+        assert!(copy_tree(&src, &dest, true, |_| Ok(())).is_err());
+        assert!(dest.exists());
+    }
+}
@@ -0,0 +1,167 @@
+use super::errors::FxError;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+/// Rows are counted up to this, shown as `10000+` beyond it.
+#[cfg(feature = "sqlite")]
+const MAX_ROWS_COUNTED: u64 = 10_000;
+/// Rows of the tables after this are not counted, as they do not fit in the preview anyway.
+#[cfg(feature = "sqlite")]
+const MAX_TABLES_COUNTED: usize = 100;
+/// Databases of which the tables are kept in `TableCache`.
+const MAX_CACHED_DATABASES: usize = 16;
+
+/// Check the header of the file, so that databases with any extension
+/// (`.sqlite`, `.db`, or none) are detected.
+pub fn is_sqlite(path: &Path) -> bool {
+    let mut header = [0; 16];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && &header == SQLITE_HEADER
+}
+
+/// The tables listed by `list_tables`, by the path and the modified time of the database,
+/// so that moving the cursor over the database does not query it again.
+#[derive(Debug, Default)]
+pub struct TableCache(RefCell<BTreeMap<PathBuf, (Option<SystemTime>, String)>>);
+
+impl TableCache {
+    pub fn list_tables(&self, path: &Path) -> Result<String, FxError> {
+        let modified = std::fs::metadata(path)?.modified().ok();
+        if let Some((cached_at, tables)) = self.0.borrow().get(path) {
+            if *cached_at == modified {
+                return Ok(tables.clone());
+            }
+        }
+        let tables = list_tables(path)?;
+        let mut cache = self.0.borrow_mut();
+        if cache.len() >= MAX_CACHED_DATABASES && !cache.contains_key(path) {
+            cache.pop_first();
+        }
+        cache.insert(path.to_path_buf(), (modified, tables.clone()));
+        Ok(tables)
+    }
+}
+
+/// List the tables and the number of their rows as TSV with the header,
+/// opening the database read-only.
+#[cfg(feature = "sqlite")]
+pub fn list_tables(path: &Path) -> Result<String, FxError> {
+    use rusqlite::{Connection, OpenFlags};
+
+    let to_error = |e: rusqlite::Error| FxError::Io(e.to_string());
+    let connection = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(to_error)?;
+    let mut statement = connection
+        .prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        )
+        .map_err(to_error)?;
+    let names = statement
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(to_error)?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(to_error)?;
+
+    let mut result = "table\trows\n".to_owned();
+    for (i, name) in names.iter().enumerate() {
+        let rows = if i < MAX_TABLES_COUNTED {
+            let count: u64 = connection
+                .query_row(&count_query(name), [], |row| row.get(0))
+                .map_err(to_error)?;
+            if count > MAX_ROWS_COUNTED {
+                format!("{}+", MAX_ROWS_COUNTED)
+            } else {
+                count.to_string()
+            }
+        } else {
+            String::new()
+        };
+        result.push_str(&format!("{}\t{}\n", escape_name(name), rows));
+    }
+    Ok(result)
+}
+
+/// Built without the `sqlite` feature.
+#[cfg(not(feature = "sqlite"))]
+pub fn list_tables(_path: &Path) -> Result<String, FxError> {
+    Err(FxError::Io(
+        "SQLite database (built without the sqlite feature)".to_owned(),
+    ))
+}
+
+/// Count the rows of the table, up to one more than `MAX_ROWS_COUNTED`.
+#[cfg(feature = "sqlite")]
+fn count_query(name: &str) -> String {
+    format!(
+        "SELECT count(*) FROM (SELECT 1 FROM \"{}\" LIMIT {})",
+        name.replace('"', "\"\""),
+        MAX_ROWS_COUNTED + 1
+    )
+}
+
+/// Tabs and newlines in the name would break the rows of the TSV.
+#[cfg(feature = "sqlite")]
+fn escape_name(name: &str) -> String {
+    name.replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_sqlite() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("a.db");
+        let mut contents = SQLITE_HEADER.to_vec();
+        contents.extend([0; 84]);
+        std::fs::write(&db, contents).unwrap();
+        assert!(is_sqlite(&db));
+
+        let text = dir.path().join("b.db");
+        std::fs::write(&text, "SQLite").unwrap();
+        assert!(!is_sqlite(&text));
+        assert!(!is_sqlite(&dir.path().join("none")));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_list_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("a.db");
+        let connection = rusqlite::Connection::open(&db).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE users (id INTEGER);
+                 INSERT INTO users VALUES (1), (2);
+                 CREATE TABLE \"it's \"\"q\"\"\nline\" (id INTEGER);
+                 CREATE TABLE many (id INTEGER);
+                 WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 10005)
+                 INSERT INTO many SELECT i FROM n;",
+            )
+            .unwrap();
+        drop(connection);
+        let tables = "table\trows\nit's \"q\"\\nline\t0\nmany\t10000+\nusers\t2\n";
+        assert_eq!(list_tables(&db).unwrap(), tables);
+        assert!(is_sqlite(&db));
+
+        let cache = TableCache::default();
+        assert_eq!(cache.list_tables(&db).unwrap(), tables);
+        assert!(cache.0.borrow().contains_key(&db));
+        // Read-only: the database is not changed or created.
+        assert!(list_tables(&dir.path().join("none.db")).is_err());
+        assert!(!dir.path().join("none.db").exists());
+    }
+}
@@ -0,0 +1,63 @@
+use super::functions::to_proper_size;
+use std::path::Path;
+
+/// Free and total space of the filesystem containing the path, in bytes.
+#[cfg(target_family = "unix")]
+#[allow(clippy::unnecessary_cast)]
+pub fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    let stat = nix::sys::statvfs::statvfs(path).ok()?;
+    let fragment = stat.fragment_size() as u64;
+    Some((
+        stat.blocks_available() as u64 * fragment,
+        stat.blocks() as u64 * fragment,
+    ))
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn disk_space(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// Shown in the header, so that one knows when deleting or putting large items is risky.
+/// The size of the trash is omitted until calculated in the background.
+pub fn format_disk_status(space: Option<(u64, u64)>, trash_size: Option<u64>) -> String {
+    let trash = trash_size.map(|size| format!("trash: {}", to_proper_size(size)));
+    match (space, trash) {
+        (Some((free, total)), Some(trash)) => format!(
+            " [free: {}/{} {}]",
+            to_proper_size(free),
+            to_proper_size(total),
+            trash
+        ),
+        (Some((free, total)), None) => format!(
+            " [free: {}/{}]",
+            to_proper_size(free),
+            to_proper_size(total)
+        ),
+        (None, Some(trash)) => format!(" [{}]", trash),
+        (None, None) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_disk_status() {
+        assert_eq!(
+            format_disk_status(Some((3_000_000_000, 100_000_000_000)), Some(2000)),
+            " [free: 3GB/100GB trash: 2KB]"
+        );
+        assert_eq!(
+            format_disk_status(Some((1000, 2000)), None),
+            " [free: 1KB/2KB]"
+        );
+        assert_eq!(format_disk_status(None, Some(0)), " [trash: 0B]");
+        assert_eq!(format_disk_status(None, None), "");
+        let dir = tempfile::tempdir().unwrap();
+        if let Some((free, total)) = disk_space(dir.path()) {
+            assert!(free <= total);
+        }
+    }
+}
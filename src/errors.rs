@@ -15,11 +15,16 @@ pub enum FxError {
     Encode,
     PutItem(PathBuf),
     RemoveItem(PathBuf),
-    TooSmallWindowSize,
+    SpecialFile(PathBuf),
     Log(String),
     Unpack(String),
     InvalidPath,
     Panic,
+    Script(String),
+    Pattern(String),
+    Filter(String),
+    Keys(String),
+    Canceled,
     #[cfg(any(target_os = "linux", target_os = "netbsd"))]
     Nix(String),
     #[default]
@@ -46,11 +51,18 @@ impl std::fmt::Display for FxError {
             FxError::Encode => "Error: Incorrect encoding".to_owned(),
             FxError::PutItem(s) => format!("Error: Cannot copy -> {:?}", s),
             FxError::RemoveItem(s) => format!("Error: Cannot remove -> {:?}", s),
-            FxError::TooSmallWindowSize => "Error: Too small window size".to_owned(),
+            FxError::SpecialFile(s) => {
+                format!("Error: Cannot copy FIFO, socket or device file -> {:?}", s)
+            }
             FxError::Log(s) => s.to_owned(),
             FxError::Unpack(s) => s.to_owned(),
             FxError::InvalidPath => "Error: Path may contain invalid unicode".to_owned(),
             FxError::Panic => "Error: felix panicked".to_owned(),
+            FxError::Script(s) => s.to_owned(),
+            FxError::Pattern(s) => format!("Error: Invalid pattern -> {}", s),
+            FxError::Filter(s) => format!("Error: Invalid filter -> {}", s),
+            FxError::Keys(s) => format!("Error: Invalid keys -> {}", s),
+            FxError::Canceled => "Canceled.".to_owned(),
             #[cfg(any(target_os = "linux", target_os = "netbsd"))]
             FxError::Nix(s) => s.to_owned(),
             FxError::Unknown => "Unknown error.".to_owned(),
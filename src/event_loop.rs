@@ -0,0 +1,49 @@
+use felix::errors::FxError;
+use felix::events::AppEvent;
+
+use crossterm::event;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+/// Interval to check messages from other threads while waiting for the input.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Merge the terminal input and messages sent from other threads
+/// (watchers, background jobs) into one stream of AppEvent.
+/// The terminal input is read in the main thread, so that modes reading keys directly
+/// (e.g. the command line) keep working.
+pub struct EventLoop {
+    sender: Sender<AppEvent>,
+    receiver: Receiver<AppEvent>,
+}
+
+impl Default for EventLoop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventLoop {
+    pub fn new() -> Self {
+        let (sender, receiver) = channel();
+        EventLoop { sender, receiver }
+    }
+
+    /// Sender for other threads to dispatch events to the main loop.
+    pub fn sender(&self) -> Sender<AppEvent> {
+        self.sender.clone()
+    }
+
+    /// Wait for the next event.
+    /// Messages from other threads take precedence over the terminal input.
+    pub fn next(&self) -> Result<AppEvent, FxError> {
+        loop {
+            if let Ok(ev) = self.receiver.try_recv() {
+                return Ok(ev);
+            }
+            if event::poll(POLL_INTERVAL)? {
+                return Ok(AppEvent::Input(event::read()?));
+            }
+        }
+    }
+}
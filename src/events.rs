@@ -0,0 +1,24 @@
+use super::errors::FxError;
+
+use crossterm::event::Event;
+
+/// Events dispatched to the main loop.
+#[derive(Debug)]
+pub enum AppEvent {
+    /// Terminal events (key, resize, etc.).
+    Input(Event),
+    /// The config file has been modified.
+    ConfigUpdated,
+    /// The total size of the trash directory, calculated on startup.
+    TrashSize(u64),
+    /// Sent periodically to check the changes of the current directory.
+    Tick,
+    /// Items in the current directory have been changed by other programs.
+    DirChanged,
+    /// A background job has finished, completing `finished` of its items.
+    JobFinished {
+        id: usize,
+        finished: usize,
+        result: Result<(), FxError>,
+    },
+}
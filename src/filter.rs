@@ -0,0 +1,332 @@
+use super::errors::FxError;
+use super::item::{FileType, ItemInfo};
+use super::pattern::Pattern;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+const MINUTE: f64 = 60.0;
+const HOUR: f64 = 3_600.0;
+const DAY: f64 = 86_400.0;
+const WEEK: f64 = 604_800.0;
+const YEAR: f64 = 31_536_000.0;
+
+/// Condition on the size or the modified time of the item.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Predicate {
+    /// Larger than the bytes.
+    Larger(u64),
+    /// Smaller than the bytes.
+    Smaller(u64),
+    /// Modified within the seconds.
+    Newer(i64),
+    /// Modified before the seconds.
+    Older(i64),
+    /// Modified at or after the unix timestamp.
+    Since(i64),
+}
+
+impl Predicate {
+    /// Parse `>10M`, `<1.5GB`, `<7d`, `>2w`, `since:2024-01-01` etc.
+    /// Size units are B, K(B), M(B), G(B) and T(B); time units are s, min, h, d, w and y.
+    /// `<` with time means "modified within", `>` means "modified before".
+    pub fn parse(s: &str) -> Result<Self, FxError> {
+        let invalid = || FxError::Filter(s.to_owned());
+        if let Some(date) = s.strip_prefix("since:") {
+            return parse_date(date).map(Predicate::Since).ok_or_else(invalid);
+        }
+        let mut chars = s.chars();
+        let larger = match chars.next() {
+            Some('>') => true,
+            Some('<') => false,
+            _ => return Err(invalid()),
+        };
+        let rest = chars.as_str();
+
+        if let Some(seconds) = parse_duration(rest) {
+            return Ok(if larger {
+                Predicate::Older(seconds)
+            } else {
+                Predicate::Newer(seconds)
+            });
+        }
+        let bytes = parse_size(rest).ok_or_else(invalid)?;
+        Ok(if larger {
+            Predicate::Larger(bytes)
+        } else {
+            Predicate::Smaller(bytes)
+        })
+    }
+
+    /// The size of a directory does not reflect its contents,
+    /// so directories always pass the size predicates.
+    pub fn is_match(&self, item: &ItemInfo, now: &DateTime<Local>) -> bool {
+        let is_dir = item.file_type == FileType::Directory || item.symlink_dir_path.is_some();
+        match self {
+            Predicate::Larger(bytes) => is_dir || item.file_size > *bytes,
+            Predicate::Smaller(bytes) => is_dir || item.file_size < *bytes,
+            Predicate::Newer(seconds) => age(item, now).is_some_and(|age| age < *seconds),
+            Predicate::Older(seconds) => age(item, now).is_some_and(|age| age > *seconds),
+            Predicate::Since(timestamp) => modified_at(item).is_some_and(|t| t >= *timestamp),
+        }
+    }
+}
+
+/// Split `10M` into `10` and `M`.
+fn split_unit(s: &str) -> (&str, &str) {
+    let unit_start = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    s.split_at(unit_start)
+}
+
+/// Parse the size such as `100M` or `4k` into bytes.
+pub fn parse_size(s: &str) -> Option<u64> {
+    let (number, unit) = split_unit(s);
+    let number: f64 = number.parse().ok()?;
+    let bytes = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" => 1e3,
+        "m" | "mb" => 1e6,
+        "g" | "gb" => 1e9,
+        "t" | "tb" => 1e12,
+        _ => return None,
+    };
+    Some((number * bytes) as u64)
+}
+
+/// Parse the duration such as `2h` or `1.5d` into seconds.
+pub fn parse_duration(s: &str) -> Option<i64> {
+    let (number, unit) = split_unit(s);
+    let number: f64 = number.parse().ok()?;
+    let seconds = match unit {
+        "s" => 1.0,
+        "min" => MINUTE,
+        "h" => HOUR,
+        "d" => DAY,
+        "w" => WEEK,
+        "y" => YEAR,
+        _ => return None,
+    };
+    Some((number * seconds) as i64)
+}
+
+/// Parse `2024-01-01` or `2024-01-01T12:00` in the local time into the unix timestamp.
+pub fn parse_date(s: &str) -> Option<i64> {
+    let datetime = match NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M") {
+        Ok(datetime) => datetime,
+        Err(_) => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0)?,
+    };
+    Some(Local.from_local_datetime(&datetime).earliest()?.timestamp())
+}
+
+/// Unix timestamp of the modified time.
+fn modified_at(item: &ItemInfo) -> Option<i64> {
+    Some(DateTime::<Local>::from(item.modified?).timestamp())
+}
+
+/// Seconds since the item was modified.
+fn age(item: &ItemInfo, now: &DateTime<Local>) -> Option<i64> {
+    Some(now.timestamp() - modified_at(item)?)
+}
+
+/// Predicates to narrow the list. Items have to match all of them.
+/// `name:{pattern}` matches the item name by the glob or `/regex/`,
+/// or by any of the comma-separated globs (e.g. `name:*.png,*.jpg`),
+/// and `-r` searches the subdirectories recursively.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    text: String,
+    predicates: Vec<Predicate>,
+    names: Vec<Vec<Pattern>>,
+    pub recursive: bool,
+}
+
+impl Filter {
+    /// Parse whitespace-separated predicates (e.g. `>10M <7d name:*.log -r`).
+    pub fn parse(s: &str, ignore_case: bool) -> Result<Self, FxError> {
+        let mut predicates = vec![];
+        let mut names = vec![];
+        let mut recursive = false;
+        for token in s.split_whitespace() {
+            if token == "-r" {
+                recursive = true;
+            } else if let Some(pattern) = token.strip_prefix("name:") {
+                // Commas can be in the regex.
+                let alternatives = if pattern.starts_with('/') {
+                    vec![Pattern::new(pattern, ignore_case)?]
+                } else {
+                    pattern
+                        .split(',')
+                        .map(|glob| Pattern::new(glob, ignore_case))
+                        .collect::<Result<_, _>>()?
+                };
+                names.push(alternatives);
+            } else {
+                predicates.push(Predicate::parse(token)?);
+            }
+        }
+        if predicates.is_empty() && names.is_empty() {
+            return Err(FxError::Filter(s.to_owned()));
+        }
+        Ok(Filter {
+            text: s.split_whitespace().collect::<Vec<_>>().join(" "),
+            predicates,
+            names,
+            recursive,
+        })
+    }
+
+    /// Whether the size or the time of the items is needed.
+    pub fn has_predicates(&self) -> bool {
+        !self.predicates.is_empty()
+    }
+
+    /// The name patterns are matched against the file name,
+    /// not the relative path shown in the recursive search.
+    pub fn is_match(&self, item: &ItemInfo, now: &DateTime<Local>) -> bool {
+        let name = item
+            .file_path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        self.names
+            .iter()
+            .all(|alternatives| alternatives.iter().any(|p| p.is_match(&name)))
+            && self.predicates.iter().all(|p| p.is_match(item, now))
+    }
+}
+
+/// Filter applied and cleared by a key, set by `filter_presets` in the config
+/// as `{name}: {filter}` (e.g. `images: name:*.png,*.jpg`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterPreset {
+    pub name: String,
+    pub filter: String,
+}
+
+impl FilterPreset {
+    pub fn parse(s: &str) -> Result<Self, FxError> {
+        let invalid = || FxError::Filter(format!("{} (expected `{{name}}: {{filter}}`)", s));
+        let (name, filter) = s.split_once(':').ok_or_else(invalid)?;
+        let (name, filter) = (name.trim(), filter.trim());
+        // Without the name, `name:*.png` would be split at `name:`.
+        if name.is_empty() || name == "name" || name == "since" {
+            return Err(invalid());
+        }
+        Filter::parse(filter, false)?;
+        Ok(FilterPreset {
+            name: name.to_owned(),
+            filter: filter.to_owned(),
+        })
+    }
+}
+
+impl std::fmt::Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_parse_predicate() {
+        assert_eq!(
+            Predicate::parse(">10M").unwrap(),
+            Predicate::Larger(10_000_000)
+        );
+        assert_eq!(
+            Predicate::parse("<1.5kb").unwrap(),
+            Predicate::Smaller(1_500)
+        );
+        assert_eq!(Predicate::parse("<512").unwrap(), Predicate::Smaller(512));
+        assert_eq!(Predicate::parse("<7d").unwrap(), Predicate::Newer(604_800));
+        assert_eq!(Predicate::parse(">30min").unwrap(), Predicate::Older(1_800));
+        assert!(Predicate::parse("10M").is_err());
+        assert!(Predicate::parse(">").is_err());
+        assert!(Predicate::parse(">10X").is_err());
+        assert!(Filter::parse("  ", false).is_err());
+        assert_eq!(
+            Predicate::parse("since:2024-01-01").unwrap(),
+            Predicate::Since(parse_date("2024-01-01").unwrap())
+        );
+        assert!(Predicate::parse("since:yesterday").is_err());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_duration("2h"), Some(7_200));
+        assert_eq!(parse_duration("10M"), None);
+        assert_eq!(
+            parse_date("2024-01-01T12:30").unwrap() - parse_date("2024-01-01").unwrap(),
+            45_000
+        );
+        assert_eq!(parse_date("2024-13-01"), None);
+    }
+
+    #[test]
+    fn test_filter() {
+        let now = Local::now();
+        let modified = |days: i64| Some((now - Duration::days(days)).into());
+        let large_old = ItemInfo {
+            file_size: 20_000_000,
+            modified: modified(30),
+            ..Default::default()
+        };
+        let small_new = ItemInfo {
+            file_size: 100,
+            modified: modified(1),
+            ..Default::default()
+        };
+        let dir = ItemInfo {
+            file_type: FileType::Directory,
+            modified: modified(1),
+            ..Default::default()
+        };
+
+        let filter = Filter::parse(">10M", false).unwrap();
+        assert!(filter.is_match(&large_old, &now));
+        assert!(!filter.is_match(&small_new, &now));
+        assert!(filter.is_match(&dir, &now));
+
+        let filter = Filter::parse("<10M  <7d", false).unwrap();
+        assert_eq!(filter.to_string(), "<10M <7d");
+        assert!(!filter.is_match(&large_old, &now));
+        assert!(filter.is_match(&small_new, &now));
+        assert!(filter.is_match(&dir, &now));
+
+        let filter = Filter::parse(">7d", false).unwrap();
+        assert!(filter.is_match(&large_old, &now));
+        assert!(!filter.is_match(&dir, &now));
+
+        let since = (now - Duration::days(7)).format("%Y-%m-%d").to_string();
+        let filter = Filter::parse(&format!("since:{}", since), false).unwrap();
+        assert!(!filter.is_match(&large_old, &now));
+        assert!(filter.is_match(&small_new, &now));
+
+        let log = ItemInfo {
+            file_name: "sub/app.log".to_string(),
+            file_path: std::path::PathBuf::from("/tmp/sub/app.log"),
+            file_size: 20_000_000,
+            ..Default::default()
+        };
+        let filter = Filter::parse("name:*.log >10M -r", false).unwrap();
+        assert!(filter.recursive);
+        assert!(filter.is_match(&log, &now));
+        assert!(!filter.is_match(&large_old, &now));
+        assert!(!Filter::parse("name:app.* <7d", false)
+            .unwrap()
+            .is_match(&log, &now));
+        assert!(Filter::parse("-r", false).is_err());
+        let filter = Filter::parse("name:*.png,*.log", false).unwrap();
+        assert!(filter.is_match(&log, &now));
+        assert!(!Filter::parse("name:*.png,*.jpg", false)
+            .unwrap()
+            .is_match(&log, &now));
+    }
+}
@@ -0,0 +1,241 @@
+use super::errors::FxError;
+
+use std::io::Read;
+use std::path::Path;
+
+const TAG_TRUETYPE: [u8; 4] = [0x00, 0x01, 0x00, 0x00];
+const TAG_TRUETYPE_MAC: &[u8; 4] = b"true";
+const TAG_OPENTYPE: &[u8; 4] = b"OTTO";
+const TAG_COLLECTION: &[u8; 4] = b"ttcf";
+const TAG_WOFF: &[u8; 4] = b"wOFF";
+const TAG_WOFF2: &[u8; 4] = b"wOF2";
+const TAG_NAME: &[u8; 4] = b"name";
+
+/// Names shown in the preview, by the name ID.
+/// The typographic family and subfamily (16, 17) are preferred if present.
+const NAMES: [(&str, &[u16]); 4] = [
+    ("Family", &[16, 1]),
+    ("Style", &[17, 2]),
+    ("Full name", &[4]),
+    ("Version", &[5]),
+];
+/// Windows, English (United States).
+const LANGUAGE_EN_US: u16 = 0x0409;
+
+/// Check the signature of TrueType, OpenType, font collections and WOFF.
+pub fn is_font(p: &Path) -> bool {
+    let mut tag = [0; 4];
+    std::fs::File::open(p)
+        .and_then(|mut file| file.read_exact(&mut tag))
+        .is_ok()
+        && (tag == TAG_TRUETYPE
+            || [
+                TAG_TRUETYPE_MAC,
+                TAG_OPENTYPE,
+                TAG_COLLECTION,
+                TAG_WOFF,
+                TAG_WOFF2,
+            ]
+            .contains(&&tag))
+}
+
+/// Lines to show the format and the names of the font, read from the `name` table.
+pub fn describe_font(p: &Path) -> Result<Vec<String>, FxError> {
+    let data = std::fs::read(p)?;
+    let (format, name_table) = match data.get(..4) {
+        Some(tag) if tag == TAG_WOFF => ("WOFF".to_owned(), woff_table(&data, TAG_NAME)?),
+        Some(tag) if tag == TAG_WOFF2 => {
+            //Tables of WOFF2 are compressed by Brotli.
+            return Ok(vec!["Format:    WOFF2 (names not available)".to_owned()]);
+        }
+        Some(tag) if tag == TAG_COLLECTION => {
+            let count = read_u32(&data, 8)?;
+            let first = read_u32(&data, 12)? as usize;
+            (
+                format!("Font collection ({} fonts, showing the first)", count),
+                sfnt_table(&data, first, TAG_NAME)?.to_vec(),
+            )
+        }
+        Some(tag) if tag == TAG_OPENTYPE => (
+            "OpenType (CFF)".to_owned(),
+            sfnt_table(&data, 0, TAG_NAME)?.to_vec(),
+        ),
+        _ => (
+            "TrueType".to_owned(),
+            sfnt_table(&data, 0, TAG_NAME)?.to_vec(),
+        ),
+    };
+    let names = parse_names(&name_table)?;
+
+    let mut lines = vec![format!("{:<10} {}", "Format:", format)];
+    for (label, ids) in NAMES.iter() {
+        if let Some(name) = ids
+            .iter()
+            .find_map(|id| names.iter().find(|(name_id, _)| name_id == id))
+        {
+            lines.push(format!("{:<10} {}", format!("{}:", label), name.1));
+        }
+    }
+    Ok(lines)
+}
+
+/// Find the table in the font at the offset (not 0 in collections).
+fn sfnt_table<'a>(data: &'a [u8], offset: usize, tag: &[u8; 4]) -> Result<&'a [u8], FxError> {
+    let count = read_u16(data, offset + 4)? as usize;
+    for i in 0..count {
+        let record = offset + 12 + i * 16;
+        if data.get(record..record + 4) == Some(tag) {
+            let start = read_u32(data, record + 8)? as usize;
+            let len = read_u32(data, record + 12)? as usize;
+            return data.get(start..start + len).ok_or_else(invalid);
+        }
+    }
+    Err(FxError::Io("The font has no name table.".to_owned()))
+}
+
+/// Find the table in WOFF, decompressing it if needed.
+fn woff_table(data: &[u8], tag: &[u8; 4]) -> Result<Vec<u8>, FxError> {
+    let count = read_u16(data, 12)? as usize;
+    for i in 0..count {
+        let record = 44 + i * 20;
+        if data.get(record..record + 4) == Some(tag) {
+            let start = read_u32(data, record + 4)? as usize;
+            let compressed = read_u32(data, record + 8)? as usize;
+            let original = read_u32(data, record + 12)? as usize;
+            let table = data.get(start..start + compressed).ok_or_else(invalid)?;
+            if compressed == original {
+                return Ok(table.to_vec());
+            }
+            let mut decoded = Vec::with_capacity(original);
+            flate2::read::ZlibDecoder::new(table).read_to_end(&mut decoded)?;
+            return Ok(decoded);
+        }
+    }
+    Err(FxError::Io("The font has no name table.".to_owned()))
+}
+
+/// Read the name records, preferring English names for Windows,
+/// then Unicode and Macintosh ones.
+fn parse_names(table: &[u8]) -> Result<Vec<(u16, String)>, FxError> {
+    let count = read_u16(table, 2)? as usize;
+    let storage = read_u16(table, 4)? as usize;
+    let mut names: Vec<(u16, u8, String)> = vec![];
+    for i in 0..count {
+        let record = 6 + i * 12;
+        let platform = read_u16(table, record)?;
+        let language = read_u16(table, record + 4)?;
+        let name_id = read_u16(table, record + 6)?;
+        let len = read_u16(table, record + 8)? as usize;
+        let start = storage + read_u16(table, record + 10)? as usize;
+        let bytes = match table.get(start..start + len) {
+            Some(bytes) => bytes,
+            None => continue,
+        };
+        let (priority, name) = match platform {
+            3 if language == LANGUAGE_EN_US => (0, utf16_be(bytes)),
+            3 | 0 => (1, utf16_be(bytes)),
+            //Mac Roman, mostly ASCII in practice.
+            1 => (2, bytes.iter().map(|b| *b as char).collect()),
+            _ => continue,
+        };
+        match names.iter_mut().find(|(id, _, _)| *id == name_id) {
+            Some(found) if found.1 > priority => *found = (name_id, priority, name),
+            Some(_) => {}
+            None => names.push((name_id, priority, name)),
+        }
+    }
+    Ok(names.into_iter().map(|(id, _, name)| (id, name)).collect())
+}
+
+fn utf16_be(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, FxError> {
+    let bytes = data.get(offset..offset + 2).ok_or_else(invalid)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, FxError> {
+    let bytes = data.get(offset..offset + 4).ok_or_else(invalid)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn invalid() -> FxError {
+    FxError::Io("Invalid font file.".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a font with only the name table of the records (platform, language, id, name).
+    fn build_font(records: &[(u16, u16, u16, &str)]) -> Vec<u8> {
+        let mut storage = vec![];
+        let mut name = vec![];
+        name.extend(0u16.to_be_bytes());
+        name.extend((records.len() as u16).to_be_bytes());
+        name.extend((6 + records.len() as u16 * 12).to_be_bytes());
+        for (platform, language, id, s) in records {
+            let bytes: Vec<u8> = if *platform == 1 {
+                s.bytes().collect()
+            } else {
+                s.encode_utf16()
+                    .flat_map(|unit| unit.to_be_bytes())
+                    .collect()
+            };
+            for value in [*platform, 0, *language, *id, bytes.len() as u16] {
+                name.extend(value.to_be_bytes());
+            }
+            name.extend((storage.len() as u16).to_be_bytes());
+            storage.extend(bytes);
+        }
+        name.extend(storage);
+
+        let mut font = TAG_TRUETYPE.to_vec();
+        font.extend(1u16.to_be_bytes());
+        font.extend([0; 6]);
+        font.extend(TAG_NAME);
+        font.extend([0; 4]);
+        font.extend(28u32.to_be_bytes());
+        font.extend((name.len() as u32).to_be_bytes());
+        font.extend(name);
+        font
+    }
+
+    #[test]
+    fn test_describe_font() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("a.ttf");
+        std::fs::write(
+            &p,
+            build_font(&[
+                (1, 0, 1, "Mac Family"),
+                (3, 0x0411, 1, "Japanese Family"),
+                (3, LANGUAGE_EN_US, 1, "Sans"),
+                (3, LANGUAGE_EN_US, 2, "Bold"),
+                (3, LANGUAGE_EN_US, 4, "Sans Bold"),
+            ]),
+        )
+        .unwrap();
+        assert!(is_font(&p));
+        assert_eq!(
+            describe_font(&p).unwrap(),
+            vec![
+                "Format:    TrueType",
+                "Family:    Sans",
+                "Style:     Bold",
+                "Full name: Sans Bold",
+            ]
+        );
+
+        let p = dir.path().join("b.ttf");
+        std::fs::write(&p, TAG_TRUETYPE).unwrap();
+        assert!(describe_font(&p).is_err());
+        assert!(!is_font(&dir.path().join("none")));
+    }
+}
@@ -0,0 +1,141 @@
+use super::errors::FxError;
+use super::report::ErrorReport;
+use super::state::State;
+
+use crossterm::event::{KeyCode, KeyEvent};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+use std::sync::Mutex;
+
+/// How `State` shows what it does and asks the user while an operation runs.
+/// The terminal UI of `fx` implements this; without it, the state reports to `Headless`,
+/// so that the operations can be run without the terminal (e.g. in tests).
+/// The progress of copying is reported from the copying threads as well.
+pub trait Frontend: std::fmt::Debug + Send + Sync {
+    /// Draw the list with the cursor at `y`, after the list or the cursor is changed.
+    fn redraw(&self, state: &mut State, y: u16);
+
+    /// Draw the items found so far with the status, while listing or searching takes long.
+    fn draw_partial(&self, state: &mut State, status: &str);
+
+    /// Show the result of the operation.
+    fn info(&self, message: &str);
+
+    /// Show what went wrong, or what is not allowed.
+    fn warn(&self, message: &str);
+
+    /// Show what is being processed, e.g. `DELETE: Processing...` or the count of the items.
+    fn status(&self, status: &str);
+
+    /// Show the progress of copying after the status, replacing the last one.
+    fn progress(&self, progress: &str);
+
+    /// Whether the user has asked to cancel the operation since the last check.
+    fn is_canceled(&self) -> bool;
+
+    /// Ask the question, and return the key pressed as the answer.
+    fn ask(&self, question: &str) -> Result<KeyEvent, FxError>;
+
+    /// Show the lines (e.g. the new names to confirm) in place of the list.
+    fn show_lines(&self, state: &mut State, lines: &[String]);
+
+    /// List the items that failed in the batch operation with the errors.
+    fn show_report(&self, operation: &str, report: &ErrorReport) -> Result<(), FxError>;
+
+    /// Let the user choose the permission bits to turn on and off for the targets
+    /// (the path, the name and the current mode). Return None if canceled.
+    fn edit_mode_bits(
+        &self,
+        state: &mut State,
+        targets: &[(PathBuf, String, u32)],
+    ) -> Result<Option<(u32, u32)>, FxError>;
+
+    /// Run the program taking over the terminal (e.g. the editor), and wait for it to exit.
+    fn run(&self, command: &mut Command) -> std::io::Result<ExitStatus>;
+
+    /// Run the program reading the input by lines (e.g. `sudo` asking the password),
+    /// and wait for it to exit.
+    fn run_line_input(&self, command: &mut Command) -> std::io::Result<ExitStatus>;
+}
+
+/// Frontend without the terminal: nothing is drawn, the messages are kept,
+/// and the questions are answered from `answers`, or by <Esc> when they run out.
+#[derive(Debug, Default)]
+pub struct Headless {
+    pub answers: Mutex<VecDeque<KeyEvent>>,
+    pub messages: Mutex<Vec<String>>,
+}
+
+impl Headless {
+    /// Answer the next questions by the keys in order.
+    pub fn answer(&self, keys: &str) {
+        if let Ok(mut answers) = self.answers.lock() {
+            answers.extend(keys.chars().map(|c| KeyEvent::from(KeyCode::Char(c))));
+        }
+    }
+
+    /// The last message shown.
+    pub fn last_message(&self) -> Option<String> {
+        self.messages.lock().ok()?.last().cloned()
+    }
+
+    fn record(&self, message: &str) {
+        if let Ok(mut messages) = self.messages.lock() {
+            messages.push(message.to_owned());
+        }
+    }
+}
+
+impl Frontend for Headless {
+    fn redraw(&self, _state: &mut State, _y: u16) {}
+
+    fn draw_partial(&self, _state: &mut State, _status: &str) {}
+
+    fn info(&self, message: &str) {
+        self.record(message);
+    }
+
+    fn warn(&self, message: &str) {
+        self.record(message);
+    }
+
+    fn status(&self, _status: &str) {}
+
+    fn progress(&self, _progress: &str) {}
+
+    fn is_canceled(&self) -> bool {
+        false
+    }
+
+    fn ask(&self, _question: &str) -> Result<KeyEvent, FxError> {
+        let answer = self
+            .answers
+            .lock()
+            .ok()
+            .and_then(|mut answers| answers.pop_front());
+        Ok(answer.unwrap_or_else(|| KeyEvent::from(KeyCode::Esc)))
+    }
+
+    fn show_lines(&self, _state: &mut State, _lines: &[String]) {}
+
+    fn show_report(&self, _operation: &str, _report: &ErrorReport) -> Result<(), FxError> {
+        Ok(())
+    }
+
+    fn edit_mode_bits(
+        &self,
+        _state: &mut State,
+        _targets: &[(PathBuf, String, u32)],
+    ) -> Result<Option<(u32, u32)>, FxError> {
+        Ok(None)
+    }
+
+    fn run(&self, command: &mut Command) -> std::io::Result<ExitStatus> {
+        command.status()
+    }
+
+    fn run_line_input(&self, command: &mut Command) -> std::io::Result<ExitStatus> {
+        command.status()
+    }
+}
@@ -1,115 +1,180 @@
-use super::config::Colorname;
 use super::errors::FxError;
-use super::term::*;
 
-use crossterm::style::Stylize;
-use log::{info, warn};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Utc};
+use chrono_tz::Tz;
+use log::info;
 use simplelog::{ConfigBuilder, LevelFilter, WriteLogger};
 use std::collections::{BTreeMap, BTreeSet};
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-pub const PROCESS_INDICATOR_LENGTH: u16 = 7;
 const KB: u64 = 1000;
 const MB: u64 = 1_000_000;
 const GB: u64 = 1_000_000_000;
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// Upper limit of the threads walking or copying a directory tree.
+const MAX_TREE_WORKERS: usize = 8;
+/// Suffixes of the files being downloaded.
+const PARTIAL_EXTENSIONS: [&str; 3] = ["part", "crdownload", "tmp"];
+
+/// How the modified time is shown, set by `time_format` in the config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// strftime format, e.g. `%Y-%m-%d %H:%M`.
+    Strftime(String),
+    /// Time since modified, e.g. `3h ago`.
+    Relative,
+}
+
+impl Default for TimeFormat {
+    fn default() -> Self {
+        TimeFormat::Strftime(DEFAULT_TIME_FORMAT.to_owned())
+    }
+}
+
+impl TimeFormat {
+    /// `relative`, or a strftime format.
+    pub fn parse(s: &str) -> Result<Self, FxError> {
+        if s == "relative" {
+            return Ok(TimeFormat::Relative);
+        }
+        if StrftimeItems::new(s).any(|item| item == Item::Error) {
+            return Err(FxError::Yaml(format!("Invalid time_format `{}`.", s)));
+        }
+        Ok(TimeFormat::Strftime(s.to_owned()))
+    }
+}
+
+/// The timezone to display the modified time in, set by `timezone` in the config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Timezone {
+    #[default]
+    Local,
+    Named(Tz),
+}
+
+impl Timezone {
+    /// `UTC`, `Local` or a name in the tz database (e.g. `Asia/Tokyo`).
+    pub fn parse(s: &str) -> Result<Self, FxError> {
+        if s.eq_ignore_ascii_case("local") {
+            Ok(Timezone::Local)
+        } else if s.eq_ignore_ascii_case("utc") {
+            Ok(Timezone::Named(Tz::UTC))
+        } else {
+            s.parse()
+                .map(Timezone::Named)
+                .map_err(|_| FxError::Yaml(format!("Unknown timezone `{}`.", s)))
+        }
+    }
+}
 
 /// Generate modified time as `String`.
-pub fn format_time(time: &Option<String>) -> String {
-    match time {
-        Some(datetime) => format!("{} {}", &datetime[0..10], &datetime[11..16]),
-        None => "".to_string(),
+pub fn format_time(time: Option<SystemTime>, format: &TimeFormat, timezone: Timezone) -> String {
+    let Some(time) = time else {
+        return "".to_string();
+    };
+    match format {
+        TimeFormat::Strftime(format) => match timezone {
+            Timezone::Local => DateTime::<Local>::from(time).format(format).to_string(),
+            Timezone::Named(tz) => DateTime::<Utc>::from(time)
+                .with_timezone(&tz)
+                .format(format)
+                .to_string(),
+        },
+        TimeFormat::Relative => {
+            to_relative_time(SystemTime::now().duration_since(time).unwrap_or_default())
+        }
+    }
+}
+
+/// Create the time passed as String (e.g. `3h ago`).
+fn to_relative_time(duration: Duration) -> String {
+    let s = duration.as_secs();
+    if s < 60 {
+        "just now".to_owned()
+    } else if s < 3600 {
+        format!("{}m ago", s / 60)
+    } else if s < 86_400 {
+        format!("{}h ago", s / 3600)
+    } else if s < 30 * 86_400 {
+        format!("{}d ago", s / 86_400)
+    } else if s < 365 * 86_400 {
+        format!("{}mo ago", s / (30 * 86_400))
+    } else {
+        format!("{}y ago", s / (365 * 86_400))
+    }
+}
+
+/// Expand the leading `~` to the home directory.
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Whether the file is being downloaded, by the suffix of browsers and download tools.
+pub fn is_partial(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| PARTIAL_EXTENSIONS.iter().any(|partial| ext == *partial))
+}
+
+/// The path shown relative to the anchor, led by the name of the anchor
+/// (e.g. `felix/src` for `/home/user/felix/src` with the anchor `/home/user/felix`).
+/// Return None if the path is not under the anchor.
+pub fn relative_display(path: &Path, anchor: &Path) -> Option<String> {
+    let rest = path.strip_prefix(anchor).ok()?;
+    let name = anchor.file_name()?.to_string_lossy();
+    if rest.as_os_str().is_empty() {
+        Some(name.into_owned())
+    } else {
+        Some(format!("{}/{}", name, rest.display()))
     }
 }
 
 /// Rename the put file, in order to avoid the name conflict.
-pub fn rename_file(file_name: &str, name_set: &BTreeSet<String>) -> String {
+/// Names are compared as they are on the disk, even if not valid UTF-8.
+pub fn rename_file(file_name: &OsStr, name_set: &BTreeSet<OsString>) -> OsString {
     let mut count: usize = 1;
     let (stem, extension) = {
-        let file_name = PathBuf::from(file_name);
+        let file_name = Path::new(file_name);
         (
-            file_name.file_stem().unwrap().to_owned(),
+            file_name.file_stem().unwrap_or_default().to_owned(),
             file_name.extension().map(|x| x.to_owned()),
         )
     };
     let mut new_name = file_name.to_owned();
 
     while name_set.contains(&new_name) {
-        let mut suffix = OsString::from("_");
-        suffix.push({
-            let count: OsString = count.to_string().into();
-            count
-        });
-        let mut rename = stem.to_os_string();
+        let mut rename = stem.clone();
+        rename.push(format!("_{}", count));
         if let Some(ref ext) = extension {
-            rename.push(suffix);
             rename.push(".");
             rename.push(ext);
-        } else {
-            rename.push(suffix);
         }
-        new_name = rename.into_string().unwrap();
+        new_name = rename;
         count += 1;
     }
     new_name
 }
 
 /// Rename the put directory, in order to avoid the name conflict.
-pub fn rename_dir(dir_name: &str, name_set: &BTreeSet<String>) -> String {
+pub fn rename_dir(dir_name: &OsStr, name_set: &BTreeSet<OsString>) -> OsString {
     let mut count: usize = 1;
     let mut new_name = dir_name.to_owned();
     while name_set.contains(&new_name) {
-        let mut suffix = "_".to_string();
-        suffix.push_str(&count.to_string());
         let mut rename = dir_name.to_owned();
-        rename.push_str(&suffix);
+        rename.push(format!("_{}", count));
         new_name = rename;
         count += 1;
     }
     new_name
 }
 
-/// Print the result of operation, such as put/delete/redo/undo.
-pub fn print_info<T: std::fmt::Display>(message: T, then: u16) {
-    delete_pointer();
-    go_to_info_line_and_reset();
-    info!("{}", message);
-
-    let (width, _) = terminal_size().unwrap();
-    let trimmed = shorten_str_including_wide_char(&message.to_string(), (width - 1).into());
-    print!("{}", trimmed);
-
-    hide_cursor();
-    move_to(1, then);
-    print_pointer();
-}
-
-/// When something goes wrong or does not work, print information about it.
-pub fn print_warning<T: std::fmt::Display>(message: T, then: u16) {
-    delete_pointer();
-    go_to_info_line_and_reset();
-    warn!("{}", message);
-
-    let (width, _) = terminal_size().unwrap();
-    let trimmed = shorten_str_including_wide_char(&message.to_string(), (width - 1).into());
-    set_color(&TermColor::ForeGround(&Colorname::White));
-    set_color(&TermColor::BackGround(&Colorname::LightRed));
-    print!("{}", trimmed);
-    reset_color();
-
-    hide_cursor();
-    move_to(1, then);
-    print_pointer();
-}
-
-/// Print process of put/delete.
-pub fn print_process<T: std::fmt::Display>(message: T) {
-    print!("{}", message);
-    move_left(PROCESS_INDICATOR_LENGTH);
-}
-
 /// Print the number of process (put/delete).
 pub fn display_count(i: usize, all: usize) -> String {
     let mut result = String::new();
@@ -119,24 +184,6 @@ pub fn display_count(i: usize, all: usize) -> String {
     result
 }
 
-/// Convert extension setting in the config to BTreeMap.
-pub fn to_extension_map(
-    config: &Option<BTreeMap<String, Vec<String>>>,
-) -> Option<BTreeMap<String, String>> {
-    let mut new_map = BTreeMap::new();
-    match config {
-        Some(config) => {
-            for (command, extensions) in config.iter() {
-                for ext in extensions.iter() {
-                    new_map.insert(ext.to_lowercase(), command.clone());
-                }
-            }
-        }
-        None => return None,
-    }
-    Some(new_map)
-}
-
 /// Create the duration as String. Used after print_process(put/delete).
 pub fn duration_to_string(duration: Duration) -> String {
     let s = duration.as_secs_f32();
@@ -145,6 +192,18 @@ pub fn duration_to_string(duration: Duration) -> String {
     result
 }
 
+/// Create the rough duration as String (e.g. `40s`, `3min`), to estimate the time of an operation.
+pub fn to_rough_duration(duration: Duration) -> String {
+    let s = duration.as_secs();
+    if s < 60 {
+        format!("{}s", s.max(1))
+    } else if s < 3600 {
+        format!("{}min", s / 60)
+    } else {
+        format!("{:.1}h", s as f64 / 3600.0)
+    }
+}
+
 /// Get the size format of item.
 pub fn to_proper_size(byte: u64) -> String {
     let mut result: String;
@@ -164,6 +223,122 @@ pub fn to_proper_size(byte: u64) -> String {
     result
 }
 
+/// Get the size with one decimal place (e.g. `1.2 GB`), to report the change by an operation.
+pub fn to_decimal_size(byte: u64) -> String {
+    let (size, unit) = if byte < KB {
+        return format!("{} B", byte);
+    } else if byte < MB {
+        (byte as f64 / KB as f64, "KB")
+    } else if byte < GB {
+        (byte as f64 / MB as f64, "MB")
+    } else {
+        (byte as f64 / GB as f64, "GB")
+    };
+    format!("{:.1} {}", size, unit)
+}
+
+/// Launch another fx at the directory, in a new window of the terminal emulator.
+/// `terminal` is the command to run a program in the new window (e.g. `alacritty -e`).
+pub fn spawn_new_instance(terminal: &str, dir: &Path) -> Result<(), FxError> {
+    let mut command = terminal.split_ascii_whitespace();
+    let program = command
+        .next()
+        .ok_or_else(|| FxError::OpenNewWindow("`terminal` is empty.".to_owned()))?;
+    std::process::Command::new(program)
+        .args(command)
+        .arg(std::env::current_exe()?)
+        .arg(dir)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| FxError::OpenNewWindow(e.to_string()))?;
+    Ok(())
+}
+
+/// Run the command (e.g. `alacritty`) in the directory, without waiting for it.
+pub fn spawn_in_dir(command: &str, dir: &Path) -> Result<(), FxError> {
+    let mut command = command.split_ascii_whitespace();
+    let program = command
+        .next()
+        .ok_or_else(|| FxError::OpenNewWindow("`terminal_here` is empty.".to_owned()))?;
+    std::process::Command::new(program)
+        .args(command)
+        .current_dir(dir)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| FxError::OpenNewWindow(e.to_string()))?;
+    Ok(())
+}
+
+/// Split the current tmux window, with the new pane starting in the directory.
+pub fn spawn_tmux_split(dir: &Path) -> Result<(), FxError> {
+    let status = std::process::Command::new("tmux")
+        .args(["split-window", "-c"])
+        .arg(dir)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map_err(|e| FxError::OpenNewWindow(e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(FxError::OpenNewWindow(
+            "Cannot split the tmux window.".to_owned(),
+        ))
+    }
+}
+
+/// Threads used for the operations on directory trees.
+pub fn tree_workers() -> usize {
+    std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_TREE_WORKERS)
+}
+
+/// Sum of the size of the files in the directory, recursively.
+/// The subdirectories are walked in parallel.
+pub fn dir_size(path: &Path) -> u64 {
+    let mut size = 0;
+    let mut subdirs = Vec::new();
+    for entry in walkdir::WalkDir::new(path)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+    {
+        if entry.depth() > 0 && entry.file_type().is_dir() {
+            subdirs.push(entry.into_path());
+        } else if let Ok(metadata) = entry.metadata() {
+            if metadata.is_file() {
+                size += metadata.len();
+            }
+        }
+    }
+
+    let next = AtomicUsize::new(0);
+    let total = AtomicU64::new(size);
+    std::thread::scope(|scope| {
+        for _ in 0..tree_workers().min(subdirs.len()) {
+            scope.spawn(|| {
+                while let Some(dir) = subdirs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let size: u64 = walkdir::WalkDir::new(dir)
+                        .into_iter()
+                        .filter_map(|entry| entry.ok())
+                        .filter_map(|entry| entry.metadata().ok())
+                        .filter(|metadata| metadata.is_file())
+                        .map(|metadata| metadata.len())
+                        .sum();
+                    total.fetch_add(size, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+    total.into_inner()
+}
+
 /// Generate the contents of the directory to preview.
 pub fn list_up_contents(path: &Path, width: u16) -> Result<String, FxError> {
     let mut file_v = Vec::new();
@@ -209,31 +384,14 @@ pub fn format_txt(txt: &str, width: u16, is_help: bool) -> Vec<String> {
     v
 }
 
-/// Print help text.
-pub fn print_help(v: &[String], skip_number: usize, row: u16) {
-    let mut row_count = 0;
-    for (i, line) in v.iter().enumerate() {
-        if i < skip_number {
-            continue;
-        }
-
-        move_to(1, (i + 1 - skip_number) as u16);
-        if row_count == row - 1 {
-            print!("{}", "...".negative());
-            break;
-        }
-        print!("{}", line);
-        row_count += 1;
-    }
-}
-
 /// Initialize the log if `-l` option is added.
 pub fn init_log(data_local_path: &Path) -> Result<(), FxError> {
     let mut log_name = chrono::Local::now().format("%F-%H-%M-%S").to_string();
     log_name.push_str(".log");
     let config = ConfigBuilder::new()
+        //The offset cannot be read if other threads run; UTC is used then.
         .set_time_offset_to_local()
-        .unwrap()
+        .unwrap_or_else(|builder| builder)
         .build();
     let log_path = {
         let mut path = data_local_path.to_path_buf();
@@ -257,21 +415,144 @@ pub fn convert_to_permissions(permissions: u32) -> String {
     permissions.chars().rev().collect()
 }
 
+thread_local! {
+    /// Names looked up by the uid/gid, as the same owners repeat in a directory.
+    static USER_NAMES: std::cell::RefCell<BTreeMap<u32, String>> = Default::default();
+    static GROUP_NAMES: std::cell::RefCell<BTreeMap<u32, String>> = Default::default();
+}
+
+/// The user name of the uid, or the uid itself if not found.
+pub fn user_name(uid: u32) -> String {
+    USER_NAMES.with(|names| {
+        names
+            .borrow_mut()
+            .entry(uid)
+            .or_insert_with(|| {
+                #[cfg(unix)]
+                if let Ok(Some(user)) = nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))
+                {
+                    return user.name;
+                }
+                uid.to_string()
+            })
+            .clone()
+    })
+}
+
+/// The group name of the gid, or the gid itself if not found.
+pub fn group_name(gid: u32) -> String {
+    GROUP_NAMES.with(|names| {
+        names
+            .borrow_mut()
+            .entry(gid)
+            .or_insert_with(|| {
+                #[cfg(unix)]
+                if let Ok(Some(group)) =
+                    nix::unistd::Group::from_gid(nix::unistd::Gid::from_raw(gid))
+                {
+                    return group.name;
+                }
+                gid.to_string()
+            })
+            .clone()
+    })
+}
+
+/// Convert the permission bits to `rwxr-xr-x`.
+pub fn to_mode_string(mode: u32) -> String {
+    "rwxrwxrwx"
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if mode & (0o400 >> i) != 0 { c } else { '-' })
+        .collect()
+}
+
 /// Shorten &str to specific width. With unicode_width, even if the string includes wide chars,
 /// it'd be properly split, using full width of the terminal.
+/// Clusters are not split, and control characters are dropped.
 pub fn shorten_str_including_wide_char(s: &str, i: usize) -> String {
     let mut result = "".to_owned();
-    for c in s.chars() {
-        let result_length = UnicodeWidthStr::width(result.as_str());
-        if let Some(c_width) = UnicodeWidthChar::width(c) {
-            if result_length + c_width > i {
-                return result;
+    let mut result_width = 0;
+    for (cluster, width) in clusters(s) {
+        if result_width + width > i {
+            break;
+        }
+        result.push_str(cluster);
+        result_width += width;
+    }
+    result
+}
+
+/// Split the string into the units shown together, with their width:
+/// a character followed by the combining marks, the variation selectors
+/// and the characters joined by ZWJ (e.g. family emoji), or a pair of regional indicators (flags).
+/// Control characters, which have no width, are skipped.
+pub fn clusters(s: &str) -> impl Iterator<Item = (&str, usize)> {
+    let is_regional_indicator = |c: char| ('\u{1F1E6}'..='\u{1F1FF}').contains(&c);
+    let mut start = 0;
+    let mut prev: Option<char> = None;
+    //Whether the cluster is a single regional indicator, waiting for the pair.
+    let mut lone_indicator = false;
+    let mut bounds = vec![];
+    for (i, c) in s.char_indices() {
+        let Some(width) = UnicodeWidthChar::width(c) else {
+            if i > start {
+                bounds.push((start, i));
             }
-            result.push(c);
+            start = i + c.len_utf8();
+            prev = None;
             continue;
+        };
+        let joins = match prev {
+            None => false,
+            Some('\u{200D}') => true,
+            Some(_) if width == 0 => true,
+            Some(_) => lone_indicator && is_regional_indicator(c),
+        };
+        if joins {
+            lone_indicator = false;
+        } else {
+            if i > start {
+                bounds.push((start, i));
+            }
+            start = i;
+            lone_indicator = is_regional_indicator(c);
         }
+        prev = Some(c);
+    }
+    if s.len() > start {
+        bounds.push((start, s.len()));
+    }
+    bounds.into_iter().map(|(start, end)| {
+        let cluster = &s[start..end];
+        (cluster, UnicodeWidthStr::width(cluster))
+    })
+}
+
+/// The width of the string shown in the terminal, without control characters.
+pub fn str_width(s: &str) -> usize {
+    clusters(s).map(|(_, width)| width).sum()
+}
+
+/// Shorten the name to the width, replacing the end by `..` if it does not fit.
+pub fn shorten_name(name: &str, width: usize) -> String {
+    if str_width(name) <= width {
+        shorten_str_including_wide_char(name, width)
+    } else {
+        let mut name = shorten_str_including_wide_char(name, width.saturating_sub(2));
+        name.push_str("..");
+        name
+    }
+}
+
+/// Pad the string with spaces to the width, to the left if `right` is true.
+pub fn pad_str(s: &str, width: usize, right: bool) -> String {
+    let padding = " ".repeat(width.saturating_sub(str_width(s)));
+    if right {
+        padding + s
+    } else {
+        s.to_owned() + padding.as_str()
     }
-    result
 }
 
 /// Wrap texts.
@@ -302,12 +583,94 @@ pub fn split_lines_including_wide_char(s: &str, width: usize) -> Vec<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_dir_size() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), [0; 10]).unwrap();
+        std::fs::create_dir(dir.path().join("b")).unwrap();
+        std::fs::write(dir.path().join("b").join("c"), [0; 20]).unwrap();
+        assert_eq!(dir_size(dir.path()), 30);
+        for i in 0..20 {
+            std::fs::create_dir_all(dir.path().join(format!("d/{}/e", i))).unwrap();
+            std::fs::write(dir.path().join(format!("d/{}/e/f", i)), [0; 5]).unwrap();
+        }
+        assert_eq!(dir_size(dir.path()), 130);
+        assert_eq!(dir_size(&dir.path().join("a")), 10);
+    }
+
+    #[test]
+    fn test_is_partial() {
+        assert!(is_partial(Path::new("/tmp/movie.mp4.part")));
+        assert!(is_partial(Path::new("setup.exe.crdownload")));
+        assert!(is_partial(Path::new("a.tmp")));
+        assert!(!is_partial(Path::new("a.txt")));
+        assert!(!is_partial(Path::new(".part")));
+    }
+
+    #[test]
+    fn test_relative_display() {
+        let anchor = Path::new("/home/user/felix");
+        assert_eq!(
+            relative_display(Path::new("/home/user/felix/src/state"), anchor),
+            Some("felix/src/state".to_owned())
+        );
+        assert_eq!(relative_display(anchor, anchor), Some("felix".to_owned()));
+        assert_eq!(relative_display(Path::new("/home/user"), anchor), None);
+        assert_eq!(relative_display(Path::new("/tmp"), Path::new("/")), None);
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_tilde(Path::new("~/logs")), home.join("logs"));
+        assert_eq!(expand_tilde(Path::new("~")), home);
+        assert_eq!(expand_tilde(Path::new("/tmp/~")), PathBuf::from("/tmp/~"));
+        assert_eq!(expand_tilde(Path::new("~foo")), PathBuf::from("~foo"));
+    }
+
     #[test]
     fn test_format_time() {
-        let time1 = Some("1970-01-01 00:00:00".to_string());
+        // 1970-06-30 12:00 UTC, the same date in any timezone.
+        let time1 = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(180 * 86_400 + 43_200));
         let time2 = None;
-        assert_eq!(format_time(&time1), "1970-01-01 00:00".to_string());
-        assert_eq!(format_time(&time2), "".to_string());
+        let default = TimeFormat::default();
+        let local = Timezone::Local;
+        assert!(format_time(time1, &default, local).starts_with("1970-06-30 "));
+        assert_eq!(format_time(time1, &default, local).len(), 16);
+        assert_eq!(format_time(time2, &default, local), "".to_string());
+        let format = TimeFormat::parse("%d/%m/%y").unwrap();
+        assert_eq!(format_time(time1, &format, local), "30/06/70".to_string());
+        assert!(TimeFormat::parse("%Q").is_err());
+
+        let relative = TimeFormat::parse("relative").unwrap();
+        let ago = |secs| Some(SystemTime::now() - Duration::from_secs(secs));
+        assert_eq!(format_time(ago(10), &relative, local), "just now");
+        assert_eq!(format_time(ago(3 * 3600 + 100), &relative, local), "3h ago");
+        assert_eq!(format_time(ago(2 * 86_400), &relative, local), "2d ago");
+        assert_eq!(format_time(ago(400 * 86_400), &relative, local), "1y ago");
+    }
+
+    #[test]
+    fn test_timezone() {
+        assert_eq!(Timezone::parse("Local").unwrap(), Timezone::Local);
+        assert_eq!(Timezone::parse("utc").unwrap(), Timezone::Named(Tz::UTC));
+        let tokyo = Timezone::parse("Asia/Tokyo").unwrap();
+        assert_eq!(tokyo, Timezone::Named(Tz::Asia__Tokyo));
+        assert_eq!(
+            Timezone::parse("Mars/Olympus").unwrap_err().to_string(),
+            "Unknown timezone `Mars/Olympus`."
+        );
+
+        // 1970-06-30 20:00 UTC
+        let time = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(180 * 86_400 + 72_000));
+        let format = TimeFormat::default();
+        assert_eq!(
+            format_time(time, &format, Timezone::Named(Tz::UTC)),
+            "1970-06-30 20:00"
+        );
+        assert_eq!(format_time(time, &format, tokyo), "1970-07-01 05:00");
+        let format = TimeFormat::parse("%H:%M %Z").unwrap();
+        assert_eq!(format_time(time, &format, tokyo), "05:00 JST");
     }
 
     #[test]
@@ -323,6 +686,20 @@ mod tests {
         assert_eq!(to_proper_size(6000000000), "6GB".to_string());
     }
 
+    #[test]
+    fn test_decimal_size() {
+        assert_eq!(to_decimal_size(50), "50 B".to_string());
+        assert_eq!(to_decimal_size(2_500), "2.5 KB".to_string());
+        assert_eq!(to_decimal_size(1_234_567_890), "1.2 GB".to_string());
+    }
+
+    #[test]
+    fn test_rough_duration() {
+        assert_eq!(to_rough_duration(Duration::from_millis(200)), "1s");
+        assert_eq!(to_rough_duration(Duration::from_secs(150)), "2min");
+        assert_eq!(to_rough_duration(Duration::from_secs(5400)), "1.5h");
+    }
+
     #[test]
     fn test_duration_to_string() {
         assert_eq!(
@@ -346,6 +723,12 @@ mod tests {
         assert_eq!(&convert_to_permissions(dir), "755");
     }
 
+    #[test]
+    fn test_mode_string() {
+        assert_eq!(to_mode_string(0o755), "rwxr-xr-x".to_string());
+        assert_eq!(to_mode_string(0o100640), "rw-r-----".to_string());
+    }
+
     #[test]
     fn test_split_str_including_wide_char() {
         let teststr = "Ｈｅｌｌｏ, ｗｏｒｌｄ!";
@@ -361,6 +744,34 @@ mod tests {
             "Ｈｅｌｌｏ, ｗ".to_owned(),
             shorten_str_including_wide_char(teststr, 15)
         );
+
+        // Clusters are kept whole.
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let flag = "\u{1F1EF}\u{1F1F5}";
+        let s = format!("e\u{301}{}{}x", family, flag);
+        assert_eq!(str_width(&s), 6);
+        assert_eq!(
+            shorten_str_including_wide_char(&s, 4),
+            format!("e\u{301}{}", family)
+        );
+        assert_eq!(
+            shorten_str_including_wide_char(&s, 5),
+            format!("e\u{301}{}{}", family, flag)
+        );
+        assert_eq!(shorten_str_including_wide_char("a\nb", 3), "ab");
+    }
+
+    #[test]
+    fn test_shorten_name() {
+        assert_eq!(shorten_name("foo.txt", 7), "foo.txt");
+        assert_eq!(shorten_name("foo.txt", 6), "foo...");
+        assert_eq!(shorten_name("日本語.txt", 10), "日本語.txt");
+        assert_eq!(shorten_name("日本語.txt", 9), "日本語...");
+        assert_eq!(shorten_name("日本語.txt", 6), "日本..");
+        assert_eq!(shorten_name("日本語.txt", 5), "日..");
+        assert_eq!(pad_str("日本", 6, false), "日本  ");
+        assert_eq!(pad_str("日本", 6, true), "  日本");
+        assert_eq!(pad_str("日本語", 4, true), "日本語");
     }
 
     #[test]
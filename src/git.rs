@@ -0,0 +1,69 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// The branch of the repository at the directory, shown in the header.
+#[cfg(feature = "git")]
+pub fn branch(dir: &Path) -> Option<String> {
+    let repo = git2::Repository::open(dir).ok()?;
+    let head = repo.head().ok()?;
+    head.shorthand().map(|branch| branch.trim().to_owned())
+}
+
+/// The root of the working tree of the repository containing the directory.
+#[cfg(feature = "git")]
+pub fn root(dir: &Path) -> Option<PathBuf> {
+    let repo = git2::Repository::discover(dir).ok()?;
+    repo.workdir().map(|workdir| workdir.to_path_buf())
+}
+
+/// Paths of the changed and untracked files in the repository containing the directory,
+/// and their ancestors, to show that a directory may contain them.
+#[cfg(feature = "git")]
+pub fn dirty_paths(dir: &Path) -> BTreeSet<PathBuf> {
+    let mut dirty_paths = BTreeSet::new();
+    let Ok(repo) = git2::Repository::discover(dir) else {
+        return dirty_paths;
+    };
+    let mut opts = git2::DiffOptions::new();
+    // When detecting dirty files, includes untracked files.
+    opts.include_untracked(true);
+    if let Ok(diff) = repo.diff_index_to_workdir(None, Some(&mut opts)) {
+        // Current directory does not always point to the root (e.g. in the child dir),
+        // so uses repo.path() and pop() here.
+        let mut root = repo.path().to_path_buf();
+        root.pop();
+        diff.foreach(
+            &mut |x, _| {
+                if let Some(new_file) = x.new_file().path() {
+                    let dirty_path = root.join(new_file);
+                    for ancestor in dirty_path.ancestors() {
+                        dirty_paths.insert(ancestor.to_owned());
+                    }
+                }
+                true
+            },
+            None,
+            None,
+            None,
+        )
+        // Ignores error to continue the update_list process.
+        .unwrap_or(());
+    }
+    dirty_paths
+}
+
+/// Built without the `git` feature (e.g. a static build for rescue images).
+#[cfg(not(feature = "git"))]
+pub fn branch(_dir: &Path) -> Option<String> {
+    None
+}
+
+#[cfg(not(feature = "git"))]
+pub fn root(_dir: &Path) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(not(feature = "git"))]
+pub fn dirty_paths(_dir: &Path) -> BTreeSet<PathBuf> {
+    BTreeSet::new()
+}
@@ -15,6 +15,21 @@ Both relative and absolute path available.
 `--log`  | `-l`   => Launch the app, automatically generating a log file.
 `--init`          => Returns a shell script that can be sourcedfor
                      for shell integration.
+`--new-window [<directory path>]`
+                  => Launch fx in a new window of the terminal set by `terminal`
+                     in the config.
+`--config <path>` => Read the config file instead of the default one.
+                     Same as setting `FELIX_CONFIG`.
+`--choose-files <path>`
+                  => Work as a file picker: on exit, write the selected items
+                     to the file, one per line. Opening a file chooses it.
+                     Exits with 0 if chosen, 1 if not, 2 on error.
+`--null`          => Terminate the chosen paths by NUL instead of newline.
+
+## Commands
+`fx completions <shell>` => Print the completion script for bash, zsh or fish.
+`fx man`                 => Print the man page.
+To open a directory named `man` or `completions`, use `fx ./man`.
 
 ## Manual
 j / <Down>         :Go down.
@@ -23,6 +38,8 @@ k / <Up>           :Go up.
 <C-u>>             :Go up 1/2 page.
 h / <Left>         :Go to the parent directory if exists.
 l / <Right> / <CR> :Open item or change directory.
+                    For a file over `open_size_limit`, asks whether to
+                    open anyway, preview it or open in the pager.
 gg                 :Go to the top.
 G                  :Go to the bottom.
 z<CR>              :Go to the home directory.
@@ -33,11 +50,23 @@ z {keyword}<CR>    :Jump to a directory that matches the keyword.
 i{file name}<CR>   :Create a new empty file.
 I{dir name}<CR>    :Create a new empty directory.
 o                  :Open item in a new window.
+O                  :Run the alternate action of the item.
+                    (`alt_exec` in the config required)
+W                  :Launch fx at the directory in a new terminal window.
+                    (`terminal` in the config required)
+T                  :Open a terminal at the directory.
+                    (`terminal_here` in the config, or a tmux split)
 e                  :Unpack archive/compressed file.
 dd                 :Delete and yank item.
 yy                 :Yank item.
+x                  :Cut item.
 p                  :Put yanked item(s) from register zero
                     in the current directory.
+                    After x, moves the cut item(s) instead.
+                    If the name exists, asks to overwrite / skip / rename,
+                    or merge the directories (`on_conflict` in the config).
+<Esc> / <C-c>      :While putting or deleting, cancel copying.
+                    The partially copied item is removed.
 :reg               :Show registers. To hide it, press v.
 "ayy               :Yank item to register a.
 "add               :Delete and yank item to register a.
@@ -47,62 +76,163 @@ p                  :Put yanked item(s) from register zero
 V                  :Switch to the linewise visual mode.
   - y              :In the visual mode, yank selected item(s).
   - d              :In the visual mode, delete and yank selected item(s).
+  - x              :In the visual mode, cut selected item(s).
+                    If some of them are out of the screen, asks before deleting.
   - "ay            :In the visual mode, yank items to register a.
   - "ad            :In the visual mode, delete and yank items to register a.
   - "Ay            :In the visual mode, append items to register a.
   - "Ad            :In the visual mode, delete and append items to register a.
   - c              :Rename selected items in default editor.
-u                  :Undo put/delete/rename.
-<C-r>              :Redo put/delete/rename.
+m                  :Mark item. Marks are kept across directories.
+  - m              :In the visual mode, mark selected items.
+P                  :Put marked items in the current directory.
+<C-a>              :Select all items and switch to the visual mode.
+*                  :Invert the selection.
+u                  :Undo put/delete/rename/create/move/chmod/chown/unpack.
+<C-r>              :Redo put/delete/rename/create/move/chmod/chown/unpack.
+<C-z>              :Suspend felix to the shell. `fg` to resume.
 v                  :Toggle whether to show the preview.
 s                  :Toggle between vertical / horizontal split in the preview mode.
-<Alt-j>
+J / <Alt-j>
  / <Alt-<Down>>    :Scroll down the preview text.
-<Alt-k> 
+K / <Alt-k> 
  / <Alt-<Up>>      :Scroll up the preview text.
 <BS>               :Toggle whether to show hidden items.
-t                  :Toggle the sort order (name <-> modified time).
+t                  :Cycle the sort order
+                    (name -> modified time -> size -> extension -> `sort_scripts` in the config).
 c                  :Switch to the rename mode.
 /{keyword}         :Search items by a keyword.
 n                  :Go forward to the item that matches the keyword.
 N                  :Go backward to the item that matches the keyword.
 :                  :Switch to the command line.
+!                  :Run a shell command in the current directory. In the command,
+                    %f is the item under the cursor, %s the selected items
+                    (or the item under the cursor), %d the current directory, and %% is %.
+  - Up / Down      :Go through the history of the commands.
   - <C-r>a         :In the command line, paste item name in register a.
 :cd<CR>            :Go to the home directory.
 :cd {path}<CR>     :Go to the path.
 :e<CR>             :Reload the current directory.
 :config<CR>        :Go to the directory that contains the config file if exists.
-:trash<CR>         :Go to the trash directory.
-:empty<CR>         :Empty the trash directory.
+:reload-config<CR> :Read the config file and the theme again, and apply them.
+                    The config file is also reloaded automatically when modified.
+:trash<CR>         :Go to the trash directory. In it, go to the trash
+                    at the top of the next mount (`.Trash-$uid`) if any.
+                    Items are grouped by the deletion date.
+  - <Space>        :In the trash directory, collapse/expand the group.
+:empty<CR>         :Empty the trash directory, and the trash
+                    at the top of the other mounts (`.Trash-$uid`).
+:jobs<CR>          :Show the background jobs. Put and delete of large items
+                    (`background_threshold` in the config) run in the background.
+  - p / <Space>    :In the jobs panel, pause/resume the job.
+  - x              :In the jobs panel, cancel the job.
+:journal<CR>       :Check the put, move and delete interrupted by a crash,
+                    showing how far each went. c to clear it from the journal.
+:compare [dir]<CR> :Compare the archive under the cursor with the directory
+                    (default: the sibling named after the archive, e.g. `foo` for `foo.tar.gz`),
+                    listing the files only in the directory (+), only in the archive (-) and changed (M).
+:relative<CR>      :Toggle the paths relative to `relative_base` in the config,
+                    or to the root of the git repository, in the header and the search results.
+:unmark<CR>        :Clear all marks.
+:select {pattern}<CR>
+                   :Select items matching the glob (e.g. *.log)
+                    or regex (e.g. /^\d+\.txt$/), and enter the visual mode.
+:unselect {pattern}<CR>
+                   :Deselect items matching the pattern.
+:filter {predicates}<CR>
+                   :Show only items matching all the size/time predicates,
+                    e.g. `>10M` (larger than 10MB), `<7d` (modified within 7 days).
+                    Size units: B, K, M, G, T / time units: s, min, h, d, w, y.
+                    `since:{date}` matches items modified since the date.
+                    `name:{pattern}` matches the name by the glob or /regex/,
+                    or by any of the comma-separated globs (e.g. `name:*.png,*.jpg`).
+                    With `-r`, files in the subdirectories are searched
+                    recursively (<Esc> to stop searching).
+:filter<CR>        :Clear the filter.
+:larger {size}<CR> / :smaller {size}<CR>
+                   :Add the filter for items larger/smaller than the size,
+                    e.g. :larger 100M name:*.iso -r
+:newer {duration}<CR>
+                   :Add the filter for items modified within the duration, e.g. 2h.
+:modified-since {date}<CR>
+                   :Add the filter for items modified since the date,
+                    e.g. 2024-01-01 or 2024-01-01T12:00.
+:search<CR>        :Pick one of the saved searches in the config and open it.
+:search {name}<CR> :Open the saved search.
+:rename s/{regex}/{replacement}/{flags}<CR>
+                   :Rename the selected items (in the visual mode) or all items
+                    by the regex substitution, previewing the new names.
+                    $1..$9 in the replacement are the capture groups.
+                    Flags: g (replace all matches), i (ignore case).
+:chmod<CR>         :Edit the permissions of the selected items (in the visual mode)
+                    or the item, showing the current and new mode of each.
+                    h/l moves between the bits, <Space> cycles unchanged/on/off,
+                    and 3 digits set the octal mode.
+:chmod {mode}<CR>  :Set the octal mode, e.g. :chmod 644
+:chown {user:group}<CR>
+                   :Change the owner and/or the group of the selected items
+                    (in the visual mode) or the item, e.g. :chown www-data:www-data
+                    or :chown :staff. If not permitted, asks to retry with sudo.
+:chown -R {user:group}<CR>
+                   :Change the owner of everything under the items as well,
+                    after confirming the number of entries.
+:script {name} {args}<CR>
+                   :Run the user script defined in the config.
 :h<CR>             :Show help.
 :q<CR>             :Exit.
 :{command}         :Execute a command e.g. :zip test *.md
-<Esc>              :Return to the normal mode.
+<Esc>              :Return to the normal mode, clearing the selection.
 <C-h>              :Works as Backspace after `i`, `I`, `c`, `/`, `:` and `z`.
 ZZ                 :Exit without cd to last working directory
                     (if `match_vim_exit_behavior` is `false`).
 ZQ                 :cd into the last working directory and exit
                     (if shell setting is ready and `match_vim_exit_behavior is `false`).
 
+## Mouse
+Click an item to move the cursor to it, and double-click to open it.
+The wheel scrolls the list, or the preview under the pointer.
+To select text in the terminal, hold Shift, or set `mouse: false` in the config.
+
 ## Preview feature
 By default, text files and directories can be previewed.
+Markdown files (.md) are rendered with basic styling,
+CSV/TSV files are shown as a table, and JSON/YAML files are pretty-printed
+with deeply nested parts folded.
 To preview images, you need to install chafa (>= v1.10.0).
 Please see https://hpjansson.org/chafa/
 
+## Scripts
+User scripts defined under `script` in the config are written in rhai,
+and run by `:script {name} {args}` or the keys bound as `script:{name}` in `keys`.
+`args` holds the arguments, and these functions drive felix:
+cwd(), item(), selected(), items()               : Read the state.
+cd(path), go_to(name), reload(), info(message)   : Navigate.
+select(name), unselect(name)                     : Select items.
+touch(name), mkdir(name), rename(from, to), delete(name)
+                                                 : Manipulate files (undoable).
+
 ## Configuration
 
 *Both `config.yaml` and `config.yml` work.*
 
+The keys above can be bound to others by `keys` in the config (e.g. `move_down: <C-n>`).
+Sequences of keys work too, with `<leader>` set by `leader` (e.g. `go_to_top: <leader>k`).
+Filters can be bound to keys by `filter_presets` (e.g. `<F1>: "images: name:*.png,*.jpg"`).
+
 ### Linux
 config file    : $XDG_CONFIG_HOME/felix/config.yaml(config.yml)
-trash directory: $XDG_DATA_HOME/felix/trash
+trash directory: $XDG_DATA_HOME/Trash/files
+                 ($XDG_DATA_HOME/felix/Trash if `legacy_trash: true`)
+                 (items on other filesystems: $topdir/.Trash-$uid/files of their mount)
 log files      : $XDG_DATA_HOME/felix/log
+session        : $XDG_STATE_HOME/felix
 
 ### macOS
 On macOS, felix looks for the config file in the following locations:
 
-1. `$HOME/Library/Application Support/felix/config.yaml(config.yml)`
-2. `$HOME/.config/felix/config.yaml`
+1. `$XDG_CONFIG_HOME/felix/config.yaml(config.yml)`, if `XDG_CONFIG_HOME` is set
+2. `$HOME/Library/Application Support/felix/config.yaml(config.yml)`
+3. `$HOME/.config/felix/config.yaml`
 
 trash directory: $HOME/Library/Application Support/felix/trash
 log files      : $HOME/Library/Application Support/felix/log
@@ -0,0 +1,451 @@
+use super::layout::PreviewType;
+use super::script::{run_sort_script, SortContext};
+use super::session::{NameKey, NameOrder, SortKey};
+
+use log::error;
+use normpath::PathExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+#[cfg(target_family = "unix")]
+use std::os::unix::fs::MetadataExt;
+#[cfg(target_family = "unix")]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// An item in the list, with what is shown and what is needed to preview it.
+#[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct ItemInfo {
+    pub file_type: FileType,
+    pub file_name: String,
+    /// The name on the disk if it is not valid UTF-8, when `file_name` is lossy.
+    pub os_name: Option<OsString>,
+    pub file_path: std::path::PathBuf,
+    pub symlink_dir_path: Option<PathBuf>,
+    pub file_size: u64,
+    pub file_ext: Option<String>,
+    pub modified: Option<std::time::SystemTime>,
+    pub is_hidden: bool,
+    pub selected: bool,
+    pub matches: bool,
+    pub preview_type: Option<PreviewType>,
+    pub preview_scroll: usize,
+    pub content: Option<String>,
+    pub permissions: Option<u32>,
+    /// uid and gid.
+    pub owner: Option<(u32, u32)>,
+    pub is_dirty: bool,
+    pub section: Option<String>,
+    /// The size, the time, the permissions and the owner are not read yet.
+    /// See `load_metadata`.
+    pub metadata_pending: bool,
+    /// Made by `NameOrder::key` when sorted, and reused until the list is read again.
+    pub name_key: Option<NameKey>,
+    /// The modified time formatted for the column; see `State::list_up`.
+    pub modified_string: Option<String>,
+}
+
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum FileType {
+    Directory,
+    #[default]
+    File,
+    Symlink,
+    /// FIFO, socket or device file.
+    Other,
+}
+
+/// Compare the names of the items by the keys if made.
+fn compare_names(name_order: NameOrder, a: &ItemInfo, b: &ItemInfo) -> std::cmp::Ordering {
+    match (&a.name_key, &b.name_key) {
+        (Some(key_a), Some(key_b)) => key_a.compare(key_b),
+        _ => None,
+    }
+    .unwrap_or_else(|| name_order.compare(&a.file_name, &b.file_name))
+}
+
+pub fn sort_items_by(
+    items: &mut [ItemInfo],
+    sort_by: &SortKey,
+    name_order: NameOrder,
+    sort_scripts: &BTreeMap<String, String>,
+) {
+    for item in items.iter_mut() {
+        if !name_order.made(&item.name_key) {
+            item.name_key = name_order.key(&item.file_name);
+        }
+    }
+    match sort_by {
+        SortKey::Name => {
+            items.sort_by(|a, b| compare_names(name_order, a, b));
+        }
+        SortKey::Time => {
+            items.sort_by_key(|item| std::cmp::Reverse(item.modified));
+        }
+        SortKey::Size => {
+            items.sort_by(|a, b| {
+                b.file_size
+                    .cmp(&a.file_size)
+                    .then_with(|| compare_names(name_order, a, b))
+            });
+        }
+        SortKey::Extension => {
+            items.sort_by(|a, b| {
+                a.file_ext
+                    .as_ref()
+                    .map(|x| x.to_lowercase())
+                    .cmp(&b.file_ext.as_ref().map(|x| x.to_lowercase()))
+                    .then_with(|| compare_names(name_order, a, b))
+            });
+        }
+        SortKey::Custom(name) => {
+            let Some(script) = sort_scripts.get(name) else {
+                error!("Sort script not found: {}", name);
+                return sort_items_by(items, &SortKey::Name, name_order, sort_scripts);
+            };
+            let contexts: Vec<SortContext> = items.iter().map(sort_context).collect();
+            let keys = match run_sort_script(script, &contexts) {
+                Ok(keys) => keys,
+                Err(e) => {
+                    error!("{}", e);
+                    return sort_items_by(items, &SortKey::Name, name_order, sort_scripts);
+                }
+            };
+            let mut keyed: Vec<(String, ItemInfo)> =
+                keys.into_iter().zip(items.iter().cloned()).collect();
+            //Items without the key come last.
+            keyed.sort_by(|(key_a, a), (key_b, b)| {
+                key_a
+                    .is_empty()
+                    .cmp(&key_b.is_empty())
+                    .then_with(|| natord::compare_ignore_case(key_a, key_b))
+                    .then_with(|| compare_names(name_order, a, b))
+            });
+            for (item, (_, sorted)) in items.iter_mut().zip(keyed) {
+                *item = sorted;
+            }
+        }
+    }
+}
+
+/// What the sort script reads of the item.
+fn sort_context(item: &ItemInfo) -> SortContext {
+    SortContext {
+        name: item.file_name.clone(),
+        path: item.file_path.clone(),
+        ext: item.file_ext.clone().unwrap_or_default(),
+        is_dir: item.file_type == FileType::Directory,
+        size: item.file_size,
+        modified: item
+            .modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_secs() as i64),
+    }
+}
+
+/// Read item information from `std::fs::DirEntry`.
+pub fn read_item(entry: fs::DirEntry) -> ItemInfo {
+    let name = entry.file_name().to_string_lossy().into_owned();
+    read_item_at(entry.path(), name)
+}
+
+/// The name of the item on the disk, if it is not valid UTF-8.
+fn non_utf8_name(path: &Path) -> Option<OsString> {
+    path.file_name()
+        .filter(|name| name.to_str().is_none())
+        .map(|name| name.to_owned())
+}
+
+/// Read the name and the type of the entry, which usually needs no stat,
+/// leaving the metadata to `load_metadata`.
+/// Symlinks are followed to tell the directories.
+pub fn read_item_without_metadata(entry: fs::DirEntry) -> ItemInfo {
+    let Ok(file_type) = entry.file_type() else {
+        return read_item(entry);
+    };
+    let name = entry.file_name().to_string_lossy().into_owned();
+    let path = entry.path();
+    let file_type = if file_type.is_dir() {
+        FileType::Directory
+    } else if file_type.is_file() {
+        FileType::File
+    } else if file_type.is_symlink() {
+        FileType::Symlink
+    } else {
+        FileType::Other
+    };
+    let symlink_dir_path = match file_type {
+        FileType::Symlink if path.is_dir() => path.normalize().map(|p| p.into_path_buf()).ok(),
+        _ => None,
+    };
+    let file_ext = match file_type {
+        FileType::Directory => None,
+        _ => path
+            .extension()
+            .map(|s| s.to_string_lossy().to_ascii_lowercase()),
+    };
+    ItemInfo {
+        file_type,
+        is_hidden: name.starts_with('.'),
+        file_name: name,
+        os_name: non_utf8_name(&path),
+        file_path: path,
+        symlink_dir_path,
+        file_ext,
+        metadata_pending: true,
+        ..Default::default()
+    }
+}
+
+/// Read the metadata left by `read_item_without_metadata`.
+pub fn load_metadata(item: &mut ItemInfo) {
+    if !item.metadata_pending {
+        return;
+    }
+    let read = read_item_at(item.file_path.clone(), String::new());
+    item.file_size = read.file_size;
+    item.modified = read.modified;
+    item.modified_string = None;
+    item.permissions = read.permissions;
+    item.owner = read.owner;
+    item.metadata_pending = false;
+}
+
+/// Read item information of the path, listed as `name`.
+pub fn read_item_at(path: PathBuf, name: String) -> ItemInfo {
+    let metadata = fs::symlink_metadata(&path);
+
+    let hidden = matches!(name.chars().next(), Some('.'));
+    let os_name = non_utf8_name(&path);
+
+    let ext = path.extension().map(|s| {
+        s.to_os_string()
+            .into_string()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    });
+
+    match metadata {
+        Ok(metadata) => {
+            let time = Some(metadata.modified().unwrap_or(UNIX_EPOCH));
+
+            let filetype = {
+                let file_type = metadata.file_type();
+                if file_type.is_dir() {
+                    FileType::Directory
+                } else if file_type.is_file() {
+                    FileType::File
+                } else if file_type.is_symlink() {
+                    FileType::Symlink
+                } else {
+                    FileType::Other
+                }
+            };
+
+            let sym_dir_path = {
+                if filetype == FileType::Symlink {
+                    if let Ok(sym_meta) = fs::metadata(&path) {
+                        if sym_meta.is_dir() {
+                            path.normalize().map(|p| p.into_path_buf()).ok()
+                        } else {
+                            None
+                        }
+                    } else {
+                        None
+                    }
+                } else {
+                    None
+                }
+            };
+
+            #[cfg(target_family = "unix")]
+            let permissions = Some(metadata.permissions().mode());
+            #[cfg(not(target_family = "unix"))]
+            let permissions = None;
+            #[cfg(target_family = "unix")]
+            let owner = Some((metadata.uid(), metadata.gid()));
+            #[cfg(not(target_family = "unix"))]
+            let owner = None;
+
+            let size = metadata.len();
+            ItemInfo {
+                file_type: filetype,
+                file_name: name,
+                os_name,
+                file_path: path,
+                symlink_dir_path: sym_dir_path,
+                file_size: size,
+                file_ext: match filetype {
+                    FileType::Directory => None,
+                    _ => ext,
+                },
+                modified: time,
+                selected: false,
+                matches: false,
+                is_hidden: hidden,
+                preview_type: None,
+                preview_scroll: 0,
+                content: None,
+                permissions,
+                owner,
+                is_dirty: false,
+                section: None,
+                metadata_pending: false,
+                name_key: None,
+                modified_string: None,
+            }
+        }
+        Err(_) => ItemInfo {
+            file_type: FileType::File,
+            file_name: name,
+            os_name,
+            file_path: path,
+            symlink_dir_path: None,
+            file_size: 0,
+            file_ext: ext,
+            modified: None,
+            selected: false,
+            matches: false,
+            is_hidden: false,
+            preview_type: None,
+            preview_scroll: 0,
+            content: None,
+            permissions: None,
+            owner: None,
+            is_dirty: false,
+            section: None,
+            metadata_pending: false,
+            name_key: None,
+            modified_string: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_items() {
+        let item = |name: &str, size: u64| ItemInfo {
+            file_name: name.to_string(),
+            file_size: size,
+            file_ext: std::path::Path::new(name)
+                .extension()
+                .map(|x| x.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let mut items = vec![
+            item("b.txt", 10),
+            item("c.md", 30),
+            item("a.TXT", 30),
+            item("d", 20),
+        ];
+        let names = |items: &[ItemInfo]| {
+            items
+                .iter()
+                .map(|x| x.file_name.clone())
+                .collect::<Vec<String>>()
+        };
+
+        let sort_items = |items: &mut [ItemInfo], sort_by: &SortKey, name_order| {
+            sort_items_by(items, sort_by, name_order, &BTreeMap::new())
+        };
+        sort_items(&mut items, &SortKey::Size, NameOrder::Natural);
+        assert_eq!(names(&items), vec!["a.TXT", "c.md", "d", "b.txt"]);
+        sort_items(&mut items, &SortKey::Extension, NameOrder::Natural);
+        assert_eq!(names(&items), vec!["d", "c.md", "a.TXT", "b.txt"]);
+        sort_items(&mut items, &SortKey::Name, NameOrder::Natural);
+        assert_eq!(names(&items), vec!["a.TXT", "b.txt", "c.md", "d"]);
+
+        items.push(item("B.md", 0));
+        sort_items(&mut items, &SortKey::Name, NameOrder::Natural);
+        assert_eq!(names(&items), vec!["a.TXT", "B.md", "b.txt", "c.md", "d"]);
+        sort_items(&mut items, &SortKey::Name, NameOrder::NaturalCaseSensitive);
+        assert_eq!(names(&items), vec!["B.md", "a.TXT", "b.txt", "c.md", "d"]);
+        assert!(items.iter().all(|item| item.name_key.is_none()));
+
+        // The keys are made again for the other order.
+        items.push(item("item10", 0));
+        items.push(item("Item9", 0));
+        sort_items(&mut items, &SortKey::Name, NameOrder::Natural);
+        assert_eq!(
+            names(&items),
+            vec!["a.TXT", "B.md", "b.txt", "c.md", "d", "Item9", "item10"]
+        );
+        assert_eq!(
+            items[1].name_key,
+            Some(NameKey::Lowercase("b.md".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_sort_items_by_script() {
+        let item = |name: &str| ItemInfo {
+            file_name: name.to_string(),
+            file_path: std::env::temp_dir().join(name),
+            ..Default::default()
+        };
+        let mut items = vec![item("a"), item("b10"), item("c"), item("d2")];
+        let names = |items: &[ItemInfo]| {
+            items
+                .iter()
+                .map(|x| x.file_name.clone())
+                .collect::<Vec<String>>()
+        };
+        //The digits in the name, if any.
+        let scripts = BTreeMap::from([
+            (
+                "num".to_owned(),
+                r#"
+                    let digits = "";
+                    for c in name { if c >= '0' && c <= '9' { digits += c } }
+                    if digits != "" { digits }
+                "#
+                .to_owned(),
+            ),
+            ("broken".to_owned(), "throw 1".to_owned()),
+        ]);
+        let custom = |name: &str| SortKey::Custom(name.to_owned());
+
+        sort_items_by(&mut items, &custom("num"), NameOrder::Natural, &scripts);
+        assert_eq!(names(&items), vec!["d2", "b10", "a", "c"]);
+        sort_items_by(&mut items, &custom("broken"), NameOrder::Natural, &scripts);
+        assert_eq!(names(&items), vec!["a", "b10", "c", "d2"]);
+        sort_items_by(&mut items, &custom("num"), NameOrder::Natural, &scripts);
+        sort_items_by(&mut items, &custom("missing"), NameOrder::Natural, &scripts);
+        assert_eq!(names(&items), vec!["a", "b10", "c", "d2"]);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_load_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.TXT"), "abc").unwrap();
+        std::fs::create_dir(dir.path().join("b")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("b"), dir.path().join("c")).unwrap();
+
+        let mut items: Vec<ItemInfo> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| read_item_without_metadata(entry.unwrap()))
+            .collect();
+        items.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        assert!(items.iter().all(|item| item.metadata_pending));
+        assert_eq!(items[0].file_ext, Some("txt".to_owned()));
+        assert_eq!(items[1].file_type, FileType::Directory);
+        assert!(items[2].symlink_dir_path.is_some());
+
+        for item in items.iter_mut() {
+            load_metadata(item);
+        }
+        let expected: Vec<ItemInfo> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| read_item(entry.unwrap()))
+            .collect::<Vec<_>>();
+        for item in items {
+            assert!(expected.contains(&item));
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use super::copy::{copy_file, copy_tree};
+use super::errors::FxError;
+use super::events::AppEvent;
+use super::functions::dir_size;
+use super::journal::JournalGuard;
+use super::progress::format_progress;
+use super::state::ItemBuffer;
+use super::trash::TrashLock;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const RUNNING: u8 = 0;
+const PAUSED: u8 = 1;
+const CANCELED: u8 = 2;
+/// While paused, the worker checks whether to resume at this interval.
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobKind {
+    /// Copy the items to the directory.
+    Put,
+    /// Copy the items to the trash, and remove the originals.
+    Delete,
+}
+
+/// Put or delete running in a worker thread.
+/// When finished, the worker sends `AppEvent::JobFinished` to the main loop,
+/// which records the operation for undo.
+#[derive(Debug)]
+pub struct Job {
+    pub id: usize,
+    pub kind: JobKind,
+    /// Items put or deleted, in the same order as `pairs`.
+    pub items: Vec<ItemBuffer>,
+    /// Pairs of the source and the destination.
+    pub pairs: Vec<(PathBuf, PathBuf)>,
+    /// Pairs of the item overwritten by the put one and its path in the trash.
+    pub trashed: Vec<(PathBuf, PathBuf)>,
+    /// The directory where the operation started.
+    pub dir: PathBuf,
+    total: u64,
+    done: Arc<AtomicU64>,
+    control: Arc<AtomicU8>,
+    start: Instant,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Job {
+    /// Pause the running job, or resume the paused one.
+    pub fn toggle_pause(&self) {
+        //A canceled job stays canceled.
+        let _ = self
+            .control
+            .compare_exchange(RUNNING, PAUSED, Ordering::Relaxed, Ordering::Relaxed)
+            .or_else(|_| {
+                self.control
+                    .compare_exchange(PAUSED, RUNNING, Ordering::Relaxed, Ordering::Relaxed)
+            });
+    }
+
+    /// Stop the job after the current chunk. The partially copied item is removed.
+    pub fn cancel(&self) {
+        self.control.store(CANCELED, Ordering::Relaxed);
+    }
+
+    /// One line to show in the jobs panel, e.g. `#1 PUT 3 items [»»»-------]  30% ...`.
+    pub fn summary(&self) -> String {
+        let kind = match self.kind {
+            JobKind::Put => "PUT",
+            JobKind::Delete => "DELETE",
+        };
+        let count = match self.items.len() {
+            1 => self.items[0].file_name.clone(),
+            n => format!("{} items", n),
+        };
+        let state = match self.control.load(Ordering::Relaxed) {
+            PAUSED => " (paused)",
+            CANCELED => " (canceling)",
+            _ => "",
+        };
+        format!(
+            "#{} {} {}{}{}",
+            self.id,
+            kind,
+            count,
+            format_progress(
+                self.done.load(Ordering::Relaxed),
+                self.total,
+                self.start.elapsed()
+            ),
+            state
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Jobs {
+    pub list: Vec<Job>,
+    next_id: usize,
+    sender: Option<Sender<AppEvent>>,
+}
+
+impl Jobs {
+    /// Jobs report to the main loop through this. Without it, nothing runs in the background.
+    pub fn set_sender(&mut self, sender: Sender<AppEvent>) {
+        self.sender = Some(sender);
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Start copying the pairs in a worker thread, and return the id of the job.
+    /// `total` is the size of the sources, to show the progress.
+    /// `locks` of the trash are held by the worker until the job ends.
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn(
+        &mut self,
+        kind: JobKind,
+        items: Vec<ItemBuffer>,
+        pairs: Vec<(PathBuf, PathBuf)>,
+        trashed: Vec<(PathBuf, PathBuf)>,
+        dir: PathBuf,
+        total: u64,
+        preserve: bool,
+        locks: Vec<TrashLock>,
+        journal: JournalGuard,
+    ) -> Result<usize, FxError> {
+        let sender = self
+            .sender
+            .clone()
+            .ok_or_else(|| FxError::Io("Background jobs are not available.".to_owned()))?;
+        self.next_id += 1;
+        let id = self.next_id;
+        let done = Arc::new(AtomicU64::new(0));
+        let control = Arc::new(AtomicU8::new(RUNNING));
+
+        let worker_pairs = pairs.clone();
+        let worker_done = done.clone();
+        let worker_control = control.clone();
+        let handle = std::thread::spawn(move || {
+            let _locks = locks;
+            let (finished, result) = work(
+                &worker_pairs,
+                kind == JobKind::Delete,
+                preserve,
+                &worker_done,
+                &worker_control,
+            );
+            //Failed or canceled by the user, the job is not incomplete either.
+            journal.finish();
+            let _ = sender.send(AppEvent::JobFinished {
+                id,
+                finished,
+                result,
+            });
+        });
+
+        self.list.push(Job {
+            id,
+            kind,
+            items,
+            pairs,
+            trashed,
+            dir,
+            total,
+            done,
+            control,
+            start: Instant::now(),
+            handle: Some(handle),
+        });
+        Ok(id)
+    }
+
+    /// Remove the finished job from the list.
+    pub fn take(&mut self, id: usize) -> Option<Job> {
+        let i = self.list.iter().position(|job| job.id == id)?;
+        let mut job = self.list.remove(i);
+        if let Some(handle) = job.handle.take() {
+            let _ = handle.join();
+        }
+        Some(job)
+    }
+
+    /// Percentage of the bytes done by all the jobs, shown in the status line.
+    pub fn percent(&self) -> u64 {
+        let (done, total) = self.list.iter().fold((0, 0), |(done, total), job| {
+            (done + job.done.load(Ordering::Relaxed), total + job.total)
+        });
+        (done * 100)
+            .checked_div(total)
+            .map_or(100, |percent| percent.min(100))
+    }
+
+    /// Destinations of the running jobs, not to put another item to the same path.
+    pub fn pending_targets(&self) -> impl Iterator<Item = &Path> {
+        self.list
+            .iter()
+            .flat_map(|job| job.pairs.iter().map(|(_, to)| to.as_path()))
+    }
+
+    /// Cancel all jobs and wait for them to clean up, before exit.
+    pub fn cancel_all_and_wait(&mut self) {
+        for job in self.list.iter() {
+            job.cancel();
+        }
+        for job in self.list.iter_mut() {
+            if let Some(handle) = job.handle.take() {
+                let _ = handle.join();
+            }
+        }
+        self.list.clear();
+    }
+}
+
+/// Copy the pairs in order, removing the sources if `remove_source` is true.
+/// Return the number of the finished pairs and the error that stopped it, if any.
+fn work(
+    pairs: &[(PathBuf, PathBuf)],
+    remove_source: bool,
+    preserve: bool,
+    done: &AtomicU64,
+    control: &AtomicU8,
+) -> (usize, Result<(), FxError>) {
+    let mut progress = |bytes| {
+        done.fetch_add(bytes, Ordering::Relaxed);
+        wait_if_paused(control)
+    };
+    for (i, (from, to)) in pairs.iter().enumerate() {
+        if let Err(e) = progress(0) {
+            return (i, Err(e));
+        }
+        let is_dir = match fs::symlink_metadata(from) {
+            Ok(metadata) => metadata.is_dir(),
+            Err(e) => return (i, Err(e.into())),
+        };
+        //On the same filesystem, the item is moved at once.
+        if remove_source && fs::rename(from, to).is_ok() {
+            done.fetch_add(dir_size(to), Ordering::Relaxed);
+            continue;
+        }
+        let copied = if is_dir {
+            copy_tree(from, to, preserve, progress)
+        } else {
+            copy_file(from, to, preserve, &mut progress)
+        };
+        if let Err(e) = copied {
+            return (i, Err(e));
+        }
+        if remove_source {
+            let removed = if is_dir {
+                fs::remove_dir_all(from)
+            } else {
+                fs::remove_file(from)
+            };
+            if removed.is_err() {
+                return (i, Err(FxError::RemoveItem(from.to_owned())));
+            }
+        }
+    }
+    (pairs.len(), Ok(()))
+}
+
+/// Block while paused. Return `FxError::Canceled` if the job is canceled.
+fn wait_if_paused(control: &AtomicU8) -> Result<(), FxError> {
+    loop {
+        match control.load(Ordering::Relaxed) {
+            PAUSED => std::thread::sleep(PAUSE_CHECK_INTERVAL),
+            CANCELED => return Err(FxError::Canceled),
+            _ => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_work() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("a")).unwrap();
+        fs::write(dir.path().join("a/x"), "xx").unwrap();
+        fs::write(dir.path().join("b"), "bbb").unwrap();
+        let pairs = vec![
+            (dir.path().join("a"), dir.path().join("a2")),
+            (dir.path().join("b"), dir.path().join("b2")),
+        ];
+        let done = AtomicU64::new(0);
+        let control = AtomicU8::new(RUNNING);
+
+        let (finished, result) = work(&pairs, true, true, &done, &control);
+        assert_eq!(finished, 2);
+        assert!(result.is_ok());
+        assert_eq!(done.load(Ordering::Relaxed), 5);
+        assert_eq!(fs::read_to_string(dir.path().join("a2/x")).unwrap(), "xx");
+        assert!(!dir.path().join("a").exists());
+        assert!(!dir.path().join("b").exists());
+
+        // Canceled before the first item.
+        let pairs = vec![(dir.path().join("b2"), dir.path().join("b3"))];
+        let control = AtomicU8::new(CANCELED);
+        let (finished, result) = work(&pairs, false, true, &done, &control);
+        assert_eq!(finished, 0);
+        assert!(matches!(result, Err(FxError::Canceled)));
+        assert!(!dir.path().join("b3").exists());
+    }
+
+    #[test]
+    fn test_percent() {
+        let job = |done, total| Job {
+            id: 1,
+            kind: JobKind::Put,
+            items: vec![],
+            pairs: vec![],
+            trashed: vec![],
+            dir: PathBuf::new(),
+            total,
+            done: Arc::new(AtomicU64::new(done)),
+            control: Arc::new(AtomicU8::new(RUNNING)),
+            start: Instant::now(),
+            handle: None,
+        };
+        let mut jobs = Jobs::default();
+        assert_eq!(jobs.percent(), 100);
+        jobs.list = vec![job(10, 100), job(50, 100)];
+        assert_eq!(jobs.percent(), 30);
+    }
+}
@@ -0,0 +1,298 @@
+use super::errors::FxError;
+
+use chrono::{Local, SecondsFormat};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+const JOURNAL_EXTENSION: &str = "yaml";
+
+/// Kind of the journaled operation.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalKind {
+    /// Copy the items to `dest`.
+    Put,
+    /// Move the items to `dest`.
+    Move,
+    /// Move the items to the trash.
+    Delete,
+}
+
+/// An operation modifying files, written before it starts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub kind: JournalKind,
+    pub items: Vec<PathBuf>,
+    pub dest: PathBuf,
+    /// When the operation started, in RFC 3339.
+    pub started: String,
+}
+
+impl JournalEntry {
+    /// e.g. `A move of 3 items to /foo may be incomplete.`
+    pub fn describe(&self) -> String {
+        let count = match self.items.len() {
+            1 => "1 item".to_owned(),
+            n => format!("{} items", n),
+        };
+        match self.kind {
+            JournalKind::Put => format!(
+                "A copy of {} to {} may be incomplete.",
+                count,
+                self.dest.display()
+            ),
+            JournalKind::Move => format!(
+                "A move of {} to {} may be incomplete.",
+                count,
+                self.dest.display()
+            ),
+            JournalKind::Delete => format!("A deletion of {} may be incomplete.", count),
+        }
+    }
+
+    /// Check how far the operation went.
+    /// Items put with another name to avoid the conflict are not found.
+    pub fn verify(&self) -> String {
+        let total = self.items.len();
+        match self.kind {
+            JournalKind::Put => {
+                let put = self
+                    .items
+                    .iter()
+                    .filter(|item| {
+                        item.file_name()
+                            .is_some_and(|name| self.dest.join(name).exists())
+                    })
+                    .count();
+                format!("{}/{} in the destination.", put, total)
+            }
+            JournalKind::Move | JournalKind::Delete => {
+                let left = self.items.iter().filter(|item| item.exists()).count();
+                format!("{}/{} left in the source.", left, total)
+            }
+        }
+    }
+}
+
+/// Journal of the operations in progress, each in a file of the directory.
+/// The file is locked while the operation runs, and removed when it finishes.
+/// After a crash, the file is left unlocked, so that it can be reported on the next run.
+#[derive(Debug, Default)]
+pub struct Journal {
+    dir: Option<PathBuf>,
+}
+
+/// The journal file of the running operation.
+/// The entry is removed when the guard is dropped, whether the operation succeeded or failed.
+/// Only a crash, or a panic while it runs, leaves the entry as incomplete.
+#[derive(Debug, Default)]
+pub struct JournalGuard {
+    path: Option<PathBuf>,
+    #[cfg(target_family = "unix")]
+    _lock: Option<nix::fcntl::Flock<fs::File>>,
+}
+
+impl JournalGuard {
+    /// Remove the entry, as the operation has ended. Same as dropping the guard.
+    pub fn finish(self) {}
+}
+
+impl Drop for JournalGuard {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        if let Some(path) = self.path.take() {
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Cannot remove the journal {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+impl Journal {
+    pub fn new(dir: PathBuf) -> Self {
+        Journal { dir: Some(dir) }
+    }
+
+    /// Write the entry before the operation.
+    /// If it cannot be written, the operation runs without the journal.
+    pub fn begin(&self, kind: JournalKind, items: &[PathBuf], dest: &Path) -> JournalGuard {
+        let Some(dir) = &self.dir else {
+            return JournalGuard::default();
+        };
+        let entry = JournalEntry {
+            kind,
+            items: items.to_vec(),
+            dest: dest.to_path_buf(),
+            started: Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        };
+        match write_entry(dir, &entry) {
+            Ok(guard) => guard,
+            Err(e) => {
+                warn!("Cannot write the journal: {}", e);
+                JournalGuard::default()
+            }
+        }
+    }
+
+    /// Entries left by operations that did not finish, with the paths of their files.
+    /// Those locked by the operations running in other instances are skipped.
+    pub fn incomplete(&self) -> Vec<(PathBuf, JournalEntry)> {
+        let Some(Ok(entries)) = self.dir.as_ref().map(fs::read_dir) else {
+            return vec![];
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == JOURNAL_EXTENSION))
+            .collect();
+        paths.sort();
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let s = read_unlocked(&path)?;
+                //Empty if being written by another instance.
+                let entry = serde_yaml::from_str(&s).ok()?;
+                Some((path, entry))
+            })
+            .collect()
+    }
+
+    /// Remove the entry after it is checked.
+    pub fn clear(path: &Path) -> Result<(), FxError> {
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
+
+fn write_entry(dir: &Path, entry: &JournalEntry) -> Result<JournalGuard, FxError> {
+    fs::create_dir_all(dir)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let path = dir.join(format!(
+        "{}-{}.{}",
+        now.as_nanos(),
+        std::process::id(),
+        JOURNAL_EXTENSION
+    ));
+    let file = fs::File::options()
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    let s = serde_yaml::to_string(entry)?;
+
+    #[cfg(target_family = "unix")]
+    {
+        use nix::fcntl::{Flock, FlockArg};
+
+        let mut lock = Flock::lock(file, FlockArg::LockExclusiveNonblock)
+            .map_err(|(_, e)| FxError::Io(e.to_string()))?;
+        lock.write_all(s.as_bytes())?;
+        Ok(JournalGuard {
+            path: Some(path),
+            _lock: Some(lock),
+        })
+    }
+    #[cfg(not(target_family = "unix"))]
+    {
+        let mut file = file;
+        file.write_all(s.as_bytes())?;
+        Ok(JournalGuard { path: Some(path) })
+    }
+}
+
+/// Read the journal file if no running operation holds it.
+#[cfg(target_family = "unix")]
+fn read_unlocked(path: &Path) -> Option<String> {
+    use nix::fcntl::{Flock, FlockArg};
+
+    let file = fs::File::open(path).ok()?;
+    let mut lock = Flock::lock(file, FlockArg::LockSharedNonblock).ok()?;
+    let mut s = String::new();
+    lock.read_to_string(&mut s).ok()?;
+    Some(s)
+}
+
+#[cfg(not(target_family = "unix"))]
+fn read_unlocked(path: &Path) -> Option<String> {
+    let mut s = String::new();
+    fs::File::open(path).ok()?.read_to_string(&mut s).ok()?;
+    Some(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal::new(dir.path().join("journal"));
+        let items = vec![dir.path().join("a"), dir.path().join("b")];
+        fs::write(&items[0], "").unwrap();
+
+        //Finished.
+        journal.begin(JournalKind::Put, &items, dir.path()).finish();
+        assert!(journal.incomplete().is_empty());
+
+        //Running.
+        let guard = journal.begin(JournalKind::Move, &items, Path::new("/tmp/x"));
+        if cfg!(target_family = "unix") {
+            assert!(journal.incomplete().is_empty());
+        }
+
+        //Dropped on an error, which is not incomplete either.
+        drop(guard);
+        assert!(journal.incomplete().is_empty());
+
+        //Crashed.
+        let guard = journal.begin(JournalKind::Move, &items, Path::new("/tmp/x"));
+        let crashed = std::thread::spawn(move || {
+            let _guard = guard;
+            panic!("crashed");
+        });
+        assert!(crashed.join().is_err());
+        let incomplete = journal.incomplete();
+        assert_eq!(incomplete.len(), 1);
+        let (path, entry) = &incomplete[0];
+        assert_eq!(entry.kind, JournalKind::Move);
+        assert_eq!(entry.items, items);
+        assert_eq!(
+            entry.describe(),
+            "A move of 2 items to /tmp/x may be incomplete."
+        );
+        assert_eq!(entry.verify(), "1/2 left in the source.");
+        Journal::clear(path).unwrap();
+        assert!(journal.incomplete().is_empty());
+
+        //Without the directory.
+        assert!(Journal::default().incomplete().is_empty());
+        Journal::default()
+            .begin(JournalKind::Delete, &items, dir.path())
+            .finish();
+    }
+
+    #[test]
+    fn test_verify_put() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("dest");
+        fs::create_dir(&dest).unwrap();
+        fs::write(dest.join("a"), "").unwrap();
+        let entry = JournalEntry {
+            kind: JournalKind::Put,
+            items: vec![PathBuf::from("/src/a"), PathBuf::from("/src/b")],
+            dest,
+            started: String::new(),
+        };
+        assert_eq!(entry.verify(), "1/2 in the destination.");
+        let entry = JournalEntry {
+            kind: JournalKind::Delete,
+            items: vec![PathBuf::from("/src/a")],
+            ..entry
+        };
+        assert_eq!(entry.describe(), "A deletion of 1 item may be incomplete.");
+    }
+}
@@ -0,0 +1,950 @@
+use super::errors::FxError;
+use super::filter::FilterPreset;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// How long to wait for the next key of a sequence.
+const SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+/// `script:{name}` in `keys` binds the user script to the keys.
+pub const SCRIPT_PREFIX: &str = "script:";
+
+/// Actions performed by the keys, which can be bound to other keys by `keys` in the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    MoveDown,
+    MoveUp,
+    HalfPageDown,
+    HalfPageUp,
+    Parent,
+    Open,
+    GoToTop,
+    GoToBottom,
+    Jump,
+    JumpBackward,
+    JumpForward,
+    NewFile,
+    NewDir,
+    OpenInNewWindow,
+    AltExec,
+    NewTerminalWindow,
+    Terminal,
+    Unpack,
+    Delete,
+    Yank,
+    Cut,
+    Put,
+    Register,
+    Visual,
+    Escape,
+    Mark,
+    PutMarked,
+    SelectAll,
+    InvertSelection,
+    ToggleTrashGroup,
+    Undo,
+    Redo,
+    TogglePreview,
+    ToggleSplit,
+    ScrollDownPreview,
+    ScrollUpPreview,
+    ToggleHidden,
+    Sort,
+    Rename,
+    Search,
+    SearchNext,
+    SearchPrev,
+    Command,
+    Shell,
+    Suspend,
+    Quit,
+    QuitAlt,
+}
+
+impl Action {
+    const ALL: [Action; 47] = [
+        Action::MoveDown,
+        Action::MoveUp,
+        Action::HalfPageDown,
+        Action::HalfPageUp,
+        Action::Parent,
+        Action::Open,
+        Action::GoToTop,
+        Action::GoToBottom,
+        Action::Jump,
+        Action::JumpBackward,
+        Action::JumpForward,
+        Action::NewFile,
+        Action::NewDir,
+        Action::OpenInNewWindow,
+        Action::AltExec,
+        Action::NewTerminalWindow,
+        Action::Terminal,
+        Action::Unpack,
+        Action::Delete,
+        Action::Yank,
+        Action::Cut,
+        Action::Put,
+        Action::Register,
+        Action::Visual,
+        Action::Escape,
+        Action::Mark,
+        Action::PutMarked,
+        Action::SelectAll,
+        Action::InvertSelection,
+        Action::ToggleTrashGroup,
+        Action::Undo,
+        Action::Redo,
+        Action::TogglePreview,
+        Action::ToggleSplit,
+        Action::ScrollDownPreview,
+        Action::ScrollUpPreview,
+        Action::ToggleHidden,
+        Action::Sort,
+        Action::Rename,
+        Action::Search,
+        Action::SearchNext,
+        Action::SearchPrev,
+        Action::Command,
+        Action::Shell,
+        Action::Suspend,
+        Action::Quit,
+        Action::QuitAlt,
+    ];
+
+    /// The name used in the config.
+    fn name(&self) -> &'static str {
+        match self {
+            Action::MoveDown => "move_down",
+            Action::MoveUp => "move_up",
+            Action::HalfPageDown => "half_page_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::Parent => "parent",
+            Action::Open => "open",
+            Action::GoToTop => "go_to_top",
+            Action::GoToBottom => "go_to_bottom",
+            Action::Jump => "jump",
+            Action::JumpBackward => "jump_backward",
+            Action::JumpForward => "jump_forward",
+            Action::NewFile => "new_file",
+            Action::NewDir => "new_dir",
+            Action::OpenInNewWindow => "open_in_new_window",
+            Action::AltExec => "alt_exec",
+            Action::NewTerminalWindow => "new_terminal_window",
+            Action::Terminal => "terminal",
+            Action::Unpack => "unpack",
+            Action::Delete => "delete",
+            Action::Yank => "yank",
+            Action::Cut => "cut",
+            Action::Put => "put",
+            Action::Register => "register",
+            Action::Visual => "visual",
+            Action::Escape => "escape",
+            Action::Mark => "mark",
+            Action::PutMarked => "put_marked",
+            Action::SelectAll => "select_all",
+            Action::InvertSelection => "invert_selection",
+            Action::ToggleTrashGroup => "toggle_trash_group",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::TogglePreview => "toggle_preview",
+            Action::ToggleSplit => "toggle_split",
+            Action::ScrollDownPreview => "scroll_down_preview",
+            Action::ScrollUpPreview => "scroll_up_preview",
+            Action::ToggleHidden => "toggle_hidden",
+            Action::Sort => "sort",
+            Action::Rename => "rename",
+            Action::Search => "search",
+            Action::SearchNext => "search_next",
+            Action::SearchPrev => "search_prev",
+            Action::Command => "command",
+            Action::Shell => "shell",
+            Action::Suspend => "suspend",
+            Action::Quit => "quit",
+            Action::QuitAlt => "quit_alt",
+        }
+    }
+
+    /// The keys performing the action unless it is bound to others, as written in `keys`.
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::MoveDown => &["j", "<Down>"],
+            Action::MoveUp => &["k", "<Up>"],
+            Action::HalfPageDown => &["<C-d>"],
+            Action::HalfPageUp => &["<C-u>"],
+            Action::Parent => &["h", "<Left>"],
+            Action::Open => &["l", "<CR>", "<Right>"],
+            Action::GoToTop => &["gg"],
+            Action::GoToBottom => &["G"],
+            Action::Jump => &["z"],
+            Action::JumpBackward => &["<C-o>"],
+            Action::JumpForward => &["<Tab>"],
+            Action::NewFile => &["i"],
+            Action::NewDir => &["I"],
+            Action::OpenInNewWindow => &["o"],
+            Action::AltExec => &["O"],
+            Action::NewTerminalWindow => &["W"],
+            Action::Terminal => &["T"],
+            Action::Unpack => &["e"],
+            Action::Delete => &["dd"],
+            Action::Yank => &["yy"],
+            Action::Cut => &["x"],
+            Action::Put => &["p"],
+            Action::Register => &["\""],
+            Action::Visual => &["V"],
+            Action::Escape => &["<Esc>"],
+            Action::Mark => &["m"],
+            Action::PutMarked => &["P"],
+            Action::SelectAll => &["<C-a>"],
+            Action::InvertSelection => &["*"],
+            Action::ToggleTrashGroup => &["<Space>"],
+            Action::Undo => &["u"],
+            Action::Redo => &["<C-r>"],
+            Action::TogglePreview => &["v"],
+            Action::ToggleSplit => &["s"],
+            Action::ScrollDownPreview => &["J", "<A-j>", "<A-Down>"],
+            Action::ScrollUpPreview => &["K", "<A-k>", "<A-Up>"],
+            Action::ToggleHidden => &["<BS>"],
+            Action::Sort => &["t"],
+            Action::Rename => &["c"],
+            Action::Search => &["/"],
+            Action::SearchNext => &["n"],
+            Action::SearchPrev => &["N"],
+            Action::Command => &[":"],
+            Action::Shell => &["!"],
+            Action::Suspend => &["<C-z>"],
+            Action::Quit => &["ZZ"],
+            Action::QuitAlt => &["ZQ"],
+        }
+    }
+
+    /// The default keys working only in visual mode, e.g. `d` deleting the selected items.
+    fn visual_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Delete => &["d"],
+            Action::Yank => &["y"],
+            _ => &[],
+        }
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = FxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Action::ALL
+            .into_iter()
+            .find(|action| action.name() == s)
+            .ok_or_else(|| FxError::Keys(format!("Unknown action `{}`.", s)))
+    }
+}
+
+/// Names of the keys written as `<{name}>`, e.g. `<CR>`. The first of the same key is shown.
+const NAMED_KEYS: [(&str, KeyCode); 15] = [
+    ("CR", KeyCode::Enter),
+    ("Enter", KeyCode::Enter),
+    ("Space", KeyCode::Char(' ')),
+    ("Tab", KeyCode::Tab),
+    ("Esc", KeyCode::Esc),
+    ("BS", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+];
+
+/// A key with the modifiers, e.g. `j`, `<C-n>`, `<A-j>`, `<Down>`.
+/// Shift is not part of it: it is given as the upper case letter, and ignored on the other keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Key {
+    modifiers: KeyModifiers,
+    code: KeyCode,
+}
+
+impl Key {
+    fn from_event(event: &KeyEvent) -> Self {
+        Key {
+            modifiers: event.modifiers - KeyModifiers::SHIFT,
+            code: event.code,
+        }
+    }
+}
+
+impl std::str::FromStr for Key {
+    type Err = FxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || FxError::Keys(format!("Invalid key `{}`.", s));
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(Key {
+                modifiers: KeyModifiers::NONE,
+                code: KeyCode::Char(c),
+            });
+        }
+        let inner = s
+            .strip_prefix('<')
+            .and_then(|s| s.strip_suffix('>'))
+            .ok_or_else(invalid)?;
+        let (modifiers, name) = match inner.split_once('-') {
+            Some(("C", name)) if !name.is_empty() => (KeyModifiers::CONTROL, name),
+            Some(("A", name)) if !name.is_empty() => (KeyModifiers::ALT, name),
+            _ => (KeyModifiers::NONE, inner),
+        };
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match NAMED_KEYS.iter().find(|(n, _)| *n == name) {
+                Some((_, code)) => *code,
+                None => match name.strip_prefix('F').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=12) => KeyCode::F(n),
+                    _ => return Err(invalid()),
+                },
+            },
+        };
+        Ok(Key { modifiers, code })
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self.code {
+            KeyCode::Char(c) if c != ' ' && self.modifiers.is_empty() => {
+                return write!(f, "{}", c);
+            }
+            KeyCode::Char(' ') => "Space".to_owned(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            code => NAMED_KEYS
+                .iter()
+                .find(|(_, c)| *c == code)
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_else(|| "?".to_owned()),
+        };
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "<C-{}>", name)
+        } else if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "<A-{}>", name)
+        } else {
+            write!(f, "<{}>", name)
+        }
+    }
+}
+
+/// Parse the sequence of keys, e.g. `gj`, `<leader>f`, `<Space><C-n>`.
+/// `<leader>` is replaced by the leader key.
+fn parse_sequence(s: &str, leader: Option<Key>) -> Result<Vec<Key>, FxError> {
+    let mut keys = Vec::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        // `<` is the key itself unless it starts a name, e.g. `<>`.
+        let name = rest
+            .find('>')
+            .filter(|_| c == '<')
+            .map(|end| &rest[..=end])
+            .filter(|name| name.len() > 2 && !name[1..].contains('<'));
+        match name {
+            Some("<leader>") => {
+                let leader = leader.ok_or_else(|| {
+                    FxError::Keys(format!("`{}` has <leader>, but `leader` is not set.", s))
+                })?;
+                keys.push(leader);
+                rest = &rest["<leader>".len()..];
+            }
+            Some(name) => {
+                keys.push(name.parse()?);
+                rest = &rest[name.len()..];
+            }
+            None => {
+                keys.push(Key {
+                    modifiers: KeyModifiers::NONE,
+                    code: KeyCode::Char(c),
+                });
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if keys.is_empty() {
+        return Err(FxError::Keys("Empty key.".to_owned()));
+    }
+    Ok(keys)
+}
+
+/// Keys bound to the actions and the user scripts by `keys` in the config,
+/// and to the filters by `filter_presets`, each of which can be a sequence of keys.
+/// The actions not bound in `keys` are performed by their default keys.
+#[derive(Debug, Default)]
+pub struct Keymap {
+    /// The actions bound in `keys`, followed by the default keys of the others.
+    bindings: Vec<(Vec<Key>, Action)>,
+    /// The default keys working only in visual mode, which come first there.
+    visual: Vec<(Vec<Key>, Action)>,
+    scripts: Vec<(Vec<Key>, String)>,
+    presets: Vec<(Vec<Key>, FilterPreset)>,
+    /// Keys typed so far of a bound sequence.
+    pending: Vec<Key>,
+    pending_since: Option<Instant>,
+    /// Keys fed again after a sequence breaks.
+    replay: VecDeque<Key>,
+    /// What the held keys resolve to after they time out.
+    expired: Option<Feed>,
+}
+
+/// What the main loop does with the input.
+#[derive(Debug, PartialEq)]
+pub enum Feed {
+    /// Perform the action.
+    Action(Action),
+    /// Apply or clear the filter preset.
+    Preset(FilterPreset),
+    /// Run the user script with the name.
+    Script(String),
+    /// Wait for the next key of the sequence.
+    Wait,
+    /// Keys bound to nothing, which do nothing.
+    Ignore,
+}
+
+impl Keymap {
+    /// Parse `keys` (action name or `script:{name}` -> keys)
+    /// and `filter_presets` (keys -> preset) in the config.
+    /// Return an error on an unknown action, invalid keys or preset,
+    /// or keys bound to two of them, including a sequence starting with another.
+    /// The default keys of the actions not bound in `keys` count as bound to them,
+    /// and those of the bound actions no longer perform them.
+    /// A sequence may start with the default keys, which are performed if it breaks.
+    pub fn new(
+        keys: &BTreeMap<String, String>,
+        presets: &BTreeMap<String, String>,
+        leader: Option<&str>,
+    ) -> Result<Self, FxError> {
+        let leader = match leader {
+            Some(leader) => Some(
+                leader
+                    .parse::<Key>()
+                    .map_err(|_| FxError::Keys(format!("Invalid leader `{}`.", leader)))?,
+            ),
+            None => None,
+        };
+        let mut keymap = Keymap::default();
+        // (keys, as written, bound to)
+        let mut bound: Vec<(Vec<Key>, &str, String)> = Vec::new();
+        for (name, key) in keys {
+            let script = name.strip_prefix(SCRIPT_PREFIX);
+            let action: Option<Action> = match script {
+                Some(_) => None,
+                None => Some(name.parse()?),
+            };
+            let sequence = parse_sequence(key, leader)
+                .map_err(|_| FxError::Keys(format!("Invalid key `{}` for {}.", key, name)))?;
+            bound.push((sequence.clone(), key, name.to_owned()));
+            match (action, script) {
+                (Some(action), _) => keymap.bindings.push((sequence, action)),
+                (None, Some(script)) => keymap.scripts.push((sequence, script.to_owned())),
+                (None, None) => unreachable!(),
+            }
+        }
+        for (key, preset) in presets {
+            let preset = FilterPreset::parse(preset)?;
+            let sequence = parse_sequence(key, leader)?;
+            bound.push((
+                sequence.clone(),
+                key,
+                format!("the filter preset {}", preset.name),
+            ));
+            keymap.presets.push((sequence, preset));
+        }
+        for (i, (a, a_key, a_name)) in bound.iter().enumerate() {
+            for (b, b_key, b_name) in bound[i + 1..].iter() {
+                if a == b {
+                    return Err(FxError::Keys(format!(
+                        "`{}` is bound to both {} and {}.",
+                        a_key, a_name, b_name
+                    )));
+                }
+                if a.starts_with(b) || b.starts_with(a) {
+                    return Err(FxError::Keys(format!(
+                        "`{}` for {} and `{}` for {} start with the same keys.",
+                        a_key, a_name, b_key, b_name
+                    )));
+                }
+            }
+        }
+        let rebound: Vec<Action> = keymap.bindings.iter().map(|(_, action)| *action).collect();
+        for action in Action::ALL {
+            if rebound.contains(&action) {
+                continue;
+            }
+            let defaults = action
+                .default_keys()
+                .iter()
+                .map(|key| (key, false))
+                .chain(action.visual_keys().iter().map(|key| (key, true)));
+            for (default, visual) in defaults {
+                let default = parse_sequence(default, None)?;
+                if let Some((_, key, name)) =
+                    bound.iter().find(|(keys, _, _)| default.starts_with(keys))
+                {
+                    return Err(FxError::Keys(format!(
+                        "`{}` for {} is the default key of {}, which is not bound to other keys.",
+                        key,
+                        name,
+                        action.name()
+                    )));
+                }
+                if visual {
+                    keymap.visual.push((default, action));
+                } else {
+                    keymap.bindings.push((default, action));
+                }
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// Take the key typed. A key starting a bound sequence is held until the sequence completes.
+    /// In visual mode, the keys working only there come first.
+    pub fn feed(&mut self, event: &KeyEvent, visual: bool) -> Feed {
+        let key = Key::from_event(event);
+        if self.is_expired() {
+            self.replay.push_back(key);
+            return self.break_sequence(visual);
+        }
+        self.resolve(key, visual)
+    }
+
+    /// If the held keys are not completed within `SEQUENCE_TIMEOUT`, resolve them,
+    /// to be handled by `replayed`. Return true if so.
+    pub fn expire(&mut self, visual: bool) -> bool {
+        if !self.is_expired() {
+            return false;
+        }
+        self.expired = Some(self.break_sequence(visual));
+        true
+    }
+
+    /// What the timed out keys or those fed again resolve to, handled before the next input.
+    pub fn replayed(&mut self, visual: bool) -> Option<Feed> {
+        if let Some(feed) = self.expired.take() {
+            return Some(feed);
+        }
+        let key = self.replay.pop_front()?;
+        Some(self.resolve(key, visual))
+    }
+
+    /// The keys held, shown while waiting for the rest of the sequence.
+    pub fn pending_keys(&self) -> String {
+        self.pending.iter().map(|key| key.to_string()).collect()
+    }
+
+    fn is_expired(&self) -> bool {
+        !self.pending.is_empty()
+            && self
+                .pending_since
+                .is_some_and(|since| since.elapsed() >= SEQUENCE_TIMEOUT)
+    }
+
+    /// Keys bound by themselves and starting a longer sequence, e.g. the leader,
+    /// wait for the rest, except the keys of visual mode there.
+    fn resolve(&mut self, key: Key, visual: bool) -> Feed {
+        self.pending.push(key);
+        let in_visual = visual && self.visual.iter().any(|(keys, _)| *keys == self.pending);
+        let is_prefix = self
+            .bindings
+            .iter()
+            .map(|(keys, _)| keys)
+            .chain(self.scripts.iter().map(|(keys, _)| keys))
+            .chain(self.presets.iter().map(|(keys, _)| keys))
+            .any(|keys| keys.len() > self.pending.len() && keys.starts_with(&self.pending));
+        if is_prefix && !in_visual {
+            self.pending_since = Some(Instant::now());
+            return Feed::Wait;
+        }
+        self.break_sequence(visual)
+    }
+
+    /// Resolve the held keys, which no longer wait for the rest: the longest sequence bound
+    /// at the start is performed, or the first key is dropped if none, and the rest is fed again.
+    fn break_sequence(&mut self, visual: bool) -> Feed {
+        let keys: Vec<Key> = self.pending.drain(..).collect();
+        self.pending_since = None;
+        let (feed, len) = (1..=keys.len())
+            .rev()
+            .find_map(|len| self.lookup(&keys[..len], visual).map(|feed| (feed, len)))
+            .unwrap_or((Feed::Ignore, 1));
+        for key in keys[len..].iter().rev() {
+            self.replay.push_front(*key);
+        }
+        feed
+    }
+
+    /// What the keys are bound to, if any.
+    fn lookup(&self, keys: &[Key], visual: bool) -> Option<Feed> {
+        let visual: &[(Vec<Key>, Action)] = if visual { &self.visual } else { &[] };
+        if let Some((_, action)) = visual
+            .iter()
+            .chain(self.bindings.iter())
+            .find(|(bound, _)| bound == keys)
+        {
+            return Some(Feed::Action(*action));
+        }
+        if let Some((_, preset)) = self.presets.iter().find(|(bound, _)| bound == keys) {
+            return Some(Feed::Preset(preset.clone()));
+        }
+        self.scripts
+            .iter()
+            .find(|(bound, _)| bound == keys)
+            .map(|(_, script)| Feed::Script(script.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(modifiers: KeyModifiers, code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    fn char(c: char) -> KeyEvent {
+        press(KeyModifiers::NONE, KeyCode::Char(c))
+    }
+
+    #[test]
+    fn test_keymap() {
+        let keys = BTreeMap::from([
+            ("move_down".to_owned(), "<C-n>".to_owned()),
+            ("move_up".to_owned(), "<C-p>".to_owned()),
+            ("toggle_hidden".to_owned(), ".".to_owned()),
+            ("quit".to_owned(), "Q".to_owned()),
+        ]);
+        let mut keymap = Keymap::new(&keys, &BTreeMap::new(), None).unwrap();
+        assert_eq!(
+            keymap.feed(&press(KeyModifiers::CONTROL, KeyCode::Char('n')), false),
+            Feed::Action(Action::MoveDown)
+        );
+        assert_eq!(
+            keymap.feed(&char('.'), false),
+            Feed::Action(Action::ToggleHidden)
+        );
+        assert_eq!(
+            keymap.feed(&press(KeyModifiers::SHIFT, KeyCode::Char('Q')), false),
+            Feed::Action(Action::Quit)
+        );
+        // Not bound.
+        assert_eq!(keymap.feed(&char('l'), false), Feed::Action(Action::Open));
+        assert_eq!(
+            keymap.feed(&press(KeyModifiers::SHIFT, KeyCode::Right), false),
+            Feed::Action(Action::Open)
+        );
+        assert_eq!(keymap.feed(&char('q'), false), Feed::Ignore);
+        // Rebound.
+        assert_eq!(keymap.feed(&char('j'), false), Feed::Ignore);
+        assert_eq!(
+            keymap.feed(&press(KeyModifiers::NONE, KeyCode::Down), false),
+            Feed::Ignore
+        );
+
+        let empty = BTreeMap::new();
+        let bind = |action: &str, key: &str| BTreeMap::from([(action.to_owned(), key.to_owned())]);
+        assert!(Keymap::new(&bind("fly", "f"), &empty, None).is_err());
+        assert!(Keymap::new(&bind("open", "<X-o>"), &empty, None).is_err());
+        let conflict = Keymap::new(
+            &BTreeMap::from([
+                ("move_down".to_owned(), "n".to_owned()),
+                ("search_next".to_owned(), "n".to_owned()),
+            ]),
+            &empty,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            conflict.to_string(),
+            "Error: Invalid keys -> `n` is bound to both move_down and search_next."
+        );
+    }
+
+    #[test]
+    fn test_default_keys() {
+        let bind = |keys: &[(&str, &str)]| {
+            keys.iter()
+                .map(|(action, key)| (action.to_string(), key.to_string()))
+                .collect::<BTreeMap<String, String>>()
+        };
+        let empty = BTreeMap::new();
+        //`x` cuts unless cut is bound to other keys.
+        assert_eq!(
+            Keymap::new(&bind(&[("delete", "x")]), &empty, None)
+                .unwrap_err()
+                .to_string(),
+            "Error: Invalid keys -> `x` for delete is the default key of cut, which is not bound to other keys."
+        );
+        assert!(Keymap::new(&bind(&[("script:s", "g")]), &empty, None).is_err());
+        assert!(Keymap::new(&empty, &bind(&[("x", "big: >1M")]), None).is_err());
+        let mut keymap =
+            Keymap::new(&bind(&[("delete", "x"), ("cut", "X")]), &empty, None).unwrap();
+        assert_eq!(keymap.feed(&char('x'), false), Feed::Action(Action::Delete));
+        assert_eq!(keymap.feed(&char('X'), false), Feed::Action(Action::Cut));
+
+        //The default keys of the bound actions do nothing.
+        assert_eq!(keymap.feed(&char('d'), false), Feed::Ignore);
+        assert_eq!(keymap.feed(&char('d'), true), Feed::Ignore);
+        let mut keymap = Keymap::new(&bind(&[("go_to_top", "<Home>")]), &empty, None).unwrap();
+        assert_eq!(keymap.feed(&char('g'), false), Feed::Ignore);
+        //Bound to itself, it keeps working.
+        let mut keymap = Keymap::new(&bind(&[("move_down", "j")]), &empty, None).unwrap();
+        assert_eq!(
+            keymap.feed(&char('j'), false),
+            Feed::Action(Action::MoveDown)
+        );
+    }
+
+    #[test]
+    fn test_default_sequences() {
+        let mut keymap = Keymap::new(&BTreeMap::new(), &BTreeMap::new(), None).unwrap();
+        assert_eq!(keymap.feed(&char('g'), false), Feed::Wait);
+        assert_eq!(keymap.pending_keys(), "g");
+        assert_eq!(
+            keymap.feed(&char('g'), false),
+            Feed::Action(Action::GoToTop)
+        );
+        keymap.feed(&press(KeyModifiers::SHIFT, KeyCode::Char('Z')), false);
+        assert_eq!(
+            keymap.feed(&press(KeyModifiers::SHIFT, KeyCode::Char('Q')), false),
+            Feed::Action(Action::QuitAlt)
+        );
+        // In visual mode, `d` and `y` act at once.
+        assert_eq!(keymap.feed(&char('d'), true), Feed::Action(Action::Delete));
+        assert_eq!(keymap.feed(&char('y'), true), Feed::Action(Action::Yank));
+        assert_eq!(keymap.feed(&char('d'), false), Feed::Wait);
+        assert_eq!(keymap.feed(&char('d'), false), Feed::Action(Action::Delete));
+        // The key breaking `dd` is fed again.
+        keymap.feed(&char('d'), false);
+        assert_eq!(keymap.feed(&char('j'), false), Feed::Ignore);
+        assert_eq!(keymap.pending_keys(), "");
+        assert_eq!(keymap.replayed(false), Some(Feed::Action(Action::MoveDown)));
+        assert_eq!(keymap.replayed(false), None);
+        assert_eq!(
+            keymap.feed(&press(KeyModifiers::NONE, KeyCode::Esc), false),
+            Feed::Action(Action::Escape)
+        );
+        assert_eq!(
+            keymap.feed(&press(KeyModifiers::ALT, KeyCode::Char('j')), false),
+            Feed::Action(Action::ScrollDownPreview)
+        );
+        assert_eq!(
+            keymap.feed(&press(KeyModifiers::CONTROL, KeyCode::Char('j')), false),
+            Feed::Ignore
+        );
+        // Every default key performs the action.
+        for action in Action::ALL {
+            for key in action.default_keys() {
+                let mut feed = Feed::Wait;
+                for key in parse_sequence(key, None).unwrap() {
+                    feed = keymap.feed(&press(key.modifiers, key.code), false);
+                }
+                assert_eq!(feed, Feed::Action(action));
+            }
+        }
+    }
+
+    #[test]
+    fn test_sequences() {
+        let keys = BTreeMap::from([
+            ("go_to_bottom".to_owned(), "<leader>j".to_owned()),
+            ("go_to_top".to_owned(), "<leader>k".to_owned()),
+            ("toggle_hidden".to_owned(), "<leader>.".to_owned()),
+        ]);
+        let mut keymap = Keymap::new(&keys, &BTreeMap::new(), Some("<Space>")).unwrap();
+        assert_eq!(keymap.feed(&char(' '), false), Feed::Wait);
+        assert_eq!(keymap.pending_keys(), "<Space>");
+        assert_eq!(
+            keymap.feed(&char('j'), false),
+            Feed::Action(Action::GoToBottom)
+        );
+        assert_eq!(keymap.pending_keys(), "");
+        keymap.feed(&char(' '), false);
+        assert_eq!(
+            keymap.feed(&char('k'), false),
+            Feed::Action(Action::GoToTop)
+        );
+        assert_eq!(keymap.replayed(false), None);
+
+        // Broken sequence: the leader performs its default action, and the rest is fed again.
+        assert_eq!(keymap.feed(&char(' '), false), Feed::Wait);
+        assert_eq!(
+            keymap.feed(&char('x'), false),
+            Feed::Action(Action::ToggleTrashGroup)
+        );
+        assert_eq!(keymap.replayed(false), Some(Feed::Action(Action::Cut)));
+        assert_eq!(keymap.replayed(false), None);
+
+        // Timed out.
+        keymap.feed(&char(' '), false);
+        keymap.pending_since = Some(Instant::now() - SEQUENCE_TIMEOUT);
+        assert!(keymap.expire(false));
+        assert_eq!(
+            keymap.replayed(false),
+            Some(Feed::Action(Action::ToggleTrashGroup))
+        );
+        assert!(!keymap.expire(false));
+        // A key typed after the timeout comes after the held keys.
+        keymap.feed(&char(' '), false);
+        keymap.pending_since = Some(Instant::now() - SEQUENCE_TIMEOUT);
+        assert_eq!(
+            keymap.feed(&char('x'), false),
+            Feed::Action(Action::ToggleTrashGroup)
+        );
+        assert_eq!(keymap.replayed(false), Some(Feed::Action(Action::Cut)));
+
+        let bind = |action: &str, key: &str| BTreeMap::from([(action.to_owned(), key.to_owned())]);
+        let empty = BTreeMap::new();
+        assert!(Keymap::new(&bind("open", "<leader>o"), &empty, None).is_err());
+        assert!(Keymap::new(&bind("open", "o"), &empty, Some("<Foo>")).is_err());
+        let conflict = Keymap::new(
+            &BTreeMap::from([
+                ("move_down".to_owned(), "gj".to_owned()),
+                ("move_up".to_owned(), "g".to_owned()),
+            ]),
+            &empty,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            conflict.to_string(),
+            "Error: Invalid keys -> `gj` for move_down and `g` for move_up start with the same keys."
+        );
+    }
+
+    #[test]
+    fn test_filter_presets() {
+        let bind = |key: &str, preset: &str| BTreeMap::from([(key.to_owned(), preset.to_owned())]);
+        let mut keymap = Keymap::new(
+            &BTreeMap::new(),
+            &bind("<F1>", "images: name:*.png,*.jpg"),
+            None,
+        )
+        .unwrap();
+        let Feed::Preset(preset) = keymap.feed(&press(KeyModifiers::NONE, KeyCode::F(1)), false)
+        else {
+            panic!("The preset is not bound.");
+        };
+        assert_eq!(preset.name, "images");
+        assert_eq!(preset.filter, "name:*.png,*.jpg");
+        assert_eq!(
+            keymap.feed(&press(KeyModifiers::NONE, KeyCode::F(2)), false),
+            Feed::Ignore
+        );
+
+        let empty = BTreeMap::new();
+        assert!(Keymap::new(&empty, &bind("<F1>", "name:*.png"), None).is_err());
+        assert!(Keymap::new(&empty, &bind("<F13>", "images: name:*.png"), None).is_err());
+        let conflict = Keymap::new(
+            &BTreeMap::from([("move_down".to_owned(), "<F1>".to_owned())]),
+            &bind("<F1>", "images: name:*.png"),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            conflict.to_string(),
+            "Error: Invalid keys -> `<F1>` is bound to both move_down and the filter preset images."
+        );
+    }
+
+    #[test]
+    fn test_scripts() {
+        let keys = BTreeMap::from([
+            ("script:git-root".to_owned(), "<leader>g".to_owned()),
+            ("move_down".to_owned(), "<C-n>".to_owned()),
+        ]);
+        let mut keymap = Keymap::new(&keys, &BTreeMap::new(), Some("<Space>")).unwrap();
+        assert_eq!(keymap.feed(&char(' '), false), Feed::Wait);
+        assert_eq!(
+            keymap.feed(&char('g'), false),
+            Feed::Script("git-root".to_owned())
+        );
+        assert_eq!(keymap.pending_keys(), "");
+
+        let conflict = Keymap::new(
+            &BTreeMap::from([
+                ("script:a".to_owned(), "<F1>".to_owned()),
+                ("script:b".to_owned(), "<F1>".to_owned()),
+            ]),
+            &BTreeMap::new(),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            conflict.to_string(),
+            "Error: Invalid keys -> `<F1>` is bound to both script:a and script:b."
+        );
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(
+            "<Down>".parse::<Key>().unwrap(),
+            Key {
+                modifiers: KeyModifiers::NONE,
+                code: KeyCode::Down
+            }
+        );
+        assert_eq!(
+            "<A-j>".parse::<Key>().unwrap(),
+            Key {
+                modifiers: KeyModifiers::ALT,
+                code: KeyCode::Char('j')
+            }
+        );
+        assert_eq!(
+            "-".parse::<Key>().unwrap(),
+            Key {
+                modifiers: KeyModifiers::NONE,
+                code: KeyCode::Char('-')
+            }
+        );
+        assert!("jj".parse::<Key>().is_err());
+        assert!("<Foo>".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn test_parse_sequence() {
+        let char = |c| Key {
+            modifiers: KeyModifiers::NONE,
+            code: KeyCode::Char(c),
+        };
+        let space = Some(char(' '));
+        assert_eq!(
+            parse_sequence("gj", None).unwrap(),
+            vec![char('g'), char('j')]
+        );
+        assert_eq!(
+            parse_sequence("<leader><C-n>", space).unwrap(),
+            vec![
+                char(' '),
+                Key {
+                    modifiers: KeyModifiers::CONTROL,
+                    code: KeyCode::Char('n')
+                }
+            ]
+        );
+        assert_eq!(
+            parse_sequence("<>", None).unwrap(),
+            vec![char('<'), char('>')]
+        );
+        assert_eq!(char(' ').to_string(), "<Space>");
+        assert_eq!(
+            parse_sequence("<A-Tab>", None).unwrap()[0].to_string(),
+            "<A-Tab>"
+        );
+        assert!(parse_sequence("<leader>f", None).is_err());
+        assert!(parse_sequence("g<Foo>", None).is_err());
+        assert!(parse_sequence("", None).is_err());
+    }
+}
@@ -1,20 +1,41 @@
+use super::binary::summarize_binary;
+use super::column::*;
 use super::config::*;
+use super::database::TableCache;
 use super::errors::FxError;
+use super::font::describe_font;
 use super::functions::*;
+use super::item::{FileType, ItemInfo};
+use super::ls_colors::LsColors;
+use super::magic_packed::list_archive;
+use super::markdown::render_markdown;
 use super::nums::*;
-use super::session::{read_session, SortKey};
-use super::state::{ItemInfo, BEGINNING_ROW};
-use super::term::*;
+use super::palette::render_palette;
+use super::session::{read_session, NameOrder, SortKey};
+use super::state::BEGINNING_ROW;
+use super::structured::render_structured;
+use super::table::{render_table, table_delimiter};
+use super::template::Template;
+use super::theme::Theme;
 
 use log::error;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 pub const CHAFA_WARNING: &str =
     "From v1.1.0, the image preview needs chafa (>= v1.10.0). For more details, please see help by `:h` ";
 
-pub const PROPER_WIDTH: u16 = 28;
-pub const TIME_WIDTH: u16 = 16;
+/// The list cannot be shown in the terminal smaller than this.
+pub const MIN_COLUMNS: u16 = 4;
+pub const MIN_ROWS: u16 = 4;
+/// If the name cannot get this width, columns are not shown.
+const MIN_NAME_WIDTH: u16 = 11;
 const EXTRA_SPACES: u16 = 3;
+const DEFAULT_PREVIEW_SIZE: u16 = 50;
+/// Entries of an archive more than this are not listed in the preview.
+const MAX_ARCHIVE_ENTRIES: usize = 200;
+/// Cached thumbnails not regenerated for this long are removed on startup.
+const THUMBNAIL_LIFETIME: std::time::Duration = std::time::Duration::from_secs(30 * 86_400);
 
 #[derive(Debug, Default)]
 pub struct Layout {
@@ -23,17 +44,36 @@ pub struct Layout {
     pub terminal_row: u16,
     pub terminal_column: u16,
     pub name_max_len: usize,
-    pub time_start_pos: u16,
+    pub column_start_pos: u16,
+    pub columns: Vec<Column>,
+    pub column_widths: ColumnWidths,
+    pub time_format: TimeFormat,
+    /// Replace the default header and status line if set.
+    pub header_format: Option<Template>,
+    pub status_format: Option<Template>,
+    pub shown_columns: Vec<Column>,
     pub colors: ConfigColor,
+    pub theme: Theme,
+    /// Read from `LS_COLORS` if `ls_colors` is enabled in the config.
+    pub ls_colors: Option<LsColors>,
     pub sort_by: SortKey,
+    pub name_order: NameOrder,
+    /// Scripts printing the sort keys, by the name shown as the sort key.
+    pub sort_scripts: BTreeMap<String, String>,
+    pub directories_first: bool,
     pub show_hidden: bool,
     pub side: Side,
     pub split: Split,
+    /// Percentage of the terminal used by the preview (or registers).
+    pub preview_size: u16,
     pub preview_start: (u16, u16),
     pub preview_space: (u16, u16),
     pub has_chafa: bool,
     pub has_bat: bool,
+    pub tables: TableCache,
     pub is_kitty: bool,
+    /// Directory to cache the image previews. If None, they are not cached.
+    pub thumbnail_dir: Option<std::path::PathBuf>,
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Clone)]
@@ -45,6 +85,22 @@ pub enum PreviewType {
     Image,
     Text,
     Binary,
+    Database,
+    Archive,
+    Font,
+}
+
+/// What the preview area shows for the item, drawn by the frontend.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Preview {
+    /// A note in place of the content, e.g. `(file not readable)`.
+    Note(&'static str),
+    /// Lines of the content, scrolled by `preview_scroll` of the item.
+    Text(Vec<String>),
+    /// Output of chafa.
+    Image(String),
+    /// An image, but chafa is not installed.
+    NoChafa,
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Clone, Default)]
@@ -63,49 +119,93 @@ pub enum Split {
 }
 
 impl Layout {
-    pub fn new(session_path: &std::path::Path, config: Config) -> Result<Self, FxError> {
-        let (original_column, original_row) = terminal_size()?;
-        // Return error if terminal size may cause panic
-        if original_column < 4 {
-            error!("Too small terminal size (less than 4 columns).");
-            return Err(FxError::TooSmallWindowSize);
-        };
-        if original_row < 4 {
-            error!("Too small terminal size. (less than 4 rows)");
-            return Err(FxError::TooSmallWindowSize);
-        };
-
+    /// The layout for the terminal of the size, which is not too small.
+    pub fn new(
+        session_path: &std::path::Path,
+        config: Config,
+        original_column: u16,
+        original_row: u16,
+    ) -> Self {
         // Prepare state fields.
-        let (time_start, name_max) = make_layout(original_column);
+        let columns = config
+            .columns
+            .clone()
+            .unwrap_or_else(Column::default_columns);
+        let column_widths = config.column_widths.clone().unwrap_or_default();
+        let time_format = config
+            .time_format
+            .as_deref()
+            .and_then(|format| TimeFormat::parse(format).ok())
+            .unwrap_or_default();
+        let (column_start, name_max, shown_columns) =
+            make_layout(original_column, &columns, &column_widths);
         let session = read_session(session_path);
-        let split = session.split.unwrap_or_default();
+        let split = config.preview_split.or(session.split).unwrap_or_default();
+        let preview_size = to_preview_size(config.preview_size);
         let has_bat = check_bat();
         let has_chafa = check_chafa();
         let is_kitty = check_kitty_support();
 
-        let colors = config.color.unwrap_or_default();
+        let theme = read_theme(config.theme.as_deref());
+        let colors = config.color.unwrap_or_else(|| theme.item_colors());
+        let ls_colors = read_ls_colors(config.ls_colors);
 
-        Ok(Layout {
+        Layout {
             nums: Num::new(),
             y: BEGINNING_ROW,
             terminal_row: original_row,
             terminal_column: original_column,
             name_max_len: name_max,
-            time_start_pos: time_start,
+            column_start_pos: column_start,
+            columns,
+            column_widths,
+            time_format,
+            header_format: to_template(config.header_format.as_deref()),
+            status_format: to_template(config.status_format.as_deref()),
+            shown_columns,
             sort_by: session.sort_by,
+            name_order: config.name_order.unwrap_or_default(),
+            sort_scripts: config.sort_scripts.clone().unwrap_or_default(),
+            directories_first: config.directories_first.unwrap_or(true),
             show_hidden: session.show_hidden,
             side: match session.preview.unwrap_or(false) {
                 true => Side::Preview,
                 false => Side::None,
             },
             split,
+            preview_size,
             preview_start: (0, 0),
             preview_space: (0, 0),
             has_bat,
             has_chafa,
+            tables: TableCache::default(),
             is_kitty,
             colors,
-        })
+            theme,
+            ls_colors,
+            thumbnail_dir: None,
+        }
+    }
+
+    /// The color of the item name. Changed files in git take `dirty_fg`,
+    /// then the extension in the config, `LS_COLORS` and the color of the file type follow.
+    pub fn item_color(&self, item: &ItemInfo) -> &Colorname {
+        if item.is_dirty {
+            return &self.colors.dirty_fg;
+        }
+        let ext_color = match (&item.file_type, &item.file_ext) {
+            (FileType::File, Some(ext)) => self.colors.ext.get(ext),
+            _ => None,
+        };
+        if let Some(color) = ext_color.or_else(|| self.ls_colors.as_ref()?.color_of(item)) {
+            return color;
+        }
+        match item.file_type {
+            FileType::Directory => &self.colors.dir_fg,
+            FileType::File => &self.colors.file_fg,
+            FileType::Symlink => &self.colors.symlink_fg,
+            FileType::Other => &self.colors.other_fg,
+        }
     }
 
     pub fn is_preview(&self) -> bool {
@@ -128,200 +228,118 @@ impl Layout {
         self.side = Side::None;
     }
 
-    pub fn print_reg(&self, reg: &[String]) {
-        match self.split {
-            Split::Vertical => {
-                self.clear_preview(self.preview_start.0);
+    /// What to show in the preview area for the item, by its preview type.
+    pub fn preview(&self, item: &ItemInfo) -> Result<Preview, FxError> {
+        let preview = match item.preview_type {
+            Some(PreviewType::NotReadable) => Preview::Note("(file not readable)"),
+            Some(PreviewType::TooLargeImage) => {
+                Preview::Note("(image too big for preview: over 100MB)")
             }
-            Split::Horizontal => {
-                self.clear_preview(self.preview_start.1);
+            Some(PreviewType::TooLargeText) => {
+                Preview::Note("(text too big for preview: over 1MB)")
             }
-        }
-
-        if reg.iter().all(|x| x.is_empty()) {
-            print!("No registers found.");
-            return;
-        }
-
-        match self.split {
-            Split::Vertical => {
-                for (i, line) in reg.iter().enumerate() {
-                    let row = self.preview_start.1 + i as u16;
-                    move_to(self.preview_start.0, row);
-                    print!("{}", line);
-                    if i as u16 == self.preview_space.1 - 1 {
-                        break;
-                    }
+            Some(PreviewType::Directory) => Preview::Text(self.preview_directory(item)),
+            Some(PreviewType::Image) => {
+                if self.has_chafa {
+                    Preview::Image(self.preview_image(item)?)
+                } else {
+                    Preview::NoChafa
                 }
             }
-            Split::Horizontal => {
-                for (i, line) in reg.iter().enumerate() {
-                    let row = self.preview_start.1 + i as u16;
-                    move_to(1, row);
-                    print!("{}", line);
-                    if row == self.terminal_row + self.preview_space.1 {
-                        break;
-                    }
+            Some(PreviewType::Text) => Preview::Text(self.preview_text(item)?),
+            Some(PreviewType::Binary) => {
+                match summarize_binary(&item.file_path, self.preview_space.0) {
+                    Ok(lines) => Preview::Text(lines),
+                    Err(_) => Preview::Note("(binary file)"),
                 }
             }
-        }
+            Some(PreviewType::Database) => Preview::Text(self.preview_database(item)?),
+            Some(PreviewType::Font) => Preview::Text(describe_font(&item.file_path)?),
+            Some(PreviewType::Archive) => Preview::Text(self.preview_archive(item)?),
+            _ => Preview::Note("(not available)"),
+        };
+        Ok(preview)
     }
 
-    /// Print preview according to the preview type.
-    pub fn print_preview(&self, item: Option<&ItemInfo>, y: u16) {
-        if let Some(item) = item {
-            match self.split {
-                Split::Vertical => {
-                    //At least print the item name
-                    self.print_file_name(item);
-                    //Clear preview space
-                    self.clear_preview(self.preview_start.0);
-                }
-                Split::Horizontal => {
-                    self.clear_preview(self.preview_start.1);
-                }
-            }
-
-            match item.preview_type {
-                Some(PreviewType::NotReadable) => {
-                    print!("(file not readable)");
-                }
-                Some(PreviewType::TooLargeImage) => {
-                    print!("(image too big for preview: over 100MB)");
-                }
-                Some(PreviewType::TooLargeText) => {
-                    print!("(text too big for preview: over 1MB)");
-                }
-                Some(PreviewType::Directory) => {
-                    self.preview_directory(item);
-                }
-                Some(PreviewType::Image) => {
-                    if self.has_chafa {
-                        if let Err(e) = self.preview_image(item) {
-                            print_warning(e, y);
-                        }
-                    } else {
-                        let help = format_txt(CHAFA_WARNING, self.terminal_column - 1, false);
-                        for (i, line) in help.iter().enumerate() {
-                            move_to(self.preview_start.0, BEGINNING_ROW + i as u16);
-                            print!("{}", line,);
-                            if BEGINNING_ROW + i as u16 == self.terminal_row - 1 {
-                                break;
-                            }
-                        }
-                    }
-                }
-                Some(PreviewType::Text) => {
-                    if let Err(e) = self.preview_text(item) {
-                        print_warning(e, y);
-                    }
-                }
-                Some(PreviewType::Binary) => {
-                    print!("(binary file)");
-                }
-                _ => {
-                    print!("(not available)");
-                }
-            }
-        }
+    fn preview_text(&self, item: &ItemInfo) -> Result<Vec<String>, FxError> {
+        let Some(content) = &item.content else {
+            return Ok(vec![]);
+        };
+        let lines = if is_markdown(item) {
+            render_markdown(content, self.preview_space.0)
+        } else if let Some(lines) = render_palette(item, content, self.preview_space.0) {
+            lines
+        } else if let Some(lines) = render_structured(item, content, self.preview_space.0) {
+            lines
+        } else if let Some(delimiter) = table_delimiter(item) {
+            render_table(content, delimiter, self.preview_space.0)
+        } else if !self.has_bat {
+            format_txt(content, self.preview_space.0, false)
+        } else {
+            let path = item.file_path.to_str().ok_or(FxError::InvalidPath)?;
+            let output = std::process::Command::new("bat")
+                .args([
+                    path,
+                    "-fpP",
+                    "--tabs",
+                    "4",
+                    "--wrap",
+                    "character",
+                    "--terminal-width",
+                    &format!("{}", self.preview_space.0),
+                ])
+                .output()?
+                .stdout;
+            let content = String::from_utf8(output)?;
+            content
+                .split('\n')
+                .map(|x| x.to_owned())
+                .collect::<Vec<String>>()
+        };
+        Ok(lines)
     }
 
-    /// Print item name at the top.
-    fn print_file_name(&self, item: &ItemInfo) {
-        move_to(self.preview_start.0 - 1, 1);
-        clear_until_newline();
-        move_right(1);
-        let mut file_name = format!("[{}]", item.file_name);
-        if file_name.bytes().len() > self.preview_space.0 as usize {
-            file_name = shorten_str_including_wide_char(&file_name, self.preview_space.0 as usize);
-        }
-        print!("{}", file_name);
+    /// The tables and the number of their rows.
+    fn preview_database(&self, item: &ItemInfo) -> Result<Vec<String>, FxError> {
+        let tables = self.tables.list_tables(&item.file_path)?;
+        Ok(render_table(&tables, '\t', self.preview_space.0))
     }
 
-    fn preview_text(&self, item: &ItemInfo) -> Result<(), FxError> {
-        if let Some(content) = &item.content {
-            if !self.has_bat {
-                self.print_txt_in_preview_area(
-                    item,
-                    &format_txt(content, self.preview_space.0, false),
-                );
-            } else {
-                let path = item.file_path.to_str().ok_or(FxError::InvalidPath)?;
-                let output = std::process::Command::new("bat")
-                    .args([
-                        path,
-                        "-fpP",
-                        "--tabs",
-                        "4",
-                        "--wrap",
-                        "character",
-                        "--terminal-width",
-                        &format!("{}", self.preview_space.0),
-                    ])
-                    .output()?
-                    .stdout;
-                let content = String::from_utf8(output)?;
-                let content = content
-                    .split('\n')
-                    .map(|x| x.to_owned())
-                    .collect::<Vec<String>>();
-                self.print_txt_in_preview_area(item, &content);
-            }
+    /// The first entries of the archive with their uncompressed sizes.
+    fn preview_archive(&self, item: &ItemInfo) -> Result<Vec<String>, FxError> {
+        let summary = list_archive(&item.file_path, MAX_ARCHIVE_ENTRIES)?;
+        let width = self.preview_space.0 as usize;
+        let mut lines = vec![shorten_str_including_wide_char(
+            &format!(
+                "{} entries, {} uncompressed",
+                summary.count,
+                to_proper_size(summary.total)
+            ),
+            width,
+        )];
+        for (name, size) in summary.entries.iter() {
+            let line = format!("{:>6} {}", to_proper_size(*size), name);
+            lines.push(shorten_str_including_wide_char(&line, width));
+        }
+        if summary.count > summary.entries.len() {
+            lines.push(format!("… {} more", summary.count - summary.entries.len()));
         }
-        Ok(())
+        Ok(lines)
     }
 
-    fn preview_directory(&self, item: &ItemInfo) {
+    fn preview_directory(&self, item: &ItemInfo) -> Vec<String> {
         let contents = match &item.symlink_dir_path {
             None => list_up_contents(&item.file_path, self.preview_space.0),
             Some(p) => list_up_contents(p, self.preview_space.0),
         };
-        if let Ok(contents) = contents {
-            self.print_txt_in_preview_area(
-                item,
-                &format_txt(&contents, self.preview_space.0, false),
-            );
+        match contents {
+            Ok(contents) => format_txt(&contents, self.preview_space.0, false),
+            Err(_) => vec![],
         }
     }
 
-    fn print_txt_in_preview_area(&self, item: &ItemInfo, content: &[String]) {
-        match self.split {
-            Split::Vertical => {
-                for (i, line) in content.iter().enumerate() {
-                    if i < item.preview_scroll {
-                        continue;
-                    }
-                    let sum = (i - item.preview_scroll) as u16;
-                    let row = self.preview_start.1 + sum;
-                    move_to(self.preview_start.0, row);
-                    set_color(&TermColor::ForeGround(&Colorname::LightBlack));
-                    print!("{}", line);
-                    if sum == self.preview_space.1 - 1 {
-                        break;
-                    }
-                }
-            }
-            Split::Horizontal => {
-                for (i, line) in content.iter().enumerate() {
-                    if i < item.preview_scroll {
-                        continue;
-                    }
-                    let sum = (i - item.preview_scroll) as u16;
-                    let row = self.preview_start.1 + sum;
-                    move_to(1, row);
-                    set_color(&TermColor::ForeGround(&Colorname::LightBlack));
-                    print!("{}", line);
-                    if row == self.terminal_row + self.preview_space.1 {
-                        break;
-                    }
-                }
-            }
-        }
-        reset_color();
-    }
-
-    /// Print text preview on the right half of the terminal (Experimental).
-    fn preview_image(&self, item: &ItemInfo) -> Result<(), FxError> {
+    /// The image drawn by chafa to fit in the preview area.
+    fn preview_image(&self, item: &ItemInfo) -> Result<String, FxError> {
         let wxh = match self.split {
             Split::Vertical => {
                 format!("--size={}x{}", self.preview_space.0, self.preview_space.1)
@@ -334,88 +352,154 @@ impl Layout {
                 )
             }
         };
+        self.thumbnail(item, &wxh)
+    }
+
+    /// Output of chafa for the image, read from the cache if the image has not changed.
+    fn thumbnail(&self, item: &ItemInfo, wxh: &str) -> Result<String, FxError> {
+        let cache = self
+            .thumbnail_dir
+            .as_ref()
+            .and_then(|dir| thumbnail_path(dir, &item.file_path, wxh));
+        if let Some(Ok(output)) = cache.as_ref().map(std::fs::read_to_string) {
+            return Ok(output);
+        }
 
         let file_path = item.file_path.to_str().ok_or(FxError::InvalidPath)?;
         let output = std::process::Command::new("chafa")
-            .args(["--animate=false", &wxh, file_path])
+            .args(["--animate=false", wxh, file_path])
             .output()?
             .stdout;
         let output = String::from_utf8(output)?;
-
-        match self.split {
-            Split::Vertical => {
-                for (i, line) in output.lines().enumerate() {
-                    print!("{}", line);
-                    let next_line: u16 = BEGINNING_ROW + (i as u16) + 1;
-                    move_to(self.preview_start.0, next_line);
-                }
+        if let Some(cache) = cache {
+            if let Some(dir) = &self.thumbnail_dir {
+                let _ = std::fs::create_dir_all(dir);
             }
-            Split::Horizontal => {
-                for (i, line) in output.lines().enumerate() {
-                    print!("{}", line);
-                    let next_line: u16 = self.preview_start.1 + (i as u16) + 1;
-                    move_to(1, next_line);
-                }
+            if let Err(e) = std::fs::write(&cache, &output) {
+                error!("Cannot cache the thumbnail: {}", e);
             }
         }
-        Ok(())
+        Ok(output)
     }
 
-    /// Clear the preview space.
-    fn clear_preview(&self, preview_start_point: u16) {
+    /// Size of the item list when the terminal is split for the preview,
+    /// leaving `preview_size` percent of the terminal to it.
+    pub fn list_area(&self, column: u16, row: u16) -> (u16, u16) {
+        let rest = |len: u16| (len as u32 * (100 - self.preview_size) as u32 / 100) as u16;
         match self.split {
-            Split::Vertical => {
-                for i in 0..=self.terminal_row {
-                    move_to(preview_start_point, BEGINNING_ROW + i);
-                    clear_until_newline();
-                }
-                move_to(self.preview_start.0, BEGINNING_ROW);
-            }
-            Split::Horizontal => {
-                for i in 0..=self.terminal_row {
-                    move_to(1, preview_start_point + i);
-                    clear_until_newline();
-                }
-                move_to(1, preview_start_point);
-            }
+            Split::Vertical => (rest(column), row),
+            Split::Horizontal => (column, rest(row)),
         }
     }
+}
 
-    pub fn update_column_and_row(&mut self) -> Result<(u16, u16), FxError> {
-        if self.is_preview() || self.is_reg() {
-            match self.split {
-                Split::Vertical => Ok((self.terminal_column >> 1, self.terminal_row)),
-                Split::Horizontal => Ok((self.terminal_column, self.terminal_row >> 1)),
-            }
-        } else {
-            terminal_size()
-        }
+/// Percentage of the preview, clamped so that the list is usable.
+pub fn to_preview_size(size: Option<u16>) -> u16 {
+    size.unwrap_or(DEFAULT_PREVIEW_SIZE).clamp(10, 90)
+}
+
+/// Template of the header or the status line. Checked when reading the config.
+pub fn to_template(format: Option<&str>) -> Option<Template> {
+    format.and_then(|format| Template::parse(format).ok())
+}
+
+/// Read the theme, falling back to the default if it cannot be read.
+pub fn read_theme(name: Option<&str>) -> Theme {
+    Theme::load(name).unwrap_or_else(|e| {
+        error!("{}", e);
+        Theme::default()
+    })
+}
+
+/// Read `LS_COLORS` if enabled in the config.
+pub fn read_ls_colors(enabled: Option<bool>) -> Option<LsColors> {
+    if enabled.unwrap_or_default() {
+        LsColors::from_env()
+    } else {
+        None
     }
 }
 
+pub fn is_too_small(column: u16, row: u16) -> bool {
+    column < MIN_COLUMNS || row < MIN_ROWS
+}
+
 /// Make app's layout according to terminal width and app's config.
-pub fn make_layout(column: u16) -> (u16, usize) {
-    let mut time_start: u16;
-    let mut name_max: usize;
-
-    if column < PROPER_WIDTH {
-        time_start = column;
-        name_max = (column - 2).into();
-        (time_start, name_max)
-    } else {
-        time_start = column - TIME_WIDTH;
-        name_max = (time_start - EXTRA_SPACES).into();
-        let required = time_start + TIME_WIDTH - 1;
-        if required > column {
-            let diff = required - column;
-            name_max -= diff as usize;
-            time_start -= diff;
+/// Returns the position where the columns start, the max length of the item name,
+/// and the columns to be shown.
+/// As the terminal narrows, columns with lower priority are hidden one by one,
+/// so that the item name keeps its minimum width.
+pub fn make_layout(
+    column: u16,
+    columns: &[Column],
+    widths: &ColumnWidths,
+) -> (u16, usize, Vec<Column>) {
+    let mut shown_columns = columns.to_vec();
+    while !shown_columns.is_empty()
+        && column < columns_width(&shown_columns, widths) + MIN_NAME_WIDTH
+    {
+        if let Some(i) = shown_columns
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, c)| c.priority())
+            .map(|(i, _)| i)
+        {
+            shown_columns.remove(i);
         }
+    }
 
-        (time_start, name_max)
+    if shown_columns.is_empty() {
+        (column, (column - 2).into(), shown_columns)
+    } else {
+        let column_start = column - columns_width(&shown_columns, widths) + 1;
+        (
+            column_start,
+            (column_start - EXTRA_SPACES).into(),
+            shown_columns,
+        )
     }
 }
 
+/// Path of the cached thumbnail, keyed by the image path, its modified time and the size.
+fn thumbnail_path(
+    dir: &std::path::Path,
+    path: &std::path::Path,
+    wxh: &str,
+) -> Option<std::path::PathBuf> {
+    use std::hash::{Hash, Hasher};
+
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    path.hash(&mut hasher);
+    modified.hash(&mut hasher);
+    wxh.hash(&mut hasher);
+    Some(dir.join(format!("{:016x}", hasher.finish())))
+}
+
+/// Remove the thumbnails not regenerated for `THUMBNAIL_LIFETIME`.
+pub fn prune_thumbnails(dir: &std::path::Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|elapsed| elapsed > THUMBNAIL_LIFETIME);
+        if expired {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+fn is_markdown(item: &ItemInfo) -> bool {
+    item.file_ext
+        .as_ref()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+}
+
 /// Check if bat is installed.
 fn check_bat() -> bool {
     std::process::Command::new("bat")
@@ -440,3 +524,84 @@ fn check_kitty_support() -> bool {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make_layout() {
+        let columns = [Column::Size, Column::Mtime, Column::Git];
+        // size (6) + mtime (17) + git (2)
+        let widths = ColumnWidths::new();
+        assert_eq!(
+            make_layout(100, &columns, &widths),
+            (76, 73, columns.to_vec())
+        );
+        assert_eq!(
+            make_layout(36, &columns, &widths),
+            (12, 9, columns.to_vec())
+        );
+        // mtime is hidden first, then size.
+        assert_eq!(
+            make_layout(35, &columns, &widths),
+            (28, 25, vec![Column::Size, Column::Git])
+        );
+        assert_eq!(
+            make_layout(18, &columns, &widths),
+            (17, 14, vec![Column::Git])
+        );
+        assert_eq!(make_layout(12, &columns, &widths), (12, 10, vec![]));
+        // size (9) + mtime (11) + git (2)
+        let widths = ColumnWidths::from([(Column::Size, 8), (Column::Mtime, 10)]);
+        assert_eq!(
+            make_layout(100, &columns, &widths),
+            (79, 76, columns.to_vec())
+        );
+    }
+
+    #[test]
+    fn test_thumbnail_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("a.png");
+        std::fs::write(&image, "").unwrap();
+        let cache = thumbnail_path(dir.path(), &image, "--size=10x10").unwrap();
+        assert_eq!(
+            thumbnail_path(dir.path(), &image, "--size=10x10"),
+            Some(cache.clone())
+        );
+        assert_ne!(
+            thumbnail_path(dir.path(), &image, "--size=20x10"),
+            Some(cache.clone())
+        );
+
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        std::fs::File::options()
+            .write(true)
+            .open(&image)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert_ne!(
+            thumbnail_path(dir.path(), &image, "--size=10x10"),
+            Some(cache)
+        );
+        assert_eq!(
+            thumbnail_path(dir.path(), &dir.path().join("none.png"), "--size=10x10"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_list_area() {
+        let mut layout = Layout {
+            preview_size: 50,
+            ..Default::default()
+        };
+        assert_eq!(layout.list_area(101, 40), (50, 40));
+        layout.preview_size = 30;
+        assert_eq!(layout.list_area(100, 40), (70, 40));
+        layout.split = Split::Horizontal;
+        assert_eq!(layout.list_area(100, 40), (100, 28));
+    }
+}
@@ -0,0 +1,52 @@
+//! The core of felix: the state of the file manager and the file operations.
+//! The library does not touch the terminal: the state reports what it does through
+//! `frontend::Frontend`, which the `fx` binary implements as the terminal UI.
+
+pub mod binary;
+pub mod chooser;
+pub mod color;
+pub mod column;
+pub mod completions;
+pub mod config;
+pub mod conflict;
+pub mod copy;
+pub mod database;
+pub mod disk;
+pub mod errors;
+pub mod events;
+pub mod filter;
+pub mod font;
+pub mod frontend;
+pub mod functions;
+pub mod git;
+pub mod help;
+pub mod item;
+pub mod jobs;
+pub mod journal;
+pub mod jumplist;
+pub mod keymap;
+pub mod layout;
+pub mod ls_colors;
+pub mod magic_image;
+pub mod magic_packed;
+pub mod markdown;
+pub mod mouse;
+pub mod nums;
+pub mod op;
+pub mod opener;
+pub mod palette;
+pub mod pattern;
+pub mod progress;
+pub mod report;
+pub mod script;
+pub mod selinux;
+pub mod session;
+pub mod shell;
+pub mod shell_command;
+pub mod state;
+pub mod structured;
+pub mod table;
+pub mod template;
+pub mod theme;
+pub mod trash;
+pub mod watcher;
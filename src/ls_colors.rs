@@ -0,0 +1,193 @@
+use super::config::Colorname;
+use super::item::{FileType, ItemInfo};
+
+use std::collections::BTreeMap;
+
+/// Colors of the items by the extension and the file type, read from `LS_COLORS`
+/// (e.g. `di=01;34:ln=01;36:ex=01;32:*.tar=01;31`).
+/// Only the foreground color is used; the attributes such as bold are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LsColors {
+    /// Lowercase extension without the dot -> color.
+    extensions: BTreeMap<String, Colorname>,
+    dir: Option<Colorname>,
+    file: Option<Colorname>,
+    symlink: Option<Colorname>,
+    exec: Option<Colorname>,
+    other: Option<Colorname>,
+}
+
+impl LsColors {
+    /// Read `LS_COLORS`, if set.
+    pub fn from_env() -> Option<Self> {
+        std::env::var("LS_COLORS").ok().map(|s| Self::parse(&s))
+    }
+
+    /// Parse the value of `LS_COLORS`. Unknown keys and invalid codes are ignored.
+    pub fn parse(s: &str) -> Self {
+        let mut colors = LsColors::default();
+        for entry in s.split(':') {
+            let Some((key, codes)) = entry.split_once('=') else {
+                continue;
+            };
+            let Some(color) = parse_sgr(codes) else {
+                continue;
+            };
+            if let Some(ext) = key.strip_prefix("*.") {
+                colors.extensions.insert(ext.to_ascii_lowercase(), color);
+                continue;
+            }
+            match key {
+                "di" => colors.dir = Some(color),
+                "fi" => colors.file = Some(color),
+                "ln" => colors.symlink = Some(color),
+                "ex" => colors.exec = Some(color),
+                //Among FIFOs, sockets and devices, the first one set is used.
+                "pi" | "so" | "bd" | "cd" | "do" => {
+                    colors.other.get_or_insert(color);
+                }
+                _ => {}
+            }
+        }
+        colors
+    }
+
+    /// The color of the extension.
+    pub fn extension(&self, ext: &str) -> Option<&Colorname> {
+        self.extensions.get(ext)
+    }
+
+    /// The color of the item by its file type, or by its extension for files.
+    pub fn color_of(&self, item: &ItemInfo) -> Option<&Colorname> {
+        match item.file_type {
+            FileType::Directory => self.dir.as_ref(),
+            FileType::Symlink => self.symlink.as_ref(),
+            FileType::Other => self.other.as_ref(),
+            FileType::File => {
+                let is_exec = item.permissions.is_some_and(|mode| mode & 0o111 != 0);
+                item.file_ext
+                    .as_deref()
+                    .and_then(|ext| self.extension(ext))
+                    .or(if is_exec { self.exec.as_ref() } else { None })
+                    .or(self.file.as_ref())
+            }
+        }
+    }
+}
+
+/// The foreground color in the SGR codes (e.g. `01;34`, `38;5;208`, `38;2;255;128;0`).
+fn parse_sgr(codes: &str) -> Option<Colorname> {
+    let codes: Vec<u8> = codes
+        .split(';')
+        .map(|code| code.parse().ok())
+        .collect::<Option<_>>()?;
+    let mut color = None;
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            code @ 30..=37 => color = Some(basic_color(code - 30, false)),
+            code @ 90..=97 => color = Some(basic_color(code - 90, true)),
+            38 => match codes.get(i + 1) {
+                Some(5) => {
+                    color = Some(Colorname::AnsiValue(*codes.get(i + 2)?));
+                    i += 2;
+                }
+                Some(2) => {
+                    color = Some(Colorname::Rgb(
+                        *codes.get(i + 2)?,
+                        *codes.get(i + 3)?,
+                        *codes.get(i + 4)?,
+                    ));
+                    i += 4;
+                }
+                _ => return None,
+            },
+            //The background with the 256/true colors takes the values as well.
+            48 => match codes.get(i + 1) {
+                Some(5) => i += 2,
+                Some(2) => i += 4,
+                _ => return None,
+            },
+            _ => {}
+        }
+        i += 1;
+    }
+    color
+}
+
+fn basic_color(n: u8, light: bool) -> Colorname {
+    match (n, light) {
+        (0, false) => Colorname::Black,
+        (1, false) => Colorname::Red,
+        (2, false) => Colorname::Green,
+        (3, false) => Colorname::Yellow,
+        (4, false) => Colorname::Blue,
+        (5, false) => Colorname::Magenta,
+        (6, false) => Colorname::Cyan,
+        (_, false) => Colorname::White,
+        (0, true) => Colorname::LightBlack,
+        (1, true) => Colorname::LightRed,
+        (2, true) => Colorname::LightGreen,
+        (3, true) => Colorname::LightYellow,
+        (4, true) => Colorname::LightBlue,
+        (5, true) => Colorname::LightMagenta,
+        (6, true) => Colorname::LightCyan,
+        (_, true) => Colorname::LightWhite,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sgr() {
+        assert_eq!(parse_sgr("01;34"), Some(Colorname::Blue));
+        assert_eq!(parse_sgr("91"), Some(Colorname::LightRed));
+        assert_eq!(parse_sgr("38;5;208"), Some(Colorname::AnsiValue(208)));
+        assert_eq!(
+            parse_sgr("38;2;255;128;0"),
+            Some(Colorname::Rgb(255, 128, 0))
+        );
+        assert_eq!(parse_sgr("48;5;1;33"), Some(Colorname::Yellow));
+        assert_eq!(parse_sgr("01"), None);
+        assert_eq!(parse_sgr("38;5"), None);
+        assert_eq!(parse_sgr("foo"), None);
+    }
+
+    #[test]
+    fn test_ls_colors() {
+        let colors =
+            LsColors::parse("rs=0:di=01;34:ln=01;36:ex=01;32:pi=33:so=35:*.TAR=01;31:*.md=");
+        let item = |file_type, ext: Option<&str>, permissions| ItemInfo {
+            file_type,
+            file_ext: ext.map(|ext| ext.to_owned()),
+            permissions,
+            ..Default::default()
+        };
+        assert_eq!(
+            colors.color_of(&item(FileType::Directory, None, None)),
+            Some(&Colorname::Blue)
+        );
+        assert_eq!(
+            colors.color_of(&item(FileType::Symlink, None, None)),
+            Some(&Colorname::Cyan)
+        );
+        assert_eq!(
+            colors.color_of(&item(FileType::Other, None, None)),
+            Some(&Colorname::Yellow)
+        );
+        assert_eq!(
+            colors.color_of(&item(FileType::File, Some("tar"), Some(0o100755))),
+            Some(&Colorname::Red)
+        );
+        assert_eq!(
+            colors.color_of(&item(FileType::File, Some("sh"), Some(0o100755))),
+            Some(&Colorname::Green)
+        );
+        assert_eq!(
+            colors.color_of(&item(FileType::File, Some("md"), Some(0o100644))),
+            None
+        );
+    }
+}
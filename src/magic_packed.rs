@@ -1,7 +1,8 @@
 /// Based on [List of file signatures - Wikipedia](https://en.wikipedia.org/wiki/List_of_file_signatures)
 use super::errors::FxError;
+use std::collections::BTreeSet;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
 const HEADER_GZIP: [u8; 2] = [0x1F, 0x8B];
 const HEADER_XZ: [u8; 6] = [0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00];
@@ -125,10 +126,12 @@ impl std::fmt::Display for CompressionSignature {
 
 fn inspect_compression(p: &Path) -> Result<CompressionSignature, FxError> {
     let mut file = std::fs::File::open(p)?;
-    let len = file.metadata().unwrap().len();
+    let len = file.metadata()?.len();
     let buffer = if len < 265 {
         let mut v = vec![];
         file.read_to_end(&mut v)?;
+        //Padded, not to slice a short file out of range.
+        v.resize(265, 0);
         v
     } else {
         let mut buffer = [0; 265];
@@ -216,54 +219,101 @@ fn is_tar(b: &[u8]) -> bool {
     b.len() >= 265 && (b[257..265] == HEADER_TAR1 || b[257..265] == HEADER_TAR2)
 }
 
-pub fn unpack(p: &Path, dest: &Path) -> Result<(), FxError> {
-    let sign = inspect_compression(p)?;
-    match sign {
+/// Archive opened by the shared reading layer, for both unpacking and listing.
+enum Archive {
+    /// Decompressed contents, which are a tar archive or a single file.
+    Decoded(Vec<u8>),
+    Zip(zip::ZipArchive<std::fs::File>),
+}
+
+/// Entries listed without extracting the archive.
+#[derive(Debug, Default)]
+pub struct ArchiveSummary {
+    /// The first entries: the path (ending with `/` if directory) and the uncompressed size.
+    pub entries: Vec<(String, u64)>,
+    /// The number of all the entries.
+    pub count: usize,
+    /// The uncompressed size of all the entries.
+    pub total: u64,
+}
+
+/// Files differing between an archive and a directory, by the relative paths.
+#[derive(Debug, Default, PartialEq)]
+pub struct ArchiveDiff {
+    /// Only in the directory.
+    pub added: Vec<PathBuf>,
+    /// Only in the archive.
+    pub removed: Vec<PathBuf>,
+    /// In both, with the different contents.
+    pub changed: Vec<PathBuf>,
+    /// The number of the files in the archive.
+    pub count: usize,
+}
+
+impl ArchiveDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compressed streams are decoded into memory to read the entries,
+/// so larger ones are not listed in the preview.
+const MAX_SIZE_TO_LIST: u64 = 100_000_000;
+
+/// Check if the file can be unpacked (and listed) by felix.
+pub fn is_archive(p: &Path) -> bool {
+    matches!(
+        inspect_compression(p),
+        Ok(CompressionSignature::Gzip
+            | CompressionSignature::Xz
+            | CompressionSignature::Zstd
+            | CompressionSignature::Tar
+            | CompressionSignature::Pkzip)
+    )
+}
+
+/// Name of the compression format (e.g. `7z`), if the file is compressed.
+pub fn describe(p: &Path) -> Option<String> {
+    match inspect_compression(p) {
+        Ok(CompressionSignature::NonArchived) | Err(_) => None,
+        Ok(sign) => Some(sign.to_string()),
+    }
+}
+
+fn open_archive(p: &Path, sign: CompressionSignature) -> Result<Archive, FxError> {
+    let archive = match sign {
         CompressionSignature::Gzip => {
             let file = std::fs::File::open(p)?;
             let file = std::io::BufReader::new(file);
             let mut decoder = flate2::bufread::GzDecoder::new(file);
-            let mut output = std::fs::File::create(dest)?;
-            std::io::copy(&mut decoder, &mut output)?;
-            let input = std::fs::read(dest)?;
-            if is_tar(&input) {
-                let mut archive = tar::Archive::new(input.as_slice());
-                std::fs::remove_file(dest)?;
-                archive.unpack(dest)?;
-            }
+            let mut decoded = Vec::new();
+            decoder.read_to_end(&mut decoded)?;
+            Archive::Decoded(decoded)
         }
         CompressionSignature::Xz => {
             let file = std::fs::File::open(p)?;
             let mut file = std::io::BufReader::new(file);
-            let mut decoder: Vec<u8> = Vec::new();
-            lzma_rs::xz_decompress(&mut file, &mut decoder).unwrap();
-            if is_tar(&decoder) {
-                let mut archive = tar::Archive::new(decoder.as_slice());
-                archive.unpack(dest)?;
-            } else {
-                std::fs::write(dest, decoder)?;
-            }
+            let mut decoded: Vec<u8> = Vec::new();
+            lzma_rs::xz_decompress(&mut file, &mut decoded)
+                .map_err(|e| FxError::Unpack(e.to_string()))?;
+            Archive::Decoded(decoded)
         }
+        #[cfg(feature = "zstd")]
         CompressionSignature::Zstd => {
             let file = std::fs::File::open(p)?;
             let file = std::io::BufReader::new(file);
-            let decoder = zstd::stream::decode_all(file).unwrap();
-            if is_tar(&decoder) {
-                let mut archive = tar::Archive::new(decoder.as_slice());
-                archive.unpack(dest)?;
-            } else {
-                std::fs::write(dest, decoder)?;
-            }
+            Archive::Decoded(zstd::stream::decode_all(file)?)
         }
-        CompressionSignature::Tar => {
-            let file = std::fs::read(p)?;
-            let mut archive = tar::Archive::new(file.as_slice());
-            archive.unpack(dest)?;
+        #[cfg(not(feature = "zstd"))]
+        CompressionSignature::Zstd => {
+            return Err(FxError::Unpack(
+                "zstd is not supported in this build.".to_owned(),
+            ))
         }
+        CompressionSignature::Tar => Archive::Decoded(std::fs::read(p)?),
         CompressionSignature::Pkzip => {
             let file = std::fs::File::open(p)?;
-            let mut archive = zip::ZipArchive::new(file)?;
-            archive.extract(dest)?;
+            Archive::Zip(zip::ZipArchive::new(file)?)
         }
         CompressionSignature::NonArchived => {
             return Err(FxError::Unpack("Seems not an archive file.".to_owned()))
@@ -274,11 +324,209 @@ pub fn unpack(p: &Path, dest: &Path) -> Result<(), FxError> {
                 sign
             )));
         }
+    };
+    Ok(archive)
+}
+
+pub fn unpack(p: &Path, dest: &Path) -> Result<(), FxError> {
+    let sign = inspect_compression(p)?;
+    match open_archive(p, sign)? {
+        Archive::Decoded(decoded) => {
+            if is_tar(&decoded) {
+                let mut archive = tar::Archive::new(decoded.as_slice());
+                archive.unpack(dest)?;
+            } else {
+                std::fs::write(dest, decoded)?;
+            }
+        }
+        Archive::Zip(mut archive) => {
+            archive.extract(dest)?;
+        }
     }
 
     Ok(())
 }
 
+/// List the first `max` entries of the archive and the total uncompressed size.
+/// A compressed single file is listed as one entry named after the archive.
+pub fn list_archive(p: &Path, max: usize) -> Result<ArchiveSummary, FxError> {
+    let sign = inspect_compression(p)?;
+    if sign != CompressionSignature::Pkzip && std::fs::metadata(p)?.len() > MAX_SIZE_TO_LIST {
+        return Err(FxError::Unpack(
+            "Archive too big to list its entries.".to_owned(),
+        ));
+    }
+    let mut summary = ArchiveSummary::default();
+    let mut add = |name: String, size: u64| {
+        if summary.count < max {
+            summary.entries.push((name, size));
+        }
+        summary.count += 1;
+        summary.total += size;
+    };
+    match open_archive(p, sign)? {
+        Archive::Decoded(decoded) => {
+            if is_tar(&decoded) {
+                let mut archive = tar::Archive::new(decoded.as_slice());
+                for entry in archive.entries()? {
+                    let entry = entry?;
+                    let mut name = entry.path()?.to_string_lossy().into_owned();
+                    if entry.header().entry_type().is_dir() && !name.ends_with('/') {
+                        name.push('/');
+                    }
+                    add(name, entry.size());
+                }
+            } else {
+                let name = p
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                add(name, decoded.len() as u64);
+            }
+        }
+        Archive::Zip(mut archive) => {
+            for i in 0..archive.len() {
+                let file = archive.by_index_raw(i)?;
+                add(file.name().to_owned(), file.size());
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Compare the regular files in the archive with those in the directory,
+/// e.g. to verify the extraction or to see what changed in a new release.
+/// If all the files are under one top directory that the directory does not have
+/// (e.g. `foo-1.0/` of `foo-1.0.tar.gz` extracted as `foo-1.0`), it is ignored.
+pub fn diff_archive(p: &Path, dir: &Path) -> Result<ArchiveDiff, FxError> {
+    if !dir.is_dir() {
+        return Err(FxError::Unpack(format!(
+            "Not a directory: {}",
+            dir.display()
+        )));
+    }
+    let sign = inspect_compression(p)?;
+    let mut archive = open_archive(p, sign)?;
+    let names = file_names(&mut archive)?;
+    let top = common_top(&names).filter(|top| !dir.join(top).is_dir());
+
+    let mut diff = ArchiveDiff::default();
+    let mut in_archive: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut compare = |name: PathBuf, reader: &mut dyn Read| -> Result<(), FxError> {
+        let rel = match &top {
+            Some(top) => name.strip_prefix(top).unwrap_or(&name).to_path_buf(),
+            None => name,
+        };
+        let path = dir.join(&rel);
+        if !path.is_file() {
+            diff.removed.push(rel.clone());
+        } else if !same_contents(&path, reader)? {
+            diff.changed.push(rel.clone());
+        }
+        diff.count += 1;
+        in_archive.insert(rel);
+        Ok(())
+    };
+    match &mut archive {
+        Archive::Decoded(decoded) => {
+            let mut archive = tar::Archive::new(decoded.as_slice());
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                if entry.header().entry_type().is_file() {
+                    let name = normalize(&entry.path()?);
+                    compare(name, &mut entry)?;
+                }
+            }
+        }
+        Archive::Zip(archive) => {
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i)?;
+                if file.is_file() {
+                    let name = normalize(Path::new(file.name()));
+                    compare(name, &mut file)?;
+                }
+            }
+        }
+    }
+
+    for entry in walkdir::WalkDir::new(dir).min_depth(1) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if let Ok(rel) = entry.path().strip_prefix(dir) {
+            if !in_archive.contains(rel) {
+                diff.added.push(rel.to_path_buf());
+            }
+        }
+    }
+    diff.added.sort();
+    diff.removed.sort();
+    diff.changed.sort();
+    Ok(diff)
+}
+
+/// Paths of the regular files in the archive.
+fn file_names(archive: &mut Archive) -> Result<Vec<PathBuf>, FxError> {
+    let mut names = vec![];
+    match archive {
+        Archive::Decoded(decoded) => {
+            if !is_tar(decoded) {
+                return Err(FxError::Unpack(
+                    "Not an archive of files to compare.".to_owned(),
+                ));
+            }
+            let mut archive = tar::Archive::new(decoded.as_slice());
+            for entry in archive.entries()? {
+                let entry = entry?;
+                if entry.header().entry_type().is_file() {
+                    names.push(normalize(&entry.path()?));
+                }
+            }
+        }
+        Archive::Zip(archive) => {
+            for i in 0..archive.len() {
+                let file = archive.by_index_raw(i)?;
+                if file.is_file() {
+                    names.push(normalize(Path::new(file.name())));
+                }
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Drop `.`, `..` and the root, so that the path stays in the directory.
+fn normalize(p: &Path) -> PathBuf {
+    p.components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+/// The top directory, if all the paths are under it.
+fn common_top(names: &[PathBuf]) -> Option<PathBuf> {
+    let mut top: Option<&std::ffi::OsStr> = None;
+    for name in names {
+        let mut components = name.components();
+        let first = components.next()?.as_os_str();
+        components.next()?;
+        match top {
+            Some(top) if top != first => return None,
+            _ => top = Some(first),
+        }
+    }
+    top.map(PathBuf::from)
+}
+
+fn same_contents(path: &Path, reader: &mut dyn Read) -> Result<bool, FxError> {
+    let mut contents = Vec::new();
+    reader.read_to_end(&mut contents)?;
+    if std::fs::metadata(path)?.len() != contents.len() as u64 {
+        return Ok(false);
+    }
+    Ok(std::fs::read(path)? == contents)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -336,6 +584,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "zstd")]
     fn test_inspect_signature_tar_zst() {
         let p = PathBuf::from("testfiles/archives/archive.tar.zst");
         assert_eq!(CompressionSignature::Zstd, inspect_compression(&p).unwrap());
@@ -346,6 +595,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "zstd")]
     fn test_inspect_signature_zst() {
         let p = PathBuf::from("testfiles/archives/archive.txt.zst");
         assert_eq!(CompressionSignature::Zstd, inspect_compression(&p).unwrap());
@@ -416,4 +666,65 @@ mod tests {
         let dest = PathBuf::from("testfiles/archives/bz2");
         assert!(unpack(&p, &dest).is_err());
     }
+
+    #[test]
+    fn test_list_archive() {
+        let p = PathBuf::from("testfiles/archives/archive.tar");
+        assert!(is_archive(&p));
+        let summary = list_archive(&p, 2).unwrap();
+        assert_eq!(
+            summary.entries,
+            vec![("src/".to_owned(), 0), ("src/op.rs".to_owned(), 2145)]
+        );
+        assert_eq!(summary.count, 14);
+        assert_eq!(summary.total, 189_614);
+
+        let p = PathBuf::from("testfiles/archives/archive.tar.gz");
+        let summary = list_archive(&p, 10).unwrap();
+        assert_eq!(summary.entries, vec![("multibytes.txt".to_owned(), 440)]);
+
+        let p = PathBuf::from("testfiles/archives/archive_deflate.zip");
+        let summary = list_archive(&p, 10).unwrap();
+        assert_eq!(summary.entries, vec![("multibytes.txt".to_owned(), 440)]);
+
+        let p = PathBuf::from("testfiles/archives/archive.txt.gz");
+        let summary = list_archive(&p, 10).unwrap();
+        assert_eq!(summary.entries, vec![("archive.txt".to_owned(), 124)]);
+        assert_eq!(summary.total, 124);
+        assert!(!is_archive(&PathBuf::from(
+            "testfiles/archives/archive.tar.bz2"
+        )));
+    }
+
+    #[test]
+    fn test_diff_archive() {
+        let p = PathBuf::from("testfiles/archives/archive.tar");
+        let dir = tempfile::tempdir().unwrap();
+        unpack(&p, dir.path()).unwrap();
+        let diff = diff_archive(&p, dir.path()).unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(diff.count, 13);
+        //Compared with the top directory.
+        assert!(diff_archive(&p, &dir.path().join("src"))
+            .unwrap()
+            .is_empty());
+
+        let src = dir.path().join("src");
+        std::fs::write(src.join("op.rs"), "changed").unwrap();
+        std::fs::remove_file(src.join("nums.rs")).unwrap();
+        std::fs::write(src.join("new.rs"), "").unwrap();
+        let diff = diff_archive(&p, dir.path()).unwrap();
+        assert_eq!(diff.added, vec![PathBuf::from("src/new.rs")]);
+        assert_eq!(diff.removed, vec![PathBuf::from("src/nums.rs")]);
+        assert_eq!(diff.changed, vec![PathBuf::from("src/op.rs")]);
+
+        let p = PathBuf::from("testfiles/archives/archive_deflate.zip");
+        let diff = diff_archive(&p, dir.path()).unwrap();
+        assert_eq!(diff.removed, vec![PathBuf::from("multibytes.txt")]);
+        assert_eq!(diff.added.len(), 13);
+
+        let p = PathBuf::from("testfiles/archives/archive.txt.gz");
+        assert!(diff_archive(&p, dir.path()).is_err());
+        assert!(diff_archive(&p, &src.join("op.rs")).is_err());
+    }
 }
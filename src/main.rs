@@ -1,32 +1,66 @@
-mod config;
-mod errors;
-mod functions;
-mod help;
-mod jumplist;
-mod layout;
-mod magic_image;
-mod magic_packed;
-mod nums;
-mod op;
+mod command_line;
+mod event_loop;
+mod preview;
 mod run;
-mod session;
-mod shell;
-mod state;
+mod screen;
 mod term;
 
+use felix::{chooser, completions, config, errors, functions, help, shell};
+use normpath::PathExt;
 use std::path::PathBuf;
 
 fn main() -> Result<(), errors::FxError> {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    //`--config <path>` goes with the other options, and is passed to new instances by the env.
+    if let Some(i) = args.iter().position(|arg| arg == "--config") {
+        if i + 1 == args.len() {
+            eprintln!("--config needs the path of the config file.\n`fx -h` shows help.");
+            return Ok(());
+        }
+        let path = std::env::current_dir()?.join(args.remove(i + 1));
+        args.remove(i);
+        std::env::set_var(config::CONFIG_ENV, path);
+    }
+    //`--choose-files <path>` makes fx a file picker, writing the chosen paths to the file.
+    let null = match args.iter().position(|arg| arg == "--null") {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    };
+    let chooser = match args.iter().position(|arg| arg == "--choose-files") {
+        Some(i) => {
+            if i + 1 == args.len() {
+                eprintln!("--choose-files needs the path of the output file.\n`fx -h` shows help.");
+                std::process::exit(2);
+            }
+            let path = std::env::current_dir()?.join(args.remove(i + 1));
+            args.remove(i);
+            match chooser::Chooser::new(path, null) {
+                Ok(chooser) => Some(chooser),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                }
+            }
+        }
+        None => {
+            if null {
+                eprintln!("--null goes with --choose-files.\n`fx -h` shows help.");
+                return Ok(());
+            }
+            None
+        }
+    };
     let len = args.len();
     match len {
         1 => {
-            if let Err(e) = run::run(
+            launch(
                 std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
                 false,
-            ) {
-                eprintln!("{}", e);
-            }
+                chooser,
+            );
         }
 
         2 => match args[1].as_str() {
@@ -34,25 +68,37 @@ fn main() -> Result<(), errors::FxError> {
                 print!("{}", help::HELP);
             }
             "-l" | "--log" => {
-                if let Err(e) = run::run(
+                launch(
                     std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
                     true,
-                ) {
-                    eprintln!("{}", e);
-                }
+                    chooser,
+                );
             }
             "--init" => {
                 print!("{}", shell::INTEGRATION_CODE);
             }
-            _ => {
-                if let Err(e) = run::run(PathBuf::from(&args[1]), false) {
+            "man" => {
+                print!("{}", completions::man_page());
+            }
+            "--new-window" => {
+                if let Err(e) = new_window(std::env::current_dir()?) {
                     eprintln!("{}", e);
                 }
             }
+            _ => {
+                launch(PathBuf::from(&args[1]), false, chooser);
+            }
         },
         3 => {
             if args[1] == "-l" || args[1] == "--log" {
-                if let Err(e) = run::run(PathBuf::from(&args[2]), true) {
+                launch(PathBuf::from(&args[2]), true, chooser);
+            } else if args[1] == "completions" {
+                match completions::completions(&args[2]) {
+                    Ok(script) => print!("{}", script),
+                    Err(e) => eprintln!("{}", e),
+                }
+            } else if args[1] == "--new-window" {
+                if let Err(e) = new_window(PathBuf::from(&args[2])) {
                     eprintln!("{}", e);
                 }
             } else {
@@ -63,3 +109,37 @@ fn main() -> Result<(), errors::FxError> {
     }
     Ok(())
 }
+
+/// Run the app, and in the file-picker mode, exit with 0 if any path is chosen,
+/// 1 if quit without choosing, and 2 on error.
+fn launch(dir: PathBuf, log: bool, chooser: Option<chooser::Chooser>) {
+    let result = run::run(dir, log, chooser.clone());
+    if let Err(e) = &result {
+        eprintln!("{}", e);
+    }
+    if let Some(chooser) = chooser {
+        std::process::exit(match result {
+            Err(_) => 2,
+            Ok(()) if chooser.chosen() => 0,
+            Ok(()) => 1,
+        });
+    }
+}
+
+/// Launch fx at the directory in a new window of the terminal set in the config.
+fn new_window(dir: PathBuf) -> Result<(), errors::FxError> {
+    if !dir.is_dir() {
+        return Err(errors::FxError::Arg(format!(
+            "Invalid path: {}\n`fx -h` shows help.",
+            dir.display()
+        )));
+    }
+    let dir = dir.normalize()?.into_path_buf();
+    let config = config::read_config_or_default()?.config;
+    match config.terminal {
+        Some(terminal) => functions::spawn_new_instance(&terminal, &dir),
+        None => Err(errors::FxError::OpenNewWindow(
+            "Set `terminal` in the config to open a new window.".to_owned(),
+        )),
+    }
+}
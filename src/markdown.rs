@@ -0,0 +1,266 @@
+use crossterm::style::{Attribute, Color, Stylize};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Style of a span in the markdown preview.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Style {
+    Plain,
+    Bold,
+    Italic,
+    Code,
+    Link,
+    Heading,
+}
+
+type Span = (String, Style);
+
+/// Render the markdown text for the preview, wrapping lines by `width`.
+/// Only the basic syntax is handled: headings, emphasis, inline code, links,
+/// lists, block quotes, rules and fenced code blocks.
+pub fn render_markdown(content: &str, width: u16) -> Vec<String> {
+    layout_markdown(content, width.into())
+        .into_iter()
+        .map(|line| {
+            line.into_iter()
+                .map(|(text, style)| styled(text, style))
+                .collect()
+        })
+        .collect()
+}
+
+fn styled(text: String, style: Style) -> String {
+    let text = text.with(match style {
+        Style::Code => Color::Yellow,
+        Style::Link => Color::Blue,
+        Style::Heading => Color::Cyan,
+        _ => Color::DarkGrey,
+    });
+    match style {
+        Style::Bold | Style::Heading => text.attribute(Attribute::Bold),
+        Style::Italic => text.attribute(Attribute::Italic),
+        Style::Link => text.attribute(Attribute::Underlined),
+        _ => text,
+    }
+    .to_string()
+}
+
+fn layout_markdown(content: &str, width: usize) -> Vec<Vec<Span>> {
+    let mut lines = vec![];
+    let mut in_code = false;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            let code = line.replace('\t', "    ");
+            lines.extend(wrap(
+                ("  ".to_owned(), Style::Plain),
+                vec![(code, Style::Code)],
+                width,
+            ));
+            continue;
+        }
+
+        let indent = &line[..line.len() - trimmed.len()];
+        if trimmed.is_empty() {
+            lines.push(vec![]);
+        } else if is_rule(trimmed) {
+            lines.push(vec![("─".repeat(width), Style::Plain)]);
+        } else if is_heading(trimmed) {
+            lines.extend(wrap(
+                (String::new(), Style::Plain),
+                vec![(trimmed.to_owned(), Style::Heading)],
+                width,
+            ));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            let spans = parse_inline(quote.trim_start())
+                .into_iter()
+                .map(|(text, style)| match style {
+                    Style::Plain => (text, Style::Italic),
+                    _ => (text, style),
+                })
+                .collect();
+            lines.extend(wrap((format!("{}│ ", indent), Style::Plain), spans, width));
+        } else if let Some((marker, item)) = list_item(trimmed) {
+            lines.extend(wrap(
+                (format!("{}{} ", indent, marker), Style::Plain),
+                parse_inline(item),
+                width,
+            ));
+        } else {
+            lines.extend(wrap(
+                (indent.to_owned(), Style::Plain),
+                parse_inline(trimmed),
+                width,
+            ));
+        }
+    }
+    lines
+}
+
+fn is_heading(s: &str) -> bool {
+    let level = s.chars().take_while(|c| *c == '#').count();
+    (1..=6).contains(&level) && (s[level..].is_empty() || s[level..].starts_with(' '))
+}
+
+fn is_rule(s: &str) -> bool {
+    let s: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+    s.len() >= 3 && ['-', '*', '_'].iter().any(|c| s.iter().all(|x| x == c))
+}
+
+/// Return the marker to show and the rest of the list item.
+/// Bullets become "•", while numbers are kept.
+fn list_item(s: &str) -> Option<(String, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = s.strip_prefix(bullet) {
+            return Some(("•".to_owned(), item));
+        }
+    }
+    let digits = s.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && (s[digits..].starts_with(". ") || s[digits..].starts_with(") ")) {
+        return Some((s[..digits + 1].to_owned(), &s[digits + 2..]));
+    }
+    None
+}
+
+/// Split the line into spans, removing the markers of emphasis, code and links.
+fn parse_inline(s: &str) -> Vec<Span> {
+    let mut spans = vec![];
+    let mut plain = String::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        if let Some((text, style, len)) = inline_span(rest, plain.chars().last()) {
+            if !plain.is_empty() {
+                spans.push((std::mem::take(&mut plain), Style::Plain));
+            }
+            spans.push((text.to_owned(), style));
+            rest = &rest[len..];
+        } else {
+            plain.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if !plain.is_empty() {
+        spans.push((plain, Style::Plain));
+    }
+    spans
+}
+
+/// If `s` starts with an inline element, return its text, style and length in bytes.
+fn inline_span(s: &str, prev: Option<char>) -> Option<(&str, Style, usize)> {
+    if let Some(inner) = s.strip_prefix('`') {
+        let end = inner.find('`')?;
+        return Some((&inner[..end], Style::Code, end + 2));
+    }
+    if let Some(inner) = s.strip_prefix('[') {
+        let close = inner.find("](")?;
+        let end = close + inner[close..].find(')')?;
+        return Some((&inner[..close], Style::Link, end + 2));
+    }
+    //Avoid treating snake_case or 2*3*4 as emphasis.
+    if prev.is_some_and(|c| c.is_alphanumeric()) {
+        return None;
+    }
+    for (marker, style) in [
+        ("**", Style::Bold),
+        ("__", Style::Bold),
+        ("*", Style::Italic),
+        ("_", Style::Italic),
+    ] {
+        if let Some(inner) = s.strip_prefix(marker) {
+            if inner.starts_with(char::is_whitespace) {
+                return None;
+            }
+            match inner.find(marker) {
+                Some(end) if end > 0 => {
+                    return Some((&inner[..end], style, end + marker.len() * 2));
+                }
+                _ => continue,
+            }
+        }
+    }
+    None
+}
+
+/// Wrap the spans by `width`, indenting the continued lines by the width of `prefix`.
+fn wrap(prefix: Span, spans: Vec<Span>, width: usize) -> Vec<Vec<Span>> {
+    let indent_width = UnicodeWidthStr::width(prefix.0.as_str());
+    let indent = " ".repeat(indent_width);
+    let mut lines = vec![];
+    let mut line_width = indent_width;
+    let mut line = vec![prefix];
+    for (text, style) in spans {
+        let mut current = String::new();
+        for c in text.chars() {
+            let c_width = UnicodeWidthChar::width(c).unwrap_or(0);
+            if line_width + c_width > width && line_width > indent_width {
+                if !current.is_empty() {
+                    line.push((std::mem::take(&mut current), style));
+                }
+                lines.push(std::mem::replace(
+                    &mut line,
+                    vec![(indent.clone(), Style::Plain)],
+                ));
+                line_width = indent_width;
+            }
+            current.push(c);
+            line_width += c_width;
+        }
+        if !current.is_empty() {
+            line.push((current, style));
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(text: &str, style: Style) -> Span {
+        (text.to_owned(), style)
+    }
+
+    #[test]
+    fn test_parse_inline() {
+        assert_eq!(
+            parse_inline("a **bold** and *it* with `code` and [link](https://example.com)."),
+            vec![
+                span("a ", Style::Plain),
+                span("bold", Style::Bold),
+                span(" and ", Style::Plain),
+                span("it", Style::Italic),
+                span(" with ", Style::Plain),
+                span("code", Style::Code),
+                span(" and ", Style::Plain),
+                span("link", Style::Link),
+                span(".", Style::Plain),
+            ]
+        );
+        assert_eq!(
+            parse_inline("snake_case_name, 2 * 3 and *unclosed"),
+            vec![span("snake_case_name, 2 * 3 and *unclosed", Style::Plain)]
+        );
+    }
+
+    #[test]
+    fn test_layout_markdown() {
+        let md = "# Title\n\n- first item wraps\n1. one\n> quote\n---\n```\nlet a = 1;\n```";
+        assert_eq!(
+            layout_markdown(md, 12),
+            vec![
+                vec![span("", Style::Plain), span("# Title", Style::Heading)],
+                vec![],
+                vec![span("• ", Style::Plain), span("first item", Style::Plain)],
+                vec![span("  ", Style::Plain), span(" wraps", Style::Plain)],
+                vec![span("1. ", Style::Plain), span("one", Style::Plain)],
+                vec![span("│ ", Style::Plain), span("quote", Style::Italic)],
+                vec![span(&"─".repeat(12), Style::Plain)],
+                vec![span("  ", Style::Plain), span("let a = 1;", Style::Code)],
+            ]
+        );
+    }
+}
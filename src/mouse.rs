@@ -0,0 +1,138 @@
+use super::layout::{Layout, Split};
+use super::state::BEGINNING_ROW;
+
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use std::time::{Duration, Instant};
+
+/// Two clicks on the same row within this are a double-click.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+/// Lines scrolled by a notch of the wheel.
+pub const SCROLL_LINES: usize = 3;
+
+/// What the main loop does with the mouse event.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MouseAction {
+    /// Move the cursor to the item at the row.
+    Select {
+        index: usize,
+        y: u16,
+    },
+    /// Move the cursor to the item and open it.
+    Open {
+        index: usize,
+        y: u16,
+    },
+    ScrollDown,
+    ScrollUp,
+    ScrollPreviewDown,
+    ScrollPreviewUp,
+    None,
+}
+
+/// Remember the last click to tell a double-click.
+#[derive(Debug, Default)]
+pub struct Mouse {
+    last_click: Option<(Instant, u16)>,
+}
+
+impl Mouse {
+    /// Map the mouse event to the item in the list, or the preview.
+    pub fn translate(&mut self, event: MouseEvent, layout: &Layout, len: usize) -> MouseAction {
+        // crossterm counts from 0, and the layout from 1.
+        let (x, y) = (event.column + 1, event.row + 1);
+        let in_preview = layout.is_preview()
+            && match layout.split {
+                Split::Vertical => x >= layout.preview_start.0,
+                Split::Horizontal => y >= layout.preview_start.1,
+            };
+        match event.kind {
+            MouseEventKind::ScrollDown if in_preview => MouseAction::ScrollPreviewDown,
+            MouseEventKind::ScrollUp if in_preview => MouseAction::ScrollPreviewUp,
+            MouseEventKind::ScrollDown => MouseAction::ScrollDown,
+            MouseEventKind::ScrollUp => MouseAction::ScrollUp,
+            MouseEventKind::Down(MouseButton::Left) if !in_preview => {
+                if y < BEGINNING_ROW || y >= layout.terminal_row {
+                    return MouseAction::None;
+                }
+                let index = (layout.nums.skip + y - BEGINNING_ROW) as usize;
+                if index >= len {
+                    return MouseAction::None;
+                }
+                let now = Instant::now();
+                let is_double = self.last_click.is_some_and(|(time, row)| {
+                    row == y && now.duration_since(time) < DOUBLE_CLICK_INTERVAL
+                });
+                // A third click starts over.
+                self.last_click = (!is_double).then_some((now, y));
+                if is_double {
+                    MouseAction::Open { index, y }
+                } else {
+                    MouseAction::Select { index, y }
+                }
+            }
+            _ => MouseAction::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Side;
+    use crossterm::event::KeyModifiers;
+
+    fn event(kind: MouseEventKind, column: u16, row: u16) -> MouseEvent {
+        MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    #[test]
+    fn test_translate() {
+        let mut layout = Layout {
+            terminal_row: 20,
+            terminal_column: 40,
+            preview_start: (42, BEGINNING_ROW),
+            side: Side::None,
+            ..Default::default()
+        };
+        layout.nums.skip = 5;
+        let mut mouse = Mouse::default();
+        let click = |column, row| event(MouseEventKind::Down(MouseButton::Left), column, row);
+
+        assert_eq!(
+            mouse.translate(click(3, 2), &layout, 10),
+            MouseAction::Select { index: 5, y: 3 }
+        );
+        assert_eq!(
+            mouse.translate(click(3, 2), &layout, 10),
+            MouseAction::Open { index: 5, y: 3 }
+        );
+        assert_eq!(
+            mouse.translate(click(3, 2), &layout, 10),
+            MouseAction::Select { index: 5, y: 3 }
+        );
+        // The header, the status line and rows without items.
+        assert_eq!(mouse.translate(click(3, 0), &layout, 10), MouseAction::None);
+        assert_eq!(
+            mouse.translate(click(3, 19), &layout, 30),
+            MouseAction::None
+        );
+        assert_eq!(mouse.translate(click(3, 9), &layout, 10), MouseAction::None);
+
+        let wheel = event(MouseEventKind::ScrollDown, 50, 5);
+        assert_eq!(mouse.translate(wheel, &layout, 10), MouseAction::ScrollDown);
+        layout.side = Side::Preview;
+        assert_eq!(
+            mouse.translate(wheel, &layout, 10),
+            MouseAction::ScrollPreviewDown
+        );
+        assert_eq!(
+            mouse.translate(click(50, 5), &layout, 10),
+            MouseAction::None
+        );
+    }
+}
@@ -1,22 +1,47 @@
-use super::state::ItemBuffer;
+use super::errors::FxError;
+use super::magic_packed;
+use super::report::ErrorReport;
+use super::state::{create_item, move_path, set_mode, set_owner, ItemBuffer, State};
 
-use log::info;
-use std::path::PathBuf;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
+/// Deletions more than this are not saved in the undo log.
+const MAX_SAVED_DELETIONS: usize = 100;
+
+/// The history of operations, which `u` and `Ctrl-r` go back and forth.
 #[derive(Debug, Default, Clone)]
 pub struct Operation {
+    /// The number of undone operations, counted from the last one.
     pub pos: usize,
-    pub op_list: Vec<OpKind>,
+    pub op_list: Vec<Rc<dyn Invertible>>,
 }
 
-#[derive(Debug, Clone)]
-pub enum OpKind {
-    Delete(DeletedFiles),
-    Put(PutFiles),
-    Rename(Vec<(PathBuf, PathBuf)>),
+/// A mutating operation recorded in the history, which can be undone and redone.
+/// Each operation records what is needed to invert itself at the time it is done.
+pub trait Invertible: std::fmt::Debug {
+    /// The name shown in the info line and the log, e.g. `PUT`.
+    fn name(&self) -> &'static str;
+
+    /// The details for the log.
+    fn detail(&self) -> String;
+
+    /// Revert the operation. Items that fail are added to `report` if the rest can go on.
+    fn undo(&self, state: &mut State, report: &mut ErrorReport) -> Result<(), FxError>;
+
+    /// Do the operation again.
+    fn redo(&self, state: &mut State, report: &mut ErrorReport) -> Result<(), FxError>;
+
+    /// The deletion to save in the undo log, which is undoable in the next session
+    /// as long as the items are in the trash.
+    fn deletion(&self) -> Option<&DeletedFiles> {
+        None
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeletedFiles {
     pub trash: Vec<ItemBuffer>,
     pub original: Vec<ItemBuffer>,
@@ -25,84 +50,622 @@ pub struct DeletedFiles {
 
 #[derive(Debug, Clone)]
 pub struct PutFiles {
-    pub original: Vec<ItemBuffer>,
-    pub put: Vec<PathBuf>,
+    /// Pairs of the source and the path it was put to.
+    pub put: Vec<(PathBuf, PathBuf)>,
+    /// Pairs of the overwritten path and the path in the trash it was moved to.
+    pub trashed: Vec<(PathBuf, PathBuf)>,
     pub dir: PathBuf,
 }
 
+/// Pairs of the original path and the new one.
+#[derive(Debug, Clone)]
+pub struct Renamed(pub Vec<(PathBuf, PathBuf)>);
+
+/// Pairs of the original path and the new one, possibly on another file system.
+#[derive(Debug, Clone)]
+pub struct Moved(pub Vec<(PathBuf, PathBuf)>);
+
+#[derive(Debug, Clone)]
+pub struct CreatedItem {
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangedMode {
+    pub path: PathBuf,
+    pub old: u32,
+    pub new: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangedModes(pub Vec<ChangedMode>);
+
+#[derive(Debug, Clone)]
+pub struct ChangedOwner {
+    pub path: PathBuf,
+    /// The uid and the gid before the change.
+    pub old: (u32, u32),
+    /// The uid and/or the gid given, `None` if unchanged.
+    pub new: (Option<u32>, Option<u32>),
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangedOwners(pub Vec<ChangedOwner>);
+
+#[derive(Debug, Clone)]
+pub struct Unpacked {
+    pub archive: PathBuf,
+    pub dest: PathBuf,
+}
+
 impl Operation {
+    /// Record the new operation, discarding the undone ones.
+    pub fn push(&mut self, op: impl Invertible + 'static) {
+        self.branch();
+        info!("{}: {}", op.name(), op.detail());
+        self.op_list.push(Rc::new(op));
+        self.pos = 0;
+    }
+
+    /// The operation to undo next, if any.
+    pub fn to_undo(&self) -> Option<Rc<dyn Invertible>> {
+        let len = self.op_list.len();
+        if len <= self.pos {
+            return None;
+        }
+        self.op_list.get(len - self.pos - 1).cloned()
+    }
+
+    /// The operation to redo next, if any.
+    pub fn to_redo(&self) -> Option<Rc<dyn Invertible>> {
+        let len = self.op_list.len();
+        if self.pos == 0 || len < self.pos {
+            return None;
+        }
+        self.op_list.get(len - self.pos).cloned()
+    }
+
+    /// Restore the deletions saved in the previous session,
+    /// skipping those of which the items are no longer in the trash.
+    pub fn read_log(path: &Path) -> Self {
+        let deletions: Vec<DeletedFiles> = match std::fs::read_to_string(path) {
+            Ok(s) => serde_yaml::from_str(&s).unwrap_or_else(|e| {
+                warn!("Cannot read the undo log: {}", e);
+                vec![]
+            }),
+            Err(_) => vec![],
+        };
+        let op_list: Vec<Rc<dyn Invertible>> = deletions
+            .into_iter()
+            .filter(|deleted| deleted.trash.iter().all(|item| item.file_path.exists()))
+            .map(|deleted| Rc::new(deleted) as Rc<dyn Invertible>)
+            .collect();
+        Operation { pos: 0, op_list }
+    }
+
+    /// Save the deletions not undone to the undo log.
+    pub fn write_log(&self, path: &Path) -> Result<(), FxError> {
+        let len = self.op_list.len().saturating_sub(self.pos);
+        let deletions: Vec<&DeletedFiles> = self.op_list[..len]
+            .iter()
+            .filter_map(|op| op.deletion())
+            .collect();
+        let skip = deletions.len().saturating_sub(MAX_SAVED_DELETIONS);
+        std::fs::write(path, serde_yaml::to_string(&deletions[skip..])?)?;
+        Ok(())
+    }
+
     /// Discard undone operations when new one is pushed.
-    pub fn branch(&mut self) {
-        if self.pos == 0 {
-            return;
+    fn branch(&mut self) {
+        let len = self.op_list.len().saturating_sub(self.pos);
+        self.op_list.truncate(len);
+    }
+}
+
+impl Invertible for DeletedFiles {
+    fn name(&self) -> &'static str {
+        "DELETE"
+    }
+
+    fn detail(&self) -> String {
+        format!("{:?}", item_to_pathvec(&self.original))
+    }
+
+    fn undo(&self, state: &mut State, report: &mut ErrorReport) -> Result<(), FxError> {
+        for (original, trashed) in self.original.iter().zip(self.trash.iter()) {
+            if let Err(e) = state.restore_from_trash(&trashed.file_path, &original.file_path) {
+                report.add(&original.file_path, &e);
+            }
         }
-        for _i in 0..self.pos {
-            self.op_list.pop();
+        Ok(())
+    }
+
+    fn redo(&self, state: &mut State, report: &mut ErrorReport) -> Result<(), FxError> {
+        for (original, trashed) in self.original.iter().zip(self.trash.iter()) {
+            if let Err(e) = state.trash_again(&original.file_path, &trashed.file_path) {
+                report.add(&original.file_path, &e);
+            }
         }
+        Ok(())
     }
 
-    pub fn push(&mut self, op: OpKind) {
-        log(&op);
-        self.op_list.push(op);
-        self.pos = 0;
+    fn deletion(&self) -> Option<&DeletedFiles> {
+        Some(self)
     }
 }
 
-fn log(op: &OpKind) {
-    match op {
-        OpKind::Put(op) => {
-            info!("PUT: {:?}", op.put);
+impl Invertible for PutFiles {
+    fn name(&self) -> &'static str {
+        "PUT"
+    }
+
+    fn detail(&self) -> String {
+        if self.trashed.is_empty() {
+            pairs_to_string(&self.put)
+        } else {
+            format!(
+                "{} overwritten: {}",
+                pairs_to_string(&self.put),
+                pairs_to_string(&self.trashed)
+            )
+        }
+    }
+
+    fn undo(&self, state: &mut State, report: &mut ErrorReport) -> Result<(), FxError> {
+        for (_, x) in &self.put {
+            //An overwritten item that cannot go back keeps the put one in its place.
+            let lost = self
+                .trashed
+                .iter()
+                .any(|(original, trashed)| original == x && trashed.symlink_metadata().is_err());
+            if lost {
+                report.add(x, &FxError::PutItem(x.clone()));
+                continue;
+            }
+            let removed = if x.is_dir() && !x.is_symlink() {
+                std::fs::remove_dir_all(x)
+            } else {
+                std::fs::remove_file(x)
+            };
+            if let Err(e) = removed {
+                report.add(x, &e.into());
+            }
         }
-        OpKind::Delete(op) => {
-            info!("DELETE: {:?}", item_to_pathvec(&op.original));
+        //The overwritten items go back only after the put ones are out of the way.
+        for (original, trashed) in self.trashed.iter().rev() {
+            if let Err(e) = state.restore_from_trash(trashed, original) {
+                report.add(original, &e);
+            }
+        }
+        Ok(())
+    }
+
+    fn redo(&self, state: &mut State, report: &mut ErrorReport) -> Result<(), FxError> {
+        //Overwrite the same items again, so that the paths put to are the same as recorded.
+        for (original, trashed) in &self.trashed {
+            if let Err(e) = state.trash_again(original, trashed) {
+                report.add(original, &e);
+            }
         }
-        OpKind::Rename(op) => {
-            if !op.is_empty() {
-                info!(
-                    "RENAME: {:?}",
-                    op.iter()
-                        .map(|v| format!("{:?} -> {:?}", v.0, v.1))
-                        .collect::<Vec<String>>()
-                );
+        for (from, to) in &self.put {
+            if let Err(e) = state.put_again(from, to) {
+                report.add(to, &e);
             }
         }
+        Ok(())
     }
 }
 
-pub fn relog(op: &OpKind, undo: bool) {
-    let mut result = if undo {
-        "UNDO: ".to_string()
-    } else {
-        "REDO: ".to_string()
-    };
-    match op {
-        OpKind::Put(op) => {
-            result.push_str("PUT");
-            info!("{} {:?}", result, op.put);
-        }
-        OpKind::Delete(op) => {
-            result.push_str("DELETE");
-            info!("{} {:?}", result, item_to_pathvec(&op.original));
-        }
-        OpKind::Rename(op) => {
-            if !op.is_empty() {
-                result.push_str("RENAME");
-                info!(
-                    "{} {:?}",
-                    result,
-                    op.iter()
-                        .map(|v| format!("{:?} -> {:?}", v.0, v.1))
-                        .collect::<Vec<String>>()
-                );
+impl Invertible for Renamed {
+    fn name(&self) -> &'static str {
+        "RENAME"
+    }
+
+    fn detail(&self) -> String {
+        pairs_to_string(&self.0)
+    }
+
+    fn undo(&self, _state: &mut State, _report: &mut ErrorReport) -> Result<(), FxError> {
+        for (original, new) in &self.0 {
+            std::fs::rename(new, original)?;
+        }
+        Ok(())
+    }
+
+    fn redo(&self, _state: &mut State, _report: &mut ErrorReport) -> Result<(), FxError> {
+        for (original, new) in &self.0 {
+            std::fs::rename(original, new)?;
+        }
+        Ok(())
+    }
+}
+
+impl Invertible for Moved {
+    fn name(&self) -> &'static str {
+        "MOVE"
+    }
+
+    fn detail(&self) -> String {
+        pairs_to_string(&self.0)
+    }
+
+    fn undo(&self, _state: &mut State, report: &mut ErrorReport) -> Result<(), FxError> {
+        for (original, new) in self.0.iter().rev() {
+            if let Err(e) = move_back(new, original) {
+                report.add(new, &e);
+            }
+        }
+        Ok(())
+    }
+
+    fn redo(&self, _state: &mut State, report: &mut ErrorReport) -> Result<(), FxError> {
+        for (original, new) in &self.0 {
+            if let Err(e) = move_back(original, new) {
+                report.add(original, &e);
             }
         }
+        Ok(())
     }
 }
 
-fn item_to_pathvec(v: &Vec<ItemBuffer>) -> Vec<PathBuf> {
-    let mut result = Vec::new();
-    for p in v {
-        result.push(p.file_path.clone());
+/// Move the item by undo or redo. An item created at the path after the move is not replaced.
+fn move_back(from: &Path, to: &Path) -> Result<(), FxError> {
+    if to.symlink_metadata().is_ok() {
+        return Err(FxError::PutItem(to.to_path_buf()));
+    }
+    move_path(from, to)
+}
+
+impl Invertible for CreatedItem {
+    fn name(&self) -> &'static str {
+        "CREATE"
+    }
+
+    fn detail(&self) -> String {
+        format!("{:?}", self.path)
+    }
+
+    fn undo(&self, _state: &mut State, _report: &mut ErrorReport) -> Result<(), FxError> {
+        //Fails if something is put in the directory after created.
+        if self.is_dir {
+            std::fs::remove_dir(&self.path)?;
+        } else {
+            std::fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+
+    fn redo(&self, _state: &mut State, _report: &mut ErrorReport) -> Result<(), FxError> {
+        create_item(&self.path, self.is_dir)
+    }
+}
+
+impl Invertible for ChangedModes {
+    fn name(&self) -> &'static str {
+        "CHMOD"
+    }
+
+    fn detail(&self) -> String {
+        format!(
+            "{:?}",
+            self.0
+                .iter()
+                .map(|v| format!("{:?} {:o} -> {:o}", v.path, v.old, v.new))
+                .collect::<Vec<String>>()
+        )
+    }
+
+    fn undo(&self, _state: &mut State, report: &mut ErrorReport) -> Result<(), FxError> {
+        for changed in &self.0 {
+            if let Err(e) = set_mode(&changed.path, changed.old) {
+                report.add(&changed.path, &e);
+            }
+        }
+        Ok(())
+    }
+
+    fn redo(&self, _state: &mut State, report: &mut ErrorReport) -> Result<(), FxError> {
+        for changed in &self.0 {
+            if let Err(e) = set_mode(&changed.path, changed.new) {
+                report.add(&changed.path, &e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Invertible for ChangedOwners {
+    fn name(&self) -> &'static str {
+        "CHOWN"
+    }
+
+    fn detail(&self) -> String {
+        format!(
+            "{:?}",
+            self.0
+                .iter()
+                .map(|v| format!("{:?} {:?} -> {:?}", v.path, v.old, v.new))
+                .collect::<Vec<String>>()
+        )
+    }
+
+    fn undo(&self, _state: &mut State, report: &mut ErrorReport) -> Result<(), FxError> {
+        for changed in &self.0 {
+            let (uid, gid) = changed.old;
+            if let Err(e) = set_owner(&changed.path, Some(uid), Some(gid)) {
+                report.add(&changed.path, &e);
+            }
+        }
+        Ok(())
+    }
+
+    fn redo(&self, _state: &mut State, report: &mut ErrorReport) -> Result<(), FxError> {
+        for changed in &self.0 {
+            let (uid, gid) = changed.new;
+            if let Err(e) = set_owner(&changed.path, uid, gid) {
+                report.add(&changed.path, &e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Invertible for Unpacked {
+    fn name(&self) -> &'static str {
+        "UNPACK"
+    }
+
+    fn detail(&self) -> String {
+        format!("{:?} -> {:?}", self.archive, self.dest)
+    }
+
+    fn undo(&self, _state: &mut State, _report: &mut ErrorReport) -> Result<(), FxError> {
+        if self.dest.is_dir() {
+            std::fs::remove_dir_all(&self.dest)?;
+        } else {
+            std::fs::remove_file(&self.dest)?;
+        }
+        Ok(())
+    }
+
+    fn redo(&self, _state: &mut State, _report: &mut ErrorReport) -> Result<(), FxError> {
+        magic_packed::unpack(&self.archive, &self.dest)
+    }
+}
+
+fn pairs_to_string(pairs: &[(PathBuf, PathBuf)]) -> String {
+    format!(
+        "{:?}",
+        pairs
+            .iter()
+            .map(|v| format!("{:?} -> {:?}", v.0, v.1))
+            .collect::<Vec<String>>()
+    )
+}
+
+pub fn item_to_pathvec(v: &[ItemBuffer]) -> Vec<PathBuf> {
+    v.iter().map(|p| p.file_path.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::FileType;
+
+    fn created(name: &str) -> CreatedItem {
+        CreatedItem {
+            path: PathBuf::from(name),
+            is_dir: false,
+        }
+    }
+
+    fn undo_target(operations: &Operation) -> Option<String> {
+        operations.to_undo().map(|op| op.detail())
+    }
+
+    #[test]
+    fn test_operation_history() {
+        let mut operations = Operation::default();
+        assert!(operations.to_undo().is_none());
+        assert!(operations.to_redo().is_none());
+
+        operations.push(created("a"));
+        operations.push(created("b"));
+        assert_eq!(undo_target(&operations), Some("\"b\"".to_owned()));
+        assert!(operations.to_redo().is_none());
+
+        operations.pos = 1;
+        assert_eq!(undo_target(&operations), Some("\"a\"".to_owned()));
+        assert_eq!(
+            operations.to_redo().map(|op| op.detail()),
+            Some("\"b\"".to_owned())
+        );
+        operations.pos = 2;
+        assert!(operations.to_undo().is_none());
+
+        //The undone operations are discarded by a new one.
+        operations.push(created("c"));
+        assert_eq!(operations.op_list.len(), 1);
+        assert_eq!(operations.pos, 0);
+        assert_eq!(undo_target(&operations), Some("\"c\"".to_owned()));
+    }
+
+    #[test]
+    fn test_undo_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("undo_log");
+        let in_trash = dir.path().join("1_a.txt");
+        std::fs::write(&in_trash, "a").unwrap();
+        let deleted = |trash: &Path| DeletedFiles {
+            trash: vec![ItemBuffer {
+                file_type: FileType::File,
+                file_name: "1_a.txt".to_owned(),
+                os_name: None,
+                file_path: trash.to_path_buf(),
+            }],
+            original: vec![],
+            dir: PathBuf::from("/home"),
+        };
+
+        let mut operations = Operation::default();
+        operations.push(deleted(&in_trash));
+        operations.push(created("b"));
+        //The trash is emptied.
+        operations.push(deleted(&dir.path().join("2_b.txt")));
+        //Undone, so not saved.
+        operations.push(deleted(&in_trash));
+        operations.pos = 1;
+        operations.write_log(&log).unwrap();
+
+        let restored = Operation::read_log(&log);
+        assert_eq!(restored.op_list.len(), 1);
+        assert_eq!(restored.pos, 0);
+        assert_eq!(
+            restored.to_undo().unwrap().deletion().unwrap().trash[0].file_path,
+            in_trash
+        );
+
+        assert!(Operation::read_log(&dir.path().join("none"))
+            .op_list
+            .is_empty());
+    }
+
+    #[test]
+    fn test_undo_overwrite() {
+        use crate::conflict::OnConflict;
+        use crate::item::read_item_at;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dest) = (dir.path().join("src"), dir.path().join("dest"));
+        let trash = dir.path().join("Trash");
+        for d in [&src, &dest, &trash.join("files"), &trash.join("info")] {
+            std::fs::create_dir_all(d).unwrap();
+        }
+        std::fs::write(src.join("a.txt"), "new").unwrap();
+        std::fs::write(dest.join("a.txt"), "old").unwrap();
+
+        let mut state = State {
+            current_dir: dest.clone(),
+            trash_dir: trash.join("files"),
+            trash_info_dir: Some(trash.join("info")),
+            on_conflict: OnConflict::Overwrite,
+            ..Default::default()
+        };
+        let item = ItemBuffer::new(&read_item_at(src.join("a.txt"), "a.txt".to_owned()));
+        let mut report = ErrorReport::default();
+        state.put_item(&[item], &mut report).unwrap();
+        assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "new");
+        //The overwritten one is in the trash, not lost.
+        assert_eq!(
+            std::fs::read_to_string(trash.join("files/a.txt")).unwrap(),
+            "old"
+        );
+        assert!(trash.join("info/a.txt.trashinfo").exists());
+
+        let op = state.operations.to_undo().unwrap();
+        op.undo(&mut state, &mut report).unwrap();
+        assert_eq!(report.summary(), "");
+        assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "old");
+        assert!(!trash.join("files/a.txt").exists());
+        assert!(!trash.join("info/a.txt.trashinfo").exists());
+
+        //Redo overwrites the same item again, not putting it as `a_1.txt`.
+        op.redo(&mut state, &mut report).unwrap();
+        assert_eq!(report.summary(), "");
+        assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "new");
+        assert!(!dest.join("a_1.txt").exists());
+        assert_eq!(
+            std::fs::read_to_string(trash.join("files/a.txt")).unwrap(),
+            "old"
+        );
+
+        op.undo(&mut state, &mut report).unwrap();
+        assert_eq!(report.summary(), "");
+        assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "old");
+        assert!(!trash.join("files/a.txt").exists());
+
+        //The put item is kept if the overwritten one is gone from the trash.
+        op.redo(&mut state, &mut report).unwrap();
+        std::fs::remove_file(trash.join("files/a.txt")).unwrap();
+        op.undo(&mut state, &mut report).unwrap();
+        assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "new");
+        assert_ne!(report.summary(), "");
+    }
+
+    #[test]
+    fn test_undo_move() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.txt"), dir.path().join("sub/a.txt"));
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(&b, "moved").unwrap();
+        let op = Moved(vec![(a.clone(), b.clone())]);
+        let mut state = State::default();
+
+        //Created at the original path after the move.
+        std::fs::write(&a, "new").unwrap();
+        let mut report = ErrorReport::default();
+        op.undo(&mut state, &mut report).unwrap();
+        assert_ne!(report.summary(), "");
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "new");
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "moved");
+
+        std::fs::remove_file(&a).unwrap();
+        let mut report = ErrorReport::default();
+        op.undo(&mut state, &mut report).unwrap();
+        assert_eq!(report.summary(), "");
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "moved");
+        assert!(!b.exists());
+
+        std::fs::write(&b, "other").unwrap();
+        op.redo(&mut state, &mut report).unwrap();
+        assert_ne!(report.summary(), "");
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "other");
+    }
+
+    #[test]
+    fn test_undo_delete() {
+        use crate::item::read_item_at;
+
+        let dir = tempfile::tempdir().unwrap();
+        let trash = dir.path().join("Trash");
+        for d in [&trash.join("files"), &trash.join("info")] {
+            std::fs::create_dir_all(d).unwrap();
+        }
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "a").unwrap();
+
+        let mut state = State {
+            current_dir: dir.path().to_path_buf(),
+            trash_dir: trash.join("files"),
+            trash_info_dir: Some(trash.join("info")),
+            ..Default::default()
+        };
+        let item = ItemBuffer::new(&read_item_at(file.clone(), "a.txt".to_owned()));
+        let mut report = ErrorReport::default();
+        let (original, trashed, _) = state.move_to_trash(&[item], &mut report).unwrap();
+        let in_trash = trash.join("files/a.txt");
+        let info = trash.join("info/a.txt.trashinfo");
+        assert_eq!(trashed[0].file_path, in_trash);
+        let op = DeletedFiles {
+            trash: trashed,
+            original,
+            dir: dir.path().to_path_buf(),
+        };
+
+        //Moved back, not copied.
+        op.undo(&mut state, &mut report).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "a");
+        assert!(!in_trash.exists());
+        assert!(!info.exists());
+
+        //Trashed at the same path, to be undone again.
+        op.redo(&mut state, &mut report).unwrap();
+        assert!(!file.exists());
+        assert!(in_trash.exists());
+        assert!(std::fs::read_to_string(&info).unwrap().contains("Path=/"));
+        op.undo(&mut state, &mut report).unwrap();
+        assert!(file.exists());
+        assert_eq!(report.summary(), "");
     }
-    result
 }
@@ -0,0 +1,273 @@
+use super::errors::FxError;
+use super::functions::expand_tilde;
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// The value of a command in `exec` and `alt_exec`:
+/// the extensions alone, or with the options.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum OpenerConfig {
+    Extensions(Vec<String>),
+    Options {
+        extensions: Vec<String>,
+        #[serde(default)]
+        detach: bool,
+        #[serde(default)]
+        cwd: WorkingDir,
+    },
+}
+
+/// Where the command runs.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(from = "String")]
+pub enum WorkingDir {
+    /// The directory shown in fx.
+    #[default]
+    Current,
+    /// The directory of the file, which differs in the search results.
+    File,
+    /// Relative to the current directory if not absolute. `~` is expanded.
+    Path(PathBuf),
+}
+
+impl From<String> for WorkingDir {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "current" => WorkingDir::Current,
+            "file" => WorkingDir::File,
+            _ => WorkingDir::Path(expand_tilde(Path::new(&s))),
+        }
+    }
+}
+
+/// The command to open files, with the placeholders, and how to run it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Opener {
+    pub command: String,
+    /// Spawn it detached from the terminal (GUI apps), instead of handing over the terminal.
+    pub detach: bool,
+    pub cwd: WorkingDir,
+}
+
+impl Opener {
+    /// Run in the terminal, in the current directory.
+    pub fn new(command: &str) -> Self {
+        Opener {
+            command: command.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// The program and the arguments to open the file, with the placeholders expanded:
+    /// `%f` to the path, `%d` to the directory of the file, and `%%` to `%`.
+    /// Without `%f`, the path is added as the last argument.
+    pub fn args(&self, path: &Path) -> Result<Vec<OsString>, FxError> {
+        let words = split_command(&self.command)?;
+        if words.is_empty() {
+            return Err(FxError::OpenItem("Empty command.".to_owned()));
+        }
+        let dir = path.parent().unwrap_or(path);
+        let mut has_path = false;
+        let mut args = Vec::with_capacity(words.len() + 1);
+        for word in words {
+            let mut arg = OsString::new();
+            let mut chars = word.chars();
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    arg.push(c.encode_utf8(&mut [0; 4]));
+                    continue;
+                }
+                match chars.clone().next() {
+                    Some('f') => {
+                        arg.push(path);
+                        has_path = true;
+                    }
+                    Some('d') => arg.push(dir),
+                    Some('%') => arg.push("%"),
+                    _ => {
+                        arg.push("%");
+                        continue;
+                    }
+                }
+                chars.next();
+            }
+            args.push(arg);
+        }
+        if !has_path {
+            args.push(path.into());
+        }
+        Ok(args)
+    }
+
+    /// The directory to run the command in.
+    pub fn dir(&self, path: &Path, current_dir: &Path) -> PathBuf {
+        match &self.cwd {
+            WorkingDir::Current => current_dir.to_path_buf(),
+            WorkingDir::File => path.parent().unwrap_or(current_dir).to_path_buf(),
+            WorkingDir::Path(dir) => current_dir.join(dir),
+        }
+    }
+}
+
+/// Map the extensions in `exec` or `alt_exec` (in lowercase) to the commands opening them.
+pub fn to_opener_map(
+    config: &Option<BTreeMap<String, OpenerConfig>>,
+) -> Option<BTreeMap<String, Opener>> {
+    let config = config.as_ref()?;
+    let mut map = BTreeMap::new();
+    for (command, value) in config.iter() {
+        let (extensions, opener) = match value {
+            OpenerConfig::Extensions(extensions) => (extensions, Opener::new(command)),
+            OpenerConfig::Options {
+                extensions,
+                detach,
+                cwd,
+            } => (
+                extensions,
+                Opener {
+                    command: command.clone(),
+                    detach: *detach,
+                    cwd: cwd.clone(),
+                },
+            ),
+        };
+        for ext in extensions.iter() {
+            map.insert(ext.to_lowercase(), opener.clone());
+        }
+    }
+    Some(map)
+}
+
+/// Split the command line into words as the shell does, without expanding anything:
+/// `'...'` is taken as is, and in `"..."` and outside of quotes, `\` escapes the next character.
+pub fn split_command(command: &str) -> Result<Vec<String>, FxError> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                words.extend(word.take());
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(unclosed(command)),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(unclosed(command)),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(unclosed(command)),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    word.get_or_insert_with(String::new).push(c);
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+fn unclosed(command: &str) -> FxError {
+    FxError::OpenItem(format!("Unclosed quote in the command: {}", command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command(r#"mpv  --title="My \"video\"" 'it''s' a\ b %f"#).unwrap(),
+            vec!["mpv", r#"--title=My "video""#, "its", "a b", "%f"]
+        );
+        assert_eq!(split_command(r#"echo "" '\n'"#).unwrap(), vec!["echo", "", "\\n"]);
+        assert!(split_command("").unwrap().is_empty());
+        assert!(split_command("vim 'a").is_err());
+    }
+
+    #[test]
+    fn test_opener() {
+        let path = Path::new("/tmp/dir/a b.mp4");
+        let opener = Opener::new("mpv --no-terminal %f --title=%d/%%f");
+        assert_eq!(
+            opener.args(path).unwrap(),
+            vec![
+                "mpv",
+                "--no-terminal",
+                "/tmp/dir/a b.mp4",
+                "--title=/tmp/dir/%f"
+            ]
+        );
+        assert_eq!(
+            Opener::new("feh -.").args(path).unwrap(),
+            vec!["feh", "-.", "/tmp/dir/a b.mp4"]
+        );
+        assert!(Opener::new(" ").args(path).is_err());
+
+        let current = Path::new("/home/user");
+        assert_eq!(opener.dir(path, current), current);
+        let opener = Opener {
+            cwd: WorkingDir::File,
+            ..opener
+        };
+        assert_eq!(opener.dir(path, current), Path::new("/tmp/dir"));
+        let opener = Opener {
+            cwd: WorkingDir::from("build".to_owned()),
+            ..opener
+        };
+        assert_eq!(opener.dir(path, current), Path::new("/home/user/build"));
+    }
+
+    #[test]
+    fn test_to_opener_map() {
+        let config: BTreeMap<String, OpenerConfig> = serde_yaml::from_str(
+            r#"
+zathura: [pdf]
+'mpv --no-terminal %f':
+  extensions: [MP4, mkv]
+  detach: true
+  cwd: file
+"#,
+        )
+        .unwrap();
+        let map = to_opener_map(&Some(config)).unwrap();
+        assert_eq!(map["pdf"], Opener::new("zathura"));
+        assert_eq!(
+            map["mp4"],
+            Opener {
+                command: "mpv --no-terminal %f".to_owned(),
+                detach: true,
+                cwd: WorkingDir::File,
+            }
+        );
+        assert_eq!(map["mkv"], map["mp4"]);
+        assert_eq!(to_opener_map(&None), None);
+    }
+}
@@ -0,0 +1,151 @@
+use super::functions::shorten_str_including_wide_char;
+use super::item::ItemInfo;
+
+use crossterm::style::{Color, Stylize};
+
+/// Colors more than this are not shown in the preview.
+const MAX_COLORS: usize = 500;
+const SWATCH: &str = "      ";
+
+type Rgb = (u8, u8, u8);
+
+/// Render color swatches for palettes: GIMP palettes (`.gpl`), `.hex` files of `rrggbb`,
+/// and other text files of which every line is a color like `#rrggbb` or `#rgb`.
+/// Return None if the item is not a palette, to show it as plain text.
+pub fn render_palette(item: &ItemInfo, content: &str, width: u16) -> Option<Vec<String>> {
+    let ext = item.file_ext.as_ref().map(|ext| ext.to_lowercase());
+    let colors = match ext.as_deref() {
+        Some("gpl") => parse_gpl(content)?,
+        Some("hex") => parse_hex_lines(content, false)?,
+        _ => parse_hex_lines(content, true)?,
+    };
+    let mut lines: Vec<String> = colors
+        .iter()
+        .take(MAX_COLORS)
+        .map(|((r, g, b), name)| {
+            let label = format!(" #{:02x}{:02x}{:02x} {}", r, g, b, name);
+            let label = shorten_str_including_wide_char(
+                label.trim_end(),
+                (width as usize).saturating_sub(SWATCH.len()),
+            );
+            format!(
+                "{}{}",
+                SWATCH.on(Color::Rgb {
+                    r: *r,
+                    g: *g,
+                    b: *b
+                }),
+                label
+            )
+        })
+        .collect();
+    if colors.len() > MAX_COLORS {
+        lines.push(format!("… {} more", colors.len() - MAX_COLORS));
+    }
+    Some(lines)
+}
+
+/// Parse GIMP palette, which starts with `GIMP Palette` and has lines like `255 0 0 Red`.
+fn parse_gpl(content: &str) -> Option<Vec<(Rgb, String)>> {
+    let mut lines = content.lines();
+    if lines.next()?.trim() != "GIMP Palette" {
+        return None;
+    }
+    let mut colors = vec![];
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let mut channel = || fields.next()?.parse::<u8>().ok();
+        let rgb = (channel()?, channel()?, channel()?);
+        let name = fields.collect::<Vec<&str>>().join(" ");
+        colors.push((rgb, name));
+    }
+    Some(colors)
+}
+
+/// Parse lines of hex colors. Blank lines are ignored, and any other line fails.
+fn parse_hex_lines(content: &str, require_hash: bool) -> Option<Vec<(Rgb, String)>> {
+    let colors: Vec<(Rgb, String)> = content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let hex = match line.strip_prefix('#') {
+                Some(hex) => hex,
+                None if require_hash => return None,
+                None => line,
+            };
+            Some((parse_hex(hex)?, String::new()))
+        })
+        .collect::<Option<_>>()?;
+    if colors.is_empty() {
+        None
+    } else {
+        Some(colors)
+    }
+}
+
+/// Parse `rrggbb` or `rgb`.
+fn parse_hex(hex: &str) -> Option<Rgb> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+    match hex.len() {
+        6 => Some((
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        )),
+        3 => {
+            let short = |i: usize| channel(&hex[i..i + 1]).map(|c| c * 17);
+            Some((short(0)?, short(1)?, short(2)?))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gpl() {
+        let gpl = "GIMP Palette\nName: Test\nColumns: 4\n# comment\n255   0   0\tRed\n  0 128 255 Sky blue\n";
+        assert_eq!(
+            parse_gpl(gpl),
+            Some(vec![
+                ((255, 0, 0), "Red".to_owned()),
+                ((0, 128, 255), "Sky blue".to_owned())
+            ])
+        );
+        assert_eq!(parse_gpl("255 0 0 Red"), None);
+        assert_eq!(parse_gpl("GIMP Palette\n256 0 0"), None);
+    }
+
+    #[test]
+    fn test_parse_hex_lines() {
+        assert_eq!(
+            parse_hex_lines("#ff0000\n\n#0af\n", true),
+            Some(vec![
+                ((255, 0, 0), String::new()),
+                ((0, 170, 255), String::new())
+            ])
+        );
+        assert_eq!(parse_hex_lines("ff0000\n", true), None);
+        assert_eq!(
+            parse_hex_lines("ff0000\n", false),
+            Some(vec![((255, 0, 0), String::new())])
+        );
+        assert_eq!(parse_hex_lines("#ff0000\nbody {}\n", true), None);
+        assert_eq!(parse_hex_lines("#ff00\n", true), None);
+        assert_eq!(parse_hex_lines("", true), None);
+    }
+}
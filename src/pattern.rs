@@ -0,0 +1,258 @@
+use super::errors::FxError;
+
+use regex::{Regex, RegexBuilder};
+
+/// Pattern to match item names.
+/// `/{regex}/` is treated as a regular expression (partial match),
+/// otherwise as a glob (`*`, `?` and `[...]`, matching the whole name).
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    regex: Regex,
+}
+
+impl Pattern {
+    pub fn new(s: &str, ignore_case: bool) -> Result<Self, FxError> {
+        let regex = if s.len() >= 2 && s.starts_with('/') && s.ends_with('/') {
+            build_regex(&s[1..s.len() - 1], ignore_case)?
+        } else {
+            build_regex(&glob_to_regex(s), ignore_case)?
+        };
+        Ok(Pattern { regex })
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+/// `s/{regex}/{replacement}/{flags}` to rename items.
+/// In the replacement, `$0` is the whole match and `$1`..`$9` are the capture groups.
+/// Flags: `g` replaces all the matches, `i` ignores case.
+#[derive(Debug, Clone)]
+pub struct Substitution {
+    regex: Regex,
+    /// In the syntax of the regex crate.
+    replacement: String,
+    global: bool,
+}
+
+impl Substitution {
+    pub fn new(s: &str) -> Result<Self, FxError> {
+        let invalid = || FxError::Pattern(format!("Expected s/from/to/ -> {}", s));
+        let parts = split_unescaped(s.strip_prefix("s/").ok_or_else(invalid)?, '/');
+        if parts.len() != 3 {
+            return Err(invalid());
+        }
+        let mut ignore_case = false;
+        let mut global = false;
+        for flag in parts[2].chars() {
+            match flag {
+                'g' => global = true,
+                'i' => ignore_case = true,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(Substitution {
+            regex: build_regex(&parts[0], ignore_case)?,
+            replacement: to_replacement(&parts[1]),
+            global,
+        })
+    }
+
+    /// Return the replaced name, or None if the regex does not match.
+    pub fn replace(&self, name: &str) -> Option<String> {
+        if !self.regex.is_match(name) {
+            return None;
+        }
+        let limit = if self.global { 0 } else { 1 };
+        Some(
+            self.regex
+                .replacen(name, limit, self.replacement.as_str())
+                .into_owned(),
+        )
+    }
+}
+
+/// The regex crate runs in linear time of the name, whatever the pattern is.
+fn build_regex(s: &str, ignore_case: bool) -> Result<Regex, FxError> {
+    RegexBuilder::new(s)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| FxError::Pattern(e.to_string()))
+}
+
+/// Convert `$0`..`$9` and `$$` to the syntax of the regex crate,
+/// in which `$1a` would be the group named `1a`. Other `$` is literal.
+fn to_replacement(s: &str) -> String {
+    let mut result = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some(d) if d.is_ascii_digit() => {
+                result.push_str(&format!("${{{}}}", d));
+                chars.next();
+            }
+            Some('$') => {
+                result.push_str("$$");
+                chars.next();
+            }
+            _ => result.push_str("$$"),
+        }
+    }
+    result
+}
+
+/// Split the string by the separator, except the escaped one (`\/`).
+fn split_unescaped(s: &str, separator: char) -> Vec<String> {
+    let mut result = vec![String::new()];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek() == Some(&separator) {
+            result.last_mut().unwrap().push(separator);
+            chars.next();
+        } else if c == separator {
+            result.push(String::new());
+        } else {
+            result.last_mut().unwrap().push(c);
+        }
+    }
+    result
+}
+
+/// Convert glob to the anchored regex.
+fn glob_to_regex(glob: &str) -> String {
+    let mut result = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => result.push_str(".*"),
+            '?' => result.push('.'),
+            '[' => {
+                // Copy the bracket expression as is, converting `[!...]` to `[^...]`.
+                let mut inner = vec![];
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' && !(inner.is_empty() || inner == ['!']) {
+                        closed = true;
+                        break;
+                    }
+                    inner.push(c);
+                }
+                if closed {
+                    result.push('[');
+                    for (i, c) in inner.into_iter().enumerate() {
+                        match c {
+                            '!' if i == 0 => result.push('^'),
+                            '\\' | '[' => {
+                                result.push('\\');
+                                result.push(c);
+                            }
+                            c => result.push(c),
+                        }
+                    }
+                    result.push(']');
+                } else {
+                    // Unclosed bracket is matched literally.
+                    push_escaped(&mut result, '[');
+                    for c in inner {
+                        push_escaped(&mut result, c);
+                    }
+                }
+            }
+            c => push_escaped(&mut result, c),
+        }
+    }
+    result.push('$');
+    result
+}
+
+fn push_escaped(s: &mut String, c: char) {
+    if "\\.+*?()|[]{}^$".contains(c) {
+        s.push('\\');
+    }
+    s.push(c);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob() {
+        let p = Pattern::new("*.log", false).unwrap();
+        assert!(p.is_match("a.log"));
+        assert!(p.is_match(".log"));
+        assert!(!p.is_match("a.log.gz"));
+        assert!(!p.is_match("a.LOG"));
+        assert!(Pattern::new("*.log", true).unwrap().is_match("a.LOG"));
+
+        let p = Pattern::new("file?.[ch]", false).unwrap();
+        assert!(p.is_match("file1.c"));
+        assert!(p.is_match("filea.h"));
+        assert!(!p.is_match("file10.c"));
+        assert!(!p.is_match("file1.o"));
+
+        let p = Pattern::new("[!a-c]*", false).unwrap();
+        assert!(p.is_match("data"));
+        assert!(!p.is_match("bin"));
+
+        assert!(Pattern::new("a+b(1)", false).unwrap().is_match("a+b(1)"));
+        assert!(Pattern::new("[abc", false).unwrap().is_match("[abc"));
+    }
+
+    #[test]
+    fn test_substitution() {
+        let s = Substitution::new(r"s/^IMG_(\d+)\.JPG$/photo-$1.jpg/").unwrap();
+        assert_eq!(
+            s.replace("IMG_0012.JPG"),
+            Some("photo-0012.jpg".to_string())
+        );
+        assert_eq!(s.replace("IMG_0012.png"), None);
+
+        let s = Substitution::new("s/ /_/").unwrap();
+        assert_eq!(s.replace("a b c"), Some("a_b c".to_string()));
+        let s = Substitution::new("s/ /_/g").unwrap();
+        assert_eq!(s.replace("a b c"), Some("a_b_c".to_string()));
+
+        let s = Substitution::new(r"s/readme/$0.old$$/i").unwrap();
+        assert_eq!(s.replace("README.md"), Some("README.old$.md".to_string()));
+        let s = Substitution::new(r"s/x*/-/g").unwrap();
+        assert_eq!(s.replace("abc"), Some("-a-b-c-".to_string()));
+        let s = Substitution::new(r"s/\//_/").unwrap();
+        assert_eq!(s.replace("a/b"), Some("a_b".to_string()));
+        let s = Substitution::new(r"s/(\d)(\d)/$2$1a$x/").unwrap();
+        assert_eq!(s.replace("12.txt"), Some("21a$x.txt".to_string()));
+
+        assert!(Substitution::new("s/a/b").is_err());
+        assert!(Substitution::new("s/a/b/x").is_err());
+        assert!(Substitution::new("a/b/").is_err());
+    }
+
+    #[test]
+    fn test_regex() {
+        let p = Pattern::new(r"/^\d{4}-\d{2}/", false).unwrap();
+        assert!(p.is_match("2024-01-01.md"));
+        assert!(!p.is_match("note-2024-01.md"));
+
+        let p = Pattern::new(r"/\.(jpe?g|png)$/", true).unwrap();
+        assert!(p.is_match("a.JPG"));
+        assert!(p.is_match("b.jpeg"));
+        assert!(p.is_match("c.png"));
+        assert!(!p.is_match("d.gif"));
+
+        assert!(Pattern::new("/(a*)*b/", false).unwrap().is_match("aaab"));
+        assert!(!Pattern::new("/(a*)*b/", false).unwrap().is_match("aaa"));
+        //No catastrophic backtracking.
+        assert!(!Pattern::new("/(a*)*b/", false)
+            .unwrap()
+            .is_match(&"a".repeat(100_000)));
+        assert!(Pattern::new("/[a-/", false).is_err());
+        assert!(Pattern::new("/(ab/", false).is_err());
+        assert!(Pattern::new("/ab)/", false).is_err());
+        assert!(Pattern::new("/*a/", false).is_err());
+    }
+}
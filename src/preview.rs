@@ -0,0 +1,186 @@
+use super::term::*;
+
+use felix::color::TermColor;
+use felix::errors::FxError;
+use felix::functions::{format_txt, shorten_str_including_wide_char};
+use felix::item::ItemInfo;
+use felix::layout::{Layout, Preview, Split, CHAFA_WARNING};
+use felix::state::BEGINNING_ROW;
+
+/// Drawing the preview area (the preview or the registers) of the layout.
+pub trait PreviewArea {
+    fn print_reg(&self, reg: &[String]);
+    fn print_preview(&self, item: Option<&ItemInfo>, y: u16);
+    fn update_column_and_row(&mut self) -> Result<(u16, u16), FxError>;
+}
+
+impl PreviewArea for Layout {
+    fn print_reg(&self, reg: &[String]) {
+        match self.split {
+            Split::Vertical => {
+                clear_preview(self, self.preview_start.0);
+            }
+            Split::Horizontal => {
+                clear_preview(self, self.preview_start.1);
+            }
+        }
+
+        if reg.iter().all(|x| x.is_empty()) {
+            print!("No registers found.");
+            return;
+        }
+
+        match self.split {
+            Split::Vertical => {
+                for (i, line) in reg.iter().enumerate() {
+                    let row = self.preview_start.1 + i as u16;
+                    move_to(self.preview_start.0, row);
+                    print!("{}", line);
+                    if i as u16 == self.preview_space.1 - 1 {
+                        break;
+                    }
+                }
+            }
+            Split::Horizontal => {
+                for (i, line) in reg.iter().enumerate() {
+                    let row = self.preview_start.1 + i as u16;
+                    move_to(1, row);
+                    print!("{}", line);
+                    if row == self.terminal_row + self.preview_space.1 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Print preview according to the preview type.
+    fn print_preview(&self, item: Option<&ItemInfo>, y: u16) {
+        if let Some(item) = item {
+            match self.split {
+                Split::Vertical => {
+                    //At least print the item name
+                    print_file_name(self, item);
+                    //Clear preview space
+                    clear_preview(self, self.preview_start.0);
+                }
+                Split::Horizontal => {
+                    clear_preview(self, self.preview_start.1);
+                }
+            }
+
+            match self.preview(item) {
+                Ok(Preview::Note(note)) => print!("{}", note),
+                Ok(Preview::Text(lines)) => print_txt_in_preview_area(self, item, &lines),
+                Ok(Preview::Image(output)) => print_image(self, &output),
+                Ok(Preview::NoChafa) => {
+                    let help = format_txt(CHAFA_WARNING, self.terminal_column - 1, false);
+                    for (i, line) in help.iter().enumerate() {
+                        move_to(self.preview_start.0, BEGINNING_ROW + i as u16);
+                        print!("{}", line,);
+                        if BEGINNING_ROW + i as u16 == self.terminal_row - 1 {
+                            break;
+                        }
+                    }
+                }
+                Err(e) => print_warning(e, y),
+            }
+        }
+    }
+
+    fn update_column_and_row(&mut self) -> Result<(u16, u16), FxError> {
+        if self.is_preview() || self.is_reg() {
+            Ok(self.list_area(self.terminal_column, self.terminal_row))
+        } else {
+            terminal_size()
+        }
+    }
+}
+
+/// Print item name at the top.
+fn print_file_name(layout: &Layout, item: &ItemInfo) {
+    move_to(layout.preview_start.0 - 1, 1);
+    clear_until_newline();
+    move_right(1);
+    let mut file_name = format!("[{}]", item.file_name);
+    if file_name.bytes().len() > layout.preview_space.0 as usize {
+        file_name = shorten_str_including_wide_char(&file_name, layout.preview_space.0 as usize);
+    }
+    print!("{}", file_name);
+}
+
+fn print_txt_in_preview_area(layout: &Layout, item: &ItemInfo, content: &[String]) {
+    match layout.split {
+        Split::Vertical => {
+            for (i, line) in content.iter().enumerate() {
+                if i < item.preview_scroll {
+                    continue;
+                }
+                let sum = (i - item.preview_scroll) as u16;
+                let row = layout.preview_start.1 + sum;
+                move_to(layout.preview_start.0, row);
+                set_color(&TermColor::ForeGround(&layout.theme.preview_fg));
+                print!("{}", line);
+                if sum == layout.preview_space.1 - 1 {
+                    break;
+                }
+            }
+        }
+        Split::Horizontal => {
+            for (i, line) in content.iter().enumerate() {
+                if i < item.preview_scroll {
+                    continue;
+                }
+                let sum = (i - item.preview_scroll) as u16;
+                let row = layout.preview_start.1 + sum;
+                move_to(1, row);
+                set_color(&TermColor::ForeGround(&layout.theme.preview_fg));
+                print!("{}", line);
+                if row == layout.terminal_row + layout.preview_space.1 {
+                    break;
+                }
+            }
+        }
+    }
+    reset_color();
+}
+
+/// Print the image drawn by chafa in the preview area (Experimental).
+fn print_image(layout: &Layout, output: &str) {
+    match layout.split {
+        Split::Vertical => {
+            for (i, line) in output.lines().enumerate() {
+                print!("{}", line);
+                let next_line: u16 = BEGINNING_ROW + (i as u16) + 1;
+                move_to(layout.preview_start.0, next_line);
+            }
+        }
+        Split::Horizontal => {
+            for (i, line) in output.lines().enumerate() {
+                print!("{}", line);
+                let next_line: u16 = layout.preview_start.1 + (i as u16) + 1;
+                move_to(1, next_line);
+            }
+        }
+    }
+}
+
+/// Clear the preview space.
+fn clear_preview(layout: &Layout, preview_start_point: u16) {
+    match layout.split {
+        Split::Vertical => {
+            for i in 0..=layout.terminal_row {
+                move_to(preview_start_point, BEGINNING_ROW + i);
+                clear_until_newline();
+            }
+            move_to(layout.preview_start.0, BEGINNING_ROW);
+        }
+        Split::Horizontal => {
+            for i in 0..=layout.terminal_row {
+                move_to(1, preview_start_point + i);
+                clear_until_newline();
+            }
+            move_to(1, preview_start_point);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use super::errors::FxError;
+use super::frontend::Frontend;
+use super::functions::{to_decimal_size, to_rough_duration};
+
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Redrawing for every chunk slows down copying, so the bar is updated at most at this interval.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: usize = 10;
+
+/// Progress of copying an item, shown after the count in the info line.
+/// The total is scanned before copying, and the copy loop adds the bytes written.
+/// The frontend shows the bar, and cancels the copy if asked.
+pub struct Progress {
+    total: u64,
+    done: u64,
+    start: Instant,
+    drawn: Option<Instant>,
+    ui: Arc<dyn Frontend>,
+}
+
+impl Progress {
+    pub fn new(total: u64, ui: Arc<dyn Frontend>) -> Self {
+        Progress {
+            total,
+            done: 0,
+            start: Instant::now(),
+            drawn: None,
+            ui,
+        }
+    }
+
+    /// Add the copied bytes, and redraw the bar if the interval has passed.
+    /// Return `FxError::Canceled` if canceled since the last redraw.
+    pub fn add(&mut self, bytes: u64) -> Result<(), FxError> {
+        self.done += bytes;
+        let redraw = match self.drawn {
+            None => true,
+            Some(drawn) => drawn.elapsed() >= REDRAW_INTERVAL,
+        };
+        if redraw {
+            self.ui.progress(&format_progress(
+                self.done,
+                self.total,
+                self.start.elapsed(),
+            ));
+            self.drawn = Some(Instant::now());
+            if self.ui.is_canceled() {
+                return Err(FxError::Canceled);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Format the progress like ` [»»»»------]  40% 20.0 MB/s ETA 3s`.
+pub fn format_progress(done: u64, total: u64, elapsed: Duration) -> String {
+    let ratio = if total == 0 {
+        1.0
+    } else {
+        (done as f64 / total as f64).min(1.0)
+    };
+    let filled = (ratio * BAR_WIDTH as f64) as usize;
+    let mut result = format!(
+        " [{}{}] {:>3}%",
+        "»".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        (ratio * 100.0) as u8
+    );
+    let secs = elapsed.as_secs_f64();
+    if done > 0 && secs > 0.0 {
+        let rate = done as f64 / secs;
+        let _ = write!(result, " {}/s", to_decimal_size(rate as u64));
+        if done < total {
+            let remaining = Duration::from_secs_f64(total.saturating_sub(done) as f64 / rate);
+            let _ = write!(result, " ETA {}", to_rough_duration(remaining));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_progress() {
+        assert_eq!(
+            format_progress(0, 100, Duration::ZERO),
+            " [----------]   0%"
+        );
+        assert_eq!(
+            format_progress(40_000_000, 100_000_000, Duration::from_secs(2)),
+            " [»»»»------]  40% 20.0 MB/s ETA 3s"
+        );
+        assert_eq!(
+            format_progress(100, 100, Duration::from_secs(1)),
+            " [»»»»»»»»»»] 100% 100 B/s"
+        );
+        assert_eq!(format_progress(0, 0, Duration::ZERO), " [»»»»»»»»»»] 100%");
+    }
+}
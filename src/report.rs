@@ -0,0 +1,78 @@
+use super::errors::FxError;
+
+use std::path::{Path, PathBuf};
+
+/// Errors of the items that failed in a batch operation.
+/// The operation goes on with the rest of the items, and the errors are shown at the end.
+#[derive(Debug, Default)]
+pub struct ErrorReport {
+    errors: Vec<(PathBuf, String)>,
+}
+
+impl ErrorReport {
+    pub fn add(&mut self, path: &Path, error: &FxError) {
+        self.errors.push((path.to_path_buf(), error.to_string()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// The error of the first item, to show in the info line when operating on a single item.
+    pub fn first(&self) -> Option<&str> {
+        self.errors.first().map(|(_, error)| error.as_str())
+    }
+
+    /// Appended to the result message, e.g. ` (2 failed)`.
+    pub fn summary(&self) -> String {
+        match self.errors.len() {
+            0 => String::new(),
+            n => format!(" ({} failed)", n),
+        }
+    }
+
+    /// Lines of the report screen, with the path of each item and the error under it.
+    pub fn lines(&self, operation: &str) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "{}: {} item(s) failed. j/k to scroll, any other key to close.",
+                operation,
+                self.errors.len()
+            ),
+            String::new(),
+        ];
+        for (path, error) in self.errors.iter() {
+            lines.push(path.display().to_string());
+            lines.push(format!("  {}", error));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut report = ErrorReport::default();
+        assert!(report.is_empty());
+        assert_eq!(report.summary(), "");
+
+        report.add(
+            Path::new("/a/b"),
+            &FxError::Io("Permission denied".to_owned()),
+        );
+        assert_eq!(report.summary(), " (1 failed)");
+        assert_eq!(report.first(), Some("Permission denied"));
+        assert_eq!(
+            report.lines("DELETE"),
+            vec![
+                "DELETE: 1 item(s) failed. j/k to scroll, any other key to close.",
+                "",
+                "/a/b",
+                "  Permission denied",
+            ]
+        );
+    }
+}
@@ -1,31 +1,49 @@
-use super::config::{read_config, FELIX};
-use super::errors::FxError;
-use super::functions::*;
-use super::layout::{PreviewType, Split};
-use super::nums::*;
-use super::op::*;
-use super::session::*;
-use super::state::*;
+use super::command_line::read_command;
+use super::event_loop::EventLoop;
+use super::preview::PreviewArea;
+use super::screen::{Screen, Terminal};
 use super::term::*;
 
+use felix::chooser::Chooser;
+use felix::config::{config_override, config_paths, write_default_config, FELIX};
+use felix::errors::FxError;
+use felix::events::AppEvent;
+use felix::filter::{parse_date, parse_duration, parse_size};
+use felix::functions::*;
+use felix::item::FileType;
+use felix::journal::Journal;
+use felix::keymap::{Action, Feed};
+use felix::layout::{is_too_small, prune_thumbnails, PreviewType, Split, MIN_COLUMNS, MIN_ROWS};
+use felix::mouse::{Mouse, MouseAction, SCROLL_LINES};
+use felix::nums::*;
+use felix::op::*;
+use felix::session::read_session;
+use felix::shell_command::History;
+use felix::state::*;
+use felix::trash::growth_report;
+use felix::watcher::Watcher;
+
 use crossterm::cursor::{RestorePosition, SavePosition};
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use crossterm::execute;
-use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
 use log::{error, info};
 use normpath::PathExt;
 use std::env;
-use std::io::{stdout, Write};
+use std::io::{stdout, IsTerminal, Write};
 use std::panic;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::thread;
 use std::time::Instant;
 
 const TRASH: &str = "Trash";
+const TRASH_FILES: &str = "files";
+const TRASH_INFO: &str = "info";
 const SESSION_FILE: &str = ".session";
-/// Where the item list starts to scroll.
-const SCROLL_POINT: u16 = 3;
+const UNDO_LOG_FILE: &str = ".undo_log";
+const JOURNAL_DIR: &str = ".journal";
+const SHELL_HISTORY_FILE: &str = ".shell_history";
+const THUMBNAILS: &str = "thumbnails";
+const TICK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 const CLRSCR: &str = "\x1B[2J";
 const INITIAL_POS_COMMAND_LINE: u16 = 3;
 const INITIAL_POS_Z: u16 = 2;
@@ -34,9 +52,11 @@ const PROMPT_INSERT_DIR: &str = "New directory: ";
 const PROMPT_RENAME: &str = "New name: ";
 const PROMPT_SEARCH: &str = "/";
 const PROMPT_COMMAND_LINE: &str = ":";
+const PROMPT_SHELL: &str = "!";
 
 /// Launch the app. If initialization goes wrong, return error.
-pub fn run(arg: PathBuf, log: bool) -> Result<(), FxError> {
+/// With the chooser, the app works as a file picker.
+pub fn run(arg: PathBuf, log: bool, chooser: Option<Chooser>) -> Result<(), FxError> {
     //Check if argument path is valid.
     if !&arg.exists() {
         println!();
@@ -50,15 +70,10 @@ pub fn run(arg: PathBuf, log: bool) -> Result<(), FxError> {
         ));
     }
 
+    restore_on_panic();
+
     let shell_pid: Option<String> = env::var("SHELL_PID").ok();
 
-    //Prepare config and data local path.
-    let config_dir_path = {
-        let mut path = dirs::config_dir()
-            .ok_or_else(|| FxError::Dirs("Cannot read the config directory.".to_string()))?;
-        path.push(FELIX);
-        path
-    };
     //Prepare data local and trash dir path.
     let data_local_path = {
         let mut path = dirs::data_local_dir()
@@ -66,6 +81,12 @@ pub fn run(arg: PathBuf, log: bool) -> Result<(), FxError> {
         path.push(FELIX);
         path
     };
+    //The session, the undo log and the journal are kept in XDG_STATE_HOME,
+    //or with the data where it is not defined (macOS, Windows).
+    let state_path = match dirs::state_dir() {
+        Some(path) => path.join(FELIX),
+        None => data_local_path.clone(),
+    };
     let runtime_path = {
         let mut path = {
             #[cfg(not(target_os = "macos"))]
@@ -81,12 +102,13 @@ pub fn run(arg: PathBuf, log: bool) -> Result<(), FxError> {
         path.push(FELIX);
         path
     };
-    if !config_dir_path.exists() {
-        std::fs::create_dir_all(&config_dir_path)?;
-    }
     if !data_local_path.exists() {
         std::fs::create_dir_all(&data_local_path)?;
     }
+    if !state_path.exists() {
+        std::fs::create_dir_all(&state_path)?;
+    }
+    migrate_state(&data_local_path, &state_path);
     if !runtime_path.exists() {
         std::fs::create_dir_all(&runtime_path)?;
     }
@@ -94,39 +116,94 @@ pub fn run(arg: PathBuf, log: bool) -> Result<(), FxError> {
     //Path of the file used to store lwd (Last Working Directory) at the end of the session.
     let lwd_file_path = shell_pid.map(|basename| runtime_path.join(basename));
 
-    let trash_dir_path = {
+    let legacy_trash_path = {
         let mut path = data_local_path.clone();
         path.push(TRASH);
         path
     };
-    if !trash_dir_path.exists() {
-        std::fs::create_dir_all(&trash_dir_path)?;
-    }
 
     //If `-l / --log` is set, initialize logger.
     if log {
         init_log(&data_local_path)?;
     }
+    //Before the other threads are spawned.
+    restore_on_signals();
 
     //Set the session file path.
     let session_path = {
-        let mut path = data_local_path;
+        let mut path = state_path;
         path.push(SESSION_FILE);
         path
     };
 
+    //Deletions are saved here to be undone in the next session.
+    let undo_log_path = session_path.with_file_name(UNDO_LOG_FILE);
+
+    //Image previews are cached here. Not fatal if the cache directory is unknown.
+    let thumbnail_dir_path = dirs::cache_dir().map(|mut path| {
+        path.push(FELIX);
+        path.push(THUMBNAILS);
+        path
+    });
+    if let Some(path) = thumbnail_dir_path.clone() {
+        thread::spawn(move || prune_thumbnails(&path));
+    }
+
+    //On the first run, write the config to be edited.
+    if let Err(e) = init_config() {
+        error!("Cannot create the config file: {}", e);
+    }
+
+    //Wait until the terminal gets large enough to show the list.
+    let (mut column, mut row) = terminal_size()?;
+    if is_too_small(column, row) {
+        error!("Too small terminal size: {}x{}", column, row);
+        println!(
+            "Terminal too small: resize it to {}x{} at least, or press Ctrl-C to quit.",
+            MIN_COLUMNS, MIN_ROWS
+        );
+    }
+    while is_too_small(column, row) {
+        (column, row) = wait_for_resize()?;
+    }
+
     //Initialize app state. Inside `State::new()`, config file is read.
-    let mut state = State::new(&session_path)?;
+    let mut state = State::new(&session_path, column, row)?;
+    state.frontend = Some(Arc::new(Terminal));
+    //The trash dir depends on the config.
+    //The freedesktop.org trash is for the desktop environments other than macOS.
+    let freedesktop = cfg!(all(unix, not(target_os = "macos")));
+    let (trash_dir_path, trash_info_path) = if state.legacy_trash || !freedesktop {
+        (legacy_trash_path, None)
+    } else {
+        let mut path = dirs::data_dir()
+            .ok_or_else(|| FxError::Dirs("Cannot read the data directory.".to_string()))?;
+        path.push(TRASH);
+        (path.join(TRASH_FILES), Some(path.join(TRASH_INFO)))
+    };
+    for path in std::iter::once(&trash_dir_path).chain(trash_info_path.iter()) {
+        if !path.exists() {
+            std::fs::create_dir_all(path)?;
+        }
+    }
     state.trash_dir = trash_dir_path;
+    state.trash_info_dir = trash_info_path;
+    state.layout.thumbnail_dir = thumbnail_dir_path;
     state.lwd_file = lwd_file_path;
-    let normalized_arg = arg.normalize();
-    if normalized_arg.is_err() {
-        return Err(FxError::Arg(format!(
-            "Invalid path: {}\n`fx -h` shows help.",
-            &arg.display()
-        )));
-    }
-    state.current_dir = normalized_arg.unwrap().into_path_buf();
+    state.chooser = chooser;
+    state.operations = Operation::read_log(&undo_log_path);
+    //Operations modifying files are journaled here until they finish.
+    state.journal = Journal::new(session_path.with_file_name(JOURNAL_DIR));
+    state.shell_history = History::read(session_path.with_file_name(SHELL_HISTORY_FILE));
+    state.current_dir = match arg.normalize() {
+        Ok(path) => path.into_path_buf(),
+        Err(_) => {
+            return Err(FxError::Arg(format!(
+                "Invalid path: {}\n`fx -h` shows help.",
+                &arg.display()
+            )))
+        }
+    };
     state.jumplist.add(&state.current_dir);
     state.is_ro = match has_write_permission(&state.current_dir) {
         Ok(b) => !b,
@@ -134,75 +211,127 @@ pub fn run(arg: PathBuf, log: bool) -> Result<(), FxError> {
     };
 
     //If the main function causes panic, catch it.
-    let result = panic::catch_unwind(|| _run(state, session_path));
+    //State (holding the handles of the background jobs) is moved in and never used after a panic.
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        _run(state, session_path, undo_log_path)
+    }));
+    set_mouse_capture(false);
     leave_raw_mode();
 
-    if let Err(panic) = result {
-        clear_all();
-        move_to(1, 1);
-        match panic.downcast::<String>() {
-            Ok(msg) => {
-                println!("Panic: {}", msg);
-            }
-            Err(e) => {
-                println!("{:#?}", e);
+    match result {
+        Ok(Ok(())) => Ok(()),
+        //Leave the alternate screen to show the error.
+        Ok(Err(e)) => {
+            restore_terminal();
+            Err(e)
+        }
+        //The panic hook has restored the terminal and printed the message.
+        Err(_) => Err(FxError::Panic),
+    }
+}
+
+/// Move the session, the undo log and the journal kept with the data by the older versions
+/// to the state directory, unless it has them already.
+fn migrate_state(data_local_path: &std::path::Path, state_path: &std::path::Path) {
+    if data_local_path == state_path {
+        return;
+    }
+    for name in [SESSION_FILE, UNDO_LOG_FILE, JOURNAL_DIR] {
+        let (from, to) = (data_local_path.join(name), state_path.join(name));
+        if from.exists() && !to.exists() {
+            if let Err(e) = std::fs::rename(&from, &to) {
+                error!("Cannot move {} to {}: {}", from.display(), to.display(), e);
             }
         }
-        return Err(FxError::Panic);
     }
+}
 
-    result.ok().unwrap()
+/// If no config file exists, write the commented config,
+/// asking for the command to open files if run in a terminal.
+fn init_config() -> Result<(), FxError> {
+    //The file given explicitly is not created.
+    if config_override().is_some() {
+        return Ok(());
+    }
+    let paths = config_paths()?;
+    if paths.iter().any(|p| p.exists()) {
+        return Ok(());
+    }
+    let path = &paths[0];
+    let default = if std::io::stdin().is_terminal() {
+        println!("Creating the config file: {}", path.display());
+        print!("Command to open files (empty to use $EDITOR): ");
+        stdout().flush()?;
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        let line = line.trim().to_owned();
+        (!line.is_empty()).then_some(line)
+    } else {
+        None
+    };
+    write_default_config(path, default.as_deref())?;
+    info!("Config file created: {}", path.display());
+    Ok(())
 }
 
 /// Run the app. (Containing the main loop)
-fn _run(mut state: State, session_path: PathBuf) -> Result<(), FxError> {
+fn _run(mut state: State, session_path: PathBuf, undo_log_path: PathBuf) -> Result<(), FxError> {
     //Save the current cursor position and enter the alternate screen with crossterm
     let mut screen = stdout();
     write!(screen, "{}", SavePosition)?;
     enter_raw_mode();
-    execute!(screen, EnterAlternateScreen)?;
+    set_alternate_screen(state.alternate_screen);
+    set_mouse_capture(state.mouse);
 
     //If preview is on, refresh the layout.
     if state.layout.is_preview() {
         state.update_list()?;
-        let new_column = match state.layout.split {
-            Split::Vertical => state.layout.terminal_column >> 1,
-            Split::Horizontal => state.layout.terminal_column,
-        };
-        let new_row = match state.layout.split {
-            Split::Vertical => state.layout.terminal_row,
-            Split::Horizontal => state.layout.terminal_row >> 1,
-        };
+        let (new_column, new_row) = state
+            .layout
+            .list_area(state.layout.terminal_column, state.layout.terminal_row);
         state.refresh(new_column, new_row, BEGINNING_ROW)?;
     } else {
         state.reload(BEGINNING_ROW)?;
     }
+    //Report the operations interrupted by a crash in the previous runs.
+    let incomplete = state.journal.incomplete();
+    if let Some((_, entry)) = incomplete.first() {
+        let more = match incomplete.len() {
+            1 => String::new(),
+            n => format!(" ({} more)", n - 1),
+        };
+        print_warning(
+            format!("{}{} `:journal` to check.", entry.describe(), more),
+            state.layout.y,
+        );
+    }
     screen.flush()?;
 
+    let events = EventLoop::new();
+    state.jobs.set_sender(events.sender());
+
     // Spawn another thread to watch the config file.
     let mut modified_time = match &state.config_path {
-        Some(config_path) => config_path.metadata().unwrap().modified().ok(),
+        Some(config_path) => config_path
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok(),
         None => None,
     };
-    let wait_update = Arc::new(Mutex::new(false));
-    let wait_update_clone = wait_update.clone();
     let config_path_clone = state.config_path.clone();
+    let sender = events.sender();
     // if config file does not exist, no watching.
     if modified_time.is_some() {
         // Every 2 secondes, check if the config file is updated.
         thread::spawn(move || loop {
             thread::sleep(std::time::Duration::from_secs(2));
-            if *wait_update_clone.lock().unwrap() {
-                continue;
-            }
             let metadata = config_path_clone.as_ref().unwrap().metadata();
             if let Ok(metadata) = metadata {
                 let new_modified = metadata.modified().ok();
                 if modified_time != new_modified {
-                    if let Ok(mut updated) = wait_update_clone.lock() {
-                        *updated = true;
-                        modified_time = new_modified;
-                    } else {
+                    modified_time = new_modified;
+                    // If the main loop has finished, stop watching.
+                    if sender.send(AppEvent::ConfigUpdated).is_err() {
                         break;
                     }
                 }
@@ -210,894 +339,690 @@ fn _run(mut state: State, session_path: PathBuf) -> Result<(), FxError> {
         });
     }
 
-    'main: loop {
-        // Check if config file is updated
-        if state.config_path.is_some() {
-            if let Ok(mut wait_update) = wait_update.lock() {
-                if *wait_update {
-                    if let Ok(c) = read_config(state.config_path.as_ref().unwrap()) {
-                        state.set_config(c.config);
-                        state.redraw(state.layout.y);
-                        print_info("New config set.", state.layout.y);
-                    } else {
-                        // If reading the config file fails, leave the config as is.
-                        print_warning("Something wrong with the config file.", state.layout.y);
-                    }
-                    *wait_update = false;
-                }
-            }
+    // Calculate the size of the trash directory in another thread,
+    // to report if it grew since the last run.
+    // It is calculated again whenever the trash dir is modified, to show it in the header.
+    let mut previous_trash_size = read_session(&session_path).trash_size;
+    // Keep the previous size if the calculation does not finish before exit.
+    state.trash_size = previous_trash_size;
+    state.take_trash_change();
+    calculate_trash_size(&state, events.sender());
+
+    // Tick to check the changes of the current directory.
+    let sender = events.sender();
+    thread::spawn(move || loop {
+        thread::sleep(TICK_INTERVAL);
+        if sender.send(AppEvent::Tick).is_err() {
+            break;
         }
+    });
+
+    // Refresh the list as soon as the current directory is changed by other programs.
+    let mut watcher = Watcher::new(events.sender());
 
+    let mut mouse = Mouse::default();
+
+    'main: loop {
         if state.is_out_of_bounds() {
             state.layout.nums.reset();
             state.redraw(BEGINNING_ROW);
         }
         screen.flush()?;
         let len = state.list.len();
+        if let Some(watcher) = watcher.as_mut() {
+            watcher.follow(&state.current_dir);
+        }
 
-        match event::read()? {
-            Event::Key(KeyEvent {
-                code,
-                modifiers,
-                // Explicitly ignore the key release events for Windows.
-                kind: KeyEventKind::Press,
-                ..
-            }) => {
-                match modifiers {
-                    KeyModifiers::CONTROL => match code {
-                        // go down 1/2 page
-                        KeyCode::Char('d') => {
-                            let half = state.layout.terminal_row.div_ceil(2);
-                            let mut cursor_move_count = 0;
-                            if let Some(start_pos) = state.v_start {
-                                // visual mode
-                                for _n in 0..half {
-                                    if len == 0 || state.layout.nums.index == len - 1 {
-                                        break;
-                                    } else if state.layout.y + cursor_move_count
-                                        >= state.layout.terminal_row - 4
-                                        && len
-                                            > (state.layout.terminal_row - BEGINNING_ROW) as usize
-                                                - 1
-                                    {
-                                        if state.layout.nums.index >= start_pos {
-                                            state.layout.nums.go_down();
-                                            state.layout.nums.inc_skip();
-                                            let item = state.get_item_mut()?;
-                                            item.selected = true;
-                                        } else {
-                                            let item = state.get_item_mut()?;
-                                            item.selected = false;
-                                            state.layout.nums.go_down();
-                                            state.layout.nums.inc_skip();
-                                        }
-                                    } else if state.layout.nums.index >= start_pos {
-                                        state.layout.nums.go_down();
-                                        let item = state.get_item_mut()?;
-                                        item.selected = true;
-                                        cursor_move_count += 1;
-                                    } else {
-                                        let item = state.get_item_mut()?;
-                                        item.selected = false;
-                                        state.layout.nums.go_down();
-                                        cursor_move_count += 1;
+        // Keys of a broken or timed out sequence are handled before the next input.
+        let visual = state.v_start.is_some();
+        let feed = match state.keymap.replayed(visual) {
+            Some(feed) => feed,
+            None => {
+                let ev = match events.next()? {
+                    AppEvent::Input(ev) => ev,
+                    AppEvent::ConfigUpdated => {
+                        state.reload_config();
+                        continue;
+                    }
+                    AppEvent::TrashSize(size) => {
+                        state.trash_size = Some(size);
+                        // Show the new size in the header.
+                        state.redraw(state.layout.y);
+                        // Report the growth since the last run only once.
+                        if let Some(report) = growth_report(
+                            previous_trash_size.take(),
+                            size,
+                            state.trash_report_threshold,
+                        ) {
+                            print_info(report, state.layout.y);
+                        }
+                        continue;
+                    }
+                    AppEvent::Tick => {
+                        if state.keymap.expire(visual) {
+                            print_pending_keys(&state);
+                        }
+                        if state.take_trash_change() {
+                            calculate_trash_size(&state, events.sender());
+                        }
+                        // Show the progress of the background jobs while waiting for the input.
+                        if !state.jobs.list.is_empty() {
+                            state.print_footer(state.get_item().ok());
+                        }
+                        if let Err(e) = state.refresh_if_modified() {
+                            print_warning(e, state.layout.y);
+                        }
+                        continue;
+                    }
+                    AppEvent::DirChanged => {
+                        state.dir_changed = true;
+                        if let Err(e) = state.refresh_if_modified() {
+                            print_warning(e, state.layout.y);
+                        }
+                        continue;
+                    }
+                    AppEvent::JobFinished {
+                        id,
+                        finished,
+                        result,
+                    } => {
+                        if let Err(e) = state.finish_job(id, finished, result) {
+                            print_warning(e, state.layout.y);
+                        }
+                        continue;
+                    }
+                };
+                match ev {
+                    // Explicitly ignore the key release events for Windows.
+                    Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                        let was_pending = !state.keymap.pending_keys().is_empty();
+                        let feed = state.keymap.feed(&key_event, visual);
+                        if was_pending && feed != Feed::Wait {
+                            print_pending_keys(&state);
+                        }
+                        feed
+                    }
+                    // Clicks in visual mode are ignored, as the selection follows the cursor.
+                    Event::Mouse(mouse_event) => {
+                        match mouse.translate(mouse_event, &state.layout, len) {
+                            MouseAction::Select { index, y } if !visual => {
+                                state.layout.nums.index = index;
+                                state.move_cursor(y);
+                                continue;
+                            }
+                            MouseAction::Open { index, y } if !visual => {
+                                state.layout.nums.index = index;
+                                state.move_cursor(y);
+                                Feed::Action(Action::Open)
+                            }
+                            // Moved as by the keys, so that the list scrolls as usual.
+                            MouseAction::ScrollDown => {
+                                for _ in 0..SCROLL_LINES {
+                                    if let Some(render) = state.apply(Action::MoveDown)? {
+                                        state.render(render);
                                     }
                                 }
-                                state.redraw(state.layout.y + cursor_move_count);
-                            } else {
-                                // normal mode
-                                for _n in 0..half {
-                                    if len == 0 || state.layout.nums.index == len - 1 {
-                                        break;
-                                    } else if state.layout.y + cursor_move_count
-                                        >= state.layout.terminal_row - 1 - SCROLL_POINT
-                                        && len
-                                            > (state.layout.terminal_row - BEGINNING_ROW) as usize
-                                                - 1
-                                    {
-                                        state.layout.nums.go_down();
-                                        state.layout.nums.inc_skip();
-                                    } else {
-                                        state.layout.nums.go_down();
-                                        cursor_move_count += 1;
+                                continue;
+                            }
+                            MouseAction::ScrollUp => {
+                                for _ in 0..SCROLL_LINES {
+                                    if let Some(render) = state.apply(Action::MoveUp)? {
+                                        state.render(render);
                                     }
                                 }
-                                state.redraw(state.layout.y + cursor_move_count);
+                                continue;
                             }
-                        }
-
-                        // go up 1/2 page
-                        KeyCode::Char('u') => {
-                            let half = state.layout.terminal_row.div_ceil(2);
-                            let mut cursor_move_count = 0;
-                            if let Some(start_pos) = state.v_start {
-                                // visual mode
-                                for _n in 0..half {
-                                    if state.layout.nums.index == 0 {
-                                        break;
-                                    } else if state.layout.y - cursor_move_count
-                                        <= BEGINNING_ROW + 3
-                                        && state.layout.nums.skip != 0
-                                    {
-                                        if state.layout.nums.index > start_pos {
-                                            let item = state.get_item_mut()?;
-                                            item.selected = false;
-                                            state.layout.nums.go_up();
-                                            state.layout.nums.dec_skip();
-                                        } else {
-                                            state.layout.nums.go_up();
-                                            state.layout.nums.dec_skip();
-                                            let item = state.get_item_mut()?;
-                                            item.selected = true;
-                                        }
-                                    } else if state.layout.nums.index > start_pos {
-                                        let item = state.get_item_mut()?;
-                                        item.selected = false;
-                                        state.layout.nums.go_up();
-                                        cursor_move_count += 1;
-                                    } else {
-                                        state.layout.nums.go_up();
-                                        let item = state.get_item_mut()?;
-                                        item.selected = true;
-                                        cursor_move_count += 1;
-                                    }
+                            MouseAction::ScrollPreviewDown => {
+                                for _ in 0..SCROLL_LINES {
+                                    state.scroll_down_preview(state.layout.y);
                                 }
-                                state.redraw(state.layout.y - cursor_move_count);
-                            } else {
-                                //normal mode
-                                for _n in 0..half {
-                                    if state.layout.nums.index == 0 {
-                                        break;
-                                    } else if state.layout.y - cursor_move_count
-                                        <= BEGINNING_ROW + SCROLL_POINT
-                                        && state.layout.nums.skip != 0
-                                    {
-                                        state.layout.nums.go_up();
-                                        state.layout.nums.dec_skip();
-                                    } else {
-                                        state.layout.nums.go_up();
-                                        cursor_move_count += 1;
-                                    }
+                                continue;
+                            }
+                            MouseAction::ScrollPreviewUp => {
+                                for _ in 0..SCROLL_LINES {
+                                    state.scroll_up_preview(state.layout.y);
                                 }
-                                state.redraw(state.layout.y - cursor_move_count);
+                                continue;
                             }
+                            _ => continue,
                         }
+                    }
+                    Event::Resize(column, row) => {
+                        state.resize(column, row)?;
+                        continue;
+                    }
+                    //Other events are disabled.
+                    _ => continue,
+                }
+            }
+        };
 
-                        //redo
-                        KeyCode::Char('r') => {
-                            if state.v_start.is_some() {
-                                continue;
-                            }
-                            let op_len = state.operations.op_list.len();
-                            if op_len == 0
-                                || state.operations.pos == 0
-                                || op_len < state.operations.pos
-                            {
-                                print_info("No operations left.", state.layout.y);
+        let action = match feed {
+            Feed::Action(action) => action,
+            Feed::Preset(preset) => {
+                match state.toggle_filter_preset(&preset) {
+                    Ok(count) => {
+                        if state.filter.is_some() {
+                            print_info(format!("{} items match.", count), state.layout.y);
+                        }
+                    }
+                    Err(e) => print_warning(e, state.layout.y),
+                }
+                continue;
+            }
+            Feed::Script(name) => {
+                if let Err(e) = state.run_script(&name, &[]) {
+                    print_warning(e, state.layout.y);
+                }
+                continue;
+            }
+            Feed::Wait => {
+                print_pending_keys(&state);
+                continue;
+            }
+            Feed::Ignore => continue,
+        };
+
+        // The actions changing only the state are applied and then drawn.
+        if let Some(render) = state.apply(action)? {
+            state.render(render);
+            continue;
+        }
+
+        match action {
+            //redo
+            Action::Redo => {
+                if state.v_start.is_some() {
+                    continue;
+                }
+                if let Some(op) = state.operations.to_redo() {
+                    if let Err(e) = state.redo(op.as_ref()) {
+                        print_warning(e, state.layout.y);
+                        continue;
+                    }
+                } else {
+                    print_info("No operations left.", state.layout.y);
+                }
+            }
+
+            // jump backward
+            Action::JumpBackward => {
+                if let Some(path_to_jump_to) = state.jumplist.get_backward() {
+                    if path_to_jump_to.exists() {
+                        state.chdir(&path_to_jump_to, Move::List)?;
+                        state.jumplist.pos_backward();
+                    } else {
+                        print_warning(
+                            "Directory backward not found: Removed from jumplist.",
+                            state.layout.y,
+                        );
+                        state.jumplist.remove_backward();
+                    }
+                }
+            }
+
+            //suspend to the shell, and redraw all when resumed
+            Action::Suspend => {
+                suspend();
+                let (column, row) = terminal_size()?;
+                state.resize(column, row)?;
+            }
+
+            //Collapse or expand the group in the trash directory
+            Action::ToggleTrashGroup => {
+                if len == 0 || state.v_start.is_some() || state.current_dir != state.trash_dir {
+                    continue;
+                }
+                if let Err(e) = state.toggle_trash_group() {
+                    print_warning(e, state.layout.y);
+                }
+            }
+
+            //Launch another fx in a new terminal window
+            Action::NewTerminalWindow => {
+                if state.v_start.is_some() {
+                    continue;
+                }
+                if let Err(e) = state.open_new_instance() {
+                    print_warning(e, state.layout.y);
+                }
+            }
+
+            //scroll the previewed text, same as <Alt-j> / <Alt-k>
+            Action::ScrollDownPreview => {
+                if state.layout.is_preview() {
+                    state.scroll_down_preview(state.layout.y);
+                }
+            }
+            Action::ScrollUpPreview => {
+                if state.layout.is_preview() {
+                    state.scroll_up_preview(state.layout.y);
+                }
+            }
+
+            //Open a terminal at the directory
+            Action::Terminal => {
+                if state.v_start.is_some() {
+                    continue;
+                }
+                if let Err(e) = state.open_terminal_here() {
+                    print_warning(e, state.layout.y);
+                }
+            }
+
+            //Put the marked items
+            Action::PutMarked => {
+                //In visual mode, this is disabled.
+                if state.v_start.is_some() {
+                    continue;
+                }
+                if let Err(e) = state.put_marked() {
+                    print_warning(e, state.layout.y);
+                }
+            }
+
+            //Open file or change directory
+            Action::Open => {
+                //In visual mode, this is disabled,
+                //except for choosing the selected items in the file-picker mode.
+                if state.v_start.is_some() {
+                    if state.chooser.is_some()
+                        && state.confirm_exit()?
+                        && state.export_chosen(None).is_ok()
+                    {
+                        break 'main;
+                    }
+                    continue;
+                }
+                let mut dest: Option<PathBuf> = None;
+                if let Ok(item) = state.get_item() {
+                    let mut err: Option<FxError> = None;
+                    let is_file = item.file_type == FileType::File
+                        || (item.file_type == FileType::Symlink && item.symlink_dir_path.is_none());
+                    //In the file-picker mode, opening a file chooses it and quits.
+                    if is_file && state.chooser.is_some() {
+                        let item = item.file_path.clone();
+                        if state.confirm_exit()? && state.export_chosen(Some(&item)).is_ok() {
+                            break 'main;
+                        }
+                        continue;
+                    }
+                    if is_file {
+                        match state.confirm_large_file(item)? {
+                            LargeFile::Open => {}
+                            LargeFile::Preview => {
+                                state.layout.show_preview();
+                                let (new_column, new_row) = state.layout.update_column_and_row()?;
+                                state.refresh(new_column, new_row, state.layout.y)?;
                                 continue;
                             }
-                            if let Some(op) =
-                                state.operations.op_list.get(op_len - state.operations.pos)
-                            {
-                                let op = op.clone();
-                                if let Err(e) = state.redo(&op) {
+                            LargeFile::Pager => {
+                                reenter_alternate_screen();
+                                let result = state.open_in_pager(item);
+                                reenter_alternate_screen();
+                                hide_cursor();
+                                state.reload(state.layout.y)?;
+                                if let Err(e) = result {
                                     print_warning(e, state.layout.y);
-                                    continue;
-                                }
-
-                                let new_len = state.list.len();
-                                if new_len == 0 {
-                                    state.layout.nums.reset();
-                                    state.move_cursor(BEGINNING_ROW);
-                                } else if state.layout.nums.index > new_len - 1 {
-                                    let new_y = state.layout.y
-                                        - (state.layout.nums.index - (new_len - 1)) as u16;
-                                    state.layout.nums.index = new_len - 1;
-                                    state.move_cursor(new_y)
-                                } else {
-                                    state.move_cursor(state.layout.y);
                                 }
+                                continue;
                             }
-                        }
-
-                        // jump backward
-                        KeyCode::Char('o') => {
-                            if let Some(path_to_jump_to) = state.jumplist.get_backward() {
-                                if path_to_jump_to.exists() {
-                                    state.chdir(&path_to_jump_to, Move::List)?;
-                                    state.jumplist.pos_backward();
-                                } else {
-                                    print_warning(
-                                        "Directory backward not found: Removed from jumplist.",
-                                        state.layout.y,
-                                    );
-                                    state.jumplist.remove_backward();
-                                }
+                            LargeFile::Cancel => {
+                                state.redraw(state.layout.y);
+                                continue;
                             }
                         }
-
-                        //Other commands are disabled when Ctrl is pressed,
-                        //except <C-i> (equivalent to Tab).
-                        _ => {
+                    }
+                    match item.file_type {
+                        FileType::File => {
+                            reenter_alternate_screen();
+                            if let Err(e) = state.open_file(item) {
+                                err = Some(e);
+                            }
+                            reenter_alternate_screen();
+                            hide_cursor();
+                            state.reload(state.layout.y)?;
+                            if let Some(e) = err {
+                                print_warning(e, state.layout.y);
+                            }
                             continue;
                         }
-                    },
-                    KeyModifiers::ALT => match code {
-                        //scroll down the previewed text
-                        KeyCode::Char('j') | KeyCode::Down => {
-                            if state.layout.is_preview() {
-                                state.scroll_down_preview(state.layout.y);
+                        FileType::Symlink => match &item.symlink_dir_path {
+                            Some(true_path) => {
+                                if true_path.exists() {
+                                    dest = Some(true_path.to_path_buf());
+                                } else {
+                                    print_warning("Broken link.", state.layout.y);
+                                    continue;
+                                }
                             }
-                        }
-                        //scroll up the previewed text
-                        KeyCode::Char('k') | KeyCode::Up => {
-                            if state.layout.is_preview() {
-                                state.scroll_up_preview(state.layout.y);
+                            None => {
+                                reenter_alternate_screen();
+                                if let Err(e) = state.open_file(item) {
+                                    err = Some(e);
+                                }
+                                reenter_alternate_screen();
+                                hide_cursor();
+                                state.reload(state.layout.y)?;
+                                if let Some(e) = err {
+                                    print_warning(e, state.layout.y);
+                                }
+                                continue;
+                            }
+                        },
+                        FileType::Directory => {
+                            if item.file_path.exists() {
+                                dest = Some(item.file_path.clone());
+                            } else {
+                                print_warning("Invalid directory.", state.layout.y);
+                                continue;
                             }
                         }
-                        //Other commands are disabled when Alt is pressed.
-                        _ => {
+                        FileType::Other => {
+                            print_warning(
+                                "Cannot open FIFO, socket or device file.",
+                                state.layout.y,
+                            );
                             continue;
                         }
-                    },
-                    KeyModifiers::NONE | KeyModifiers::SHIFT => {
-                        match code {
-                            //Reset visual selection and return to normal mode
-                            KeyCode::Esc => {
-                                state.reset_selection();
+                    }
+                }
+                if let Some(dest) = dest {
+                    if let Err(e) = state.chdir(&dest, Move::Down) {
+                        print_warning(e, state.layout.y);
+                    }
+                }
+            }
+
+            //Open a file in a new window
+            //This works only if i) [exec] is set in config file
+            //and ii) the extension of the item matches the key.
+            //If not, warning message appears.
+            Action::OpenInNewWindow => {
+                //In visual mode, this is disabled.
+                if state.v_start.is_some() {
+                    continue;
+                }
+                if let Ok(item) = state.get_item() {
+                    match item.file_type {
+                        FileType::File => {
+                            if let Err(e) = state.open_file_in_new_window() {
+                                hide_cursor();
                                 state.redraw(state.layout.y);
+                                print_warning(e, state.layout.y);
                                 continue;
                             }
+                            hide_cursor();
+                            state.redraw(state.layout.y);
+                            continue;
+                        }
+                        _ => {
+                            continue;
+                        }
+                    }
+                }
+            }
 
-                            //Go down. If lists exceed max-row, lists "scrolls" before the bottom of the list
-                            KeyCode::Char('j') | KeyCode::Down => {
-                                if let Some(start_pos) = state.v_start {
-                                    //In visual mode
-                                    if len == 0 || state.layout.nums.index == len - 1 {
-                                        continue;
-                                    } else if state.layout.y >= state.layout.terminal_row - 4
-                                        && len
-                                            > (state.layout.terminal_row - BEGINNING_ROW) as usize
-                                                - 1
-                                    {
-                                        if state.layout.nums.index >= start_pos {
-                                            state.layout.nums.go_down();
-                                            state.layout.nums.inc_skip();
-                                            let item = state.get_item_mut()?;
-                                            item.selected = true;
-                                            state.redraw(state.layout.y);
-                                        } else {
-                                            let item = state.get_item_mut()?;
-                                            item.selected = false;
-                                            state.layout.nums.go_down();
-                                            state.layout.nums.inc_skip();
-                                            state.redraw(state.layout.y);
-                                        }
-                                    } else if state.layout.nums.index >= start_pos {
-                                        state.layout.nums.go_down();
-                                        let item = state.get_item_mut()?;
-                                        item.selected = true;
-                                        state.redraw(state.layout.y + 1);
-                                    } else {
-                                        let item = state.get_item_mut()?;
-                                        item.selected = false;
-                                        state.layout.nums.go_down();
-                                        state.redraw(state.layout.y + 1);
-                                    }
-                                } else {
-                                    //normal mode
-                                    if len == 0 || state.layout.nums.index == len - 1 {
-                                        continue;
-                                    } else if state.layout.y
-                                        >= state.layout.terminal_row - 1 - SCROLL_POINT
-                                        && len
-                                            > (state.layout.terminal_row - BEGINNING_ROW) as usize
-                                                - 1
-                                    {
-                                        state.layout.nums.go_down();
-                                        state.layout.nums.inc_skip();
-                                        state.redraw(state.layout.y);
-                                    } else {
-                                        state.layout.nums.go_down();
-                                        state.move_cursor(state.layout.y + 1);
-                                    }
-                                }
-                            }
+            //Run the alternate action set by [alt_exec] in config file.
+            Action::AltExec => {
+                //In visual mode, this is disabled.
+                if state.v_start.is_some() {
+                    continue;
+                }
+                if let Ok(item) = state.get_item() {
+                    if item.file_type == FileType::Directory || item.symlink_dir_path.is_some() {
+                        continue;
+                    }
+                    let mut err: Option<FxError> = None;
+                    reenter_alternate_screen();
+                    if let Err(e) = state.open_file_alt(item) {
+                        err = Some(e);
+                    }
+                    reenter_alternate_screen();
+                    hide_cursor();
+                    state.reload(state.layout.y)?;
+                    if let Some(e) = err {
+                        print_warning(e, state.layout.y);
+                    }
+                }
+            }
 
-                            //Go up. If lists exceed max-row, lists "scrolls" before the top of the list
-                            KeyCode::Char('k') | KeyCode::Up => {
-                                if let Some(start_pos) = state.v_start {
-                                    //visual mode
-                                    if state.layout.nums.index == 0 {
-                                        continue;
-                                    } else if state.layout.y <= BEGINNING_ROW + 3
-                                        && state.layout.nums.skip != 0
-                                    {
-                                        if state.layout.nums.index > start_pos {
-                                            let item = state.get_item_mut()?;
-                                            item.selected = false;
-                                            state.layout.nums.go_up();
-                                            state.layout.nums.dec_skip();
-                                            state.redraw(state.layout.y);
-                                        } else {
-                                            state.layout.nums.go_up();
-                                            state.layout.nums.dec_skip();
-                                            let item = state.get_item_mut()?;
-                                            item.selected = true;
-                                            state.redraw(state.layout.y);
-                                        }
-                                    } else if state.layout.nums.index > start_pos {
-                                        let item = state.get_item_mut()?;
-                                        item.selected = false;
-                                        state.layout.nums.go_up();
-                                        state.redraw(state.layout.y - 1);
-                                    } else {
-                                        state.layout.nums.go_up();
-                                        let item = state.get_item_mut()?;
-                                        item.selected = true;
-                                        state.redraw(state.layout.y - 1);
-                                    }
-                                } else {
-                                    //normal mode
-                                    if state.layout.nums.index == 0 {
-                                        continue;
-                                    } else if state.layout.y <= BEGINNING_ROW + SCROLL_POINT
-                                        && state.layout.nums.skip != 0
-                                    {
-                                        state.layout.nums.go_up();
-                                        state.layout.nums.dec_skip();
-                                        state.redraw(state.layout.y);
-                                    } else {
-                                        state.layout.nums.go_up();
-                                        state.move_cursor(state.layout.y - 1);
-                                    }
-                                }
-                            }
+            //Go to the parent directory if exists
+            Action::Parent => {
+                //In visual mode, this is disabled.
+                if state.v_start.is_some() {
+                    continue;
+                }
+                let pre = state.current_dir.clone();
 
-                            //Go to top
-                            KeyCode::Char('g') => {
-                                if let Some(start_pos) = state.v_start {
-                                    //visual mode
-                                    if state.layout.nums.index == 0 {
-                                        continue;
-                                    } else {
-                                        go_to_info_line_and_reset();
-                                        print!("g");
-                                        show_cursor();
-                                        screen.flush()?;
-
-                                        if let Event::Key(KeyEvent {
-                                            code,
-                                            kind: KeyEventKind::Press,
-                                            ..
-                                        }) = event::read()?
-                                        {
-                                            match code {
-                                                KeyCode::Char('g') => {
-                                                    hide_cursor();
-                                                    state.select_from_top(start_pos);
-                                                    state.layout.nums.reset();
-                                                    state.redraw(BEGINNING_ROW);
-                                                }
+                match pre.parent() {
+                    Some(parent_p) => {
+                        if let Err(e) = state.chdir(parent_p, Move::Up) {
+                            print_warning(e, state.layout.y);
+                        }
+                    }
+                    None => {
+                        continue;
+                    }
+                }
+            }
 
-                                                _ => {
-                                                    state.escape();
-                                                }
-                                            }
-                                        }
-                                    }
-                                } else {
-                                    //normal mode
-                                    go_to_info_line_and_reset();
-                                    print!("g");
-                                    show_cursor();
-                                    screen.flush()?;
-
-                                    if let Event::Key(KeyEvent {
-                                        code,
-                                        kind: KeyEventKind::Press,
-                                        ..
-                                    }) = event::read()?
-                                    {
-                                        match code {
-                                            KeyCode::Char('g') => {
-                                                hide_cursor();
-                                                state.layout.nums.reset();
-                                                state.redraw(BEGINNING_ROW);
-                                            }
+            // jump forward
+            Action::JumpForward => {
+                if let Some(path_to_jump_to) = state.jumplist.get_forward() {
+                    if path_to_jump_to.exists() {
+                        state.chdir(&path_to_jump_to, Move::List)?;
+                    } else {
+                        print_warning(
+                            "Directory forward not found: Removed from jumplist.",
+                            state.layout.y,
+                        );
+                        state.jumplist.remove_forward();
+                    }
+                    state.jumplist.pos_forward();
+                }
+            }
 
-                                            _ => {
-                                                hide_cursor();
-                                                clear_current_line();
-                                                state.move_cursor(state.layout.y);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+            //Unpack archive file. Fails if it is not any of supported types
+            Action::Unpack => {
+                //In visual mode, this is disabled.
+                //TODO! Enable this in visual mode.
+                if state.v_start.is_some() {
+                    continue;
+                }
+                print_info("Unpacking...", state.layout.y);
+                screen.flush()?;
+                let start = Instant::now();
+                if let Err(e) = state.unpack() {
+                    state.reload(state.layout.y)?;
+                    print_warning(e, state.layout.y);
+                    continue;
+                }
+                let duration = duration_to_string(start.elapsed());
+                state.reload(state.layout.y)?;
+                print_info(format!("Unpacked. [{}]", duration), state.layout.y);
+            }
 
-                            //Go to bottom
-                            KeyCode::Char('G') => {
-                                if let Some(start_pos) = state.v_start {
-                                    //visual mode
-                                    if len > (state.layout.terminal_row - BEGINNING_ROW) as usize {
-                                        state.select_to_bottom(start_pos);
-                                        state.layout.nums.skip = (len as u16) + BEGINNING_ROW
-                                            - state.layout.terminal_row;
-                                        state.layout.nums.go_bottom(len - 1);
-                                        state.redraw(state.layout.terminal_row - 1);
-                                    } else {
-                                        state.select_to_bottom(start_pos);
-                                        state.layout.nums.go_bottom(len - 1);
-                                        state.redraw(len as u16 + BEGINNING_ROW - 1);
-                                    }
-                                } else {
-                                    //normal mode
-                                    if len == 0 {
-                                        continue;
-                                    }
-                                    if len > (state.layout.terminal_row - BEGINNING_ROW) as usize {
-                                        state.layout.nums.skip = (len as u16) + BEGINNING_ROW
-                                            - state.layout.terminal_row;
-                                        state.layout.nums.go_bottom(len - 1);
-                                        let cursor_pos = state.layout.terminal_row - 1;
-                                        state.redraw(cursor_pos);
-                                    } else {
-                                        state.layout.nums.go_bottom(len - 1);
-                                        state.move_cursor(len as u16 + BEGINNING_ROW - 1);
-                                    }
-                                }
+            //Jumps to the directory that matches the keyword (zoxide required)
+            Action::Jump => {
+                //If zoxide is not found, show error message.
+                if !state.has_zoxide {
+                    print_warning("zoxide not found.", state.layout.y);
+                    continue;
+                }
+                //In visual mode, this is disabled.
+                if state.v_start.is_some() {
+                    continue;
+                }
+                delete_pointer();
+                go_to_info_line_and_reset();
+                print!("z");
+                show_cursor();
+
+                let mut command: Vec<char> = vec!['z'];
+                screen.flush()?;
+
+                let mut current_pos = 3;
+                'zoxide: loop {
+                    if let Event::Key(KeyEvent {
+                        code,
+                        modifiers,
+                        kind: KeyEventKind::Press,
+                        ..
+                    }) = event::read()?
+                    {
+                        match (code, modifiers) {
+                            (KeyCode::Esc, KeyModifiers::NONE) => {
+                                state.escape();
+                                break 'zoxide;
                             }
 
-                            //Open file or change directory
-                            KeyCode::Char('l') | KeyCode::Enter | KeyCode::Right => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
+                            (KeyCode::Left, KeyModifiers::NONE) => {
+                                if current_pos == INITIAL_POS_Z {
                                     continue;
-                                }
-                                let mut dest: Option<PathBuf> = None;
-                                if let Ok(item) = state.get_item() {
-                                    let mut err: Option<FxError> = None;
-                                    match item.file_type {
-                                        FileType::File => {
-                                            execute!(screen, EnterAlternateScreen)?;
-                                            if let Err(e) = state.open_file(item) {
-                                                err = Some(e);
-                                            }
-                                            execute!(screen, EnterAlternateScreen)?;
-                                            hide_cursor();
-                                            state.reload(state.layout.y)?;
-                                            if let Some(e) = err {
-                                                print_warning(e, state.layout.y);
-                                            }
-                                            continue;
-                                        }
-                                        FileType::Symlink => match &item.symlink_dir_path {
-                                            Some(true_path) => {
-                                                if true_path.exists() {
-                                                    dest = Some(true_path.to_path_buf());
-                                                } else {
-                                                    print_warning("Broken link.", state.layout.y);
-                                                    continue;
-                                                }
-                                            }
-                                            None => {
-                                                execute!(screen, EnterAlternateScreen)?;
-                                                if let Err(e) = state.open_file(item) {
-                                                    err = Some(e);
-                                                }
-                                                execute!(screen, EnterAlternateScreen)?;
-                                                hide_cursor();
-                                                state.reload(state.layout.y)?;
-                                                if let Some(e) = err {
-                                                    print_warning(e, state.layout.y);
-                                                }
-                                                continue;
-                                            }
-                                        },
-                                        FileType::Directory => {
-                                            if item.file_path.exists() {
-                                                dest = Some(item.file_path.clone());
-                                            } else {
-                                                print_warning("Invalid directory.", state.layout.y);
-                                                continue;
-                                            }
-                                        }
-                                    }
-                                }
-                                if let Some(dest) = dest {
-                                    if let Err(e) = state.chdir(&dest, Move::Down) {
-                                        print_warning(e, state.layout.y);
-                                    }
-                                }
+                                };
+                                current_pos -= 1;
+                                move_left(1);
                             }
 
-                            //Open a file in a new window
-                            //This works only if i) [exec] is set in config file
-                            //and ii) the extension of the item matches the key.
-                            //If not, warning message appears.
-                            KeyCode::Char('o') => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
+                            (KeyCode::Right, KeyModifiers::NONE) => {
+                                if current_pos as usize == command.len() + INITIAL_POS_Z as usize {
                                     continue;
-                                }
-                                if let Ok(item) = state.get_item() {
-                                    match item.file_type {
-                                        FileType::File => {
-                                            if let Err(e) = state.open_file_in_new_window() {
-                                                hide_cursor();
-                                                state.redraw(state.layout.y);
-                                                print_warning(e, state.layout.y);
-                                                continue;
-                                            }
-                                            hide_cursor();
-                                            state.redraw(state.layout.y);
-                                            continue;
-                                        }
-                                        _ => {
-                                            continue;
-                                        }
-                                    }
-                                }
+                                };
+                                current_pos += 1;
+                                move_right(1);
                             }
 
-                            //Go to the parent directory if exists
-                            KeyCode::Char('h') | KeyCode::Left => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
-                                    continue;
-                                }
-                                let pre = state.current_dir.clone();
+                            (KeyCode::Backspace, KeyModifiers::NONE)
+                            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
+                                if current_pos == INITIAL_POS_Z + 1 {
+                                    state.escape();
+                                    break 'zoxide;
+                                };
+                                command.remove((current_pos - INITIAL_POS_Z - 1).into());
+                                current_pos -= 1;
 
-                                match pre.parent() {
-                                    Some(parent_p) => {
-                                        if let Err(e) = state.chdir(parent_p, Move::Up) {
-                                            print_warning(e, state.layout.y);
-                                        }
-                                    }
-                                    None => {
-                                        continue;
-                                    }
-                                }
+                                clear_current_line();
+                                to_info_line();
+                                print!("{}", &command.iter().collect::<String>(),);
+                                move_to(current_pos, 2);
                             }
 
-                            // jump forward
-                            KeyCode::Tab => {
-                                if let Some(path_to_jump_to) = state.jumplist.get_forward() {
-                                    if path_to_jump_to.exists() {
-                                        state.chdir(&path_to_jump_to, Move::List)?;
-                                    } else {
+                            (KeyCode::Enter, KeyModifiers::NONE) => {
+                                hide_cursor();
+                                let command = command.iter().collect::<String>();
+                                let commands = command.split_whitespace().collect::<Vec<&str>>();
+                                if commands.len() == 1 {
+                                    //go to the home directory
+                                    let home_dir = dirs::home_dir().ok_or_else(|| {
+                                        FxError::Dirs("Cannot read home dir.".to_string())
+                                    })?;
+                                    if let Err(e) = state.chdir(&home_dir, Move::Jump) {
+                                        print_warning(e, state.layout.y);
+                                    }
+                                    break 'zoxide;
+                                } else if let Ok(output) = std::process::Command::new("zoxide")
+                                    .arg("query")
+                                    .args(&commands[1..])
+                                    .output()
+                                {
+                                    let output = output.stdout;
+                                    if output.is_empty() {
                                         print_warning(
-                                            "Directory forward not found: Removed from jumplist.",
+                                            "Keyword does not match the database.",
                                             state.layout.y,
                                         );
-                                        state.jumplist.remove_forward();
-                                    }
-                                    state.jumplist.pos_forward();
-                                }
-                            }
-
-                            //Unpack archive file. Fails if it is not any of supported types
-                            KeyCode::Char('e') => {
-                                //In visual mode, this is disabled.
-                                //TODO! Enable this in visual mode.
-                                if state.v_start.is_some() {
-                                    continue;
-                                }
-                                print_info("Unpacking...", state.layout.y);
-                                screen.flush()?;
-                                let start = Instant::now();
-                                if let Err(e) = state.unpack() {
-                                    state.reload(state.layout.y)?;
-                                    print_warning(e, state.layout.y);
-                                    continue;
-                                }
-                                let duration = duration_to_string(start.elapsed());
-                                state.reload(state.layout.y)?;
-                                print_info(format!("Unpacked. [{}]", duration), state.layout.y);
-                            }
-
-                            //Jumps to the directory that matches the keyword (zoxide required)
-                            KeyCode::Char('z') => {
-                                //If zoxide is not found, show error message.
-                                if !state.has_zoxide {
-                                    print_warning("zoxide not found.", state.layout.y);
-                                    continue;
-                                }
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
-                                    continue;
-                                }
-                                delete_pointer();
-                                go_to_info_line_and_reset();
-                                print!("z");
-                                show_cursor();
-
-                                let mut command: Vec<char> = vec!['z'];
-                                screen.flush()?;
-
-                                let mut current_pos = 3;
-                                'zoxide: loop {
-                                    if let Event::Key(KeyEvent {
-                                        code,
-                                        modifiers,
-                                        kind: KeyEventKind::Press,
-                                        ..
-                                    }) = event::read()?
-                                    {
-                                        match (code, modifiers) {
-                                            (KeyCode::Esc, KeyModifiers::NONE) => {
-                                                state.escape();
+                                        break 'zoxide;
+                                    } else {
+                                        let target_dir = std::str::from_utf8(&output);
+                                        match target_dir {
+                                            Err(e) => {
+                                                print_warning(e, state.layout.y);
                                                 break 'zoxide;
                                             }
-
-                                            (KeyCode::Left, KeyModifiers::NONE) => {
-                                                if current_pos == INITIAL_POS_Z {
-                                                    continue;
-                                                };
-                                                current_pos -= 1;
-                                                move_left(1);
-                                            }
-
-                                            (KeyCode::Right, KeyModifiers::NONE) => {
-                                                if current_pos as usize
-                                                    == command.len() + INITIAL_POS_Z as usize
-                                                {
-                                                    continue;
-                                                };
-                                                current_pos += 1;
-                                                move_right(1);
-                                            }
-
-                                            (KeyCode::Backspace, KeyModifiers::NONE)
-                                            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
-                                                if current_pos == INITIAL_POS_Z + 1 {
-                                                    state.escape();
-                                                    break 'zoxide;
-                                                };
-                                                command.remove(
-                                                    (current_pos - INITIAL_POS_Z - 1).into(),
-                                                );
-                                                current_pos -= 1;
-
-                                                clear_current_line();
-                                                to_info_line();
-                                                print!("{}", &command.iter().collect::<String>(),);
-                                                move_to(current_pos, 2);
-                                            }
-
-                                            (KeyCode::Enter, KeyModifiers::NONE) => {
+                                            Ok(target_dir) => {
                                                 hide_cursor();
-                                                let command = command.iter().collect::<String>();
-                                                let commands = command
-                                                    .split_whitespace()
-                                                    .collect::<Vec<&str>>();
-                                                if commands.len() == 1 {
-                                                    //go to the home directory
-                                                    let home_dir =
-                                                        dirs::home_dir().ok_or_else(|| {
-                                                            FxError::Dirs(
-                                                                "Cannot read home dir.".to_string(),
-                                                            )
-                                                        })?;
-                                                    if let Err(e) =
-                                                        state.chdir(&home_dir, Move::Jump)
-                                                    {
-                                                        print_warning(e, state.layout.y);
-                                                    }
-                                                    break 'zoxide;
-                                                } else if let Ok(output) =
-                                                    std::process::Command::new("zoxide")
-                                                        .arg("query")
-                                                        .args(&commands[1..])
-                                                        .output()
+                                                state.layout.nums.reset();
+                                                let target_path = PathBuf::from(target_dir.trim());
+                                                if let Err(e) =
+                                                    state.chdir(&target_path, Move::Jump)
                                                 {
-                                                    let output = output.stdout;
-                                                    if output.is_empty() {
-                                                        print_warning(
-                                                            "Keyword does not match the database.",
-                                                            state.layout.y,
-                                                        );
-                                                        break 'zoxide;
-                                                    } else {
-                                                        let target_dir =
-                                                            std::str::from_utf8(&output);
-                                                        match target_dir {
-                                                            Err(e) => {
-                                                                print_warning(e, state.layout.y);
-                                                                break 'zoxide;
-                                                            }
-                                                            Ok(target_dir) => {
-                                                                hide_cursor();
-                                                                state.layout.nums.reset();
-                                                                let target_path = PathBuf::from(
-                                                                    target_dir.trim(),
-                                                                );
-                                                                if let Err(e) = state
-                                                                    .chdir(&target_path, Move::Jump)
-                                                                {
-                                                                    print_warning(
-                                                                        e,
-                                                                        state.layout.y,
-                                                                    );
-                                                                }
-                                                                break 'zoxide;
-                                                            }
-                                                        }
-                                                    }
-                                                } else {
-                                                    print_warning(
-                                                        "Failed to execute zoxide",
-                                                        state.layout.y,
-                                                    );
-                                                    break 'zoxide;
+                                                    print_warning(e, state.layout.y);
                                                 }
+                                                break 'zoxide;
                                             }
-
-                                            (KeyCode::Char(c), _) => {
-                                                command.insert(
-                                                    (current_pos - INITIAL_POS_Z).into(),
-                                                    c,
-                                                );
-                                                current_pos += 1;
-                                                clear_current_line();
-                                                to_info_line();
-                                                print!("{}", &command.iter().collect::<String>(),);
-                                                move_to(current_pos, 2);
-                                            }
-
-                                            _ => continue,
                                         }
-                                        screen.flush()?;
                                     }
-                                }
-                            }
-
-                            //insert mode
-                            KeyCode::Char('i') | KeyCode::Char('I') => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
-                                    continue;
-                                }
-                                let is_dir = code == KeyCode::Char('I');
-                                delete_pointer();
-                                go_to_info_line_and_reset();
-                                if is_dir {
-                                    print!("{}", PROMPT_INSERT_DIR);
                                 } else {
-                                    print!("{}", PROMPT_INSERT_FILE);
+                                    print_warning("Failed to execute zoxide", state.layout.y);
+                                    break 'zoxide;
                                 }
-                                show_cursor();
-                                screen.flush()?;
-
-                                let mut new_name: Vec<char> = Vec::new();
-
-                                // express position in terminal
-                                let (mut current_pos, _) = cursor_pos()?;
-                                // express position in Vec<Char>
-                                let mut current_char_pos = 0;
-                                'insert: loop {
-                                    if let Event::Key(KeyEvent {
-                                        code,
-                                        modifiers,
-                                        kind: KeyEventKind::Press,
-                                        ..
-                                    }) = event::read()?
-                                    {
-                                        match (code, modifiers) {
-                                            // <C-r> to put the item name(s) from register
-                                            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
-                                                if let Event::Key(KeyEvent {
-                                                    code,
-                                                    kind: KeyEventKind::Press,
-                                                    ..
-                                                }) = event::read()?
-                                                {
-                                                    if let Some(reg) =
-                                                        state.registers.check_reg(&code)
-                                                    {
-                                                        if !reg.is_empty() {
-                                                            let to_be_inserted = reg
-                                                                .iter()
-                                                                .map(|x| x.file_name.clone())
-                                                                .collect::<Vec<String>>()
-                                                                .join(" ");
-                                                            for c in to_be_inserted.chars() {
-                                                                if let Some(to_be_added) =
-                                                                    unicode_width::UnicodeWidthChar::width(c)
-                                                                {
-                                                                    if current_pos + to_be_added as u16
-                                                                        > state.layout.terminal_column
-                                                                    {
-                                                                        continue;
-                                                                    }
-                                                                    new_name.insert(current_char_pos, c);
-                                                                    current_char_pos += 1;
-                                                                    current_pos += to_be_added as u16;
-                                                                }
-                                                            }
-                                                            go_to_info_line_and_reset();
-                                                            if is_dir {
-                                                                print!(
-                                                                    "{}{}",
-                                                                    PROMPT_INSERT_DIR,
-                                                                    &new_name
-                                                                        .iter()
-                                                                        .collect::<String>(),
-                                                                );
-                                                            } else {
-                                                                print!(
-                                                                    "{}{}",
-                                                                    PROMPT_INSERT_FILE,
-                                                                    &new_name
-                                                                        .iter()
-                                                                        .collect::<String>(),
-                                                                );
-                                                            }
-                                                            move_to(current_pos + 1, 2);
-                                                            screen.flush()?;
-                                                            continue;
-                                                        } else {
-                                                            continue;
-                                                        }
-                                                    } else {
-                                                        continue;
-                                                    }
-                                                }
-                                            }
-
-                                            (KeyCode::Esc, KeyModifiers::NONE) => {
-                                                state.escape();
-                                                break 'insert;
-                                            }
-
-                                            (KeyCode::Left, KeyModifiers::NONE) => {
-                                                move_left_command_line(
-                                                    &mut new_name,
-                                                    &mut current_char_pos,
-                                                    &mut current_pos,
-                                                )
-                                            }
+                            }
 
-                                            (KeyCode::Right, KeyModifiers::NONE) => {
-                                                move_right_command_line(
-                                                    &mut new_name,
-                                                    &mut current_char_pos,
-                                                    &mut current_pos,
-                                                )
-                                            }
+                            (KeyCode::Char(c), _) => {
+                                command.insert((current_pos - INITIAL_POS_Z).into(), c);
+                                current_pos += 1;
+                                clear_current_line();
+                                to_info_line();
+                                print!("{}", &command.iter().collect::<String>(),);
+                                move_to(current_pos, 2);
+                            }
 
-                                            (KeyCode::Backspace, KeyModifiers::NONE)
-                                            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
-                                                if current_char_pos == 0 {
-                                                    continue;
-                                                };
-                                                let removed = new_name.remove(current_char_pos - 1);
-                                                if let Some(to_be_removed) =
-                                                    unicode_width::UnicodeWidthChar::width(removed)
-                                                {
-                                                    current_char_pos -= 1;
-                                                    current_pos -= to_be_removed as u16;
-
-                                                    go_to_info_line_and_reset();
-                                                    if is_dir {
-                                                        print!(
-                                                            "{}{}",
-                                                            PROMPT_INSERT_DIR,
-                                                            &new_name.iter().collect::<String>(),
-                                                        );
-                                                    } else {
-                                                        print!(
-                                                            "{}{}",
-                                                            PROMPT_INSERT_FILE,
-                                                            &new_name.iter().collect::<String>(),
-                                                        );
-                                                    }
-                                                    move_to(current_pos + 1, 2);
-                                                }
-                                            }
+                            _ => continue,
+                        }
+                        screen.flush()?;
+                    }
+                }
+            }
 
-                                            (KeyCode::Char(c), _) => {
+            //insert mode
+            Action::NewFile | Action::NewDir => {
+                //In visual mode, this is disabled.
+                if state.v_start.is_some() {
+                    continue;
+                }
+                let is_dir = action == Action::NewDir;
+                delete_pointer();
+                go_to_info_line_and_reset();
+                if is_dir {
+                    print!("{}", PROMPT_INSERT_DIR);
+                } else {
+                    print!("{}", PROMPT_INSERT_FILE);
+                }
+                show_cursor();
+                screen.flush()?;
+
+                let mut new_name: Vec<char> = Vec::new();
+
+                // express position in terminal
+                let (mut current_pos, _) = cursor_pos()?;
+                // express position in Vec<Char>
+                let mut current_char_pos = 0;
+                'insert: loop {
+                    if let Event::Key(KeyEvent {
+                        code,
+                        modifiers,
+                        kind: KeyEventKind::Press,
+                        ..
+                    }) = event::read()?
+                    {
+                        match (code, modifiers) {
+                            // <C-r> to put the item name(s) from register
+                            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                                if let Event::Key(KeyEvent {
+                                    code,
+                                    kind: KeyEventKind::Press,
+                                    ..
+                                }) = event::read()?
+                                {
+                                    if let Some(reg) = state.registers.check_reg(&code) {
+                                        if !reg.is_empty() {
+                                            let to_be_inserted = reg
+                                                .iter()
+                                                .map(|x| x.file_name.clone())
+                                                .collect::<Vec<String>>()
+                                                .join(" ");
+                                            for c in to_be_inserted.chars() {
                                                 if let Some(to_be_added) =
                                                     unicode_width::UnicodeWidthChar::width(c)
                                                 {
@@ -1109,1153 +1034,962 @@ fn _run(mut state: State, session_path: PathBuf) -> Result<(), FxError> {
                                                     new_name.insert(current_char_pos, c);
                                                     current_char_pos += 1;
                                                     current_pos += to_be_added as u16;
-
-                                                    go_to_info_line_and_reset();
-                                                    if is_dir {
-                                                        print!(
-                                                            "{}{}",
-                                                            PROMPT_INSERT_DIR,
-                                                            &new_name.iter().collect::<String>(),
-                                                        );
-                                                    } else {
-                                                        print!(
-                                                            "{}{}",
-                                                            PROMPT_INSERT_FILE,
-                                                            &new_name.iter().collect::<String>(),
-                                                        );
-                                                    }
-                                                    move_to(current_pos + 1, 2);
                                                 }
                                             }
-
-                                            (KeyCode::Enter, KeyModifiers::NONE) => {
-                                                hide_cursor();
-                                                //Set the command and argument(s).
-                                                let new_name: String = new_name.iter().collect();
-                                                if is_dir {
-                                                    if let Err(e) = std::fs::create_dir(
-                                                        &state.current_dir.join(new_name),
-                                                    ) {
-                                                        print_warning(e, state.layout.y);
-                                                        break 'insert;
-                                                    }
-                                                } else if let Err(e) = std::fs::File::options()
-                                                    .read(true)
-                                                    .write(true)
-                                                    .create_new(true)
-                                                    .open(&state.current_dir.join(new_name))
-                                                {
-                                                    print_warning(e, state.layout.y);
-                                                    break 'insert;
-                                                }
-                                                state.reload(state.layout.y)?;
-                                                break 'insert;
+                                            go_to_info_line_and_reset();
+                                            if is_dir {
+                                                print!(
+                                                    "{}{}",
+                                                    PROMPT_INSERT_DIR,
+                                                    &new_name.iter().collect::<String>(),
+                                                );
+                                            } else {
+                                                print!(
+                                                    "{}{}",
+                                                    PROMPT_INSERT_FILE,
+                                                    &new_name.iter().collect::<String>(),
+                                                );
                                             }
-
-                                            _ => continue,
+                                            move_to(current_pos + 1, 2);
+                                            screen.flush()?;
+                                            continue;
+                                        } else {
+                                            continue;
                                         }
-                                        screen.flush()?;
+                                    } else {
+                                        continue;
                                     }
                                 }
                             }
 
-                            //switch to linewise visual mode
-                            KeyCode::Char('V') => {
-                                //If in visual mode, return to normal mode.
-                                if state.v_start.is_some() {
-                                    state.reset_selection();
-                                    state.redraw(state.layout.y);
-                                    continue;
-                                }
-                                if len == 0 {
-                                    continue;
-                                }
-                                let item = state.get_item_mut()?;
-                                item.selected = true;
-                                state.redraw(state.layout.y);
-                                state.v_start = Some(state.layout.nums.index);
-                                continue;
+                            (KeyCode::Esc, KeyModifiers::NONE) => {
+                                state.escape();
+                                break 'insert;
                             }
 
-                            //Toggle sortkey
-                            KeyCode::Char('t') => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
+                            (KeyCode::Left, KeyModifiers::NONE) => move_left_command_line(
+                                &mut new_name,
+                                &mut current_char_pos,
+                                &mut current_pos,
+                            ),
+
+                            (KeyCode::Right, KeyModifiers::NONE) => move_right_command_line(
+                                &mut new_name,
+                                &mut current_char_pos,
+                                &mut current_pos,
+                            ),
+
+                            (KeyCode::Backspace, KeyModifiers::NONE)
+                            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
+                                if current_char_pos == 0 {
                                     continue;
-                                }
-                                match state.layout.sort_by {
-                                    SortKey::Name => {
-                                        state.layout.sort_by = SortKey::Time;
-                                    }
-                                    SortKey::Time => {
-                                        state.layout.sort_by = SortKey::Name;
+                                };
+                                let removed = new_name.remove(current_char_pos - 1);
+                                if let Some(to_be_removed) =
+                                    unicode_width::UnicodeWidthChar::width(removed)
+                                {
+                                    current_char_pos -= 1;
+                                    current_pos -= to_be_removed as u16;
+
+                                    go_to_info_line_and_reset();
+                                    if is_dir {
+                                        print!(
+                                            "{}{}",
+                                            PROMPT_INSERT_DIR,
+                                            &new_name.iter().collect::<String>(),
+                                        );
+                                    } else {
+                                        print!(
+                                            "{}{}",
+                                            PROMPT_INSERT_FILE,
+                                            &new_name.iter().collect::<String>(),
+                                        );
                                     }
+                                    move_to(current_pos + 1, 2);
                                 }
-                                state.layout.nums.reset();
-                                state.reorder(BEGINNING_ROW);
                             }
 
-                            //Show or hide hidden items
-                            KeyCode::Backspace => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
-                                    continue;
-                                }
-                                match state.layout.show_hidden {
-                                    true => {
-                                        state.list.retain(|x| !x.is_hidden);
-                                        state.layout.show_hidden = false;
+                            (KeyCode::Char(c), _) => {
+                                if let Some(to_be_added) = unicode_width::UnicodeWidthChar::width(c)
+                                {
+                                    if current_pos + to_be_added as u16
+                                        > state.layout.terminal_column
+                                    {
+                                        continue;
                                     }
-                                    false => {
-                                        state.layout.show_hidden = true;
-                                        state.update_list()?;
+                                    new_name.insert(current_char_pos, c);
+                                    current_char_pos += 1;
+                                    current_pos += to_be_added as u16;
+
+                                    go_to_info_line_and_reset();
+                                    if is_dir {
+                                        print!(
+                                            "{}{}",
+                                            PROMPT_INSERT_DIR,
+                                            &new_name.iter().collect::<String>(),
+                                        );
+                                    } else {
+                                        print!(
+                                            "{}{}",
+                                            PROMPT_INSERT_FILE,
+                                            &new_name.iter().collect::<String>(),
+                                        );
                                     }
+                                    move_to(current_pos + 1, 2);
                                 }
-                                state.layout.nums.reset();
-                                state.redraw(BEGINNING_ROW);
                             }
 
-                            //Toggle whether to show preview. Also hide registers.
-                            KeyCode::Char('v') => {
-                                if state.layout.is_preview() || state.layout.is_reg() {
-                                    state.layout.reset_side();
-                                } else {
-                                    state.layout.show_preview();
+                            (KeyCode::Enter, KeyModifiers::NONE) => {
+                                hide_cursor();
+                                //Set the command and argument(s).
+                                let new_name: String = new_name.iter().collect();
+                                if let Err(e) = state.create_new_item(&new_name, is_dir) {
+                                    print_warning(e, state.layout.y);
                                 }
-                                let (new_column, new_row) = state.layout.update_column_and_row()?;
-                                state.refresh(new_column, new_row, state.layout.y)?;
+                                break 'insert;
                             }
 
-                            //Toggle vertical <-> horizontal split
-                            KeyCode::Char('s') => match state.layout.split {
-                                Split::Vertical => {
-                                    state.layout.split = Split::Horizontal;
-                                    if state.layout.is_preview() || state.layout.is_reg() {
-                                        let (new_column, mut new_row) = terminal_size()?;
-                                        new_row /= 2;
-                                        state.refresh(new_column, new_row, state.layout.y)?;
-                                    }
-                                }
-                                Split::Horizontal => {
-                                    state.layout.split = Split::Vertical;
-                                    if state.layout.is_preview() || state.layout.is_reg() {
-                                        let (mut new_column, new_row) = terminal_size()?;
-                                        new_column /= 2;
-                                        state.refresh(new_column, new_row, state.layout.y)?;
-                                    }
-                                }
-                            },
+                            _ => continue,
+                        }
+                        screen.flush()?;
+                    }
+                }
+            }
 
-                            //delete
-                            KeyCode::Char('d') => {
-                                //If read-only, deleting is disabled.
-                                if state.is_ro {
-                                    print_warning(
-                                        "Cannot delete item in this directory.",
-                                        state.layout.y,
-                                    );
-                                    continue;
-                                }
-                                if let Some(_start_pos) = state.v_start {
-                                    //visual mode
-                                    if let Err(e) = state.delete_in_visual(None, false, &mut screen)
-                                    {
-                                        state.reset_selection();
-                                        state.redraw(state.layout.y);
-                                        print_warning(e, state.layout.y);
-                                        continue;
+            //Cycle the sort key (name -> time -> size -> extension)
+            Action::Sort => {
+                //In visual mode, this is disabled.
+                if state.v_start.is_some() {
+                    continue;
+                }
+                let scripts: Vec<&String> = state.layout.sort_scripts.keys().collect();
+                state.layout.sort_by = state.layout.sort_by.next(&scripts);
+                state.layout.nums.reset();
+                state.reorder(BEGINNING_ROW);
+            }
+
+            //Toggle whether to show preview. Also hide registers.
+            Action::TogglePreview => {
+                if state.layout.is_preview() || state.layout.is_reg() {
+                    state.layout.reset_side();
+                } else {
+                    state.layout.show_preview();
+                }
+                let (new_column, new_row) = state.layout.update_column_and_row()?;
+                state.refresh(new_column, new_row, state.layout.y)?;
+            }
+
+            //Toggle vertical <-> horizontal split
+            Action::ToggleSplit => match state.layout.split {
+                Split::Vertical => {
+                    state.layout.split = Split::Horizontal;
+                    if state.layout.is_preview() || state.layout.is_reg() {
+                        let (column, row) = terminal_size()?;
+                        let (new_column, new_row) = state.layout.list_area(column, row);
+                        state.refresh(new_column, new_row, state.layout.y)?;
+                    }
+                }
+                Split::Horizontal => {
+                    state.layout.split = Split::Vertical;
+                    if state.layout.is_preview() || state.layout.is_reg() {
+                        let (column, row) = terminal_size()?;
+                        let (new_column, new_row) = state.layout.list_area(column, row);
+                        state.refresh(new_column, new_row, state.layout.y)?;
+                    }
+                }
+            },
+
+            //delete
+            Action::Delete => {
+                //If read-only, deleting is disabled.
+                if state.is_ro {
+                    print_warning("Cannot delete item in this directory.", state.layout.y);
+                    continue;
+                }
+                if let Some(_start_pos) = state.v_start {
+                    //visual mode
+                    //If some of the selected items are out of the screen,
+                    //show the scope and ask before deleting.
+                    let (total, hidden) = state.selection_scope();
+                    if hidden > 0 {
+                        print_warning(
+                                            format!(
+                                                "Acting on {} selected items ({} out of the screen). Delete? (if yes: y)",
+                                                total, hidden
+                                            ),
+                                            state.layout.y,
+                                        );
+                        screen.flush()?;
+                        let confirmed = matches!(
+                            event::read()?,
+                            Event::Key(KeyEvent {
+                                code: KeyCode::Char('y') | KeyCode::Char('Y'),
+                                kind: KeyEventKind::Press,
+                                ..
+                            })
+                        );
+                        if !confirmed {
+                            state.escape();
+                            continue;
+                        }
+                    }
+                    if let Err(e) = state.delete_in_visual(None, false) {
+                        state.reset_selection();
+                        state.redraw(state.layout.y);
+                        print_warning(e, state.layout.y);
+                        continue;
+                    }
+                } else {
+                    //normal mode
+                    if len == 0 {
+                        continue;
+                    }
+                    if let Err(e) = state.delete(None, false) {
+                        print_warning(e, state.layout.y);
+                        continue;
+                    }
+                }
+            }
+
+            //yank
+            Action::Yank => {
+                if let Some(_start_pos) = state.v_start {
+                    //visual mode
+                    let items: Vec<ItemBuffer> = state
+                        .list
+                        .iter()
+                        .filter(|item| item.selected)
+                        .map(ItemBuffer::new)
+                        .collect();
+                    let item_len = state.registers.yank_item(&items, None, false);
+                    state.reset_selection();
+                    state.list_up();
+                    let mut yank_message: String = item_len.to_string();
+                    yank_message.push_str(" items yanked");
+                    print_info(yank_message, state.layout.y);
+                } else {
+                    //normal mode
+                    if let Ok(item) = state.get_item() {
+                        state
+                            .registers
+                            .yank_item(&[ItemBuffer::new(item)], None, false);
+                        print_info("1 item yanked.", state.layout.y);
+                    }
+                }
+            }
+
+            //cut: the next put moves the items
+            Action::Cut => {
+                let items: Vec<ItemBuffer> = if state.v_start.is_some() {
+                    state
+                        .list
+                        .iter()
+                        .filter(|item| item.selected)
+                        .map(ItemBuffer::new)
+                        .collect()
+                } else if let Ok(item) = state.get_item() {
+                    vec![ItemBuffer::new(item)]
+                } else {
+                    continue;
+                };
+                if state.v_start.is_some() {
+                    state.reset_selection();
+                    state.list_up();
+                }
+                state.registers.cut = items;
+                let message = match state.registers.cut.len() {
+                    1 => "1 item cut.".to_owned(),
+                    count => format!("{} items cut.", count),
+                };
+                print_info(message, state.layout.y);
+            }
+
+            //put
+            Action::Put => {
+                //In visual mode, this is disabled.
+                if state.v_start.is_some() {
+                    continue;
+                }
+                let result = if state.registers.cut.is_empty() {
+                    state.put(state.registers.unnamed.clone())
+                } else {
+                    state.move_cut()
+                };
+                if let Err(e) = result {
+                    print_warning(e, state.layout.y);
+                }
+            }
+
+            //rename
+            Action::Rename => {
+                //In visual mode, you can rename multiple items in default editor.
+                if state.v_start.is_some() {
+                    let items: Vec<ItemBuffer> = state
+                        .list
+                        .iter()
+                        .filter(|item| item.selected)
+                        .map(ItemBuffer::new)
+                        .collect();
+                    reenter_alternate_screen();
+                    let result = state.rename_multiple_items(&items);
+                    reenter_alternate_screen();
+                    hide_cursor();
+                    state.reset_selection();
+                    state.reload(state.layout.y)?;
+                    match result {
+                        Err(e) => {
+                            print_warning(e, state.layout.y);
+                        }
+                        Ok(result_len) => {
+                            let message = {
+                                match result_len {
+                                    0 => "No item renamed.".to_owned(),
+                                    1 => "1 item renamed.".to_owned(),
+                                    count => {
+                                        format!("{} items renamed.", count)
                                     }
-                                } else {
-                                    //normal mode
-                                    if len == 0 {
-                                        continue;
-                                    } else {
-                                        go_to_info_line_and_reset();
-                                        print!("d");
-                                        show_cursor();
-                                        screen.flush()?;
-
-                                        if let Event::Key(KeyEvent {
-                                            code,
-                                            kind: KeyEventKind::Press,
-                                            ..
-                                        }) = event::read()?
-                                        {
-                                            match code {
-                                                KeyCode::Char('d') => {
-                                                    if let Err(e) =
-                                                        state.delete(None, false, &mut screen)
+                                }
+                            };
+                            print_info(message, state.layout.y);
+                        }
+                    }
+                    continue;
+                }
+                if len == 0 {
+                    continue;
+                }
+                let item = state.get_item()?.clone();
+                show_cursor();
+                let mut rename = item.file_name.chars().collect::<Vec<char>>();
+                to_info_line();
+                clear_current_line();
+                print!("{}{}", PROMPT_RENAME, &rename.iter().collect::<String>(),);
+                screen.flush()?;
+
+                let (mut current_pos, _) = cursor_pos()?;
+                let mut current_char_pos = rename.len();
+                loop {
+                    if let Event::Key(KeyEvent {
+                        code,
+                        modifiers,
+                        kind: KeyEventKind::Press,
+                        ..
+                    }) = event::read()?
+                    {
+                        match (code, modifiers) {
+                            // <C-r> to put the item name(s) from register
+                            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                                if let Event::Key(KeyEvent {
+                                    code,
+                                    kind: KeyEventKind::Press,
+                                    ..
+                                }) = event::read()?
+                                {
+                                    if let Some(reg) = state.registers.check_reg(&code) {
+                                        if !reg.is_empty() {
+                                            let to_be_inserted = reg
+                                                .iter()
+                                                .map(|x| x.file_name.clone())
+                                                .collect::<Vec<String>>()
+                                                .join(" ");
+                                            for c in to_be_inserted.chars() {
+                                                if let Some(to_be_added) =
+                                                    unicode_width::UnicodeWidthChar::width(c)
+                                                {
+                                                    if current_pos + to_be_added as u16
+                                                        > state.layout.terminal_column
                                                     {
-                                                        print_warning(e, state.layout.y);
                                                         continue;
                                                     }
-                                                }
-                                                _ => {
-                                                    state.escape();
+                                                    rename.insert(current_char_pos, c);
+                                                    current_char_pos += 1;
+                                                    current_pos += to_be_added as u16;
                                                 }
                                             }
+                                            go_to_info_line_and_reset();
+                                            print!(
+                                                "{}{}",
+                                                PROMPT_RENAME,
+                                                &rename.iter().collect::<String>()
+                                            );
+                                            move_to(current_pos + 1, 2);
+                                            screen.flush()?;
+                                            continue;
+                                        } else {
+                                            continue;
                                         }
+                                    } else {
+                                        continue;
                                     }
                                 }
                             }
 
-                            //yank
-                            KeyCode::Char('y') => {
-                                if let Some(_start_pos) = state.v_start {
-                                    //visual mode
-                                    let items: Vec<ItemBuffer> = state
-                                        .list
-                                        .iter()
-                                        .filter(|item| item.selected)
-                                        .map(ItemBuffer::new)
-                                        .collect();
-                                    let item_len = state.registers.yank_item(&items, None, false);
-                                    state.reset_selection();
-                                    state.list_up();
-                                    let mut yank_message: String = item_len.to_string();
-                                    yank_message.push_str(" items yanked");
-                                    print_info(yank_message, state.layout.y);
-                                } else {
-                                    //normal mode
-                                    if len == 0 {
-                                        continue;
-                                    }
-                                    go_to_info_line_and_reset();
-                                    print!("y");
-                                    show_cursor();
-                                    screen.flush()?;
+                            (KeyCode::Esc, KeyModifiers::NONE) => {
+                                state.escape();
+                                break;
+                            }
 
-                                    if let Event::Key(KeyEvent { code, .. }) = event::read()? {
-                                        match code {
-                                            KeyCode::Char('y') => {
-                                                if let Ok(item) = state.get_item() {
-                                                    state.registers.yank_item(
-                                                        &[ItemBuffer::new(item)],
-                                                        None,
-                                                        false,
-                                                    );
-                                                    state.escape();
-                                                    print_info("1 item yanked.", state.layout.y);
-                                                }
-                                            }
+                            (KeyCode::Left, KeyModifiers::NONE) => move_left_command_line(
+                                &mut rename,
+                                &mut current_char_pos,
+                                &mut current_pos,
+                            ),
+
+                            (KeyCode::Right, KeyModifiers::NONE) => move_right_command_line(
+                                &mut rename,
+                                &mut current_char_pos,
+                                &mut current_pos,
+                            ),
+
+                            (KeyCode::Backspace, KeyModifiers::NONE)
+                            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
+                                if current_char_pos == 0 {
+                                    continue;
+                                };
+                                let removed = rename.remove(current_char_pos - 1);
+                                if let Some(to_be_removed) =
+                                    unicode_width::UnicodeWidthChar::width(removed)
+                                {
+                                    current_char_pos -= 1;
+                                    current_pos -= to_be_removed as u16;
 
-                                            _ => {
-                                                state.escape();
-                                            }
-                                        }
-                                    }
+                                    go_to_info_line_and_reset();
+                                    print!(
+                                        "{}{}",
+                                        PROMPT_RENAME,
+                                        &rename.iter().collect::<String>(),
+                                    );
+                                    move_to(current_pos + 1, 2);
                                 }
                             }
 
-                            //put
-                            KeyCode::Char('p') => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
-                                    continue;
-                                }
-                                if let Err(e) =
-                                    state.put(state.registers.unnamed.clone(), &mut screen)
+                            (KeyCode::Char(c), _) => {
+                                if let Some(to_be_added) = unicode_width::UnicodeWidthChar::width(c)
                                 {
-                                    print_warning(e, state.layout.y);
+                                    rename.insert(current_char_pos, c);
+                                    current_char_pos += 1;
+                                    current_pos += to_be_added as u16;
+
+                                    go_to_info_line_and_reset();
+                                    print!(
+                                        "{}{}",
+                                        PROMPT_RENAME,
+                                        &rename.iter().collect::<String>(),
+                                    );
+                                    move_to(current_pos + 1, 2);
                                 }
                             }
 
-                            //rename
-                            KeyCode::Char('c') => {
-                                //In visual mode, you can rename multiple items in default editor.
-                                if state.v_start.is_some() {
-                                    let items: Vec<ItemBuffer> = state
-                                        .list
-                                        .iter()
-                                        .filter(|item| item.selected)
-                                        .map(ItemBuffer::new)
-                                        .collect();
-                                    execute!(screen, EnterAlternateScreen)?;
-                                    let result = state.rename_multiple_items(&items);
-                                    execute!(screen, EnterAlternateScreen)?;
+                            (KeyCode::Enter, KeyModifiers::NONE) => {
+                                let rename = rename.iter().collect::<String>();
+                                let mut to = state.current_dir.clone();
+                                to.push(rename);
+                                if let Err(e) = std::fs::rename(&item.file_path, &to) {
                                     hide_cursor();
-                                    state.reset_selection();
-                                    state.reload(state.layout.y)?;
-                                    match result {
-                                        Err(e) => {
-                                            print_warning(e, state.layout.y);
-                                        }
-                                        Ok(result_len) => {
-                                            let message = {
-                                                match result_len {
-                                                    0 => "No item renamed.".to_owned(),
-                                                    1 => "1 item renamed.".to_owned(),
-                                                    count => {
-                                                        format!("{} items renamed.", count)
-                                                    }
-                                                }
-                                            };
-                                            print_info(message, state.layout.y);
-                                        }
-                                    }
-                                    continue;
-                                }
-                                if len == 0 {
-                                    continue;
+                                    print_warning(e, state.layout.y);
+                                    break;
                                 }
-                                let item = state.get_item()?.clone();
-                                show_cursor();
-                                let mut rename = item.file_name.chars().collect::<Vec<char>>();
-                                to_info_line();
-                                clear_current_line();
-                                print!("{}{}", PROMPT_RENAME, &rename.iter().collect::<String>(),);
-                                screen.flush()?;
-
-                                let (mut current_pos, _) = cursor_pos()?;
-                                let mut current_char_pos = rename.len();
-                                loop {
-                                    if let Event::Key(KeyEvent {
-                                        code,
-                                        modifiers,
-                                        kind: KeyEventKind::Press,
-                                        ..
-                                    }) = event::read()?
-                                    {
-                                        match (code, modifiers) {
-                                            // <C-r> to put the item name(s) from register
-                                            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
-                                                if let Event::Key(KeyEvent {
-                                                    code,
-                                                    kind: KeyEventKind::Press,
-                                                    ..
-                                                }) = event::read()?
-                                                {
-                                                    if let Some(reg) =
-                                                        state.registers.check_reg(&code)
-                                                    {
-                                                        if !reg.is_empty() {
-                                                            let to_be_inserted = reg
-                                                                .iter()
-                                                                .map(|x| x.file_name.clone())
-                                                                .collect::<Vec<String>>()
-                                                                .join(" ");
-                                                            for c in to_be_inserted.chars() {
-                                                                if let Some(to_be_added) =
-                                                                    unicode_width::UnicodeWidthChar::width(c)
-                                                                {
-                                                                    if current_pos + to_be_added as u16
-                                                                        > state.layout.terminal_column
-                                                                    {
-                                                                        continue;
-                                                                    }
-                                                                    rename.insert(current_char_pos, c);
-                                                                    current_char_pos += 1;
-                                                                    current_pos += to_be_added as u16;
-                                                                }
-                                                            }
-                                                            go_to_info_line_and_reset();
-                                                            print!(
-                                                                "{}{}",
-                                                                PROMPT_RENAME,
-                                                                &rename.iter().collect::<String>()
-                                                            );
-                                                            move_to(current_pos + 1, 2);
-                                                            screen.flush()?;
-                                                            continue;
-                                                        } else {
-                                                            continue;
-                                                        }
-                                                    } else {
-                                                        continue;
-                                                    }
-                                                }
-                                            }
 
-                                            (KeyCode::Esc, KeyModifiers::NONE) => {
-                                                state.escape();
-                                                break;
-                                            }
+                                state
+                                    .operations
+                                    .push(Renamed(vec![(item.file_path.clone(), to)]));
 
-                                            (KeyCode::Left, KeyModifiers::NONE) => {
-                                                move_left_command_line(
-                                                    &mut rename,
-                                                    &mut current_char_pos,
-                                                    &mut current_pos,
-                                                )
-                                            }
+                                hide_cursor();
+                                state.reload(state.layout.y)?;
+                                break;
+                            }
 
-                                            (KeyCode::Right, KeyModifiers::NONE) => {
-                                                move_right_command_line(
-                                                    &mut rename,
-                                                    &mut current_char_pos,
-                                                    &mut current_pos,
-                                                )
-                                            }
+                            _ => continue,
+                        }
+                        screen.flush()?;
+                    }
+                }
+            }
 
-                                            (KeyCode::Backspace, KeyModifiers::NONE)
-                                            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
-                                                if current_char_pos == 0 {
-                                                    continue;
-                                                };
-                                                let removed = rename.remove(current_char_pos - 1);
-                                                if let Some(to_be_removed) =
-                                                    unicode_width::UnicodeWidthChar::width(removed)
-                                                {
-                                                    current_char_pos -= 1;
-                                                    current_pos -= to_be_removed as u16;
-
-                                                    go_to_info_line_and_reset();
-                                                    print!(
-                                                        "{}{}",
-                                                        PROMPT_RENAME,
-                                                        &rename.iter().collect::<String>(),
-                                                    );
-                                                    move_to(current_pos + 1, 2);
-                                                }
-                                            }
+            //Search mode
+            Action::Search => {
+                //In visual mode, this is disabled.
+                //TODO! Enable this in visual mode.
+                if state.v_start.is_some() {
+                    continue;
+                }
+                if len == 0 {
+                    continue;
+                }
+                delete_pointer();
+                show_cursor();
+                go_to_info_line_and_reset();
+                print!("{}", PROMPT_SEARCH);
+                screen.flush()?;
+
+                let original_nums = state.layout.nums;
+                let original_y = state.layout.y;
+                let mut keyword: Vec<char> = Vec::new();
+
+                // express position in terminal
+                let mut current_pos = INITIAL_POS_COMMAND_LINE;
+                // express position in Vec<Char>
+                let mut current_char_pos = 0;
+                loop {
+                    if let Event::Key(KeyEvent {
+                        code,
+                        modifiers,
+                        kind: KeyEventKind::Press,
+                        ..
+                    }) = event::read()?
+                    {
+                        match (code, modifiers) {
+                            (KeyCode::Esc, KeyModifiers::NONE) => {
+                                hide_cursor();
+                                state.redraw(state.layout.y);
+                                break;
+                            }
 
-                                            (KeyCode::Char(c), _) => {
-                                                if let Some(to_be_added) =
-                                                    unicode_width::UnicodeWidthChar::width(c)
-                                                {
-                                                    rename.insert(current_char_pos, c);
-                                                    current_char_pos += 1;
-                                                    current_pos += to_be_added as u16;
+                            (KeyCode::Left, KeyModifiers::NONE) => {
+                                move_left_command_line(
+                                    &mut keyword,
+                                    &mut current_char_pos,
+                                    &mut current_pos,
+                                );
+                            }
 
-                                                    go_to_info_line_and_reset();
-                                                    print!(
-                                                        "{}{}",
-                                                        PROMPT_RENAME,
-                                                        &rename.iter().collect::<String>(),
-                                                    );
-                                                    move_to(current_pos + 1, 2);
-                                                }
-                                            }
+                            (KeyCode::Right, KeyModifiers::NONE) => {
+                                move_right_command_line(
+                                    &mut keyword,
+                                    &mut current_char_pos,
+                                    &mut current_pos,
+                                );
+                            }
 
-                                            (KeyCode::Enter, KeyModifiers::NONE) => {
-                                                let rename = rename.iter().collect::<String>();
-                                                let mut to = state.current_dir.clone();
-                                                to.push(rename);
-                                                if let Err(e) =
-                                                    std::fs::rename(&item.file_path, &to)
-                                                {
-                                                    hide_cursor();
-                                                    print_warning(e, state.layout.y);
-                                                    break;
-                                                }
+                            (KeyCode::Backspace, KeyModifiers::NONE)
+                            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
+                                if current_char_pos == 0 {
+                                    continue;
+                                };
+                                let removed = keyword.remove(current_char_pos - 1);
+                                if let Some(to_be_removed) =
+                                    unicode_width::UnicodeWidthChar::width(removed)
+                                {
+                                    current_char_pos -= 1;
+                                    current_pos -= to_be_removed as u16;
 
-                                                state.operations.branch();
-                                                state.operations.push(OpKind::Rename(vec![(
-                                                    item.file_path.clone(),
-                                                    to,
-                                                )]));
+                                    let key = &keyword.iter().collect::<String>();
 
-                                                hide_cursor();
-                                                state.reload(state.layout.y)?;
-                                                break;
-                                            }
+                                    let target =
+                                        state.list.iter().position(|x| x.file_name.contains(key));
 
-                                            _ => continue,
+                                    match target {
+                                        Some(i) => {
+                                            state.layout.nums.skip = i as u16;
+                                            state.layout.nums.index = i;
+                                            state.highlight_matches(key);
+                                            state.redraw(BEGINNING_ROW);
+                                        }
+                                        None => {
+                                            state.highlight_matches(key);
+                                            state.layout.nums = original_nums;
+                                            state.layout.y = original_y;
+                                            state.redraw(state.layout.y);
                                         }
-                                        screen.flush()?;
                                     }
+                                    go_to_info_line_and_reset();
+                                    print!("{}{}", PROMPT_SEARCH, key);
+                                    move_to(current_pos, 2);
                                 }
                             }
 
-                            //Search mode
-                            KeyCode::Char('/') => {
-                                //In visual mode, this is disabled.
-                                //TODO! Enable this in visual mode.
-                                if state.v_start.is_some() {
-                                    continue;
-                                }
-                                if len == 0 {
-                                    continue;
-                                }
-                                delete_pointer();
-                                show_cursor();
-                                go_to_info_line_and_reset();
-                                print!("{}", PROMPT_SEARCH);
-                                screen.flush()?;
-
-                                let original_nums = state.layout.nums;
-                                let original_y = state.layout.y;
-                                let mut keyword: Vec<char> = Vec::new();
-
-                                // express position in terminal
-                                let mut current_pos = INITIAL_POS_COMMAND_LINE;
-                                // express position in Vec<Char>
-                                let mut current_char_pos = 0;
-                                loop {
-                                    if let Event::Key(KeyEvent {
-                                        code,
-                                        modifiers,
-                                        kind: KeyEventKind::Press,
-                                        ..
-                                    }) = event::read()?
+                            (KeyCode::Char(c), _) => {
+                                if let Some(to_be_added) = unicode_width::UnicodeWidthChar::width(c)
+                                {
+                                    if current_pos + to_be_added as u16
+                                        > state.layout.terminal_column
                                     {
-                                        match (code, modifiers) {
-                                            (KeyCode::Esc, KeyModifiers::NONE) => {
-                                                hide_cursor();
-                                                state.redraw(state.layout.y);
-                                                break;
-                                            }
-
-                                            (KeyCode::Left, KeyModifiers::NONE) => {
-                                                move_left_command_line(
-                                                    &mut keyword,
-                                                    &mut current_char_pos,
-                                                    &mut current_pos,
-                                                );
-                                            }
+                                        continue;
+                                    }
+                                    keyword.insert(current_char_pos, c);
+                                    current_char_pos += 1;
+                                    current_pos += to_be_added as u16;
 
-                                            (KeyCode::Right, KeyModifiers::NONE) => {
-                                                move_right_command_line(
-                                                    &mut keyword,
-                                                    &mut current_char_pos,
-                                                    &mut current_pos,
-                                                );
-                                            }
+                                    let key = &keyword.iter().collect::<String>();
 
-                                            (KeyCode::Backspace, KeyModifiers::NONE)
-                                            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
-                                                if current_char_pos == 0 {
-                                                    continue;
-                                                };
-                                                let removed = keyword.remove(current_char_pos - 1);
-                                                if let Some(to_be_removed) =
-                                                    unicode_width::UnicodeWidthChar::width(removed)
-                                                {
-                                                    current_char_pos -= 1;
-                                                    current_pos -= to_be_removed as u16;
-
-                                                    let key = &keyword.iter().collect::<String>();
-
-                                                    let target = state
-                                                        .list
-                                                        .iter()
-                                                        .position(|x| x.file_name.contains(key));
-
-                                                    match target {
-                                                        Some(i) => {
-                                                            state.layout.nums.skip = i as u16;
-                                                            state.layout.nums.index = i;
-                                                            state.highlight_matches(key);
-                                                            state.redraw(BEGINNING_ROW);
-                                                        }
-                                                        None => {
-                                                            state.highlight_matches(key);
-                                                            state.layout.nums = original_nums;
-                                                            state.layout.y = original_y;
-                                                            state.redraw(state.layout.y);
-                                                        }
-                                                    }
-                                                    go_to_info_line_and_reset();
-                                                    print!("{}{}", PROMPT_SEARCH, key);
-                                                    move_to(current_pos, 2);
-                                                }
-                                            }
-
-                                            (KeyCode::Char(c), _) => {
-                                                if let Some(to_be_added) =
-                                                    unicode_width::UnicodeWidthChar::width(c)
-                                                {
-                                                    if current_pos + to_be_added as u16
-                                                        > state.layout.terminal_column
-                                                    {
-                                                        continue;
-                                                    }
-                                                    keyword.insert(current_char_pos, c);
-                                                    current_char_pos += 1;
-                                                    current_pos += to_be_added as u16;
-
-                                                    let key = &keyword.iter().collect::<String>();
+                                    let target = match state.ignore_case {
+                                        Some(true) => state.list.iter().position(|x| {
+                                            x.file_name.to_lowercase().contains(&key.to_lowercase())
+                                        }),
+                                        _ => state
+                                            .list
+                                            .iter()
+                                            .position(|x| x.file_name.contains(key)),
+                                    };
+                                    match target {
+                                        Some(i) => {
+                                            state.layout.nums.skip = i as u16;
+                                            state.layout.nums.index = i;
+                                            state.highlight_matches(key);
+                                            state.redraw(BEGINNING_ROW);
+                                        }
+                                        None => {
+                                            state.highlight_matches(key);
+                                            state.layout.nums = original_nums;
+                                            state.layout.y = original_y;
+                                            state.redraw(state.layout.y);
+                                        }
+                                    }
 
-                                                    let target = match state.ignore_case {
-                                                        Some(true) => {
-                                                            state.list.iter().position(|x| {
-                                                                x.file_name
-                                                                    .to_lowercase()
-                                                                    .contains(&key.to_lowercase())
-                                                            })
-                                                        }
-                                                        _ => state.list.iter().position(|x| {
-                                                            x.file_name.contains(key)
-                                                        }),
-                                                    };
-                                                    match target {
-                                                        Some(i) => {
-                                                            state.layout.nums.skip = i as u16;
-                                                            state.layout.nums.index = i;
-                                                            state.highlight_matches(key);
-                                                            state.redraw(BEGINNING_ROW);
-                                                        }
-                                                        None => {
-                                                            state.highlight_matches(key);
-                                                            state.layout.nums = original_nums;
-                                                            state.layout.y = original_y;
-                                                            state.redraw(state.layout.y);
-                                                        }
-                                                    }
+                                    go_to_info_line_and_reset();
+                                    print!("{}{}", PROMPT_SEARCH, key);
+                                    move_to(current_pos, 2);
+                                }
+                            }
 
-                                                    go_to_info_line_and_reset();
-                                                    print!("{}{}", PROMPT_SEARCH, key);
-                                                    move_to(current_pos, 2);
-                                                }
-                                            }
+                            (KeyCode::Enter, KeyModifiers::NONE) => {
+                                go_to_info_line_and_reset();
+                                state.keyword = Some(keyword.iter().collect());
+                                state.move_cursor(state.layout.y);
+                                break;
+                            }
 
-                                            (KeyCode::Enter, KeyModifiers::NONE) => {
-                                                go_to_info_line_and_reset();
-                                                state.keyword = Some(keyword.iter().collect());
-                                                state.move_cursor(state.layout.y);
-                                                break;
-                                            }
+                            _ => continue,
+                        }
+                        screen.flush()?;
+                    }
+                }
+                hide_cursor();
+            }
 
-                                            _ => continue,
-                                        }
-                                        screen.flush()?;
-                                    }
-                                }
-                                hide_cursor();
+            //Tinker with registers
+            Action::Register => {
+                go_to_info_line_and_reset();
+                print!("\"");
+                show_cursor();
+                screen.flush()?;
+
+                let mut command: Vec<char> = Vec::new();
+
+                let mut current_pos = INITIAL_POS_COMMAND_LINE;
+                'reg: loop {
+                    if let Event::Key(KeyEvent {
+                        code,
+                        kind: KeyEventKind::Press,
+                        ..
+                    }) = event::read()?
+                    {
+                        match code {
+                            KeyCode::Esc => {
+                                state.escape();
+                                break 'reg;
                             }
 
-                            //Search forward
-                            KeyCode::Char('n') => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
+                            KeyCode::Left => {
+                                if current_pos == INITIAL_POS_COMMAND_LINE {
                                     continue;
-                                }
-                                match &state.keyword {
-                                    None => {
-                                        continue;
-                                    }
-                                    Some(keyword) => {
-                                        let next = state
-                                            .list
-                                            .iter()
-                                            .skip(state.layout.nums.index + 1)
-                                            .position(|x| x.file_name.contains(keyword));
-                                        match next {
-                                            None => {
-                                                continue;
-                                            }
-                                            Some(i) => {
-                                                let i = i + state.layout.nums.index + 1;
-                                                state.layout.nums.skip = i as u16;
-                                                state.layout.nums.index = i;
-                                                state.redraw(BEGINNING_ROW);
-                                            }
-                                        }
-                                    }
-                                }
+                                };
+                                current_pos -= 1;
+                                move_left(1);
                             }
 
-                            //Search backward
-                            KeyCode::Char('N') => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
+                            KeyCode::Right => {
+                                if current_pos as usize
+                                    == command.len() + INITIAL_POS_COMMAND_LINE as usize
+                                {
                                     continue;
-                                }
-                                match &state.keyword {
-                                    None => {
-                                        continue;
-                                    }
-                                    Some(keyword) => {
-                                        let previous = state
-                                            .list
-                                            .iter()
-                                            .take(state.layout.nums.index)
-                                            .rposition(|x| x.file_name.contains(keyword));
-                                        match previous {
-                                            None => {
-                                                continue;
-                                            }
-                                            Some(i) => {
-                                                state.layout.nums.skip = i as u16;
-                                                state.layout.nums.index = i;
-                                                state.redraw(BEGINNING_ROW);
-                                            }
-                                        }
-                                    }
+                                };
+                                current_pos += 1;
+                                move_right(1);
+                            }
+
+                            KeyCode::Backspace => {
+                                if current_pos == INITIAL_POS_COMMAND_LINE {
+                                    state.escape();
+                                    break 'reg;
+                                } else {
+                                    command.remove(
+                                        (current_pos - INITIAL_POS_COMMAND_LINE - 1).into(),
+                                    );
+                                    current_pos -= 1;
+
+                                    clear_current_line();
+                                    to_info_line();
+                                    print!("\"{}", &command.iter().collect::<String>());
+                                    move_to(current_pos, 2);
                                 }
                             }
 
-                            //Tinker with registers
-                            KeyCode::Char('"') => {
-                                go_to_info_line_and_reset();
-                                print!("\"");
-                                show_cursor();
-                                screen.flush()?;
-
-                                let mut command: Vec<char> = Vec::new();
-
-                                let mut current_pos = INITIAL_POS_COMMAND_LINE;
-                                'reg: loop {
-                                    if let Event::Key(KeyEvent {
-                                        code,
-                                        kind: KeyEventKind::Press,
-                                        ..
-                                    }) = event::read()?
-                                    {
-                                        match code {
-                                            KeyCode::Esc => {
+                            KeyCode::Char(c) => {
+                                command.insert((current_pos - INITIAL_POS_COMMAND_LINE).into(), c);
+                                if ((state.v_start.is_some() || c == 'p') && command.len() == 2)
+                                    || (state.v_start.is_none() && command.len() == 3)
+                                {
+                                    if !command[0].is_ascii_alphanumeric() {
+                                        print_warning("Input not supported.", state.layout.y);
+                                        break 'reg;
+                                    }
+                                    let action: String = command[1..].iter().collect();
+                                    match action.as_str() {
+                                        //put
+                                        "p" => {
+                                            //In read-only directory, put disabled
+                                            if state.is_ro {
                                                 state.escape();
+                                                print_warning(
+                                                    "Cannot put item in this directory.",
+                                                    state.layout.y,
+                                                );
                                                 break 'reg;
                                             }
-
-                                            KeyCode::Left => {
-                                                if current_pos == INITIAL_POS_COMMAND_LINE {
-                                                    continue;
-                                                };
-                                                current_pos -= 1;
-                                                move_left(1);
-                                            }
-
-                                            KeyCode::Right => {
-                                                if current_pos as usize
-                                                    == command.len()
-                                                        + INITIAL_POS_COMMAND_LINE as usize
-                                                {
-                                                    continue;
-                                                };
-                                                current_pos += 1;
-                                                move_right(1);
+                                            if state.v_start.is_some() {
+                                                clear_current_line();
+                                                hide_cursor();
+                                                state.move_cursor(state.layout.y);
+                                                break 'reg;
                                             }
+                                            let target = match command[0] {
+                                                '0' => Some(&state.registers.zero),
+                                                '1'..='9' => state.registers.numbered.get(
+                                                    command[0].to_digit(10).unwrap() as usize - 1,
+                                                ),
+                                                'a'..='z' => state.registers.named.get(&command[0]),
+                                                _ => None,
+                                            };
 
-                                            KeyCode::Backspace => {
-                                                if current_pos == INITIAL_POS_COMMAND_LINE {
-                                                    state.escape();
+                                            if let Some(target) = target {
+                                                let target = target.clone();
+                                                if let Err(e) = state.put(target) {
+                                                    print_warning(e, state.layout.y);
                                                     break 'reg;
-                                                } else {
-                                                    command.remove(
-                                                        (current_pos
-                                                            - INITIAL_POS_COMMAND_LINE
-                                                            - 1)
-                                                        .into(),
+                                                }
+                                            } else {
+                                                print_warning(
+                                                    "Register not found.",
+                                                    state.layout.y,
+                                                );
+                                            }
+                                            state.move_cursor(state.layout.y);
+                                            break 'reg;
+                                        }
+                                        //yank (normal mode)
+                                        "yy" => {
+                                            if state.v_start.is_some() {
+                                                state.move_cursor(state.layout.y);
+                                                break 'reg;
+                                            }
+                                            if command[0].is_ascii_lowercase() {
+                                                if let Ok(item) = state.get_item() {
+                                                    state.registers.yank_item(
+                                                        &[ItemBuffer::new(item)],
+                                                        Some(command[0]),
+                                                        false,
                                                     );
-                                                    current_pos -= 1;
-
-                                                    clear_current_line();
-                                                    to_info_line();
-                                                    print!(
-                                                        "\"{}",
-                                                        &command.iter().collect::<String>()
+                                                }
+                                            } else if command[0].is_ascii_uppercase() {
+                                                if let Ok(item) = state.get_item() {
+                                                    state.registers.yank_item(
+                                                        &[ItemBuffer::new(item)],
+                                                        Some(command[0].to_ascii_lowercase()),
+                                                        true,
                                                     );
-                                                    move_to(current_pos, 2);
                                                 }
+                                            } else {
+                                                state.move_cursor(state.layout.y);
+                                                break 'reg;
                                             }
-
-                                            KeyCode::Char(c) => {
-                                                command.insert(
-                                                    (current_pos - INITIAL_POS_COMMAND_LINE).into(),
-                                                    c,
+                                            state.escape();
+                                            print_info("1 item yanked.", state.layout.y);
+                                            break 'reg;
+                                        }
+                                        //yank (visual mode)
+                                        "y" => {
+                                            if state.v_start.is_none() {
+                                                state.move_cursor(state.layout.y);
+                                                break 'reg;
+                                            }
+                                            let items: Vec<ItemBuffer> = state
+                                                .list
+                                                .iter()
+                                                .filter(|item| item.selected)
+                                                .map(ItemBuffer::new)
+                                                .collect();
+                                            let item_len: usize;
+                                            if command[0].is_ascii_lowercase() {
+                                                item_len = state.registers.yank_item(
+                                                    &items,
+                                                    Some(command[0]),
+                                                    false,
                                                 );
-                                                if ((state.v_start.is_some() || c == 'p')
-                                                    && command.len() == 2)
-                                                    || (state.v_start.is_none()
-                                                        && command.len() == 3)
-                                                {
-                                                    if !command[0].is_ascii_alphanumeric() {
-                                                        print_warning(
-                                                            "Input not supported.",
-                                                            state.layout.y,
-                                                        );
-                                                        break 'reg;
-                                                    }
-                                                    let action: String =
-                                                        command[1..].iter().collect();
-                                                    match action.as_str() {
-                                                        //put
-                                                        "p" => {
-                                                            //In read-only directory, put disabled
-                                                            if state.is_ro {
-                                                                state.escape();
-                                                                print_warning(
-                                        "Cannot put item in this directory.",
-                                        state.layout.y,
-                                    );
-                                                                break 'reg;
-                                                            }
-                                                            if state.v_start.is_some() {
-                                                                clear_current_line();
-                                                                hide_cursor();
-                                                                state.move_cursor(state.layout.y);
-                                                                break 'reg;
-                                                            }
-                                                            let target = match command[0] {
-                                                                '0' => Some(&state.registers.zero),
-                                                                '1'..='9' => {
-                                                                    state.registers.numbered.get(
-                                                                        command[0]
-                                                                            .to_digit(10)
-                                                                            .unwrap()
-                                                                            as usize
-                                                                            - 1,
-                                                                    )
-                                                                }
-                                                                'a'..='z' => state
-                                                                    .registers
-                                                                    .named
-                                                                    .get(&command[0]),
-                                                                _ => None,
-                                                            };
-
-                                                            if let Some(target) = target {
-                                                                let target = target.clone();
-                                                                if let Err(e) =
-                                                                    state.put(target, &mut screen)
-                                                                {
-                                                                    print_warning(
-                                                                        e,
-                                                                        state.layout.y,
-                                                                    );
-                                                                    break 'reg;
-                                                                }
-                                                            } else {
-                                                                print_warning(
-                                                                    "Register not found.",
-                                                                    state.layout.y,
-                                                                );
-                                                            }
-                                                            state.move_cursor(state.layout.y);
-                                                            break 'reg;
-                                                        }
-                                                        //yank (normal mode)
-                                                        "yy" => {
-                                                            if state.v_start.is_some() {
-                                                                state.move_cursor(state.layout.y);
-                                                                break 'reg;
-                                                            }
-                                                            if command[0].is_ascii_lowercase() {
-                                                                if let Ok(item) = state.get_item() {
-                                                                    state.registers.yank_item(
-                                                                        &[ItemBuffer::new(item)],
-                                                                        Some(command[0]),
-                                                                        false,
-                                                                    );
-                                                                }
-                                                            } else if command[0]
-                                                                .is_ascii_uppercase()
-                                                            {
-                                                                if let Ok(item) = state.get_item() {
-                                                                    state.registers.yank_item(
-                                                                        &[ItemBuffer::new(item)],
-                                                                        Some(
-                                                                            command[0]
-                                                                                .to_ascii_lowercase(
-                                                                                ),
-                                                                        ),
-                                                                        true,
-                                                                    );
-                                                                }
-                                                            } else {
-                                                                state.move_cursor(state.layout.y);
-                                                                break 'reg;
-                                                            }
-                                                            state.escape();
-                                                            print_info(
-                                                                "1 item yanked.",
-                                                                state.layout.y,
-                                                            );
-                                                            break 'reg;
-                                                        }
-                                                        //yank (visual mode)
-                                                        "y" => {
-                                                            if state.v_start.is_none() {
-                                                                state.move_cursor(state.layout.y);
-                                                                break 'reg;
-                                                            }
-                                                            let items: Vec<ItemBuffer> = state
-                                                                .list
-                                                                .iter()
-                                                                .filter(|item| item.selected)
-                                                                .map(ItemBuffer::new)
-                                                                .collect();
-                                                            let item_len: usize;
-                                                            if command[0].is_ascii_lowercase() {
-                                                                item_len =
-                                                                    state.registers.yank_item(
-                                                                        &items,
-                                                                        Some(command[0]),
-                                                                        false,
-                                                                    );
-                                                            } else if command[0]
-                                                                .is_ascii_uppercase()
-                                                            {
-                                                                item_len =
-                                                                    state.registers.yank_item(
-                                                                        &items,
-                                                                        Some(
-                                                                            command[0]
-                                                                                .to_ascii_lowercase(
-                                                                                ),
-                                                                        ),
-                                                                        true,
-                                                                    );
-                                                            } else {
-                                                                state.move_cursor(state.layout.y);
-                                                                break 'reg;
-                                                            }
-                                                            state.reset_selection();
-                                                            state.list_up();
-                                                            let mut yank_message: String =
-                                                                item_len.to_string();
-                                                            yank_message.push_str(" items yanked");
-                                                            print_info(
-                                                                yank_message,
-                                                                state.layout.y,
-                                                            );
-                                                            state.move_cursor(state.layout.y);
-                                                            break 'reg;
-                                                        }
-
-                                                        //delete (normal mode)
-                                                        "dd" => {
-                                                            //In read-only directory, delete
-                                                            //disabled
-                                                            if state.is_ro {
-                                                                state.escape();
-                                                                print_warning(
-                                        "Cannot delete item in this directory.",
-                                        state.layout.y,
-                                    );
-                                                                break 'reg;
-                                                            }
-                                                            if state.v_start.is_some() {
-                                                                state.move_cursor(state.layout.y);
-                                                                break 'reg;
-                                                            }
-                                                            if command[0].is_ascii_lowercase() {
-                                                                if let Err(e) = state.delete(
-                                                                    Some(command[0]),
-                                                                    false,
-                                                                    &mut screen,
-                                                                ) {
-                                                                    print_warning(
-                                                                        e,
-                                                                        state.layout.y,
-                                                                    );
-                                                                    break 'reg;
-                                                                }
-                                                            } else if command[0]
-                                                                .is_ascii_uppercase()
-                                                            {
-                                                                if let Err(e) = state.delete(
-                                                                    Some(
-                                                                        command[0]
-                                                                            .to_ascii_lowercase(),
-                                                                    ),
-                                                                    true,
-                                                                    &mut screen,
-                                                                ) {
-                                                                    print_warning(
-                                                                        e,
-                                                                        state.layout.y,
-                                                                    );
-                                                                    break 'reg;
-                                                                }
-                                                            }
-                                                            state.move_cursor(state.layout.y);
-                                                            break 'reg;
-                                                        }
-                                                        //delete (visual mode)
-                                                        "d" => {
-                                                            //In read-only directory, delete
-                                                            //disabled
-                                                            if state.is_ro {
-                                                                state.escape();
-                                                                print_warning(
-                                        "Cannot delete item in this directory.",
-                                        state.layout.y,
-                                    );
-                                                                break 'reg;
-                                                            }
-                                                            if state.v_start.is_none() {
-                                                                state.move_cursor(state.layout.y);
-                                                                break 'reg;
-                                                            }
-                                                            if command[0].is_ascii_lowercase() {
-                                                                if let Err(e) = state
-                                                                    .delete_in_visual(
-                                                                        Some(command[0]),
-                                                                        false,
-                                                                        &mut screen,
-                                                                    )
-                                                                {
-                                                                    state.reset_selection();
-                                                                    state.redraw(state.layout.y);
-                                                                    print_warning(
-                                                                        e,
-                                                                        state.layout.y,
-                                                                    );
-                                                                    break 'reg;
-                                                                }
-                                                            } else if command[0]
-                                                                .is_ascii_uppercase()
-                                                            {
-                                                                if let Err(e) = state
-                                                                    .delete_in_visual(
-                                                                        Some(
-                                                                            command[0]
-                                                                                .to_ascii_lowercase(
-                                                                                ),
-                                                                        ),
-                                                                        true,
-                                                                        &mut screen,
-                                                                    )
-                                                                {
-                                                                    state.reset_selection();
-                                                                    state.redraw(state.layout.y);
-                                                                    print_warning(
-                                                                        e,
-                                                                        state.layout.y,
-                                                                    );
-                                                                    break 'reg;
-                                                                }
-                                                            }
-                                                            state.move_cursor(state.layout.y);
-                                                            break 'reg;
-                                                        }
-                                                        _ => {
-                                                            clear_current_line();
-                                                            hide_cursor();
-                                                            state.move_cursor(state.layout.y);
-                                                            break 'reg;
-                                                        }
-                                                    }
-                                                } else {
-                                                    current_pos += 1;
-                                                    clear_current_line();
-                                                    to_info_line();
-                                                    print!(
-                                                        "\"{}",
-                                                        &command.iter().collect::<String>(),
-                                                    );
-                                                    move_to(current_pos, 2);
-                                                }
+                                            } else if command[0].is_ascii_uppercase() {
+                                                item_len = state.registers.yank_item(
+                                                    &items,
+                                                    Some(command[0].to_ascii_lowercase()),
+                                                    true,
+                                                );
+                                            } else {
+                                                state.move_cursor(state.layout.y);
+                                                break 'reg;
                                             }
-
-                                            _ => continue,
+                                            state.reset_selection();
+                                            state.list_up();
+                                            let mut yank_message: String = item_len.to_string();
+                                            yank_message.push_str(" items yanked");
+                                            print_info(yank_message, state.layout.y);
+                                            state.move_cursor(state.layout.y);
+                                            break 'reg;
                                         }
-                                        screen.flush()?;
-                                    }
-                                }
-                            }
 
-                            //command line
-                            KeyCode::Char(':') => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
-                                    continue;
-                                }
-                                delete_pointer();
-                                go_to_info_line_and_reset();
-                                print!("{}", PROMPT_COMMAND_LINE);
-                                show_cursor();
-                                screen.flush()?;
-
-                                let mut command: Vec<char> = Vec::new();
-
-                                // express position in terminal
-                                let mut current_pos = INITIAL_POS_COMMAND_LINE;
-                                // express position in Vec<Char>
-                                let mut current_char_pos = 0;
-                                'command: loop {
-                                    if let Event::Key(KeyEvent {
-                                        code,
-                                        modifiers,
-                                        kind: KeyEventKind::Press,
-                                        ..
-                                    }) = event::read()?
-                                    {
-                                        match (code, modifiers) {
-                                            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
-                                                if let Event::Key(KeyEvent {
-                                                    code,
-                                                    kind: KeyEventKind::Press,
-                                                    ..
-                                                }) = event::read()?
+                                        //delete (normal mode)
+                                        "dd" => {
+                                            //In read-only directory, delete
+                                            //disabled
+                                            if state.is_ro {
+                                                state.escape();
+                                                print_warning(
+                                                    "Cannot delete item in this directory.",
+                                                    state.layout.y,
+                                                );
+                                                break 'reg;
+                                            }
+                                            if state.v_start.is_some() {
+                                                state.move_cursor(state.layout.y);
+                                                break 'reg;
+                                            }
+                                            if command[0].is_ascii_lowercase() {
+                                                if let Err(e) =
+                                                    state.delete(Some(command[0]), false)
                                                 {
-                                                    if let Some(reg) =
-                                                        state.registers.check_reg(&code)
-                                                    {
-                                                        if !reg.is_empty() {
-                                                            let to_be_inserted = reg
-                                                                .iter()
-                                                                .map(|x| x.file_name.clone())
-                                                                .collect::<Vec<String>>()
-                                                                .join(" ");
-                                                            for c in to_be_inserted.chars() {
-                                                                if let Some(to_be_added) =
-                                                        unicode_width::UnicodeWidthChar::width(c)
-                                                    {
-                                                        if current_pos + to_be_added as u16
-                                                            > state.layout.terminal_column
-                                                        {
-                                                            continue;
-                                                        }
-                                                        command.insert(current_char_pos, c);
-                                                        current_char_pos += 1;
-                                                        current_pos += to_be_added as u16;
-                                                    }
-                                                            }
-                                                            go_to_info_line_and_reset();
-                                                            print!(
-                                                                "{}{}",
-                                                                PROMPT_COMMAND_LINE,
-                                                                &command.iter().collect::<String>(),
-                                                            );
-                                                            move_to(current_pos, 2);
-                                                            screen.flush()?;
-                                                            continue;
-                                                        } else {
-                                                            continue;
-                                                        }
-                                                    } else {
-                                                        continue;
-                                                    }
+                                                    print_warning(e, state.layout.y);
+                                                    break 'reg;
+                                                }
+                                            } else if command[0].is_ascii_uppercase() {
+                                                if let Err(e) = state.delete(
+                                                    Some(command[0].to_ascii_lowercase()),
+                                                    true,
+                                                ) {
+                                                    print_warning(e, state.layout.y);
+                                                    break 'reg;
                                                 }
                                             }
-
-                                            (KeyCode::Esc, KeyModifiers::NONE) => {
-                                                state.escape();
-                                                break 'command;
-                                            }
-
-                                            (KeyCode::Left, KeyModifiers::NONE) => {
-                                                move_left_command_line(
-                                                    &mut command,
-                                                    &mut current_char_pos,
-                                                    &mut current_pos,
+                                            state.move_cursor(state.layout.y);
+                                            break 'reg;
+                                        }
+                                        //delete (visual mode)
+                                        "d" => {
+                                            //In read-only directory, delete
+                                            //disabled
+                                            if state.is_ro {
+                                                state.escape();
+                                                print_warning(
+                                                    "Cannot delete item in this directory.",
+                                                    state.layout.y,
                                                 );
+                                                break 'reg;
                                             }
-
-                                            (KeyCode::Right, KeyModifiers::NONE) => {
-                                                move_right_command_line(
-                                                    &mut command,
-                                                    &mut current_char_pos,
-                                                    &mut current_pos,
-                                                );
+                                            if state.v_start.is_none() {
+                                                state.move_cursor(state.layout.y);
+                                                break 'reg;
                                             }
-
-                                            (KeyCode::Backspace, KeyModifiers::NONE)
-                                            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
-                                                if current_char_pos == 0 {
-                                                    continue;
-                                                };
-                                                let removed = command.remove(current_char_pos - 1);
-                                                if let Some(to_be_removed) =
-                                                    unicode_width::UnicodeWidthChar::width(removed)
+                                            if command[0].is_ascii_lowercase() {
+                                                if let Err(e) =
+                                                    state.delete_in_visual(Some(command[0]), false)
                                                 {
-                                                    current_char_pos -= 1;
-                                                    current_pos -= to_be_removed as u16;
-
-                                                    go_to_info_line_and_reset();
-                                                    print!(
-                                                        "{}{}",
-                                                        PROMPT_COMMAND_LINE,
-                                                        &command.iter().collect::<String>(),
-                                                    );
-                                                    move_to(current_pos, 2);
+                                                    state.reset_selection();
+                                                    state.redraw(state.layout.y);
+                                                    print_warning(e, state.layout.y);
+                                                    break 'reg;
+                                                }
+                                            } else if command[0].is_ascii_uppercase() {
+                                                if let Err(e) = state.delete_in_visual(
+                                                    Some(command[0].to_ascii_lowercase()),
+                                                    true,
+                                                ) {
+                                                    state.reset_selection();
+                                                    state.redraw(state.layout.y);
+                                                    print_warning(e, state.layout.y);
+                                                    break 'reg;
                                                 }
                                             }
+                                            state.move_cursor(state.layout.y);
+                                            break 'reg;
+                                        }
+                                        _ => {
+                                            clear_current_line();
+                                            hide_cursor();
+                                            state.move_cursor(state.layout.y);
+                                            break 'reg;
+                                        }
+                                    }
+                                } else {
+                                    current_pos += 1;
+                                    clear_current_line();
+                                    to_info_line();
+                                    print!("\"{}", &command.iter().collect::<String>(),);
+                                    move_to(current_pos, 2);
+                                }
+                            }
+
+                            _ => continue,
+                        }
+                        screen.flush()?;
+                    }
+                }
+            }
+
+            //shell command, with %f, %s and %d expanded to the paths
+            Action::Shell => {
+                delete_pointer();
+                let command = match read_command(PROMPT_SHELL, &state.shell_history)? {
+                    Some(command) if !command.trim().is_empty() => command,
+                    _ => {
+                        state.escape();
+                        continue;
+                    }
+                };
+                match state.run_shell(&command) {
+                    Ok(status) if !status.success() => {
+                        print_warning(format!("Command {}.", status), state.layout.y)
+                    }
+                    Ok(_) => {}
+                    Err(e) => print_warning(e, state.layout.y),
+                }
+            }
 
-                                            (KeyCode::Char(c), _) => {
+            //command line
+            Action::Command => {
+                //In visual mode, only :rename is available.
+                delete_pointer();
+                go_to_info_line_and_reset();
+                print!("{}", PROMPT_COMMAND_LINE);
+                show_cursor();
+                screen.flush()?;
+
+                let mut command: Vec<char> = Vec::new();
+
+                // express position in terminal
+                let mut current_pos = INITIAL_POS_COMMAND_LINE;
+                // express position in Vec<Char>
+                let mut current_char_pos = 0;
+                'command: loop {
+                    if let Event::Key(KeyEvent {
+                        code,
+                        modifiers,
+                        kind: KeyEventKind::Press,
+                        ..
+                    }) = event::read()?
+                    {
+                        match (code, modifiers) {
+                            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                                if let Event::Key(KeyEvent {
+                                    code,
+                                    kind: KeyEventKind::Press,
+                                    ..
+                                }) = event::read()?
+                                {
+                                    if let Some(reg) = state.registers.check_reg(&code) {
+                                        if !reg.is_empty() {
+                                            let to_be_inserted = reg
+                                                .iter()
+                                                .map(|x| x.file_name.clone())
+                                                .collect::<Vec<String>>()
+                                                .join(" ");
+                                            for c in to_be_inserted.chars() {
                                                 if let Some(to_be_added) =
                                                     unicode_width::UnicodeWidthChar::width(c)
                                                 {
@@ -2267,365 +2001,604 @@ fn _run(mut state: State, session_path: PathBuf) -> Result<(), FxError> {
                                                     command.insert(current_char_pos, c);
                                                     current_char_pos += 1;
                                                     current_pos += to_be_added as u16;
-
-                                                    go_to_info_line_and_reset();
-                                                    print!(
-                                                        "{}{}",
-                                                        PROMPT_COMMAND_LINE,
-                                                        &command.iter().collect::<String>(),
-                                                    );
-                                                    move_to(current_pos, 2);
                                                 }
                                             }
+                                            go_to_info_line_and_reset();
+                                            print!(
+                                                "{}{}",
+                                                PROMPT_COMMAND_LINE,
+                                                &command.iter().collect::<String>(),
+                                            );
+                                            move_to(current_pos, 2);
+                                            screen.flush()?;
+                                            continue;
+                                        } else {
+                                            continue;
+                                        }
+                                    } else {
+                                        continue;
+                                    }
+                                }
+                            }
 
-                                            (KeyCode::Enter, KeyModifiers::NONE) => {
-                                                hide_cursor();
-                                                //Set the command and argument(s).
-                                                let commands: String = command.iter().collect();
-                                                let commands: Vec<&str> =
-                                                    commands.split_whitespace().collect();
-                                                if commands.is_empty() {
-                                                    state.escape();
-                                                    break;
-                                                }
-                                                let command = commands[0];
+                            (KeyCode::Esc, KeyModifiers::NONE) => {
+                                state.escape();
+                                break 'command;
+                            }
 
-                                                if commands.len() == 1 {
-                                                    match command {
-                                                        "q" => {
-                                                            //quit
-                                                            break 'main;
-                                                        }
-                                                        "cd" | "z" => {
-                                                            //go to the home directory
-                                                            let home_dir = dirs::home_dir()
-                                                                .ok_or_else(|| {
-                                                                    FxError::Dirs(
-                                                                        "Cannot read home dir."
-                                                                            .to_string(),
-                                                                    )
-                                                                })?;
-                                                            if let Err(e) =
-                                                                state.chdir(&home_dir, Move::Jump)
-                                                            {
-                                                                print_warning(e, state.layout.y);
-                                                            }
-                                                            break 'command;
-                                                        }
-                                                        "e" => {
-                                                            //reload current dir
-                                                            state.keyword = None;
-                                                            state.layout.nums.reset();
-                                                            state.reload(BEGINNING_ROW)?;
-                                                            break 'command;
-                                                        }
-                                                        "h" => {
-                                                            //show help
-                                                            state.show_help(&screen)?;
-                                                            state.redraw(state.layout.y);
-                                                            break 'command;
-                                                        }
-                                                        "reg" => {
-                                                            //:reg - Show registers
-                                                            if state.layout.is_preview() {
-                                                                state.layout.show_reg();
-                                                                state.redraw(state.layout.y);
-                                                            } else if state.layout.is_reg() {
-                                                                state.escape();
-                                                            } else {
-                                                                state.layout.show_reg();
-                                                                let (new_column, new_row) = state
-                                                                    .layout
-                                                                    .update_column_and_row()?;
-                                                                state.refresh(
-                                                                    new_column,
-                                                                    new_row,
-                                                                    state.layout.y,
-                                                                )?;
-                                                                state.escape();
-                                                            }
-                                                            break 'command;
-                                                        }
-                                                        "trash" => {
-                                                            //move to trash dir
-                                                            state.layout.nums.reset();
-                                                            if let Err(e) = state.chdir(
-                                                                &(state.trash_dir.clone()),
-                                                                Move::Jump,
-                                                            ) {
-                                                                print_warning(e, state.layout.y);
-                                                            }
-                                                            break 'command;
-                                                        }
-                                                        "empty" => {
-                                                            //empty the trash dir
-                                                            state.empty_trash(&screen)?;
-                                                            break 'command;
-                                                        }
-                                                        "config" => {
-                                                            //move to the directory that contains
-                                                            //config path
-                                                            state.layout.nums.reset();
-                                                            if let Some(ref config_path) =
-                                                                state.config_path
-                                                            {
-                                                                if let Err(e) = state.chdir(
-                                                                    config_path
-                                                                        .clone()
-                                                                        .parent()
-                                                                        .unwrap(),
-                                                                    Move::Jump,
-                                                                ) {
-                                                                    print_warning(
-                                                                        e,
-                                                                        state.layout.y,
-                                                                    );
-                                                                }
-                                                            } else {
-                                                                print_warning(
-                                                                    "Cannot find the config path.",
-                                                                    state.layout.y,
-                                                                )
-                                                            }
-                                                            break 'command;
-                                                        }
-                                                        _ => {}
-                                                    }
-                                                } else if commands.len() == 2 && command == "cd" {
-                                                    if let Ok(target) =
-                                                        std::path::Path::new(commands[1])
-                                                            .normalize()
-                                                    {
-                                                        if target.exists() {
-                                                            if let Err(e) = state.chdir(
-                                                                &target.into_path_buf(),
-                                                                Move::Jump,
-                                                            ) {
-                                                                print_warning(e, state.layout.y);
-                                                            }
-                                                            break 'command;
-                                                        } else {
-                                                            print_warning(
-                                                                "Path does not exist.",
-                                                                state.layout.y,
-                                                            );
-                                                            break 'command;
-                                                        }
-                                                    } else {
-                                                        print_warning(
-                                                            "Path does not exist.",
-                                                            state.layout.y,
-                                                        );
-                                                        break 'command;
-                                                    }
-                                                }
+                            (KeyCode::Left, KeyModifiers::NONE) => {
+                                move_left_command_line(
+                                    &mut command,
+                                    &mut current_char_pos,
+                                    &mut current_pos,
+                                );
+                            }
 
-                                                //Execute command as is
-                                                let mut err: Option<&str> = None;
-                                                execute!(screen, EnterAlternateScreen)?;
-                                                if std::env::set_current_dir(&state.current_dir)
-                                                    .is_err()
-                                                {
-                                                    err =
-                                                        Some("Changing current directory failed.");
-                                                } else if let Ok(sh) = std::env::var("SHELL") {
-                                                    if std::process::Command::new(&sh)
-                                                        .arg("-c")
-                                                        .arg(commands.join(" "))
-                                                        .status()
-                                                        .is_err()
-                                                    {
-                                                        err = Some("Command execution failed.");
-                                                    }
-                                                } else if std::process::Command::new(command)
-                                                    .args(&commands[1..])
-                                                    .status()
-                                                    .is_err()
-                                                {
-                                                    err = Some("Command execution failed.");
-                                                }
+                            (KeyCode::Right, KeyModifiers::NONE) => {
+                                move_right_command_line(
+                                    &mut command,
+                                    &mut current_char_pos,
+                                    &mut current_pos,
+                                );
+                            }
 
-                                                execute!(screen, EnterAlternateScreen)?;
-                                                hide_cursor();
-                                                info!("SHELL: {:?}", commands);
-                                                state.reload(state.layout.y)?;
-                                                if let Some(e) = err {
-                                                    print_warning(e, state.layout.y);
-                                                }
-                                                break 'command;
-                                            }
+                            (KeyCode::Backspace, KeyModifiers::NONE)
+                            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
+                                if current_char_pos == 0 {
+                                    continue;
+                                };
+                                let removed = command.remove(current_char_pos - 1);
+                                if let Some(to_be_removed) =
+                                    unicode_width::UnicodeWidthChar::width(removed)
+                                {
+                                    current_char_pos -= 1;
+                                    current_pos -= to_be_removed as u16;
 
-                                            _ => continue,
-                                        }
-                                        screen.flush()?;
-                                    }
+                                    go_to_info_line_and_reset();
+                                    print!(
+                                        "{}{}",
+                                        PROMPT_COMMAND_LINE,
+                                        &command.iter().collect::<String>(),
+                                    );
+                                    move_to(current_pos, 2);
                                 }
                             }
 
-                            //undo
-                            KeyCode::Char('u') => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
-                                    continue;
-                                }
-                                let op_len = state.operations.op_list.len();
-                                if op_len <= state.operations.pos {
-                                    print_info("No operations left.", state.layout.y);
-                                    continue;
-                                }
-                                if let Some(op) = state
-                                    .operations
-                                    .op_list
-                                    .get(op_len - state.operations.pos - 1)
+                            (KeyCode::Char(c), _) => {
+                                if let Some(to_be_added) = unicode_width::UnicodeWidthChar::width(c)
                                 {
-                                    let op = op.clone();
-                                    if let Err(e) = state.undo(&op) {
-                                        print_warning(e, state.layout.y);
+                                    if current_pos + to_be_added as u16
+                                        > state.layout.terminal_column
+                                    {
                                         continue;
                                     }
+                                    command.insert(current_char_pos, c);
+                                    current_char_pos += 1;
+                                    current_pos += to_be_added as u16;
 
-                                    let new_len = state.list.len();
-                                    if new_len == 0 {
-                                        state.layout.nums.reset();
-                                        state.move_cursor(BEGINNING_ROW);
-                                    } else if state.layout.nums.index > new_len - 1 {
-                                        let new_y = state.layout.y
-                                            - (state.layout.nums.index - (new_len - 1)) as u16;
-                                        state.layout.nums.index = new_len - 1;
-                                        state.move_cursor(new_y)
-                                    } else {
-                                        state.move_cursor(state.layout.y);
-                                    }
+                                    go_to_info_line_and_reset();
+                                    print!(
+                                        "{}{}",
+                                        PROMPT_COMMAND_LINE,
+                                        &command.iter().collect::<String>(),
+                                    );
+                                    move_to(current_pos, 2);
                                 }
                             }
 
-                            //exit by ZZ
-                            KeyCode::Char('Z') => {
-                                //In visual mode, this is disabled.
-                                if state.v_start.is_some() {
-                                    continue;
+                            (KeyCode::Enter, KeyModifiers::NONE) => {
+                                hide_cursor();
+                                //Set the command and argument(s).
+                                let commands: String = command.iter().collect();
+                                let commands: Vec<&str> = commands.split_whitespace().collect();
+                                if commands.is_empty() {
+                                    state.escape();
+                                    break;
                                 }
-                                delete_pointer();
-                                go_to_info_line_and_reset();
-                                print!("Z");
-                                show_cursor();
-                                screen.flush()?;
+                                let command = commands[0];
 
-                                let mut next_key: Event = event::read()?;
-                                // ignore exactly one keypress Release after a Z is entered
-                                if let Event::Key(KeyEvent {
-                                    kind: KeyEventKind::Release,
-                                    ..
-                                }) = next_key
+                                if state.v_start.is_some()
+                                    && !matches!(command, "rename" | "chmod" | "chown")
                                 {
-                                    next_key = event::read()?;
+                                    print_warning(
+                                                        "Only :rename, :chmod and :chown are available in the visual mode.",
+                                                        state.layout.y,
+                                                    );
+                                    break 'command;
                                 }
 
-                                if let Event::Key(KeyEvent {
-                                    code,
-                                    kind: KeyEventKind::Press,
-                                    ..
-                                }) = next_key
+                                if command == "chown"
+                                    && (commands.len() == 2
+                                        || (commands.len() == 3 && commands[1] == "-R"))
                                 {
-                                    match code {
-                                        KeyCode::Char('Q') => {
-                                            if state.match_vim_exit_behavior
-                                                || state.export_lwd().is_ok()
+                                    //change the owner, asking for sudo if not permitted
+                                    let recursive = commands.len() == 3;
+                                    let result =
+                                        state.change_owner(commands[commands.len() - 1], recursive);
+                                    state.reset_selection();
+                                    state.reload(state.layout.y)?;
+                                    match result {
+                                        Ok(count) => print_info(
+                                            format!("{} items changed.", count),
+                                            state.layout.y,
+                                        ),
+                                        Err(e) => print_warning(e, state.layout.y),
+                                    }
+                                    break 'command;
+                                }
+
+                                if command == "chmod" {
+                                    //change the permissions, by the editor if no mode given
+                                    let result = state.change_permissions(commands.get(1).copied());
+                                    state.reset_selection();
+                                    state.reload(state.layout.y)?;
+                                    match result {
+                                        Ok(count) => print_info(
+                                            format!("{} items changed.", count),
+                                            state.layout.y,
+                                        ),
+                                        Err(e) => print_warning(e, state.layout.y),
+                                    }
+                                    break 'command;
+                                }
+
+                                if command == "compare" && commands.len() <= 2 {
+                                    //compare the archive with the directory
+                                    if let Err(e) = state.compare_archive(commands.get(1).copied())
+                                    {
+                                        print_warning(e, state.layout.y);
+                                    }
+                                    break 'command;
+                                }
+
+                                if commands.len() == 1 {
+                                    match command {
+                                        "q" => {
+                                            //quit
+                                            if state.confirm_exit()?
+                                                && state.export_chosen(None).is_ok()
                                             {
                                                 break 'main;
                                             }
+                                            break 'command;
                                         }
-
-                                        KeyCode::Char('Z') => {
-                                            if !state.match_vim_exit_behavior
-                                                || state.export_lwd().is_ok()
+                                        "cd" | "z" => {
+                                            //go to the home directory
+                                            let home_dir = dirs::home_dir().ok_or_else(|| {
+                                                FxError::Dirs("Cannot read home dir.".to_string())
+                                            })?;
+                                            if let Err(e) = state.chdir(&home_dir, Move::Jump) {
+                                                print_warning(e, state.layout.y);
+                                            }
+                                            break 'command;
+                                        }
+                                        "e" => {
+                                            //reload current dir
+                                            state.keyword = None;
+                                            state.layout.nums.reset();
+                                            state.reload(BEGINNING_ROW)?;
+                                            break 'command;
+                                        }
+                                        "h" => {
+                                            //show help
+                                            state.show_help()?;
+                                            state.redraw(state.layout.y);
+                                            break 'command;
+                                        }
+                                        "jobs" => {
+                                            //:jobs - Show background jobs
+                                            state.show_jobs()?;
+                                            break 'command;
+                                        }
+                                        "reload-config" => {
+                                            //:reload-config - Apply the config (and the theme) again
+                                            state.reload_config();
+                                            break 'command;
+                                        }
+                                        "journal" => {
+                                            //:journal - Check the incomplete operations
+                                            state.check_journal()?;
+                                            break 'command;
+                                        }
+                                        "reg" => {
+                                            //:reg - Show registers
+                                            if state.layout.is_preview() {
+                                                state.layout.show_reg();
+                                                state.redraw(state.layout.y);
+                                            } else if state.layout.is_reg() {
+                                                state.escape();
+                                            } else {
+                                                state.layout.show_reg();
+                                                let (new_column, new_row) =
+                                                    state.layout.update_column_and_row()?;
+                                                state.refresh(
+                                                    new_column,
+                                                    new_row,
+                                                    state.layout.y,
+                                                )?;
+                                                state.escape();
+                                            }
+                                            break 'command;
+                                        }
+                                        "trash" => {
+                                            //move to trash dir, or in it,
+                                            //to the trash of the next mount
+                                            let roots = state.trash_roots();
+                                            let next = roots
+                                                .iter()
+                                                .position(|(dir, _)| *dir == state.current_dir)
+                                                .map_or(0, |i| (i + 1) % roots.len());
+                                            state.layout.nums.reset();
+                                            if let Err(e) = state.chdir(&roots[next].0, Move::Jump)
                                             {
-                                                break 'main;
+                                                print_warning(e, state.layout.y);
                                             }
+                                            break 'command;
                                         }
-
-                                        _ => {
-                                            state.escape();
+                                        "relative" => {
+                                            //toggle the paths relative to the anchor
+                                            match state.toggle_relative() {
+                                                Ok(true) => print_info(
+                                                    "Paths relative to the anchor.",
+                                                    state.layout.y,
+                                                ),
+                                                Ok(false) => {
+                                                    print_info("Absolute paths.", state.layout.y)
+                                                }
+                                                Err(e) => print_warning(e, state.layout.y),
+                                            }
+                                            break 'command;
+                                        }
+                                        "unmark" => {
+                                            //clear all marks
+                                            state.marked.clear();
+                                            state.redraw(state.layout.y);
+                                            break 'command;
+                                        }
+                                        "search" => {
+                                            //pick one of the saved searches
+                                            let names: Vec<String> =
+                                                state.searches.keys().cloned().collect();
+                                            if names.is_empty() {
+                                                print_warning(
+                                                    "No saved search in the config.",
+                                                    state.layout.y,
+                                                );
+                                                break 'command;
+                                            }
+                                            let options: Vec<String> = state
+                                                .searches
+                                                .iter()
+                                                .map(|(name, search)| match &search.dir {
+                                                    Some(dir) => format!(
+                                                        "{}: {} in {}",
+                                                        name,
+                                                        search.filter,
+                                                        dir.display()
+                                                    ),
+                                                    None => format!("{}: {}", name, search.filter),
+                                                })
+                                                .collect();
+                                            match state.pick(&options)? {
+                                                Some(i) => {
+                                                    let search = state.searches[&names[i]].clone();
+                                                    match state.open_search(&search) {
+                                                        Ok(count) => print_info(
+                                                            format!("{} items match.", count),
+                                                            state.layout.y,
+                                                        ),
+                                                        Err(e) => {
+                                                            state.redraw(state.layout.y);
+                                                            print_warning(e, state.layout.y);
+                                                        }
+                                                    }
+                                                }
+                                                None => state.redraw(state.layout.y),
+                                            }
+                                            break 'command;
+                                        }
+                                        "filter" => {
+                                            //clear the filter
+                                            state.set_filter(None)?;
+                                            break 'command;
+                                        }
+                                        "empty" => {
+                                            //empty the trash dir
+                                            state.empty_trash()?;
+                                            break 'command;
+                                        }
+                                        "config" => {
+                                            //move to the directory that contains
+                                            //config path
+                                            state.layout.nums.reset();
+                                            if let Some(ref config_path) = state.config_path {
+                                                if let Err(e) = state.chdir(
+                                                    config_path.clone().parent().unwrap(),
+                                                    Move::Jump,
+                                                ) {
+                                                    print_warning(e, state.layout.y);
+                                                }
+                                            } else {
+                                                print_warning(
+                                                    "Cannot find the config path.",
+                                                    state.layout.y,
+                                                )
+                                            }
+                                            break 'command;
+                                        }
+                                        _ => {}
+                                    }
+                                } else if commands.len() >= 2
+                                    && (command == "select" || command == "unselect")
+                                {
+                                    //select/deselect items matching the pattern
+                                    let pattern = commands[1..].join(" ");
+                                    match state.select_matches(&pattern, command == "select") {
+                                        Ok(count) => {
+                                            state.redraw(state.layout.y);
+                                            let message = match command {
+                                                "select" => {
+                                                    format!("{} items selected.", count)
+                                                }
+                                                _ => format!("{} items deselected.", count),
+                                            };
+                                            print_info(message, state.layout.y);
+                                        }
+                                        Err(e) => {
+                                            print_warning(e, state.layout.y);
+                                        }
+                                    }
+                                    break 'command;
+                                } else if commands.len() >= 2 && command == "filter" {
+                                    //narrow the list by size/time predicates
+                                    match state.set_filter(Some(&commands[1..].join(" "))) {
+                                        Ok(count) => {
+                                            print_info(
+                                                format!("{} items match.", count),
+                                                state.layout.y,
+                                            );
+                                        }
+                                        Err(e) => {
+                                            print_warning(e, state.layout.y);
+                                        }
+                                    }
+                                    break 'command;
+                                } else if commands.len() >= 2
+                                    && (command == "newer"
+                                        || command == "modified-since"
+                                        || command == "larger"
+                                        || command == "smaller")
+                                {
+                                    //narrow the list to items modified
+                                    //within the duration / since the date,
+                                    //or larger / smaller than the size.
+                                    //The rest (e.g. name:*.log, -r) is
+                                    //added to the filter as is.
+                                    let arg = commands[1];
+                                    let predicate = match command {
+                                        "newer" => parse_duration(arg).map(|_| format!("<{}", arg)),
+                                        "modified-since" => {
+                                            parse_date(arg).map(|_| format!("since:{}", arg))
+                                        }
+                                        "larger" => parse_size(arg).map(|_| format!(">{}", arg)),
+                                        _ => parse_size(arg).map(|_| format!("<{}", arg)),
+                                    }
+                                    .map(|predicate| {
+                                        [&[predicate.as_str()], &commands[2..]].concat().join(" ")
+                                    });
+                                    match predicate {
+                                        Some(predicate) => match state.add_filter(&predicate) {
+                                            Ok(count) => {
+                                                print_info(
+                                                    format!("{} items match.", count),
+                                                    state.layout.y,
+                                                );
+                                            }
+                                            Err(e) => {
+                                                print_warning(e, state.layout.y);
+                                            }
+                                        },
+                                        None => {
+                                            print_warning(
+                                                FxError::Filter(commands[1].to_owned()),
+                                                state.layout.y,
+                                            );
+                                        }
+                                    }
+                                    break 'command;
+                                } else if commands.len() == 2 && command == "search" {
+                                    //open the saved search by name
+                                    match state.searches.get(commands[1]).cloned() {
+                                        Some(search) => match state.open_search(&search) {
+                                            Ok(count) => print_info(
+                                                format!("{} items match.", count),
+                                                state.layout.y,
+                                            ),
+                                            Err(e) => print_warning(e, state.layout.y),
+                                        },
+                                        None => {
+                                            print_warning(
+                                                format!("No saved search named {}.", commands[1]),
+                                                state.layout.y,
+                                            );
+                                        }
+                                    }
+                                    break 'command;
+                                } else if commands.len() >= 2 && command == "rename" {
+                                    //rename items by the regex substitution
+                                    match state.rename_by_substitution(&commands[1..].join(" ")) {
+                                        Ok(count) => {
+                                            state.reset_selection();
+                                            state.reload(state.layout.y)?;
+                                            print_info(
+                                                format!("{} items renamed.", count),
+                                                state.layout.y,
+                                            );
+                                        }
+                                        Err(e) => {
+                                            state.reset_selection();
+                                            state.reload(state.layout.y)?;
+                                            print_warning(e, state.layout.y);
+                                        }
+                                    }
+                                    break 'command;
+                                } else if commands.len() >= 2 && command == "script" {
+                                    //run the user script defined in the config
+                                    if let Err(e) = state.run_script(commands[1], &commands[2..]) {
+                                        print_warning(e, state.layout.y);
+                                    }
+                                    break 'command;
+                                } else if commands.len() == 2 && command == "cd" {
+                                    if let Ok(target) =
+                                        std::path::Path::new(commands[1]).normalize()
+                                    {
+                                        if target.exists() {
+                                            if let Err(e) =
+                                                state.chdir(&target.into_path_buf(), Move::Jump)
+                                            {
+                                                print_warning(e, state.layout.y);
+                                            }
+                                            break 'command;
+                                        } else {
+                                            print_warning("Path does not exist.", state.layout.y);
+                                            break 'command;
                                         }
+                                    } else {
+                                        print_warning("Path does not exist.", state.layout.y);
+                                        break 'command;
                                     }
                                 }
-                            }
 
-                            //If input does not match any of the defined keys, ignore it.
-                            _ => {
-                                continue;
+                                //Execute command as is
+                                let mut err: Option<&str> = None;
+                                reenter_alternate_screen();
+                                if std::env::set_current_dir(&state.current_dir).is_err() {
+                                    err = Some("Changing current directory failed.");
+                                } else if let Ok(sh) = std::env::var("SHELL") {
+                                    if run_in_terminal(
+                                        std::process::Command::new(&sh)
+                                            .arg("-c")
+                                            .arg(commands.join(" ")),
+                                    )
+                                    .is_err()
+                                    {
+                                        err = Some("Command execution failed.");
+                                    }
+                                } else if run_in_terminal(
+                                    std::process::Command::new(command).args(&commands[1..]),
+                                )
+                                .is_err()
+                                {
+                                    err = Some("Command execution failed.");
+                                }
+
+                                reenter_alternate_screen();
+                                hide_cursor();
+                                info!("SHELL: {:?}", commands);
+                                state.reload(state.layout.y)?;
+                                if let Some(e) = err {
+                                    print_warning(e, state.layout.y);
+                                }
+                                break 'command;
                             }
+
+                            _ => continue,
                         }
-                    }
-                    //Other modifiers disable commands when pressed.
-                    _ => {
-                        continue;
+                        screen.flush()?;
                     }
                 }
-                //If you use kitty, clear the screen by the escape sequence or the previewed image remains.
-                if state.layout.is_kitty && state.layout.is_preview() {
-                    if let Ok(item) = state.get_item() {
-                        if item.preview_type == Some(PreviewType::Image) {
-                            print!("{}", CLRSCR);
-                            state.clear_and_show_headline();
-                            state.list_up();
-                            state.move_cursor(state.layout.y);
-                            screen.flush()?;
-                        }
+            }
+
+            //undo
+            Action::Undo => {
+                //In visual mode, this is disabled.
+                if state.v_start.is_some() {
+                    continue;
+                }
+                if let Some(op) = state.operations.to_undo() {
+                    if let Err(e) = state.undo(op.as_ref()) {
+                        print_warning(e, state.layout.y);
+                        continue;
                     }
+                } else {
+                    print_info("No operations left.", state.layout.y);
                 }
             }
-            Event::Resize(column, row) => {
-                // Return error if terminal size may cause panic
-                if column < 4 {
-                    error!("Too small terminal size (less than 4 columns).");
-                    panic!("Error: Too small terminal size (less than 4 columns). Please restart.");
-                };
-                if row < 4 {
-                    error!("Too small terminal size (less than 4 rows).");
-                    panic!("Error: Too small terminal size (less than 4 rows). Please restart.");
-                };
-
-                if state.layout.is_preview() || state.layout.is_reg() {
-                    let new_column = match state.layout.split {
-                        Split::Vertical => column >> 1,
-                        Split::Horizontal => column,
-                    };
-                    let new_row = match state.layout.split {
-                        Split::Vertical => row,
-                        Split::Horizontal => row >> 1,
-                    };
-                    let cursor_pos = if state.layout.y < new_row {
-                        state.layout.y
-                    } else {
-                        let diff = state.layout.y + 1 - new_row;
-                        state.layout.nums.index -= diff as usize;
-                        new_row - 1
-                    };
 
-                    state.refresh(new_column, new_row, cursor_pos)?;
-                } else {
-                    let cursor_pos = if state.layout.y < row {
-                        state.layout.y
-                    } else {
-                        let diff = state.layout.y + 1 - row;
-                        state.layout.nums.index -= diff as usize;
-                        row - 1
-                    };
-                    state.refresh(column, row, cursor_pos)?;
+            //exit by ZZ, or ZQ
+            Action::Quit | Action::QuitAlt => {
+                //In visual mode, this is disabled, except in the file-picker mode.
+                if state.v_start.is_some() && state.chooser.is_none() {
+                    continue;
+                }
+                //ZQ exports the last working directory, or ZZ with match_vim_exit_behavior.
+                let export_lwd = state.match_vim_exit_behavior == (action == Action::Quit);
+                if state.confirm_exit()?
+                    && (!export_lwd || state.chooser.is_some() || state.export_lwd().is_ok())
+                    && state.export_chosen(None).is_ok()
+                {
+                    break 'main;
+                }
+            }
+            // Applied above.
+            Action::MoveDown
+            | Action::MoveUp
+            | Action::HalfPageDown
+            | Action::HalfPageUp
+            | Action::GoToTop
+            | Action::GoToBottom
+            | Action::Visual
+            | Action::Escape
+            | Action::Mark
+            | Action::SelectAll
+            | Action::InvertSelection
+            | Action::ToggleHidden
+            | Action::SearchNext
+            | Action::SearchPrev => {}
+        }
+        //If you use kitty, clear the screen by the escape sequence or the previewed image remains.
+        if state.layout.is_kitty && state.layout.is_preview() {
+            if let Ok(item) = state.get_item() {
+                if item.preview_type == Some(PreviewType::Image) {
+                    print!("{}", CLRSCR);
+                    state.clear_and_show_headline();
+                    state.list_up();
+                    state.move_cursor(state.layout.y);
+                    screen.flush()?;
                 }
             }
-            //Other events are disabled.
-            _ => {}
         }
     }
 
     //Save session, restore screen state and cursor
     state.write_session(session_path)?;
-    execute!(screen, LeaveAlternateScreen)?;
-    write!(screen, "{}", RestorePosition)?;
+    state.operations.write_log(&undo_log_path)?;
+    if state.alternate_screen {
+        set_alternate_screen(false);
+        write!(screen, "{}", RestorePosition)?;
+    } else {
+        //Leave the list as it is, and the shell prompt below it.
+        move_to(1, state.layout.terminal_row);
+        write!(screen, "\r\n")?;
+    }
     screen.flush()?;
 
     info!("===FINISH===");
     Ok(())
 }
+
+/// Calculate the size of the trash dirs in another thread, to be sent as `AppEvent::TrashSize`.
+fn calculate_trash_size(state: &State, sender: std::sync::mpsc::Sender<AppEvent>) {
+    let roots = state.trash_roots();
+    thread::spawn(move || {
+        let size = roots.iter().map(|(trash_dir, _)| dir_size(trash_dir)).sum();
+        let _ = sender.send(AppEvent::TrashSize(size));
+    });
+}
+
+/// Show the keys typed so far of a sequence in the info line, or clear it.
+fn print_pending_keys(state: &State) {
+    go_to_info_line_and_reset();
+    print!("{}", state.keymap.pending_keys());
+    move_to(1, state.layout.y);
+}
@@ -0,0 +1,826 @@
+use super::command_line::run_shell_command;
+use super::preview::PreviewArea;
+use super::term::*;
+
+use felix::color::TermColor;
+use felix::config::read_config;
+use felix::disk::{disk_space, format_disk_status};
+use felix::errors::FxError;
+use felix::frontend::Frontend;
+use felix::functions::*;
+use felix::git;
+use felix::help::HELP;
+use felix::item::{load_metadata, ItemInfo};
+use felix::layout::{is_too_small, make_layout, Split};
+use felix::report::ErrorReport;
+use felix::state::{parse_octal_mode, Render, State, BEGINNING_ROW};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::style::Stylize;
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+
+/// The jobs panel is redrawn at this interval to update the progress.
+const JOBS_REDRAW_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Drawing the state in the terminal, and the views reading the keys by themselves.
+pub trait Screen {
+    fn redraw(&mut self, y: u16);
+    fn redraw_list(&mut self, y: u16);
+    fn render(&mut self, render: Render);
+    fn reload(&mut self, y: u16) -> Result<(), FxError>;
+    fn reload_config(&mut self);
+    fn resize(&mut self, column: u16, row: u16) -> Result<(), FxError>;
+    fn refresh(&mut self, column: u16, row: u16, cursor_pos: u16) -> Result<(), FxError>;
+    fn clear_and_show_headline(&mut self);
+    fn show_headline(&mut self);
+    fn escape(&mut self);
+    fn list_up(&mut self);
+    fn move_cursor(&mut self, y: u16);
+    fn to_status_bar(&self);
+    fn clear_status_line(&self);
+    fn print_status(&self, text: &str);
+    fn print_footer(&self, item: Option<&ItemInfo>);
+    fn scroll_down_preview(&mut self, y: u16);
+    fn scroll_up_preview(&mut self, y: u16);
+    fn scroll_preview(&self, y: u16);
+    fn show_jobs(&mut self) -> Result<(), FxError>;
+    fn pick(&mut self, options: &[String]) -> Result<Option<usize>, FxError>;
+    fn show_help(&self) -> Result<(), FxError>;
+    fn compare_archive(&mut self, dir: Option<&str>) -> Result<(), FxError>;
+    fn run_shell(&mut self, command: &str) -> Result<ExitStatus, FxError>;
+}
+
+impl Screen for State {
+    /// Redraw the contents.
+    /// The screen is not cleared, and the rows of the list are printed only if changed.
+    fn redraw(&mut self, y: u16) {
+        begin_frame();
+        self.show_headline();
+        self.list_up();
+        self.move_cursor(y);
+        end_frame();
+    }
+
+    /// Redraw the list on the cursor movement, which changes only the selection or the scroll.
+    /// The header is left as it is, and the rows are printed only if changed.
+    fn redraw_list(&mut self, y: u16) {
+        begin_frame();
+        self.list_up();
+        self.move_cursor(y);
+        end_frame();
+    }
+
+    /// Draw the state changed by `apply`.
+    fn render(&mut self, render: Render) {
+        match render {
+            Render::Nothing => {}
+            Render::Cursor => self.move_cursor(self.layout.y),
+            Render::List => self.redraw_list(self.layout.y),
+            Render::All => self.redraw(self.layout.y),
+        }
+    }
+
+    /// Reload the item list and redraw it.
+    fn reload(&mut self, y: u16) -> Result<(), FxError> {
+        self.update_list()?;
+        begin_frame();
+        self.clear_and_show_headline();
+        self.list_up();
+        self.move_cursor(y);
+        end_frame();
+        Ok(())
+    }
+
+    /// Read the config file again and apply it.
+    /// If reading the config file fails, leave the config as is.
+    fn reload_config(&mut self) {
+        let Some(config_path) = &self.config_path else {
+            print_warning("Cannot find the config path.", self.layout.y);
+            return;
+        };
+        match read_config(config_path) {
+            Ok(c) => {
+                self.set_config(c.config);
+                set_mouse_capture(self.mouse);
+                // Columns and the preview may be changed, so the layout has to be recalculated.
+                // The sort order may be changed as well.
+                if let Err(e) = terminal_size()
+                    .and_then(|(column, row)| self.resize(column, row))
+                    .and_then(|_| self.reload(self.layout.y))
+                {
+                    print_warning(e, self.layout.y);
+                    return;
+                }
+                print_info("New config set.", self.layout.y);
+            }
+            // Show why, e.g. a key bound to two actions.
+            Err(e) => print_warning(e, self.layout.y),
+        }
+    }
+
+    /// Handle the terminal resize event.
+    /// In the terminal too small to show the list, wait until it is resized enough.
+    fn resize(&mut self, mut column: u16, mut row: u16) -> Result<(), FxError> {
+        while is_too_small(column, row) {
+            print_too_small(column);
+            (column, row) = wait_for_resize()?;
+        }
+
+        if self.layout.is_preview() || self.layout.is_reg() {
+            let (new_column, new_row) = self.layout.list_area(column, row);
+            let cursor_pos = if self.layout.y < new_row {
+                self.layout.y
+            } else {
+                let diff = self.layout.y + 1 - new_row;
+                self.layout.nums.index -= diff as usize;
+                new_row - 1
+            };
+
+            self.refresh(new_column, new_row, cursor_pos)
+        } else {
+            let cursor_pos = if self.layout.y < row {
+                self.layout.y
+            } else {
+                let diff = self.layout.y + 1 - row;
+                self.layout.nums.index -= diff as usize;
+                row - 1
+            };
+            self.refresh(column, row, cursor_pos)
+        }
+    }
+
+    /// Reload the app layout when terminal size changes.
+    fn refresh(&mut self, column: u16, row: u16, mut cursor_pos: u16) -> Result<(), FxError> {
+        let (column_start, name_max, shown_columns) =
+            make_layout(column, &self.layout.columns, &self.layout.column_widths);
+
+        let (original_column, original_row) = terminal_size()?;
+
+        self.layout.terminal_row = row;
+        self.layout.terminal_column = column;
+        self.layout.preview_start = match self.layout.split {
+            Split::Vertical => (column + 2, BEGINNING_ROW),
+            Split::Horizontal => (1, row + 2),
+        };
+        self.layout.preview_space = if self.layout.is_preview() || self.layout.is_reg() {
+            match self.layout.split {
+                Split::Vertical => (original_column - column - 1, row - BEGINNING_ROW),
+                Split::Horizontal => (column, original_row - row - 1),
+            }
+        } else {
+            (0, 0)
+        };
+        self.layout.name_max_len = name_max;
+        self.layout.column_start_pos = column_start;
+        self.layout.shown_columns = shown_columns;
+
+        if cursor_pos > row - 1 {
+            self.layout.nums.index -= (cursor_pos - row + 1) as usize;
+            cursor_pos = row - 1;
+        }
+
+        // The preview may be moved or hidden.
+        clear_all();
+        self.redraw(cursor_pos);
+        Ok(())
+    }
+
+    /// Clear all and show the current directory information.
+    fn clear_and_show_headline(&mut self) {
+        clear_all();
+        self.show_headline();
+    }
+
+    /// Show the current directory information, clearing the header and the info line.
+    fn show_headline(&mut self) {
+        go_to_info_line_and_reset();
+        move_to(1, 1);
+        clear_current_line();
+
+        let mut header_space = (self.layout.terminal_column - 1) as usize;
+
+        if let Some(template) = &self.layout.header_format {
+            let header = template.render(|field| self.field_value(field, None));
+            set_color(&TermColor::ForeGround(&self.layout.theme.header_fg));
+            print!(
+                "{}",
+                shorten_str_including_wide_char(&header, header_space + 1)
+            );
+            reset_color();
+            return;
+        }
+
+        // Show current directory path.
+        // crossterm's Stylize cannot be applied to PathBuf,
+        // current directory does not have any text attribute for now.
+        let current_dir = self.current_dir_display();
+        if current_dir.bytes().len() >= header_space {
+            let current_dir = shorten_str_including_wide_char(&current_dir, header_space);
+            set_color(&TermColor::ForeGround(&self.layout.theme.header_fg));
+            print!(" {}", current_dir);
+            reset_color();
+            return;
+        } else {
+            set_color(&TermColor::ForeGround(&self.layout.theme.header_fg));
+            print!(" {}", current_dir);
+            reset_color();
+            header_space -= current_dir.len();
+        }
+
+        // Show the current sort key.
+        let sort_by = format!(" [{}]", self.layout.sort_by);
+        if header_space > sort_by.len() {
+            print!("{}", sort_by);
+            header_space -= sort_by.len();
+        }
+
+        // Show the filter if set, by the name of the preset if set by it.
+        if let Some(filter) = &self.filter {
+            let filter = match &self.filter_preset {
+                Some(name) => format!(" [filter: {}]", name),
+                None => format!(" [filter: {}]", filter),
+            };
+            if header_space > filter.len() {
+                print!("{}", filter);
+                header_space -= filter.len();
+            }
+        }
+
+        // If without the write permission, print [RO].
+        if self.is_ro && header_space > 5 {
+            set_color(&TermColor::ForeGround(&self.layout.theme.read_only_fg));
+            print!(" [RO]");
+            reset_color();
+            header_space -= 5;
+        }
+
+        //If git repository exists, get the branch information and print it.
+        if let Some(branch) = git::branch(&self.current_dir) {
+            if branch.len() + 4 <= header_space {
+                header_space -= branch.len();
+                print!(" on ",);
+                set_color(&TermColor::ForeGround(&self.layout.theme.git_branch_fg));
+                print!("{}", branch.bold());
+                reset_color();
+                header_space -= 4;
+            }
+        }
+
+        // Show the free space and the size of the trash.
+        let disk_status = format_disk_status(disk_space(&self.current_dir), self.trash_size);
+        if disk_status.len() <= header_space {
+            print!("{}", disk_status);
+        }
+    }
+
+    /// Escape to normal mode.
+    fn escape(&mut self) {
+        go_to_info_line_and_reset();
+        hide_cursor();
+        self.move_cursor(self.layout.y);
+    }
+
+    /// Print items in the directory.
+    /// Only the rows changed since the last time are printed.
+    fn list_up(&mut self) {
+        let skip = self.layout.nums.skip as usize;
+        let height = self.layout.terminal_row.saturating_sub(BEGINNING_ROW) as usize;
+        for item in self.list.iter_mut().skip(skip).take(height) {
+            load_metadata(item);
+            // The relative time changes as time goes by.
+            if item.modified_string.is_none() && self.layout.time_format != TimeFormat::Relative {
+                item.modified_string = Some(format_time(
+                    item.modified,
+                    &self.layout.time_format,
+                    self.timezone,
+                ));
+            }
+        }
+        let rows = (skip..skip + height)
+            .map(|index| match self.list.get(index) {
+                Some(item) => self.render_item(item),
+                None => String::new(),
+            })
+            .collect();
+        print_rows(rows, BEGINNING_ROW, self.layout.terminal_column);
+    }
+
+    /// Change the cursor position, and print item information at the bottom.
+    /// If preview is enabled, print text preview, contents of the directory or image preview.
+    fn move_cursor(&mut self, y: u16) {
+        self.set_cursor(y);
+        remove_pointer();
+
+        if self.layout.is_reg() {
+            //Print registers by :reg
+            let reg = self.registers.prepare_reg(self.layout.preview_space.0);
+            self.layout.print_reg(&reg);
+        }
+
+        let item = self.get_item().ok();
+        //Print item information at the bottom
+        self.print_footer(item);
+        if self.layout.is_preview() {
+            //Print preview if preview is on
+            self.layout.print_preview(item, y);
+        }
+
+        print_pointer(y);
+    }
+
+    fn to_status_bar(&self) {
+        move_to(1, self.layout.terminal_row);
+    }
+
+    /// Clear status line.
+    fn clear_status_line(&self) {
+        self.to_status_bar();
+        clear_current_line();
+        reset_color();
+        self.print_status(&" ".repeat(self.layout.terminal_column as usize));
+        move_to(1, self.layout.terminal_row);
+    }
+
+    /// Print the text in the colors of the status line.
+    fn print_status(&self, text: &str) {
+        print_highlighted(
+            text,
+            self.layout.theme.status_fg.as_ref(),
+            self.layout.theme.status_bg.as_ref(),
+        );
+    }
+
+    /// Print item information at the bottom of the terminal.
+    fn print_footer(&self, item: Option<&ItemInfo>) {
+        self.clear_status_line();
+
+        if let Some(keyword) = &self.keyword {
+            let count = self
+                .list
+                .iter()
+                .filter(|x| match self.ignore_case {
+                    Some(true) => x.file_name.to_lowercase().contains(&keyword.to_lowercase()),
+                    _ => x.file_name.contains(keyword),
+                })
+                .count();
+            let count = if count <= 1 {
+                format!("{} match", count)
+            } else {
+                format!("{} matches", count)
+            };
+            move_to(1, self.layout.terminal_row);
+            self.print_status(&format!(" /{} - {}", keyword, count));
+            return;
+        }
+
+        let footer = self.make_footer(item);
+        self.print_status(&footer);
+    }
+
+    /// Scroll down previewed text.
+    fn scroll_down_preview(&mut self, y: u16) {
+        if let Ok(item) = self.get_item_mut() {
+            item.preview_scroll += 1;
+            self.scroll_preview(y)
+        }
+    }
+
+    /// Scroll up previewed text.
+    fn scroll_up_preview(&mut self, y: u16) {
+        if let Ok(item) = self.get_item_mut() {
+            if item.preview_scroll != 0 {
+                item.preview_scroll -= 1;
+                self.scroll_preview(y)
+            }
+        }
+    }
+
+    /// Scroll preview.
+    fn scroll_preview(&self, y: u16) {
+        self.layout.print_preview(self.get_item().ok(), y);
+        print_pointer(y);
+    }
+
+    /// Show the background jobs in the list area, updating their progress
+    /// (j/k to move, p to pause/resume, x to cancel, <Esc> to close).
+    fn show_jobs(&mut self) -> Result<(), FxError> {
+        if self.jobs.list.is_empty() {
+            print_info("No jobs.", self.layout.y);
+            return Ok(());
+        }
+        let rows = ((self.layout.terminal_row - BEGINNING_ROW) as usize).max(1);
+        let width = self.layout.terminal_column.saturating_sub(3) as usize;
+        let mut index: usize = 0;
+        loop {
+            self.clear_and_show_headline();
+            let skip = (index + 1).saturating_sub(rows);
+            for (i, job) in self.jobs.list.iter().enumerate().skip(skip).take(rows) {
+                move_to(3, BEGINNING_ROW + (i - skip) as u16);
+                let line = shorten_str_including_wide_char(&job.summary(), width);
+                if i == index {
+                    print!("{}", line.negative());
+                } else {
+                    print!("{}", line);
+                }
+            }
+            go_to_info_line_and_reset();
+            print!("j/k: move, p: pause/resume, x: cancel, <Esc>: close");
+            stdout().flush()?;
+
+            if !crossterm::event::poll(JOBS_REDRAW_INTERVAL)? {
+                continue;
+            }
+            if let Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) = crossterm::event::read()?
+            {
+                match code {
+                    KeyCode::Char('j') | KeyCode::Down if index + 1 < self.jobs.list.len() => {
+                        index += 1;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        index = index.saturating_sub(1);
+                    }
+                    KeyCode::Char('p') | KeyCode::Char(' ') => self.jobs.list[index].toggle_pause(),
+                    KeyCode::Char('x') => self.jobs.list[index].cancel(),
+                    KeyCode::Esc | KeyCode::Char('q') => break,
+                    _ => {}
+                }
+            }
+        }
+        self.clear_and_show_headline();
+        self.list_up();
+        go_to_info_line_and_reset();
+        self.move_cursor(self.layout.y);
+        Ok(())
+    }
+
+    /// Show the options in the list area and let the user choose one
+    /// (j/k to move, <CR> to choose, <Esc> to cancel).
+    fn pick(&mut self, options: &[String]) -> Result<Option<usize>, FxError> {
+        let rows = ((self.layout.terminal_row - BEGINNING_ROW) as usize).max(1);
+        let width = self.layout.terminal_column.saturating_sub(3) as usize;
+        let mut index: usize = 0;
+        loop {
+            self.clear_and_show_headline();
+            let skip = (index + 1).saturating_sub(rows);
+            for (i, option) in options.iter().enumerate().skip(skip).take(rows) {
+                move_to(3, BEGINNING_ROW + (i - skip) as u16);
+                let line = shorten_str_including_wide_char(option, width);
+                if i == index {
+                    print!("{}", line.negative());
+                } else {
+                    print!("{}", line);
+                }
+            }
+            go_to_info_line_and_reset();
+            print!("j/k: move, <CR>: open, <Esc>: cancel");
+            stdout().flush()?;
+
+            if let Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) = crossterm::event::read()?
+            {
+                match code {
+                    KeyCode::Char('j') | KeyCode::Down if index + 1 < options.len() => {
+                        index += 1;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        index = index.saturating_sub(1);
+                    }
+                    KeyCode::Enter => return Ok(Some(index)),
+                    KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Show help
+    fn show_help(&self) -> Result<(), FxError> {
+        clear_all();
+        move_to(1, 1);
+        stdout().flush()?;
+        let (width, height) = terminal_size()?;
+        let help = format_txt(HELP, width, true);
+        print_help(&help, 0, height);
+        stdout().flush()?;
+
+        let mut skip = 0;
+        loop {
+            if let Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) = crossterm::event::read()?
+            {
+                match code {
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        clear_all();
+                        skip += 1;
+                        print_help(&help, skip, height);
+                        stdout().flush()?;
+                        continue;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        if skip == 0 {
+                            continue;
+                        } else {
+                            clear_all();
+                            skip -= 1;
+                            print_help(&help, skip, height);
+                            stdout().flush()?;
+                            continue;
+                        }
+                    }
+                    _ => {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compare the archive under the cursor with the directory,
+    /// and list the differing files (j/k to move, <Esc> to close).
+    fn compare_archive(&mut self, dir: Option<&str>) -> Result<(), FxError> {
+        let Some((diff, dir)) = self.diff_archive(dir)? else {
+            return Ok(());
+        };
+        let mut lines: Vec<String> = vec![];
+        for (mark, paths) in [
+            ("+", &diff.added),
+            ("-", &diff.removed),
+            ("M", &diff.changed),
+        ] {
+            lines.extend(
+                paths
+                    .iter()
+                    .map(|path| format!("{} {}", mark, path.display())),
+            );
+        }
+        let rows = ((self.layout.terminal_row - BEGINNING_ROW) as usize).max(1);
+        let width = self.layout.terminal_column.saturating_sub(3) as usize;
+        let mut index: usize = 0;
+        loop {
+            self.clear_and_show_headline();
+            let skip = (index + 1).saturating_sub(rows);
+            for (i, line) in lines.iter().enumerate().skip(skip).take(rows) {
+                move_to(3, BEGINNING_ROW + (i - skip) as u16);
+                let line = shorten_str_including_wide_char(line, width);
+                if i == index {
+                    print!("{}", line.negative());
+                } else {
+                    print!("{}", line);
+                }
+            }
+            go_to_info_line_and_reset();
+            print!(
+                "+{} -{} M{} (+: only in {}) j/k: move, <Esc>: close",
+                diff.added.len(),
+                diff.removed.len(),
+                diff.changed.len(),
+                dir.file_name().unwrap_or_default().to_string_lossy()
+            );
+            stdout().flush()?;
+
+            if let Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) = crossterm::event::read()?
+            {
+                match code {
+                    KeyCode::Char('j') | KeyCode::Down if index + 1 < lines.len() => {
+                        index += 1;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        index = index.saturating_sub(1);
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') => break,
+                    _ => {}
+                }
+            }
+        }
+        self.clear_and_show_headline();
+        self.list_up();
+        go_to_info_line_and_reset();
+        self.move_cursor(self.layout.y);
+        Ok(())
+    }
+
+    /// Run the command typed after `!` by the shell, with the placeholders expanded,
+    /// and reload the list, which the command may have changed.
+    fn run_shell(&mut self, command: &str) -> Result<ExitStatus, FxError> {
+        let expanded = self.shell_command(command)?;
+        let status = run_shell_command(&expanded, &self.current_dir);
+        self.reload(self.layout.y)?;
+        status
+    }
+}
+
+/// The terminal UI, which `State` reports to while an operation runs.
+#[derive(Debug)]
+pub struct Terminal;
+
+impl Frontend for Terminal {
+    fn redraw(&self, state: &mut State, y: u16) {
+        state.redraw(y);
+    }
+
+    fn draw_partial(&self, state: &mut State, status: &str) {
+        state.clear_and_show_headline();
+        state.list_up();
+        go_to_info_line_and_reset();
+        print!("{}", status);
+        let _ = stdout().flush();
+    }
+
+    fn info(&self, message: &str) {
+        print_info(message, pointer_row());
+        let _ = stdout().flush();
+    }
+
+    fn warn(&self, message: &str) {
+        print_warning(message, pointer_row());
+        let _ = stdout().flush();
+    }
+
+    fn status(&self, status: &str) {
+        remove_pointer();
+        go_to_info_line_and_reset();
+        print!("{}", status);
+        let _ = stdout().flush();
+    }
+
+    fn progress(&self, progress: &str) {
+        print_process(progress);
+        let _ = stdout().flush();
+    }
+
+    fn is_canceled(&self) -> bool {
+        is_cancel_pressed().unwrap_or(false)
+    }
+
+    /// Ask at the info line, and clear it after the key is pressed.
+    fn ask(&self, question: &str) -> Result<KeyEvent, FxError> {
+        print_warning(question, pointer_row());
+        stdout().flush()?;
+        loop {
+            if let Event::Key(key) = crossterm::event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    go_to_info_line_and_reset();
+                    print_pointer(pointer_row());
+                    return Ok(key);
+                }
+            }
+        }
+    }
+
+    fn show_lines(&self, state: &mut State, lines: &[String]) {
+        state.clear_and_show_headline();
+        let width = state.layout.terminal_column.saturating_sub(3) as usize;
+        for (i, line) in lines.iter().enumerate() {
+            move_to(3, BEGINNING_ROW + i as u16);
+            print!("{}", shorten_str_including_wide_char(line, width));
+        }
+    }
+
+    /// After a batch operation, list the items that failed with the errors.
+    fn show_report(&self, operation: &str, report: &ErrorReport) -> Result<(), FxError> {
+        if report.is_empty() {
+            return Ok(());
+        }
+        let (_, height) = terminal_size()?;
+        let lines = report.lines(operation);
+        let mut skip = 0;
+        loop {
+            clear_all();
+            print_help(&lines, skip, height);
+            stdout().flush()?;
+            if let Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) = crossterm::event::read()?
+            {
+                match code {
+                    KeyCode::Char('j') | KeyCode::Down if skip + 1 < lines.len() => skip += 1,
+                    KeyCode::Char('k') | KeyCode::Up => skip = skip.saturating_sub(1),
+                    KeyCode::Char('j') | KeyCode::Down => {}
+                    _ => break,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Editor of the permission bits, showing the current and new mode next to each item.
+    /// Return the bits to turn on and off, or None if canceled.
+    fn edit_mode_bits(
+        &self,
+        state: &mut State,
+        targets: &[(PathBuf, String, u32)],
+    ) -> Result<Option<(u32, u32)>, FxError> {
+        let (mut set, mut clear) = (0, 0);
+        let mut cursor: usize = 0;
+        let mut octal = String::new();
+        let rows = ((state.layout.terminal_row - BEGINNING_ROW) as usize).max(1);
+        let width = state.layout.terminal_column.saturating_sub(3) as usize;
+        loop {
+            state.clear_and_show_headline();
+            for (i, (_, name, old)) in targets.iter().take(rows).enumerate() {
+                let new = (old & !clear) | set;
+                move_to(3, BEGINNING_ROW + i as u16);
+                let line = format!(
+                    "{} -> {} {}",
+                    to_mode_string(*old),
+                    to_mode_string(new),
+                    name
+                );
+                print!("{}", shorten_str_including_wide_char(&line, width));
+            }
+
+            go_to_info_line_and_reset();
+            print!("chmod ");
+            for (i, c) in "rwxrwxrwx".chars().enumerate() {
+                let bit = 0o400 >> i;
+                let c = if set & bit != 0 {
+                    c
+                } else if clear & bit != 0 {
+                    '-'
+                } else {
+                    '.'
+                };
+                if i == cursor {
+                    print!("{}", c.negative());
+                } else {
+                    print!("{}", c);
+                }
+            }
+            print!(" {}", octal);
+            print!("  h/l: move, <Space>: toggle, 0-7: octal, <CR>: apply, <Esc>: cancel");
+            stdout().flush()?;
+
+            if let Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) = crossterm::event::read()?
+            {
+                match code {
+                    KeyCode::Char('h') | KeyCode::Left => cursor = cursor.saturating_sub(1),
+                    KeyCode::Char('l') | KeyCode::Right => cursor = (cursor + 1).min(8),
+                    KeyCode::Char(' ') => {
+                        //unchanged -> on -> off -> unchanged
+                        let bit = 0o400 >> cursor;
+                        if set & bit != 0 {
+                            set &= !bit;
+                            clear |= bit;
+                        } else if clear & bit != 0 {
+                            clear &= !bit;
+                        } else {
+                            set |= bit;
+                        }
+                    }
+                    KeyCode::Char(c @ '0'..='7') => {
+                        octal.push(c);
+                        if let Some(mode) = parse_octal_mode(&octal) {
+                            set = mode;
+                            clear = !mode & 0o777;
+                            octal.clear();
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        octal.pop();
+                    }
+                    KeyCode::Enter => return Ok(Some((set, clear))),
+                    KeyCode::Esc => return Ok(None),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn run(&self, command: &mut Command) -> std::io::Result<ExitStatus> {
+        run_in_terminal(command)
+    }
+
+    /// Leave raw mode while the program runs, so that the input is read by lines.
+    fn run_line_input(&self, command: &mut Command) -> std::io::Result<ExitStatus> {
+        leave_raw_mode();
+        reenter_alternate_screen();
+        let status = run_in_terminal(command);
+        enter_raw_mode();
+        reenter_alternate_screen();
+        hide_cursor();
+        status
+    }
+}
@@ -0,0 +1,248 @@
+use super::errors::FxError;
+
+use rhai::{Array, Dynamic, Engine, Scope};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// A script is stopped after this many operations, so that a loop never freezes the UI.
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 64;
+
+/// What a user script can read of the state.
+#[derive(Debug, Default, Clone)]
+pub struct ScriptContext {
+    pub cwd: PathBuf,
+    /// The path of the item under the cursor.
+    pub item: Option<PathBuf>,
+    pub selected: Vec<PathBuf>,
+    /// The names of the listed items.
+    pub items: Vec<String>,
+}
+
+/// What a sort script can read of the item, as the constants of the same names.
+#[derive(Debug, Default, Clone)]
+pub struct SortContext {
+    pub name: String,
+    pub path: PathBuf,
+    /// The extension in lower case, empty if none.
+    pub ext: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Seconds since the Unix epoch, 0 if unknown.
+    pub modified: i64,
+}
+
+/// Actions that a user script requests by calling the functions of felix.
+/// They are applied in order after the script ends.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ScriptAction {
+    Cd(PathBuf),
+    Goto(String),
+    Select(String),
+    Unselect(String),
+    Reload,
+    Info(String),
+    CreateFile(String),
+    CreateDir(String),
+    Rename(String, String),
+    Delete(String),
+}
+
+/// Run the user script written in rhai, and return the actions it requests.
+/// Besides the functions below, the script can use the standard library of rhai,
+/// which has no access to files or processes.
+/// `args` (array of strings) holds the arguments given to `:script`.
+///
+/// To read the state: `cwd()`, `item()`, `selected()`, `items()`.
+/// To navigate: `cd(path)`, `go_to(name)`, `reload()`, `info(message)`, and `print`.
+/// To select: `select(name)`, `unselect(name)`.
+/// To manipulate files: `touch(name)`, `mkdir(name)`, `rename(from, to)`, `delete(name)`,
+/// which are undoable as their keys.
+/// Items are given by the name, or by the path as `item()` and `selected()` return.
+pub fn run_script(
+    source: &str,
+    context: &ScriptContext,
+    args: &[&str],
+) -> Result<Vec<ScriptAction>, FxError> {
+    let actions = Rc::new(RefCell::new(Vec::new()));
+    let engine = script_engine(context, &actions);
+    let mut scope = Scope::new();
+    let args: Array = args
+        .iter()
+        .map(|arg| Dynamic::from(arg.to_string()))
+        .collect();
+    scope.push_constant("args", args);
+    engine
+        .run_with_scope(&mut scope, source)
+        .map_err(|e| FxError::Script(e.to_string()))?;
+    let actions = actions.borrow().clone();
+    Ok(actions)
+}
+
+/// Check the syntax of the user script.
+pub fn compile_script(source: &str) -> Result<(), FxError> {
+    Engine::new()
+        .compile(source)
+        .map(|_| ())
+        .map_err(|e| FxError::Script(e.to_string()))
+}
+
+fn script_engine(context: &ScriptContext, actions: &Rc<RefCell<Vec<ScriptAction>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+
+    let cwd = context.cwd.to_string_lossy().into_owned();
+    engine.register_fn("cwd", move || cwd.clone());
+    let item = context
+        .item
+        .as_ref()
+        .map(|item| item.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    engine.register_fn("item", move || item.clone());
+    let selected: Array = context
+        .selected
+        .iter()
+        .map(|path| Dynamic::from(path.to_string_lossy().into_owned()))
+        .collect();
+    engine.register_fn("selected", move || selected.clone());
+    let items: Array = context.items.iter().cloned().map(Dynamic::from).collect();
+    engine.register_fn("items", move || items.clone());
+
+    let push = |action: fn(String) -> ScriptAction| {
+        let actions = Rc::clone(actions);
+        move |s: &str| actions.borrow_mut().push(action(s.to_owned()))
+    };
+    engine.register_fn("cd", push(|s| ScriptAction::Cd(PathBuf::from(s))));
+    engine.register_fn("go_to", push(ScriptAction::Goto));
+    engine.register_fn("select", push(ScriptAction::Select));
+    engine.register_fn("unselect", push(ScriptAction::Unselect));
+    engine.register_fn("info", push(ScriptAction::Info));
+    engine.register_fn("touch", push(ScriptAction::CreateFile));
+    engine.register_fn("mkdir", push(ScriptAction::CreateDir));
+    engine.register_fn("delete", push(ScriptAction::Delete));
+    let reload = Rc::clone(actions);
+    engine.register_fn("reload", move || {
+        reload.borrow_mut().push(ScriptAction::Reload)
+    });
+    let rename = Rc::clone(actions);
+    engine.register_fn("rename", move |from: &str, to: &str| {
+        rename
+            .borrow_mut()
+            .push(ScriptAction::Rename(from.to_owned(), to.to_owned()))
+    });
+    //Printing to stdout would break the screen.
+    let print = Rc::clone(actions);
+    engine.on_print(move |s| print.borrow_mut().push(ScriptAction::Info(s.to_owned())));
+    engine.on_debug(|s, _, _| log::debug!("SCRIPT: {}", s));
+    engine
+}
+
+/// Run the sort script written in rhai for each item, and return the keys in the same order.
+/// The script sees the item as `name`, `path`, `ext`, `is_dir`, `size` and `modified`,
+/// and its value is the key, compared as a string in the natural order.
+/// `()`, e.g. no value, is no key.
+pub fn run_sort_script(source: &str, items: &[SortContext]) -> Result<Vec<String>, FxError> {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    let ast = engine
+        .compile(source)
+        .map_err(|e| FxError::Script(e.to_string()))?;
+    items
+        .iter()
+        .map(|item| {
+            let mut scope = Scope::new();
+            scope.push_constant("name", item.name.clone());
+            scope.push_constant("path", item.path.to_string_lossy().into_owned());
+            scope.push_constant("ext", item.ext.clone());
+            scope.push_constant("is_dir", item.is_dir);
+            scope.push_constant("size", item.size as i64);
+            scope.push_constant("modified", item.modified);
+            let key: Dynamic = engine
+                .eval_ast_with_scope(&mut scope, &ast)
+                .map_err(|e| FxError::Script(e.to_string()))?;
+            Ok(if key.is_unit() {
+                String::new()
+            } else {
+                key.to_string()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_script() {
+        let context = ScriptContext {
+            cwd: PathBuf::from("/tmp"),
+            item: Some(PathBuf::from("/tmp/a.log")),
+            selected: vec![PathBuf::from("/tmp/b.txt")],
+            items: vec!["a.log".to_owned(), "b.txt".to_owned(), "c.log".to_owned()],
+        };
+        let source = r#"
+            for name in items() {
+                if name.ends_with(".log") { select(name) }
+            }
+            unselect(selected()[0]);
+            cd(cwd() + "/" + args[0]);
+            go_to(item());
+            touch("new.txt");
+            mkdir("new");
+            rename("a.log", "b.log");
+            delete("c.log");
+            reload();
+            print(`${items().len()} items`);
+            info("done!");
+        "#;
+        assert_eq!(
+            run_script(source, &context, &["sub"]).unwrap(),
+            vec![
+                ScriptAction::Select("a.log".to_string()),
+                ScriptAction::Select("c.log".to_string()),
+                ScriptAction::Unselect("/tmp/b.txt".to_string()),
+                ScriptAction::Cd(PathBuf::from("/tmp/sub")),
+                ScriptAction::Goto("/tmp/a.log".to_string()),
+                ScriptAction::CreateFile("new.txt".to_string()),
+                ScriptAction::CreateDir("new".to_string()),
+                ScriptAction::Rename("a.log".to_string(), "b.log".to_string()),
+                ScriptAction::Delete("c.log".to_string()),
+                ScriptAction::Reload,
+                ScriptAction::Info("3 items".to_string()),
+                ScriptAction::Info("done!".to_string()),
+            ]
+        );
+        assert!(run_script("", &context, &[]).unwrap().is_empty());
+        assert!(run_script("cd(", &context, &[]).is_err());
+        assert!(compile_script("cd(").is_err());
+        assert!(compile_script(r#"cd("..")"#).is_ok());
+        //Infinite loops are stopped.
+        assert!(run_script("loop {}", &context, &[]).is_err());
+    }
+
+    #[test]
+    fn test_run_sort_script() {
+        let item = |name: &str, size: u64| SortContext {
+            name: name.to_owned(),
+            path: PathBuf::from("/tmp").join(name),
+            size,
+            ..Default::default()
+        };
+        let items = [item("a.log", 5), item("b.txt", 20), item("c", 0)];
+        assert_eq!(
+            run_sort_script("if size > 0 { size }", &items).unwrap(),
+            vec!["5", "20", ""]
+        );
+        assert_eq!(
+            run_sort_script(r#"path.sub_string(5, 1) + name.len()"#, &items).unwrap(),
+            vec!["a5", "b5", "c1"]
+        );
+        assert!(run_sort_script("size +", &items).is_err());
+        assert!(run_sort_script("no_such_var", &items).is_err());
+        assert!(run_sort_script("loop {}", &items).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use std::path::Path;
+
+/// The label shown in the footer: the SELinux security context of the item,
+/// flagged with `!` and the type of the directory if the type differs from it.
+/// New items get the type of the directory by default, so a different one often means
+/// that the item was moved in from elsewhere and needs `restorecon`.
+/// Return None if SELinux is not enabled.
+pub fn context_label(path: &Path) -> Option<String> {
+    let context = security_context(path)?;
+    let dir_type = path
+        .parent()
+        .and_then(security_context)
+        .and_then(|dir| context_type(&dir).map(|t| t.to_owned()));
+    Some(format_label(&context, dir_type.as_deref()))
+}
+
+fn format_label(context: &str, dir_type: Option<&str>) -> String {
+    match (context_type(context), dir_type) {
+        (Some(t), Some(dir_type)) if t != dir_type => {
+            format!(" {} (! dir: {})", context, dir_type)
+        }
+        _ => format!(" {}", context),
+    }
+}
+
+/// The type, the third field of `user:role:type:level`.
+fn context_type(context: &str) -> Option<&str> {
+    context.split(':').nth(2)
+}
+
+/// Read `security.selinux` of the path, not following symlinks.
+#[cfg(target_os = "linux")]
+fn security_context(path: &Path) -> Option<String> {
+    use nix::libc;
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let name = c"security.selinux";
+    //The first call returns the size of the value.
+    let size = unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+    if size <= 0 {
+        return None;
+    }
+    let mut value = vec![0u8; size as usize];
+    let size = unsafe {
+        libc::lgetxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_mut_ptr() as *mut libc::c_void,
+            value.len(),
+        )
+    };
+    if size <= 0 {
+        return None;
+    }
+    value.truncate(size as usize);
+    let context = String::from_utf8_lossy(&value);
+    Some(context.trim_end_matches('\0').to_owned())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn security_context(_path: &Path) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_label() {
+        let context = "unconfined_u:object_r:user_home_t:s0";
+        assert_eq!(context_type(context), Some("user_home_t"));
+        assert_eq!(context_type("invalid"), None);
+        assert_eq!(
+            format_label(context, Some("user_home_t")),
+            " unconfined_u:object_r:user_home_t:s0"
+        );
+        assert_eq!(
+            format_label(context, Some("httpd_sys_content_t")),
+            " unconfined_u:object_r:user_home_t:s0 (! dir: httpd_sys_content_t)"
+        );
+        assert_eq!(
+            format_label(context, None),
+            " unconfined_u:object_r:user_home_t:s0"
+        );
+    }
+}
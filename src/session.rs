@@ -16,13 +16,165 @@ pub struct Session {
     pub show_hidden: bool,
     pub preview: Option<bool>,
     pub split: Option<Split>,
+    pub trash_size: Option<u64>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
 pub enum SortKey {
     #[default]
     Name,
     Time,
+    Size,
+    Extension,
+    /// Sorted by the keys the script of the name in `sort_scripts` returns.
+    Custom(String),
+}
+
+impl SortKey {
+    /// Return the next sort key, to cycle through them.
+    /// The scripts in `sort_scripts` come after the built-in keys.
+    pub fn next(&self, scripts: &[&String]) -> Self {
+        let custom = |i: usize| {
+            scripts
+                .get(i)
+                .map_or(SortKey::Name, |name| SortKey::Custom(name.to_string()))
+        };
+        match self {
+            SortKey::Name => SortKey::Time,
+            SortKey::Time => SortKey::Size,
+            SortKey::Size => SortKey::Extension,
+            SortKey::Extension => custom(0),
+            SortKey::Custom(name) => match scripts.iter().position(|script| *script == name) {
+                Some(i) => custom(i + 1),
+                None => SortKey::Name,
+            },
+        }
+    }
+}
+
+/// How to compare item names.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NameOrder {
+    /// Natural order, ignoring case (e.g. `README.md` next to `readme.txt`).
+    #[default]
+    Natural,
+    /// Natural order, upper case first.
+    NaturalCaseSensitive,
+    /// Collation of the current locale (LC_COLLATE).
+    Locale,
+}
+
+impl NameOrder {
+    pub fn compare(&self, a: &str, b: &str) -> std::cmp::Ordering {
+        match self {
+            NameOrder::Natural => natord::compare_ignore_case(a, b),
+            NameOrder::NaturalCaseSensitive => natord::compare(a, b),
+            NameOrder::Locale => locale_compare(a, b),
+        }
+    }
+
+    /// Make the key of the name in advance, so that sorting does not convert the names
+    /// on every comparison. None if the name itself is compared.
+    pub fn key(&self, name: &str) -> Option<NameKey> {
+        match self {
+            NameOrder::Natural => Some(NameKey::Lowercase(
+                name.chars().flat_map(char::to_lowercase).collect(),
+            )),
+            NameOrder::NaturalCaseSensitive => None,
+            NameOrder::Locale => locale_key(name).map(NameKey::Collation),
+        }
+    }
+
+    /// Whether the key was made by this order.
+    pub fn made(&self, key: &Option<NameKey>) -> bool {
+        match key {
+            Some(NameKey::Lowercase(_)) => *self == NameOrder::Natural,
+            Some(NameKey::Collation(_)) => *self == NameOrder::Locale,
+            None => *self == NameOrder::NaturalCaseSensitive,
+        }
+    }
+}
+
+/// Name of an item, made comparable by `NameOrder::key`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NameKey {
+    /// Lowercased name, compared in the natural order.
+    Lowercase(String),
+    /// strxfrm(3) of the name, compared as bytes.
+    Collation(Vec<u8>),
+}
+
+impl NameKey {
+    /// Compare the keys made by the same order.
+    pub fn compare(&self, other: &NameKey) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (NameKey::Lowercase(a), NameKey::Lowercase(b)) => Some(natord::compare(a, b)),
+            (NameKey::Collation(a), NameKey::Collation(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+}
+
+/// Use LC_COLLATE of the environment.
+#[cfg(unix)]
+fn init_collation() {
+    use std::sync::Once;
+
+    static INIT: Once = Once::new();
+    INIT.call_once(|| unsafe {
+        nix::libc::setlocale(nix::libc::LC_COLLATE, c"".as_ptr());
+    });
+}
+
+/// Compare strings by strcoll(3), falling back to the natural order.
+#[cfg(unix)]
+fn locale_compare(a: &str, b: &str) -> std::cmp::Ordering {
+    use nix::libc;
+    use std::ffi::CString;
+
+    init_collation();
+    match (CString::new(a), CString::new(b)) {
+        (Ok(a), Ok(b)) => unsafe { libc::strcoll(a.as_ptr(), b.as_ptr()) }.cmp(&0),
+        _ => natord::compare_ignore_case(a, b),
+    }
+}
+
+#[cfg(not(unix))]
+fn locale_compare(a: &str, b: &str) -> std::cmp::Ordering {
+    natord::compare_ignore_case(a, b)
+}
+
+/// Transform the string by strxfrm(3), so that the bytes compare as strcoll(3) does.
+#[cfg(unix)]
+fn locale_key(s: &str) -> Option<Vec<u8>> {
+    use nix::libc;
+    use std::ffi::CString;
+
+    init_collation();
+    let s = CString::new(s).ok()?;
+    let len = unsafe { libc::strxfrm(std::ptr::null_mut(), s.as_ptr(), 0) };
+    let mut key = vec![0u8; len + 1];
+    unsafe { libc::strxfrm(key.as_mut_ptr().cast(), s.as_ptr(), key.len()) };
+    key.truncate(len);
+    Some(key)
+}
+
+#[cfg(not(unix))]
+fn locale_key(_s: &str) -> Option<Vec<u8>> {
+    None
+}
+
+impl std::fmt::Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let printable = match self {
+            SortKey::Name => "name",
+            SortKey::Time => "time",
+            SortKey::Size => "size",
+            SortKey::Extension => "ext",
+            SortKey::Custom(name) => name,
+        };
+        write!(f, "{}", printable)
+    }
 }
 
 pub fn read_session(session_path: &Path) -> Session {
@@ -34,6 +186,7 @@ pub fn read_session(session_path: &Path) -> Session {
                 show_hidden: true,
                 preview: Some(false),
                 split: Some(Split::Vertical),
+                trash_size: None,
             },
         },
         Err(_) => Session {
@@ -41,6 +194,7 @@ pub fn read_session(session_path: &Path) -> Session {
             show_hidden: true,
             preview: Some(false),
             split: Some(Split::Vertical),
+            trash_size: None,
         },
     }
 }
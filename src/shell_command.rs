@@ -0,0 +1,152 @@
+use super::errors::FxError;
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Commands kept in the history file.
+const HISTORY_SIZE: usize = 1000;
+
+/// Shell commands run by `!`, the newest last, saved in the state directory.
+#[derive(Debug, Default)]
+pub struct History {
+    path: Option<PathBuf>,
+    entries: Vec<String>,
+}
+
+impl History {
+    /// Read the history file, which may not exist yet.
+    pub fn read(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(|line| line.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        History {
+            path: Some(path),
+            entries,
+        }
+    }
+
+    /// Add the command, moving it to the end if run before, and save the history.
+    pub fn add(&mut self, command: &str) -> Result<(), FxError> {
+        self.entries.retain(|entry| entry != command);
+        self.entries.push(command.to_owned());
+        if self.entries.len() > HISTORY_SIZE {
+            self.entries.drain(..self.entries.len() - HISTORY_SIZE);
+        }
+        if let Some(path) = &self.path {
+            let mut content = self.entries.join("\n");
+            content.push('\n');
+            fs::write(path, content)?;
+        }
+        Ok(())
+    }
+
+    /// The commands, the newest last.
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+}
+
+/// Expand the placeholders in the command:
+/// `%f` to the item under the cursor, `%s` to the selected items (or the item under the cursor
+/// if none is selected), `%d` to the current directory, and `%%` to `%`.
+/// The paths are quoted for the shell, keeping the names not valid UTF-8 as they are.
+pub fn expand_placeholders(
+    command: &str,
+    item: Option<&Path>,
+    selected: &[&Path],
+    dir: &Path,
+) -> OsString {
+    let quote = |path: &Path, expanded: &mut Vec<u8>| {
+        expanded.push(b'\'');
+        for b in path.as_os_str().as_encoded_bytes() {
+            if *b == b'\'' {
+                expanded.extend_from_slice(b"'\\''");
+            } else {
+                expanded.push(*b);
+            }
+        }
+        expanded.push(b'\'');
+    };
+
+    let mut expanded: Vec<u8> = Vec::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            let mut buf = [0; 4];
+            expanded.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.clone().next() {
+            Some('f') => item.into_iter().for_each(|item| quote(item, &mut expanded)),
+            Some('s') => {
+                let paths: Vec<&Path> = if selected.is_empty() {
+                    item.into_iter().collect()
+                } else {
+                    selected.to_vec()
+                };
+                for (i, path) in paths.iter().enumerate() {
+                    if i > 0 {
+                        expanded.push(b' ');
+                    }
+                    quote(path, &mut expanded);
+                }
+            }
+            Some('d') => quote(dir, &mut expanded),
+            Some('%') => expanded.push(b'%'),
+            _ => {
+                expanded.push(b'%');
+                continue;
+            }
+        }
+        chars.next();
+    }
+    // SAFETY: The bytes are those of `OsStr`s and `str`s concatenated at the boundaries of them,
+    // which `OsStr::as_encoded_bytes` allows.
+    unsafe { OsString::from_encoded_bytes_unchecked(expanded) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_placeholders() {
+        let dir = Path::new("/tmp/dir");
+        let item = Path::new("/tmp/dir/it's.txt");
+        let a = Path::new("/tmp/dir/a b");
+        let b = Path::new("/tmp/dir/b");
+        assert_eq!(
+            expand_placeholders("cp %s %d/x # %f 100%% %x", Some(item), &[a, b], dir),
+            OsString::from(
+                "cp '/tmp/dir/a b' '/tmp/dir/b' '/tmp/dir'/x # '/tmp/dir/it'\\''s.txt' 100% %x"
+            )
+        );
+        assert_eq!(
+            expand_placeholders("rm %s", Some(b), &[], dir),
+            OsString::from("rm '/tmp/dir/b'")
+        );
+        assert_eq!(
+            expand_placeholders("ls %f", None, &[], dir),
+            OsString::from("ls ")
+        );
+    }
+
+    #[test]
+    fn test_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+        let mut history = History::read(path.clone());
+        assert!(history.entries.is_empty());
+        history.add("ls").unwrap();
+        history.add("make").unwrap();
+        history.add("ls").unwrap();
+        assert_eq!(History::read(path).entries, vec!["make", "ls"]);
+    }
+}
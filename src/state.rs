@@ -1,34 +1,57 @@
+use super::chooser::Chooser;
+use super::color::*;
+use super::column::{columns_width, render_columns, Column};
 use super::config::*;
+use super::conflict::{OnConflict, Resolution, Resolver};
+use super::copy::{copy_file, copy_tree, merge_tree};
+use super::database::is_sqlite;
+use super::disk::disk_space;
 use super::errors::FxError;
+use super::filter::{Filter, FilterPreset};
+use super::font::is_font;
+use super::frontend::{Frontend, Headless};
 use super::functions::*;
-use super::help::HELP;
+use super::git;
+use super::item::*;
+use super::jobs::{JobKind, Jobs};
+use super::journal::{Journal, JournalKind};
 use super::jumplist::*;
+use super::keymap::{Action, Keymap};
 use super::layout::*;
 use super::magic_image;
-use super::magic_packed;
+use super::magic_packed::{self, ArchiveDiff};
 use super::nums::*;
 use super::op::*;
+use super::opener::{to_opener_map, Opener};
+use super::pattern::{Pattern, Substitution};
+use super::progress::Progress;
+use super::report::ErrorReport;
+use super::script::*;
+use super::selinux::context_label;
 use super::session::*;
-use super::term::*;
+use super::shell_command::{expand_placeholders, History};
+use super::table::table_delimiter;
+use super::template::Field;
+use super::trash::*;
 
 use chrono::prelude::*;
-use crossterm::event::KeyEventKind;
-use crossterm::event::{Event, KeyCode, KeyEvent};
-use crossterm::style::Stylize;
+use crossterm::cursor::{MoveLeft, MoveRight};
+use crossterm::event::KeyCode;
+use crossterm::style::{ResetColor, Stylize};
 use log::info;
 use normpath::PathExt;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::collections::{BTreeMap, BTreeSet};
 use std::env;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fmt::Write as _;
 use std::fs;
-use std::io::Stdout;
 use std::io::Write;
 use std::path::PathBuf;
-use std::process::{Command, ExitStatus, Stdio};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
 use std::time::Instant;
-use std::time::UNIX_EPOCH;
 
 #[cfg(target_family = "unix")]
 use nix::sys::stat::Mode;
@@ -40,9 +63,33 @@ use std::os::unix::fs::MetadataExt;
 use std::os::unix::fs::PermissionsExt;
 
 pub const BEGINNING_ROW: u16 = 3;
+/// Where the item list starts to scroll.
+const SCROLL_POINT: u16 = 3;
 pub const EMPTY_WARNING: &str = "Are you sure to empty the trash directory? (if yes: y)";
 
+/// In MB.
+const DEFAULT_TRASH_REPORT_THRESHOLD: u64 = 1_000;
+const DEFAULT_TRASH_SIZE_LIMIT: u64 = 5_000;
+const DEFAULT_BACKGROUND_THRESHOLD: u64 = 1_000;
+const DEFAULT_OPEN_SIZE_LIMIT: u64 = 1_000;
+//Bytes per second, used until a copy is measured.
+const DEFAULT_COPY_RATE: f64 = 100_000_000.0;
+const MIN_SIZE_TO_MEASURE_COPY_RATE: u64 = 10_000_000;
 const MAX_SIZE_TO_PREVIEW: u64 = 1_000_000_000;
+/// Paths passed to one `sudo chown`.
+const SUDO_CHOWN_BATCH: usize = 1_000;
+/// The progress of chown is shown every this number of entries.
+const CHOWN_PROGRESS_INTERVAL: usize = 1_000;
+const NEW_ITEM_HIGHLIGHT: std::time::Duration = std::time::Duration::from_secs(2);
+/// Bursts of changes (e.g. extracting an archive) refresh the list at most every this interval;
+/// the rest is caught by the next tick.
+const REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+const SEARCH_REDRAW_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+/// Entries sent at once from the thread reading the directory.
+const LISTING_BATCH: usize = 1_000;
+/// A large directory is shown partially after this, and the spinner turns every this interval.
+const LISTING_REDRAW_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
 const MAX_SIZE_TO_PREVIEW_TEXT: u64 = 1_000_000;
 
 #[derive(Debug, Default)]
@@ -50,12 +97,21 @@ pub struct State {
     pub list: Vec<ItemInfo>,
     pub current_dir: PathBuf,
     pub trash_dir: PathBuf,
+    /// `info` of the freedesktop.org trash, None in the legacy trash.
+    pub trash_info_dir: Option<PathBuf>,
+    pub legacy_trash: bool,
     pub config_path: Option<PathBuf>,
     pub lwd_file: Option<PathBuf>,
+    pub chooser: Option<Chooser>,
     pub match_vim_exit_behavior: bool,
     pub has_zoxide: bool,
     pub default: String,
-    pub commands: Option<BTreeMap<String, String>>,
+    pub commands: Option<BTreeMap<String, Opener>>,
+    pub alt_commands: Option<BTreeMap<String, Opener>>,
+    pub scripts: BTreeMap<String, String>,
+    pub searches: BTreeMap<String, SavedSearch>,
+    pub terminal: Option<String>,
+    pub terminal_here: Option<String>,
     pub ignore_case: Option<bool>,
     pub registers: Registers,
     pub operations: Operation,
@@ -63,9 +119,49 @@ pub struct State {
     pub c_memo: Vec<StateMemo>,
     pub p_memo: Vec<StateMemo>,
     pub keyword: Option<String>,
+    pub filter: Option<Filter>,
+    /// The name of the preset if the filter is set by it.
+    pub filter_preset: Option<String>,
     pub layout: Layout,
     pub v_start: Option<usize>,
     pub is_ro: bool,
+    pub marked: BTreeMap<PathBuf, ItemBuffer>,
+    pub collapsed_trash: BTreeSet<TrashGroup>,
+    pub trash_size: Option<u64>,
+    /// The modified time of the trash dir when its size was last calculated.
+    pub trash_modified: Option<std::time::SystemTime>,
+    pub trash_report_threshold: u64,
+    pub auto_refresh: bool,
+    pub mouse: bool,
+    pub alternate_screen: bool,
+    pub preserve_metadata: bool,
+    pub use_systemd_run: bool,
+    /// The timezone to display the modified time in.
+    pub timezone: Timezone,
+    pub keymap: Keymap,
+    /// Toggled by `:relative`.
+    pub show_relative: bool,
+    pub relative_base: Option<PathBuf>,
+    /// The dir that the paths are shown relative to, while `show_relative` is on.
+    pub anchor: Option<PathBuf>,
+    pub on_conflict: OnConflict,
+    pub trash_size_limit: Option<u64>,
+    pub open_size_limit: Option<u64>,
+    /// Program name -> size limit in MB, overriding `open_size_limit`.
+    pub command_size_limits: BTreeMap<String, u64>,
+    pub background_threshold: Option<u64>,
+    pub jobs: Jobs,
+    pub journal: Journal,
+    pub shell_history: History,
+    pub copy_rate: Option<f64>,
+    pub dir_modified: Option<std::time::SystemTime>,
+    /// Set when the watcher reports changes in the current directory,
+    /// which include writes not changing the modified time of the directory.
+    pub dir_changed: bool,
+    pub last_refresh: Option<Instant>,
+    pub new_items: BTreeMap<PathBuf, Instant>,
+    /// Draws the state and asks the user while an operation runs. None to run without it.
+    pub frontend: Option<Arc<dyn Frontend>>,
 }
 
 #[derive(Debug, Default)]
@@ -74,6 +170,8 @@ pub struct Registers {
     pub zero: Vec<ItemBuffer>,
     pub numbered: VecDeque<Vec<ItemBuffer>>,
     pub named: BTreeMap<char, Vec<ItemBuffer>>,
+    /// Items cut by `x`, moved by the next put.
+    pub cut: Vec<ItemBuffer>,
 }
 
 impl Registers {
@@ -97,6 +195,7 @@ impl Registers {
     /// Register selected items to unnamed and zero registers.
     /// Also register to named when needed.
     pub fn yank_item(&mut self, items: &[ItemBuffer], reg: Option<char>, append: bool) -> usize {
+        self.cut.clear();
         self.unnamed = items.to_vec();
         match reg {
             None => {
@@ -192,10 +291,13 @@ impl Registers {
 
 /// To avoid cost copying ItemInfo, use ItemBuffer
 /// when tinkering with register or multiple renaming.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ItemBuffer {
     pub file_type: FileType,
     pub file_name: String,
+    /// See `ItemInfo::os_name`.
+    #[serde(default)]
+    pub os_name: Option<OsString>,
     pub file_path: std::path::PathBuf,
 }
 
@@ -204,49 +306,74 @@ impl ItemBuffer {
         ItemBuffer {
             file_type: item.file_type,
             file_name: item.file_name.clone(),
+            os_name: item.os_name.clone(),
             file_path: item.file_path.clone(),
         }
     }
+
+    /// The same item, moved to the path.
+    pub fn at(&self, file_path: PathBuf) -> Self {
+        ItemBuffer {
+            file_path,
+            ..self.clone()
+        }
+    }
+
+    /// The name on the disk, kept even if it is not valid UTF-8.
+    /// `file_path` of the trashed item has the name in the trash instead.
+    pub fn os_file_name(&self) -> OsString {
+        self.os_name
+            .clone()
+            .unwrap_or_else(|| OsString::from(&self.file_name))
+    }
 }
 
-#[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub struct ItemInfo {
-    pub file_type: FileType,
-    pub file_name: String,
-    pub file_path: std::path::PathBuf,
-    pub symlink_dir_path: Option<PathBuf>,
-    pub file_size: u64,
-    pub file_ext: Option<String>,
-    pub modified: Option<String>,
-    pub is_hidden: bool,
-    pub selected: bool,
-    pub matches: bool,
-    pub preview_type: Option<PreviewType>,
-    pub preview_scroll: usize,
-    pub content: Option<String>,
-    pub permissions: Option<u32>,
-    pub is_dirty: bool,
+/// What to do with a file larger than the size limit of the command opening it.
+pub enum LargeFile {
+    Open,
+    Preview,
+    Pager,
+    Cancel,
+}
+
+/// What to draw after `State::apply`, with the cursor at `layout.y`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Render {
+    Nothing,
+    /// Move the cursor only.
+    Cursor,
+    /// Redraw the list.
+    List,
+    /// Redraw the header and the list.
+    All,
 }
 
-#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub enum FileType {
-    Directory,
-    #[default]
-    File,
-    Symlink,
+/// How to put an item, decided by `State::put_name`.
+enum PutAs {
+    /// Put with the name, which may be renamed to avoid the conflict.
+    New(OsString),
+    /// Move the existing item with the name to the trash, and put the new one.
+    Overwrite(OsString),
+    /// Merge into the existing directory with the name.
+    Merge(OsString),
 }
 
 impl State {
-    /// Initialize the state of the app.
-    pub fn new(session_path: &std::path::Path) -> Result<Self, FxError> {
+    /// Initialize the state of the app, for the terminal of the size.
+    pub fn new(session_path: &std::path::Path, column: u16, row: u16) -> Result<Self, FxError> {
         //Read config file.
         //Use default configuration if the file does not exist or cannot be read.
-        let config_with_path = read_config_or_default();
-        let (config_path, config) = match config_with_path {
+        //If the file exists but is invalid, exit with the error,
+        //rather than launching with the config different from the file.
+        let (config_path, config) = match read_config_or_default() {
             Ok(c) => (c.config_path, c.config),
+            //No config directory, so no config file.
+            Err(FxError::Dirs(_)) => (None, Config::default()),
             Err(e) => {
-                eprintln!("Cannot read the config file properly.\nError: {}\nfelix launches with default configuration.", e);
-                (None, Config::default())
+                return Err(FxError::Yaml(format!(
+                "{}\nFix the config file, or move it away to launch felix with the default config.",
+                e
+            )))
             }
         };
         let mut state = State::default();
@@ -257,21 +384,117 @@ impl State {
         Ok(State {
             config_path,
             has_zoxide,
-            layout: Layout::new(session_path, config)?,
+            layout: Layout::new(session_path, config, column, row),
             ..state
         })
     }
 
+    /// The frontend to report to, or `Headless` if not set.
+    pub fn ui(&self) -> Arc<dyn Frontend> {
+        match &self.frontend {
+            Some(frontend) => frontend.clone(),
+            None => Arc::new(Headless::default()),
+        }
+    }
+
+    /// Move the cursor to `y` and let the frontend draw the list.
+    fn draw(&mut self, y: u16) {
+        self.set_cursor(y);
+        let ui = self.ui();
+        ui.redraw(self, y);
+    }
+
+    /// Show the result of the operation.
+    fn info<T: std::fmt::Display>(&self, message: T) {
+        self.ui().info(&message.to_string());
+    }
+
+    /// Show what went wrong, or what is not allowed.
+    fn warn<T: std::fmt::Display>(&self, message: T) {
+        self.ui().warn(&message.to_string());
+    }
+
+    /// Ask the question, and return the key pressed.
+    fn ask<T: std::fmt::Display>(&self, question: T) -> Result<KeyCode, FxError> {
+        Ok(self.ui().ask(&question.to_string())?.code)
+    }
+
     /// Set configuration from config file.
     pub fn set_config(&mut self, config: Config) {
         self.default = config
             .default
             .unwrap_or_else(|| env::var("EDITOR").unwrap_or_default());
         self.match_vim_exit_behavior = config.match_vim_exit_behavior.unwrap_or_default();
-        self.commands = to_extension_map(&config.exec);
+        self.commands = to_opener_map(&config.exec);
+        self.alt_commands = to_opener_map(&config.alt_exec);
+        self.scripts = config.script.unwrap_or_default();
+        self.searches = config.searches.unwrap_or_default();
+        self.terminal = config.terminal;
+        self.terminal_here = config.terminal_here;
+        self.auto_refresh = config.auto_refresh.unwrap_or(true);
+        self.mouse = config.mouse.unwrap_or(true);
+        self.alternate_screen = config.alternate_screen.unwrap_or(true);
+        self.preserve_metadata = config.preserve_metadata.unwrap_or(true);
+        self.legacy_trash = config.legacy_trash.unwrap_or_default();
+        self.use_systemd_run = config.use_systemd_run.unwrap_or_default();
+        self.relative_base = config.relative_base.map(|base| expand_tilde(&base));
+        //Checked when reading the config.
+        self.keymap = Keymap::new(
+            &config.keys.unwrap_or_default(),
+            &config.filter_presets.unwrap_or_default(),
+            config.leader.as_deref(),
+        )
+        .unwrap_or_default();
+        self.on_conflict = config.on_conflict.unwrap_or_default();
+        self.trash_size_limit = match config.trash_size_limit {
+            Some(0) => None,
+            limit => Some(limit.unwrap_or(DEFAULT_TRASH_SIZE_LIMIT) * 1_000_000),
+        };
+        self.open_size_limit = match config.open_size_limit {
+            Some(0) => None,
+            limit => Some(limit.unwrap_or(DEFAULT_OPEN_SIZE_LIMIT) * 1_000_000),
+        };
+        self.command_size_limits = config.command_size_limits.unwrap_or_default();
+        self.background_threshold = match config.background_threshold {
+            Some(0) => None,
+            threshold => Some(threshold.unwrap_or(DEFAULT_BACKGROUND_THRESHOLD) * 1_000_000),
+        };
+        self.trash_report_threshold = config
+            .trash_report_threshold
+            .unwrap_or(DEFAULT_TRASH_REPORT_THRESHOLD)
+            * 1_000_000;
         self.ignore_case = config.ignore_case;
-        let colors = config.color.unwrap_or_default();
-        self.layout.colors = colors;
+        self.layout.theme = read_theme(config.theme.as_deref());
+        self.layout.colors = config
+            .color
+            .unwrap_or_else(|| self.layout.theme.item_colors());
+        self.layout.ls_colors = read_ls_colors(config.ls_colors);
+        self.layout.columns = config.columns.unwrap_or_else(Column::default_columns);
+        self.layout.column_widths = config.column_widths.unwrap_or_default();
+        //Checked when reading the config.
+        self.layout.time_format = config
+            .time_format
+            .as_deref()
+            .and_then(|format| TimeFormat::parse(format).ok())
+            .unwrap_or_default();
+        self.layout.header_format = to_template(config.header_format.as_deref());
+        self.layout.status_format = to_template(config.status_format.as_deref());
+        self.layout.name_order = config.name_order.unwrap_or_default();
+        self.layout.sort_scripts = config.sort_scripts.clone().unwrap_or_default();
+        self.layout.directories_first = config.directories_first.unwrap_or(true);
+        if let Some(split) = config.preview_split {
+            self.layout.split = split;
+        }
+        self.layout.preview_size = to_preview_size(config.preview_size);
+        //Checked when reading the config.
+        self.timezone = config
+            .timezone
+            .as_deref()
+            .and_then(|timezone| Timezone::parse(timezone).ok())
+            .unwrap_or_default();
+        for item in self.list.iter_mut() {
+            item.modified_string = None;
+        }
     }
 
     /// Select item that the cursor points to.
@@ -288,180 +511,186 @@ impl State {
             .ok_or(FxError::GetItem)
     }
 
+    /// The command opening the file: `exec` for the extension, or the default.
+    fn opener(&self, item: &ItemInfo) -> &str {
+        item.file_ext
+            .as_ref()
+            .and_then(|ext| self.commands.as_ref()?.get(ext))
+            .map_or(&self.default, |opener| &opener.command)
+    }
+
+    /// If the file is larger than the size limit of the command opening it,
+    /// ask whether to open it anyway, show it in the preview, or in the pager.
+    pub fn confirm_large_file(&self, item: &ItemInfo) -> Result<LargeFile, FxError> {
+        let opener = self.opener(item);
+        let program = program_name(opener);
+        let limit = match size_limit(program, self.open_size_limit, &self.command_size_limits) {
+            Some(limit) => limit,
+            None => return Ok(LargeFile::Open),
+        };
+        // Follow the symlink.
+        let size = fs::metadata(&item.file_path)?.len();
+        if size <= limit {
+            return Ok(LargeFile::Open);
+        }
+        let choice = match self.ask(format!(
+            "{} is {} (over {} for {}). o: open anyway, v: preview, p: pager, other: cancel",
+            item.file_name,
+            to_decimal_size(size),
+            to_decimal_size(limit),
+            program
+        ))? {
+            KeyCode::Char('o') => LargeFile::Open,
+            KeyCode::Char('v') => LargeFile::Preview,
+            KeyCode::Char('p') => LargeFile::Pager,
+            _ => LargeFile::Cancel,
+        };
+        Ok(choice)
+    }
+
+    /// Open the file in `$PAGER`, or `less` if not set.
+    pub fn open_in_pager(&self, item: &ItemInfo) -> Result<(), FxError> {
+        let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_owned());
+        info!("OPEN(pager): {:?}", item.file_path);
+        self.open_by(&Opener::new(&pager), &item.file_path)
+    }
+
     /// Open the selected file according to the config.
-    pub fn open_file(&self, item: &ItemInfo) -> Result<ExitStatus, FxError> {
+    pub fn open_file(&self, item: &ItemInfo) -> Result<(), FxError> {
         let path = &item.file_path;
-        let map = &self.commands;
-        let extension = item.file_ext.as_ref();
-
-        let mut default = Command::new(&self.default);
+        let opener = item
+            .file_ext
+            .as_ref()
+            .and_then(|ext| self.commands.as_ref()?.get(ext));
 
         info!("OPEN: {:?}", path);
 
-        match map {
-            None => default
-                .arg(path)
-                .status()
+        match opener {
+            Some(opener) => self.open_by(opener, path),
+            None => self
+                .open_by(&Opener::new(&self.default), path)
                 .map_err(|_| FxError::DefaultEditor),
-            Some(map) => match extension {
-                None => default
-                    .arg(path)
-                    .status()
-                    .map_err(|_| FxError::DefaultEditor),
-                Some(extension) => match map.get(extension) {
-                    Some(command) => {
-                        let command: Vec<&str> = command.split_ascii_whitespace().collect();
-                        //If the key has no arguments
-                        if command.len() == 1 {
-                            let mut ex = Command::new(command[0]);
-                            ex.arg(path)
-                                .status()
-                                .map_err(|e| FxError::OpenItem(e.to_string()))
-                        } else {
-                            let mut args: Vec<&OsStr> =
-                                command[1..].iter().map(|x| x.as_ref()).collect();
-                            args.push(path.as_ref());
-                            let mut ex = Command::new(command[0]);
-                            ex.args(args)
-                                .status()
-                                .map_err(|e| FxError::OpenItem(e.to_string()))
-                        }
-                    }
-                    None => default
-                        .arg(path)
-                        .status()
-                        .map_err(|_| FxError::DefaultEditor),
-                },
-            },
         }
     }
 
-    #[cfg(any(target_os = "linux", target_os = "netbsd"))]
-    /// Open the selected file in a new window, according to the config.
-    pub fn open_file_in_new_window(&self) -> Result<(), FxError> {
-        let item = self.get_item()?;
-        let path = &item.file_path;
-        let map = &self.commands;
-        let extension = &item.file_ext;
+    /// Run the alternate action of the selected file according to `alt_exec` in the config.
+    pub fn open_file_alt(&self, item: &ItemInfo) -> Result<(), FxError> {
+        let opener = item
+            .file_ext
+            .as_ref()
+            .and_then(|ext| self.alt_commands.as_ref()?.get(ext))
+            .ok_or_else(|| {
+                FxError::OpenItem("No alternate action for this extension.".to_owned())
+            })?;
 
-        info!("OPEN(new window): {:?}", path);
+        info!("OPEN(alternate): {:?}", item.file_path);
 
-        match map {
-            None => Err(FxError::OpenNewWindow("No exec configuration".to_owned())),
-            Some(map) => match extension {
-                Some(extension) => match map.get(extension) {
-                    Some(command) => match unsafe { nix::unistd::fork() } {
-                        Ok(result) => match result {
-                            nix::unistd::ForkResult::Parent { child } => {
-                                nix::sys::wait::waitpid(Some(child), None)?;
-                                Ok(())
-                            }
-                            nix::unistd::ForkResult::Child => {
-                                nix::unistd::setsid()?;
-                                let command: Vec<&str> = command.split_ascii_whitespace().collect();
-                                if command.len() == 1 {
-                                    let mut ex = Command::new(command[0]);
-                                    ex.arg(path)
-                                        .stdout(Stdio::null())
-                                        .stdin(Stdio::null())
-                                        .spawn()
-                                        .and(Ok(()))
-                                        .map_err(|e| FxError::OpenItem(e.to_string()))?;
-                                    drop(ex);
-                                    std::process::exit(0);
-                                } else {
-                                    let mut args: Vec<&OsStr> =
-                                        command[1..].iter().map(|x| x.as_ref()).collect();
-                                    args.push(path.as_ref());
-                                    let mut ex = Command::new(command[0]);
-                                    ex.args(args)
-                                        .stdout(Stdio::null())
-                                        .stdin(Stdio::null())
-                                        .spawn()
-                                        .and(Ok(()))
-                                        .map_err(|e| FxError::OpenItem(e.to_string()))?;
-                                    drop(ex);
-                                    std::process::exit(0);
-                                }
-                            }
-                        },
-                        Err(e) => Err(FxError::Nix(e.to_string())),
-                    },
-                    None => Err(FxError::OpenNewWindow(
-                        "Cannot open this type of item in new window".to_owned(),
-                    )),
-                },
-
-                None => Err(FxError::OpenNewWindow(
-                    "Cannot open this type of item in new window".to_owned(),
-                )),
-            },
-        }
+        self.open_by(opener, &item.file_path)
     }
 
-    #[cfg(all(not(target_os = "linux"), not(target_os = "netbsd")))]
     /// Open the selected file in a new window, according to the config.
     pub fn open_file_in_new_window(&self) -> Result<(), FxError> {
         let item = self.get_item()?;
         let path = &item.file_path;
-        let map = &self.commands;
-        let extension = &item.file_ext;
+        let map = self
+            .commands
+            .as_ref()
+            .ok_or_else(|| FxError::OpenNewWindow("No exec configuration".to_owned()))?;
+        let opener = item
+            .file_ext
+            .as_ref()
+            .and_then(|ext| map.get(ext))
+            .ok_or_else(|| {
+                FxError::OpenNewWindow("Cannot open this type of item in new window".to_owned())
+            })?;
 
         info!("OPEN(new window): {:?}", path);
 
-        match map {
-            None => Err(FxError::OpenNewWindow("No exec configuration".to_owned())),
-            Some(map) => match extension {
-                Some(extension) => match map.get(extension) {
-                    Some(command) => {
-                        let mut ex = Command::new(command);
-                        ex.arg(path)
-                            .stdout(Stdio::null())
-                            .stdin(Stdio::null())
-                            .spawn()
-                            .and(Ok(()))
-                            .map_err(|e| (FxError::OpenItem(e.to_string())))
+        self.detach(&opener.args(path)?, &opener.dir(path, &self.current_dir))
+    }
+
+    /// Run the command for the file: detached if so configured,
+    /// otherwise in the terminal, waiting for it to exit.
+    fn open_by(&self, opener: &Opener, path: &std::path::Path) -> Result<(), FxError> {
+        let args = opener.args(path)?;
+        let dir = opener.dir(path, &self.current_dir);
+        if opener.detach {
+            return self.detach(&args, &dir);
+        }
+        self.ui()
+            .run(Command::new(&args[0]).args(&args[1..]).current_dir(dir))
+            .map(|_| ())
+            .map_err(|e| FxError::OpenItem(e.to_string()))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "netbsd"))]
+    /// Spawn the program in a new session, so that it does not take the terminal,
+    /// nor is killed with fx.
+    fn detach(&self, args: &[OsString], dir: &std::path::Path) -> Result<(), FxError> {
+        match unsafe { nix::unistd::fork() } {
+            Ok(result) => match result {
+                nix::unistd::ForkResult::Parent { child } => {
+                    nix::sys::wait::waitpid(Some(child), None)?;
+                    Ok(())
+                }
+                nix::unistd::ForkResult::Child => {
+                    nix::unistd::setsid()?;
+                    //Exit here not to return to the main loop in the child.
+                    match spawn_detached(args, dir, self.use_systemd_run) {
+                        Ok(()) => std::process::exit(0),
+                        Err(_) => std::process::exit(1),
                     }
-                    None => Err(FxError::OpenNewWindow(
-                        "Cannot open this type of item in new window".to_owned(),
-                    )),
-                },
-
-                None => Err(FxError::OpenNewWindow(
-                    "Cannot open this type of item in new window".to_owned(),
-                )),
+                }
             },
+            Err(e) => Err(FxError::Nix(e.to_string())),
         }
     }
 
+    #[cfg(all(not(target_os = "linux"), not(target_os = "netbsd")))]
+    /// Spawn the program without the terminal.
+    fn detach(&self, args: &[OsString], dir: &std::path::Path) -> Result<(), FxError> {
+        Command::new(&args[0])
+            .args(&args[1..])
+            .current_dir(dir)
+            .stdout(Stdio::null())
+            .stdin(Stdio::null())
+            .spawn()
+            .and(Ok(()))
+            .map_err(|e| (FxError::OpenItem(e.to_string())))
+    }
+
     /// Delete item.
-    pub fn delete(
-        &mut self,
-        reg: Option<char>,
-        append: bool,
-        screen: &mut Stdout,
-    ) -> Result<(), FxError> {
-        hide_cursor();
-        print_info("DELETE: Processing...", self.layout.y);
-        screen.flush()?;
+    pub fn delete(&mut self, reg: Option<char>, append: bool) -> Result<(), FxError> {
+        self.ui().status("DELETE: Processing...");
         let start = Instant::now();
 
         let target = self.get_item()?;
         let target = vec![ItemBuffer::new(target)];
+        let (target, mut total, mut freed) = self.confirm_large_items(target)?;
+        if self.delete_in_background(&target)? {
+            return Ok(());
+        }
 
-        match self.move_to_trash(&target, true) {
-            Err(e) => {
-                return Err(e);
-            }
-            Ok((src, dest)) => {
-                self.yank_after_delete(&src, &dest, reg, append)?;
+        let mut canceled = false;
+        let mut report = ErrorReport::default();
+        if !target.is_empty() {
+            match self.move_to_trash(&target, &mut report) {
+                Err(e) => {
+                    return Err(e);
+                }
+                Ok((src, dest, is_canceled)) => {
+                    canceled = is_canceled;
+                    total +=
+                        self.yank_after_delete(&src, &dest, reg, append, self.current_dir.clone())?;
+                    let trashed = items_size(&dest);
+                    self.update_copy_rate(trashed, start.elapsed());
+                    freed += trashed;
+                }
             }
         }
 
-        self.clear_and_show_headline();
         self.update_list()?;
-        self.list_up();
-        self.layout.y = if self.list.is_empty() {
+        let y = if self.list.is_empty() {
             BEGINNING_ROW
         } else if self.layout.nums.index == self.list.len() {
             self.layout.nums.go_up();
@@ -469,22 +698,65 @@ impl State {
         } else {
             self.layout.y
         };
+        self.draw(y);
         let duration = duration_to_string(start.elapsed());
-        print_info(format!("1 item deleted. [{}]", duration), self.layout.y);
-        self.move_cursor(self.layout.y);
+        if target.iter().any(|item| item.file_type == FileType::Other) {
+            self.warn("FIFOs, sockets and device files cannot be moved to the trash.");
+        } else if canceled {
+            self.info("Canceled.");
+        } else if let Some(error) = report.first() {
+            self.warn(error);
+        } else if total == 0 {
+            self.info("No item deleted.");
+        } else {
+            self.info(format!(
+                "1 item deleted. [{}] freed {}",
+                duration,
+                to_decimal_size(freed)
+            ));
+        }
         Ok(())
     }
 
+    /// Launch another fx in a new terminal window, at the directory under the cursor
+    /// (or the current directory if the item is not a directory).
+    pub fn open_new_instance(&self) -> Result<(), FxError> {
+        let terminal = self
+            .terminal
+            .as_ref()
+            .ok_or_else(|| FxError::OpenNewWindow("Set `terminal` in the config.".to_owned()))?;
+        let dir = self.cursor_dir();
+        info!("NEW WINDOW: {:?}", dir);
+        spawn_new_instance(terminal, &dir)
+    }
+
+    /// Open a terminal at the directory under the cursor (or the current directory),
+    /// by `terminal_here` in the config or, inside tmux, in a split pane.
+    pub fn open_terminal_here(&self) -> Result<(), FxError> {
+        let dir = self.cursor_dir();
+        info!("TERMINAL: {:?}", dir);
+        match &self.terminal_here {
+            Some(command) => spawn_in_dir(command, &dir),
+            None if env::var_os("TMUX").is_some() => spawn_tmux_split(&dir),
+            None => Err(FxError::OpenNewWindow(
+                "Set `terminal_here` in the config.".to_owned(),
+            )),
+        }
+    }
+
+    /// The directory under the cursor, or the current directory if the item is not a directory.
+    fn cursor_dir(&self) -> PathBuf {
+        match self.get_item() {
+            Ok(item) if item.file_type == FileType::Directory => item.file_path.clone(),
+            Ok(item) if item.symlink_dir_path.is_some() => item.file_path.clone(),
+            _ => self.current_dir.clone(),
+        }
+    }
+
     /// Delete items in visual mode.
-    pub fn delete_in_visual(
-        &mut self,
-        reg: Option<char>,
-        append: bool,
-        screen: &mut Stdout,
-    ) -> Result<(), FxError> {
-        print_info("DELETE: Processing...", self.layout.y);
+    pub fn delete_in_visual(&mut self, reg: Option<char>, append: bool) -> Result<(), FxError> {
+        self.ui().status("DELETE: Processing...");
         let start = Instant::now();
-        screen.flush()?;
 
         let selected: Vec<ItemBuffer> = self
             .list
@@ -492,98 +764,192 @@ impl State {
             .filter(|item| item.selected)
             .map(ItemBuffer::new)
             .collect();
-        let total: usize = match self.move_to_trash(&selected, true) {
-            Err(e) => {
-                return Err(e);
+        let (selected, mut total, mut freed) = self.confirm_large_items(selected)?;
+        if self.delete_in_background(&selected)? {
+            self.reset_selection();
+            self.draw(self.layout.y);
+            return Ok(());
+        }
+        let mut canceled = false;
+        let mut report = ErrorReport::default();
+        if !selected.is_empty() {
+            match self.move_to_trash(&selected, &mut report) {
+                Err(e) => {
+                    return Err(e);
+                }
+                Ok((src, dest, is_canceled)) => {
+                    canceled = is_canceled;
+                    total +=
+                        self.yank_after_delete(&src, &dest, reg, append, self.current_dir.clone())?;
+                    let trashed = items_size(&dest);
+                    self.update_copy_rate(trashed, start.elapsed());
+                    freed += trashed;
+                }
             }
-            Ok((src, dest)) => self.yank_after_delete(&src, &dest, reg, append)?,
-        };
+        }
+        self.ui().show_report("DELETE", &report)?;
 
         self.update_list()?;
         let new_len = self.list.len();
-        self.clear_and_show_headline();
 
         let duration = duration_to_string(start.elapsed());
         let delete_message: String = {
-            if total == 1 {
-                format!("1 item deleted. [{}]", duration)
+            let mut count = if canceled {
+                "Canceled: ".to_owned()
+            } else {
+                String::new()
+            };
+            if total == 0 {
+                count.push_str("No item deleted.");
+            } else if total == 1 {
+                let _ = write!(count, "1 item deleted. [{}]", duration);
             } else {
-                let mut count = total.to_string();
-                let _ = write!(count, " items deleted [{}]", duration);
-                count
+                let _ = write!(count, "{} items deleted. [{}]", total, duration);
+            }
+            if total > 0 {
+                let _ = write!(count, " freed {}", to_decimal_size(freed));
             }
+            let _ = write!(count, "{}", skipped_message(&selected));
+            let _ = write!(count, "{}", report.summary());
+            count
         };
-        print_info(delete_message, self.layout.y);
-        delete_pointer();
 
         self.reset_selection();
-        if new_len == 0 {
+        let y = if new_len == 0 {
             self.layout.nums.reset();
-            self.list_up();
-            self.move_cursor(BEGINNING_ROW);
+            BEGINNING_ROW
         } else if self.is_out_of_bounds() {
             if self.layout.nums.skip as usize >= new_len {
                 self.layout.nums.skip = (new_len - 1) as u16;
                 self.layout.nums.index = self.list.len() - 1;
-                self.list_up();
-                self.move_cursor(BEGINNING_ROW);
+                BEGINNING_ROW
             } else {
                 self.layout.nums.index = self.list.len() - 1;
-                self.list_up();
-                self.move_cursor(
-                    (self.list.len() as u16) - self.layout.nums.skip + BEGINNING_ROW - 1,
-                );
+                (self.list.len() as u16) - self.layout.nums.skip + BEGINNING_ROW - 1
             }
         } else {
-            self.list_up();
-            self.move_cursor(self.layout.y);
-        }
+            self.layout.y
+        };
+        self.draw(y);
+        self.info(delete_message);
         Ok(())
     }
 
+    /// Items larger than `trash_size_limit` take long to be copied to the trash,
+    /// so ask whether to trash, delete permanently or skip each of them.
+    /// Return the items to trash, and the number and the size of the permanently deleted ones.
+    fn confirm_large_items(
+        &mut self,
+        items: Vec<ItemBuffer>,
+    ) -> Result<(Vec<ItemBuffer>, usize, u64), FxError> {
+        let limit = match self.trash_size_limit {
+            Some(limit) if self.current_trash().is_none() => limit,
+            _ => return Ok((items, 0, 0)),
+        };
+        let mut to_trash = Vec::new();
+        let mut deleted = 0;
+        let mut freed = 0;
+        for item in items {
+            if item.file_type == FileType::Symlink || self.is_renamable(&item.file_path) {
+                to_trash.push(item);
+                continue;
+            }
+            let size = dir_size(&item.file_path);
+            if size <= limit {
+                to_trash.push(item);
+                continue;
+            }
+            let rate = self.copy_rate.unwrap_or(DEFAULT_COPY_RATE);
+            let estimate = std::time::Duration::from_secs_f64(size as f64 / rate);
+            let answer = self.ask(format!(
+                "{} is {} (about {} to trash). t: trash, D: delete permanently, other: skip",
+                item.file_name,
+                to_decimal_size(size),
+                to_rough_duration(estimate)
+            ))?;
+            match answer {
+                KeyCode::Char('t') => to_trash.push(item),
+                KeyCode::Char('D') => {
+                    let removed = if item.file_type == FileType::Directory {
+                        fs::remove_dir_all(&item.file_path)
+                    } else {
+                        fs::remove_file(&item.file_path)
+                    };
+                    if removed.is_err() {
+                        return Err(FxError::RemoveItem(item.file_path));
+                    }
+                    info!("DELETE PERMANENTLY: {:?}", item.file_path);
+                    deleted += 1;
+                    freed += size;
+                }
+                _ => {}
+            }
+        }
+        self.ui().status("DELETE: Processing...");
+        Ok((to_trash, deleted, freed))
+    }
+
+    /// Remember the speed of copying, to estimate the time to trash large items.
+    /// Small copies are dominated by the overhead, so they are ignored.
+    fn update_copy_rate(&mut self, bytes: u64, elapsed: std::time::Duration) {
+        if bytes >= MIN_SIZE_TO_MEASURE_COPY_RATE && !elapsed.is_zero() {
+            self.copy_rate = Some(bytes as f64 / elapsed.as_secs_f64());
+        }
+    }
+
     /// Move items from the current directory to trash directory.
     /// This does not actually delete items.
     /// If you'd like to delete, use `:empty` after this.
-    fn move_to_trash(
+    /// Items that fail are added to `report`, and the rest are processed.
+    /// Return the items moved to the trash, their paths in the trash in the same order,
+    /// and whether canceled while copying, in which case the rest are left as is.
+    pub fn move_to_trash(
         &mut self,
         src: &[ItemBuffer],
-        new_op: bool,
-    ) -> Result<(Vec<ItemBuffer>, Vec<ItemBuffer>), FxError> {
-        if self.current_dir == self.trash_dir {
+        report: &mut ErrorReport,
+    ) -> Result<(Vec<ItemBuffer>, Vec<ItemBuffer>, bool), FxError> {
+        if self.current_trash().is_some() {
             return Err(FxError::Io(
                 "Use `:empty` to delete item in the trash dir.".to_string(),
             ));
         }
 
+        //Keep the trash from being emptied by another instance while copying.
+        let _locks = lock_trash_roots(&self.trash_roots(), false)?;
+        let journal =
+            self.journal
+                .begin(JournalKind::Delete, &item_to_pathvec(src), &self.trash_dir);
         let total_selected = src.len();
+        let mut done = Vec::new();
         let mut dest = Vec::new();
+        let ui = self.ui();
         for (i, item) in src.iter().enumerate() {
-            delete_pointer();
-            to_info_line();
-            clear_current_line();
-            print!("{}", display_count(i, total_selected));
+            ui.status(&display_count(i, total_selected));
 
-            match item.file_type {
-                FileType::Directory => match self.remove_dir(item, new_op) {
-                    Err(e) => {
-                        return Err(e);
-                    }
-                    Ok(path) => dest.push(path),
-                },
-                FileType::File | FileType::Symlink => match self.remove_file(item, new_op) {
-                    Err(e) => {
-                        return Err(e);
-                    }
-                    Ok(path) => {
-                        if let Some(p) = path {
-                            dest.push(p);
-                        }
+            let removed = match item.file_type {
+                //Copying them to the trash would hang or fail, so they are left as is.
+                FileType::Other => continue,
+                FileType::Directory => self.remove_dir(item).map(Some),
+                FileType::File | FileType::Symlink => self.remove_file(item),
+            };
+            match removed {
+                Ok(path) => {
+                    //Broken symlinks are removed, not moved to the trash.
+                    if let Some(p) = path {
+                        done.push(item.clone());
+                        dest.push(p);
                     }
-                },
+                }
+                Err(FxError::Canceled) => {
+                    journal.finish();
+                    return Ok((done, dest, true));
+                }
+                Err(e) => report.add(&item.file_path, &e),
             }
         }
+        journal.finish();
 
-        Ok((src.to_vec(), dest))
+        Ok((done, dest, false))
     }
 
     /// Add dest to register, and item infomation to operation
@@ -593,8 +959,10 @@ impl State {
         dest: &[ItemBuffer],
         reg: Option<char>,
         append: bool,
+        dir: PathBuf,
     ) -> Result<usize, FxError> {
         if !dest.is_empty() {
+            self.registers.cut.clear();
             //save to unnamed reg
             self.registers.unnamed = dest.to_vec();
             //If numbered registers is full, pop_back first
@@ -612,84 +980,36 @@ impl State {
                 }
             }
 
-            //Update operations value
-            self.operations.branch();
             //push deleted item information to operations
-            self.operations.push(OpKind::Delete(DeletedFiles {
+            self.operations.push(DeletedFiles {
                 trash: dest.to_vec(),
                 original: src.to_vec(),
-                dir: self.current_dir.clone(),
-            }));
+                dir,
+            });
         }
 
         Ok(dest.len())
     }
 
     /// Move single directory recursively to trash directory.
-    fn remove_dir(&mut self, item: &ItemBuffer, new_op: bool) -> Result<ItemBuffer, FxError> {
-        let mut base: usize = 0;
-        let mut trash_path: std::path::PathBuf = PathBuf::new();
-        let mut target: PathBuf;
-
-        if new_op {
-            let len = walkdir::WalkDir::new(&item.file_path).into_iter().count();
-            let unit = len / 5;
-            for (i, entry) in walkdir::WalkDir::new(&item.file_path)
-                .into_iter()
-                .enumerate()
-            {
-                if i > unit * 4 {
-                    print_process("[»»»»-]");
-                } else if i > unit * 3 {
-                    print_process("[»»»--]");
-                } else if i > unit * 2 {
-                    print_process("[»»---]");
-                } else if i > unit {
-                    print_process("[»----]");
-                } else if i == 0 {
-                    print_process(" [-----]");
-                }
-                let entry = entry?;
-                let entry_path = entry.path();
-                if i == 0 {
-                    base = entry_path.iter().count();
-
-                    let mut trash_name = chrono::Local::now().timestamp().to_string();
-                    trash_name.push('_');
-                    let file_name = entry.file_name().to_str();
-                    if file_name.is_none() {
-                        return Err(FxError::Encode);
-                    }
-                    trash_name.push_str(file_name.unwrap());
-                    trash_path = self.trash_dir.join(&trash_name);
-                    std::fs::create_dir(self.trash_dir.join(&trash_path))?;
-
-                    continue;
-                } else {
-                    if entry.file_type().is_symlink() && !entry_path.exists() {
-                        if std::fs::remove_file(entry_path).is_err() {
-                            return Err(FxError::RemoveItem(entry_path.to_owned()));
-                        }
-                        continue;
-                    }
-                    target = entry_path.iter().skip(base).collect();
-                    target = trash_path.join(target);
-                    if entry.file_type().is_dir() {
-                        std::fs::create_dir_all(&target)?;
-                        continue;
-                    }
-
-                    if let Some(parent) = entry_path.parent() {
-                        if !parent.exists() {
-                            std::fs::create_dir(parent)?;
-                        }
-                    }
-
-                    if std::fs::copy(entry_path, &target).is_err() {
-                        return Err(FxError::PutItem(entry_path.to_owned()));
-                    }
-                }
-            }
+    fn remove_dir(&mut self, item: &ItemBuffer) -> Result<ItemBuffer, FxError> {
+        let (trash_dir, info_dir) = self.trash_root(&item.file_path);
+        let to = trash_path(
+            &trash_dir,
+            info_dir.as_deref(),
+            &item.file_path,
+            &Local::now(),
+        )?;
+        //On the same filesystem, the dir is moved at once.
+        if is_same_device(&item.file_path, &trash_dir) && fs::rename(&item.file_path, &to).is_ok() {
+            return Ok(item.at(to));
+        }
+        let mut progress = Progress::new(dir_size(&item.file_path), self.ui());
+        if let Err(e) = copy_tree(&item.file_path, &to, self.preserve_metadata, |bytes| {
+            progress.add(bytes)
+        }) {
+            discard_trash_info(info_dir.as_deref(), &to);
+            return Err(e);
         }
 
         //remove original
@@ -697,22 +1017,13 @@ impl State {
             return Err(FxError::RemoveItem(item.file_path.clone()));
         }
 
-        Ok(ItemBuffer {
-            file_type: item.file_type,
-            file_name: item.file_name.clone(),
-            file_path: trash_path,
-        })
+        Ok(item.at(to))
     }
 
     /// Move single file to trash directory.
-    fn remove_file(
-        &mut self,
-        item: &ItemBuffer,
-        new_op: bool,
-    ) -> Result<Option<ItemBuffer>, FxError> {
+    fn remove_file(&mut self, item: &ItemBuffer) -> Result<Option<ItemBuffer>, FxError> {
         //prepare from and to for copy
         let from = &item.file_path;
-        let mut to = PathBuf::new();
 
         if item.file_type == FileType::Symlink && !from.exists() {
             match std::fs::remove_file(from) {
@@ -720,17 +1031,24 @@ impl State {
                 Err(_) => Err(FxError::RemoveItem(from.to_owned())),
             }
         } else {
-            let mut rename = Local::now().timestamp().to_string();
-            rename.push('_');
-            rename.push_str(&item.file_name);
-
-            if new_op {
-                to = self.trash_dir.join(&rename);
+            let (trash_dir, info_dir) = self.trash_root(from);
+            let to = trash_path(&trash_dir, info_dir.as_deref(), from, &Local::now())?;
+            if is_same_device(from, &trash_dir) && fs::rename(from, &to).is_ok() {
+                return Ok(Some(item.at(to)));
+            }
 
-                //copy
-                if std::fs::copy(from, &to).is_err() {
-                    return Err(FxError::PutItem(from.to_owned()));
-                }
+            //copy
+            let mut progress = Progress::new(fs::symlink_metadata(from)?.len(), self.ui());
+            let copied = copy_file(from, &to, self.preserve_metadata, |bytes| {
+                progress.add(bytes)
+            });
+            if copied.is_err() {
+                discard_trash_info(info_dir.as_deref(), &to);
+            }
+            match copied {
+                Ok(()) => {}
+                Err(FxError::Canceled) => return Err(FxError::Canceled),
+                Err(_) => return Err(FxError::PutItem(from.to_owned())),
             }
 
             //remove original
@@ -738,527 +1056,1370 @@ impl State {
                 return Err(FxError::RemoveItem(from.to_owned()));
             }
 
-            Ok(Some(ItemBuffer {
-                file_type: item.file_type,
-                file_name: item.file_name.clone(),
-                file_path: to,
-            }))
+            Ok(Some(item.at(to)))
+        }
+    }
+
+    /// Move the existing item to the trash before it is overwritten by the put one,
+    /// so that it is not lost if putting fails.
+    /// Return the pair of the path and the path in the trash, to restore it on undo.
+    fn trash_overwritten(
+        &mut self,
+        path: &std::path::Path,
+    ) -> Result<Option<(PathBuf, PathBuf)>, FxError> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let item = ItemBuffer::new(&read_item_at(path.to_path_buf(), name));
+        let _locks = lock_trash_roots(&self.trash_roots(), false)?;
+        let trashed = match item.file_type {
+            FileType::Directory => self.remove_dir(&item).map(Some)?,
+            _ => self.remove_file(&item)?,
+        };
+        Ok(trashed.map(|trashed| (item.file_path, trashed.file_path)))
+    }
+
+    /// Move the trashed item back to the original path, and remove its info file.
+    /// It is renamed, or copied if the trash is on another filesystem.
+    /// An item that has taken the original path is not replaced.
+    pub fn restore_from_trash(
+        &mut self,
+        trashed: &std::path::Path,
+        original: &std::path::Path,
+    ) -> Result<(), FxError> {
+        if original.symlink_metadata().is_ok() {
+            return Err(FxError::PutItem(original.to_path_buf()));
+        }
+        let _lock = lock_trash(trashed.parent().unwrap_or(&self.trash_dir), false)?;
+        move_path(trashed, original)?;
+        discard_trash_info(self.trash_info_dir_of(trashed).as_deref(), trashed);
+        self.trash_modified = None;
+        Ok(())
+    }
+
+    /// Move the restored item to the trash again at the same path, e.g. by redo,
+    /// so that the recorded operation can be undone again.
+    pub fn trash_again(
+        &mut self,
+        original: &std::path::Path,
+        trashed: &std::path::Path,
+    ) -> Result<(), FxError> {
+        if trashed.symlink_metadata().is_ok() {
+            return Err(FxError::RemoveItem(original.to_path_buf()));
+        }
+        let _lock = lock_trash(trashed.parent().unwrap_or(&self.trash_dir), false)?;
+        let info_dir = self.trash_info_dir_of(trashed);
+        if let Some(info_dir) = &info_dir {
+            write_trash_info(info_dir, trashed, original, &Local::now())?;
+        }
+        if let Err(e) = move_path(original, trashed) {
+            discard_trash_info(info_dir.as_deref(), trashed);
+            return Err(e);
+        }
+        self.trash_modified = None;
+        Ok(())
+    }
+
+    /// The info dir of the item in the trash, which is next to the files dir
+    /// both in the home trash and in `$topdir/.Trash-$uid`. None in the legacy trash.
+    fn trash_info_dir_of(&self, trashed: &std::path::Path) -> Option<PathBuf> {
+        match trashed.parent() {
+            Some(files) if files == self.trash_dir => self.trash_info_dir.clone(),
+            Some(files) => Some(files.with_file_name("info")),
+            None => None,
         }
     }
 
     /// Put.
-    pub fn put(&mut self, reg: Vec<ItemBuffer>, screen: &mut Stdout) -> Result<(), FxError> {
+    pub fn put(&mut self, reg: Vec<ItemBuffer>) -> Result<(), FxError> {
         //If read-only, putting is disabled.
         if self.is_ro {
-            print_warning("Cannot put into this directory.", self.layout.y);
+            self.warn("Cannot put into this directory.");
             return Ok(());
         }
         if reg.is_empty() {
             return Ok(());
         }
-        print_info("PUT: Processing...", self.layout.y);
-        screen.flush()?;
+        if self.put_in_background(&reg)? {
+            return Ok(());
+        }
+        self.ui().status("PUT: Processing...");
         let start = Instant::now();
 
-        let total = self.put_item(&reg, None)?;
+        let mut report = ErrorReport::default();
+        let journal =
+            self.journal
+                .begin(JournalKind::Put, &item_to_pathvec(&reg), &self.current_dir);
+        let (put, canceled) = self.put_item(&reg, &mut report)?;
+        journal.finish();
+        let total = put.len();
+        let used: u64 = put.iter().map(|path| dir_size(path)).sum();
+        self.update_copy_rate(used, start.elapsed());
+        self.ui().show_report("PUT", &report)?;
 
-        self.reload(self.layout.y)?;
+        self.update_list()?;
+        self.draw(self.layout.y);
 
         let duration = duration_to_string(start.elapsed());
-        let mut put_message = total.to_string();
+        let mut put_message = if canceled {
+            "Canceled: ".to_owned()
+        } else {
+            String::new()
+        };
+        let _ = write!(put_message, "{}", total);
         if total == 1 {
             let _ = write!(put_message, " item inserted. [{}]", duration);
         } else {
             let _ = write!(put_message, " items inserted. [{}]", duration);
         }
-        print_info(put_message, self.layout.y);
+        let _ = write!(put_message, " used {}", to_decimal_size(used));
+        let _ = write!(put_message, "{}", skipped_message(&reg));
+        let _ = write!(put_message, "{}", report.summary());
+        self.info(put_message);
         Ok(())
     }
 
-    /// Put items in the register to the current directory or target directory.
-    /// Return the total number of put items.
-    /// Only Redo command uses target directory.
-    fn put_item(
-        &mut self,
-        targets: &[ItemBuffer],
-        target_dir: Option<PathBuf>,
-    ) -> Result<usize, FxError> {
-        //make HashSet<String> of file_name
-        let mut name_set = BTreeSet::new();
-        match &target_dir {
-            None => {
-                for item in self.list.iter() {
-                    name_set.insert(item.file_name.clone());
-                }
-            }
-            Some(path) => {
-                for entry in std::fs::read_dir(path)? {
-                    let entry = entry?;
-                    name_set.insert(
-                        entry
-                            .file_name()
-                            .into_string()
-                            .unwrap_or_else(|_| "".to_string()),
-                    );
-                }
-            }
-        }
+    /// The trash to move the item to: the one at the top of its mount if on another filesystem.
+    fn trash_root(&self, path: &std::path::Path) -> (PathBuf, Option<PathBuf>) {
+        trash_root(path, &self.trash_dir, self.trash_info_dir.as_deref())
+    }
 
-        //prepare for operations.push
-        let mut put_v = Vec::new();
+    /// The home trash and those at the top of the other mounts, with their info dirs.
+    pub fn trash_roots(&self) -> Vec<(PathBuf, Option<PathBuf>)> {
+        trash_roots(&self.trash_dir, self.trash_info_dir.as_deref())
+    }
 
-        let total_selected = targets.len();
-        for (i, item) in targets.iter().enumerate() {
-            delete_pointer();
-            to_info_line();
-            clear_current_line();
-            print!("{}", display_count(i, total_selected));
-
-            match item.file_type {
-                FileType::Directory => {
-                    if let Ok(p) = self.put_dir(item, &target_dir, &mut name_set) {
-                        put_v.push(p);
-                    }
-                }
-                FileType::File | FileType::Symlink => {
-                    if let Ok(q) = self.put_file(item, &target_dir, &mut name_set) {
-                        put_v.push(q);
-                    }
-                }
-            }
+    /// The trash dir the current directory is, either the home one or one at the top
+    /// of another mount, with its info dir.
+    pub fn current_trash(&self) -> Option<(PathBuf, Option<PathBuf>)> {
+        if self.current_dir == self.trash_dir {
+            return Some((self.trash_dir.clone(), self.trash_info_dir.clone()));
         }
-        if target_dir.is_none() {
-            self.operations.branch();
-            //push put item information to operations
-            self.operations.push(OpKind::Put(PutFiles {
-                original: targets.to_owned(),
-                put: put_v.clone(),
-                dir: self.current_dir.clone(),
-            }));
+        //Reading the mounts for every directory is not worth it.
+        if !self.current_dir.ends_with("files") {
+            return None;
         }
+        self.trash_roots()
+            .into_iter()
+            .find(|(trash_dir, _)| *trash_dir == self.current_dir)
+    }
 
-        Ok(put_v.len())
+    /// Whether the item can be moved to its trash by rename, which needs no copy.
+    fn is_renamable(&self, path: &std::path::Path) -> bool {
+        is_same_device(path, &self.trash_root(path).0)
     }
 
-    /// Put single item to current or target directory.
-    fn put_file(
-        &mut self,
-        item: &ItemBuffer,
-        target_dir: &Option<PathBuf>,
-        name_set: &mut BTreeSet<String>,
-    ) -> Result<PathBuf, FxError> {
-        let rename = rename_file(&item.file_name, name_set);
-        let to = match target_dir {
-            None => self.current_dir.join(&rename),
-            Some(path) => path.join(&rename),
-        };
-        if std::fs::copy(&item.file_path, &to).is_err() {
-            return Err(FxError::PutItem(item.file_path.clone()));
+    /// The size of the items, if they are large enough to be copied in the background.
+    fn background_size(&self, items: &[ItemBuffer]) -> Option<u64> {
+        let threshold = self.background_threshold?;
+        if !self.jobs.is_available() {
+            return None;
         }
-        name_set.insert(rename);
-        Ok(to.to_path_buf())
+        let size = items_size(items);
+        (size >= threshold).then_some(size)
     }
 
-    /// Put single directory recursively to current or target directory.
-    fn put_dir(
-        &mut self,
-        item: &ItemBuffer,
-        target_dir: &Option<PathBuf>,
-        name_set: &mut BTreeSet<String>,
-    ) -> Result<PathBuf, FxError> {
-        let mut base: usize = 0;
-        let mut target: PathBuf = PathBuf::new();
-        let original_path = &item.file_path;
-
-        let len = walkdir::WalkDir::new(original_path).into_iter().count();
-        let unit = len / 5;
-        for (i, entry) in walkdir::WalkDir::new(original_path).into_iter().enumerate() {
-            if i > unit * 4 {
-                print_process("[»»»»-]");
-            } else if i > unit * 3 {
-                print_process("[»»»--]");
-            } else if i > unit * 2 {
-                print_process("[»»---]");
-            } else if i > unit {
-                print_process("[»----]");
-            } else if i == 0 {
-                print_process(" [»----]");
-            }
-            let entry = entry?;
-            let entry_path = entry.path();
-            if i == 0 {
-                base = entry_path.iter().count();
-
-                let rename = rename_dir(&item.file_name, name_set);
-                target = match &target_dir {
-                    None => self.current_dir.join(&rename),
-                    Some(path) => path.join(&rename),
-                };
-                name_set.insert(rename);
-                std::fs::create_dir(&target)?;
-                continue;
-            } else {
-                let child: PathBuf = entry_path.iter().skip(base).collect();
-                let child = target.join(child);
-
-                if entry.file_type().is_dir() {
-                    std::fs::create_dir_all(child)?;
-                    continue;
-                } else if let Some(parent) = entry_path.parent() {
-                    if !parent.exists() {
-                        std::fs::create_dir(parent)?;
-                    }
-                }
-
-                if std::fs::copy(entry_path, &child).is_err() {
-                    return Err(FxError::PutItem(entry_path.to_owned()));
+    /// Put the items in a background job if they are larger than `background_threshold`.
+    /// Return false if they should be put in the foreground.
+    fn put_in_background(&mut self, items: &[ItemBuffer]) -> Result<bool, FxError> {
+        let total = match self.background_size(items) {
+            Some(total) => total,
+            None => return Ok(false),
+        };
+        //Names taken by the other jobs are not in the directory yet.
+        let mut name_set = BTreeSet::new();
+        for entry in fs::read_dir(&self.current_dir)? {
+            name_set.insert(entry?.file_name());
+        }
+        for target in self.jobs.pending_targets() {
+            if target.parent() == Some(self.current_dir.as_path()) {
+                if let Some(name) = target.file_name() {
+                    name_set.insert(name.to_owned());
                 }
             }
         }
-        Ok(target)
-    }
 
-    /// Undo operations (put/delete/rename)
-    pub fn undo(&mut self, op: &OpKind) -> Result<(), FxError> {
-        match op {
-            OpKind::Rename(op) => {
-                for (original, new) in op {
-                    std::fs::rename(new, original)?;
-                }
-                self.operations.pos += 1;
-                self.update_list()?;
-                self.clear_and_show_headline();
-                self.list_up();
-                print_info("UNDONE: RENAME", BEGINNING_ROW);
-            }
-            OpKind::Put(op) => {
-                for x in &op.put {
-                    if x.is_dir() {
-                        std::fs::remove_dir_all(x)?;
-                    } else {
-                        std::fs::remove_file(x)?;
+        let items: Vec<ItemBuffer> = items
+            .iter()
+            .filter(|item| item.file_type != FileType::Other)
+            .cloned()
+            .collect();
+        //Merging asks for each file inside, so it runs in the foreground.
+        if matches!(self.on_conflict, OnConflict::Ask | OnConflict::Merge)
+            && items.iter().any(|item| {
+                item.file_type == FileType::Directory
+                    && self.current_dir.join(item.os_file_name()).is_dir()
+                    && !self.current_dir.starts_with(&item.file_path)
+            })
+        {
+            return Ok(false);
+        }
+        //Resolve all conflicts before starting, and trash the overwritten items only then,
+        //so that canceling the prompt leaves the directory as it is.
+        let mut resolver = Resolver::new(self.on_conflict);
+        let mut put_items = Vec::new();
+        let mut pairs = Vec::new();
+        let mut overwritten = Vec::new();
+        for item in items.iter() {
+            let (name, overwrite) =
+                match self.put_name(item, &self.current_dir, &name_set, &mut resolver) {
+                    Ok(Some(PutAs::New(name))) => (name, false),
+                    Ok(Some(PutAs::Overwrite(name))) => (name, true),
+                    //Not chosen, as checked above.
+                    Ok(Some(PutAs::Merge(_))) | Ok(None) => continue,
+                    Err(FxError::Canceled) => {
+                        self.info("Canceled.");
+                        return Ok(true);
                     }
-                }
-                self.operations.pos += 1;
-                self.update_list()?;
-                self.clear_and_show_headline();
-                self.list_up();
-                print_info("UNDONE: PUT", BEGINNING_ROW);
-            }
-            OpKind::Delete(op) => {
-                self.put_item(&op.trash, Some(op.dir.clone()))?;
-                self.operations.pos += 1;
-                self.update_list()?;
-                self.clear_and_show_headline();
-                self.list_up();
-                print_info("UNDONE: DELETE", BEGINNING_ROW);
+                    Err(e) => return Err(e),
+                };
+            let to = self.current_dir.join(&name);
+            if overwrite {
+                overwritten.push(to.clone());
             }
+            put_items.push(item.clone());
+            pairs.push((item.file_path.clone(), to));
+            name_set.insert(name);
         }
-        relog(op, true);
-        Ok(())
-    }
-
-    /// Redo operations (put/delete/rename)
-    pub fn redo(&mut self, op: &OpKind) -> Result<(), FxError> {
-        match op {
-            OpKind::Rename(op) => {
-                for (original, new) in op {
-                    std::fs::rename(original, new)?;
-                }
-                self.operations.pos -= 1;
-                self.update_list()?;
-                self.clear_and_show_headline();
-                self.list_up();
-                print_info("REDONE: RENAME", BEGINNING_ROW);
-            }
-            OpKind::Put(op) => {
-                self.put_item(&op.original, Some(op.dir.clone()))?;
-                self.operations.pos -= 1;
-                self.update_list()?;
-                self.clear_and_show_headline();
-                self.list_up();
-                print_info("REDONE: PUT", BEGINNING_ROW);
-            }
-            OpKind::Delete(op) => {
-                self.move_to_trash(&op.original, false)?;
-                self.operations.pos -= 1;
-                self.update_list()?;
-                self.clear_and_show_headline();
-                self.list_up();
-                print_info("REDONE DELETE", BEGINNING_ROW);
-            }
+        if pairs.is_empty() {
+            self.info("PUT: All items skipped.");
+            return Ok(true);
         }
-        relog(op, false);
-        Ok(())
+        let mut trashed = Vec::new();
+        for path in overwritten.iter() {
+            trashed.extend(self.trash_overwritten(path)?);
+        }
+        let total = if put_items.len() < items.len() {
+            items_size(&put_items)
+        } else {
+            total
+        };
+        let journal = self.journal.begin(
+            JournalKind::Put,
+            &item_to_pathvec(&put_items),
+            &self.current_dir,
+        );
+        let id = self.jobs.spawn(
+            JobKind::Put,
+            put_items,
+            pairs,
+            trashed,
+            self.current_dir.clone(),
+            total,
+            self.preserve_metadata,
+            Vec::new(),
+            journal,
+        )?;
+        self.info(format!(
+            "PUT: Started job #{} in the background. `:jobs` to show.",
+            id
+        ));
+        Ok(true)
     }
 
-    /// Redraw the contents.
-    pub fn redraw(&mut self, y: u16) {
-        self.clear_and_show_headline();
-        self.list_up();
-        self.move_cursor(y);
+    /// Move the items to the trash in a background job if they are larger than
+    /// `background_threshold`. Return false if they should be deleted in the foreground.
+    fn delete_in_background(&mut self, items: &[ItemBuffer]) -> Result<bool, FxError> {
+        if self.current_trash().is_some() {
+            return Ok(false);
+        }
+        //Renaming them takes no time.
+        if items.iter().all(|item| self.is_renamable(&item.file_path)) {
+            return Ok(false);
+        }
+        let total = match self.background_size(items) {
+            Some(total) => total,
+            None => return Ok(false),
+        };
+        let items: Vec<ItemBuffer> = items
+            .iter()
+            .filter(|item| item.file_type != FileType::Other)
+            .cloned()
+            .collect();
+        let locks = lock_trash_roots(&self.trash_roots(), false)?;
+        let now = Local::now();
+        let pairs = items
+            .iter()
+            .map(|item| {
+                let (trash_dir, info_dir) = self.trash_root(&item.file_path);
+                let to = trash_path(&trash_dir, info_dir.as_deref(), &item.file_path, &now)?;
+                Ok((item.file_path.clone(), to))
+            })
+            .collect::<Result<Vec<_>, FxError>>()?;
+        let journal = self.journal.begin(
+            JournalKind::Delete,
+            &item_to_pathvec(&items),
+            &self.trash_dir,
+        );
+        let id = self.jobs.spawn(
+            JobKind::Delete,
+            items,
+            pairs,
+            Vec::new(),
+            self.current_dir.clone(),
+            total,
+            self.preserve_metadata,
+            locks,
+            journal,
+        )?;
+        self.info(format!(
+            "DELETE: Started job #{} in the background. `:jobs` to show.",
+            id
+        ));
+        Ok(true)
+    }
+
+    /// Record the operation of the finished background job for undo, and report it.
+    pub fn finish_job(
+        &mut self,
+        id: usize,
+        finished: usize,
+        result: Result<(), FxError>,
+    ) -> Result<(), FxError> {
+        let job = match self.jobs.take(id) {
+            Some(job) => job,
+            None => return Ok(()),
+        };
+        //Items may have grown in the trash after its modified time changed.
+        self.trash_modified = None;
+        let items = &job.items[..finished];
+        let targets: Vec<PathBuf> = job.pairs[..finished]
+            .iter()
+            .map(|(_, to)| to.clone())
+            .collect();
+        let mut message = match job.kind {
+            JobKind::Put => {
+                if !targets.is_empty() || !job.trashed.is_empty() {
+                    self.operations.push(PutFiles {
+                        put: job.pairs[..finished].to_vec(),
+                        trashed: job.trashed.clone(),
+                        dir: job.dir.clone(),
+                    });
+                }
+                format!(
+                    "Job #{}: {}/{} items inserted.",
+                    id,
+                    finished,
+                    job.items.len()
+                )
+            }
+            JobKind::Delete => {
+                for (from, to) in job.pairs[finished..].iter() {
+                    discard_trash_info(self.trash_root(from).1.as_deref(), to);
+                }
+                let dest: Vec<ItemBuffer> = items
+                    .iter()
+                    .zip(targets)
+                    .map(|(item, path)| item.at(path))
+                    .collect();
+                self.yank_after_delete(items, &dest, None, false, job.dir.clone())?;
+                format!(
+                    "Job #{}: {}/{} items deleted.",
+                    id,
+                    finished,
+                    job.items.len()
+                )
+            }
+        };
+        //Not to lose the selection, the list is not reloaded in the visual mode.
+        if job.dir == self.current_dir && self.v_start.is_none() {
+            self.update_list()?;
+            self.draw(self.layout.y);
+        }
+        match result {
+            Ok(()) => self.info(message),
+            Err(e) => {
+                let _ = write!(message, " {}", e);
+                self.warn(message);
+            }
+        }
+        Ok(())
+    }
+
+    /// Show the operations interrupted in the previous runs one by one, with how far they went.
+    /// Those checked are cleared from the journal.
+    pub fn check_journal(&self) -> Result<(), FxError> {
+        let incomplete = self.journal.incomplete();
+        if incomplete.is_empty() {
+            self.info("No incomplete operations.");
+            return Ok(());
+        }
+        let mut kept = 0;
+        for (path, entry) in incomplete.iter() {
+            let answer = self.ask(format!(
+                "{} {} (started at {}) c: clear, other: keep",
+                entry.describe(),
+                entry.verify(),
+                entry.started
+            ))?;
+            match answer {
+                KeyCode::Char('c') => Journal::clear(path)?,
+                _ => kept += 1,
+            }
+        }
+        if kept == 0 {
+            self.info("Journal cleared.");
+        } else {
+            self.info(format!("{} incomplete operations kept.", kept));
+        }
+        Ok(())
     }
 
-    /// Reload the item list and redraw it.
-    pub fn reload(&mut self, y: u16) -> Result<(), FxError> {
+    /// If background jobs are running, ask whether to cancel them and exit.
+    /// Canceled jobs remove the partially copied items before exit.
+    pub fn confirm_exit(&mut self) -> Result<bool, FxError> {
+        if self.jobs.list.is_empty() {
+            return Ok(true);
+        }
+        let answer = self.ask(format!(
+            "{} job(s) running. Cancel and exit? (if yes: y)",
+            self.jobs.list.len()
+        ))?;
+        if answer == KeyCode::Char('y') {
+            self.jobs.cancel_all_and_wait();
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Move the cut items to the current directory.
+    /// Items that fail to move stay in the cut register.
+    pub fn move_cut(&mut self) -> Result<(), FxError> {
+        if self.is_ro {
+            self.warn("Cannot put into this directory.");
+            return Ok(());
+        }
+        let ui = self.ui();
+        ui.status("MOVE: Processing...");
+        let start = Instant::now();
+
+        let mut name_set = BTreeSet::new();
+        for entry in fs::read_dir(&self.current_dir)? {
+            name_set.insert(entry?.file_name());
+        }
+
+        let items = std::mem::take(&mut self.registers.cut);
+        let journal = self.journal.begin(
+            JournalKind::Move,
+            &item_to_pathvec(&items),
+            &self.current_dir,
+        );
+        let total_selected = items.len();
+        let mut moved = Vec::new();
+        let mut report = ErrorReport::default();
+        for (i, item) in items.iter().enumerate() {
+            ui.status(&display_count(i, total_selected));
+
+            //Moving to the same directory does nothing.
+            if item.file_path.parent() == Some(self.current_dir.as_path()) {
+                continue;
+            }
+            //A directory cannot be moved into itself.
+            if self.current_dir.starts_with(&item.file_path) {
+                report.add(&item.file_path, &FxError::PutItem(item.file_path.clone()));
+                self.registers.cut.push(item.clone());
+                continue;
+            }
+            let rename = match item.file_type {
+                FileType::Directory => rename_dir(&item.os_file_name(), &name_set),
+                FileType::File | FileType::Symlink | FileType::Other => {
+                    rename_file(&item.os_file_name(), &name_set)
+                }
+            };
+            let to = self.current_dir.join(&rename);
+            if let Err(e) = move_path(&item.file_path, &to) {
+                report.add(&item.file_path, &e);
+                self.registers.cut.push(item.clone());
+                continue;
+            }
+            name_set.insert(rename);
+            moved.push((item.file_path.clone(), to));
+        }
+
+        journal.finish();
+        let total = moved.len();
+        if !moved.is_empty() {
+            self.operations.push(Moved(moved));
+        }
+        ui.show_report("MOVE", &report)?;
         self.update_list()?;
-        self.clear_and_show_headline();
-        self.list_up();
-        self.move_cursor(y);
+        self.draw(self.layout.y);
+
+        let duration = duration_to_string(start.elapsed());
+        let mut move_message = total.to_string();
+        if total == 1 {
+            let _ = write!(move_message, " item moved. [{}]", duration);
+        } else {
+            let _ = write!(move_message, " items moved. [{}]", duration);
+        }
+        let _ = write!(move_message, "{}", report.summary());
+        self.info(move_message);
         Ok(())
     }
 
-    /// Reload the app layout when terminal size changes.
-    pub fn refresh(&mut self, column: u16, row: u16, mut cursor_pos: u16) -> Result<(), FxError> {
-        let (time_start, name_max) = make_layout(column);
+    /// Put items in the register to the current directory.
+    /// Return the paths of put items, and whether it is canceled while copying.
+    /// Items that fail are added to `report`, and the rest are processed.
+    pub fn put_item(
+        &mut self,
+        targets: &[ItemBuffer],
+        report: &mut ErrorReport,
+    ) -> Result<(Vec<PathBuf>, bool), FxError> {
+        let dir = self.current_dir.clone();
+        //Names on the disk, which may not be valid UTF-8.
+        let mut name_set = BTreeSet::new();
+        for entry in std::fs::read_dir(&dir)? {
+            name_set.insert(entry?.file_name());
+        }
+        let mut resolver = Resolver::new(self.on_conflict);
+
+        //prepare for operations.push
+        let mut put_v = Vec::new();
+        let mut trashed = Vec::new();
 
-        let (original_column, original_row) = terminal_size()?;
+        let mut processed = targets.len();
+        let total_selected = targets.len();
+        let ui = self.ui();
+        for (i, item) in targets.iter().enumerate() {
+            ui.status(&display_count(i, total_selected));
 
-        self.layout.terminal_row = row;
-        self.layout.terminal_column = column;
-        self.layout.preview_start = match self.layout.split {
-            Split::Vertical => (column + 2, BEGINNING_ROW),
-            Split::Horizontal => (1, row + 2),
-        };
-        self.layout.preview_space = if self.layout.is_preview() || self.layout.is_reg() {
-            match self.layout.split {
-                Split::Vertical => (original_column - column - 1, row - BEGINNING_ROW),
-                Split::Horizontal => (column, original_row - row - 1),
+            if item.file_type == FileType::Other {
+                continue;
+            }
+            let put_as = match self.put_name(item, &dir, &name_set, &mut resolver) {
+                Ok(put_as) => put_as,
+                Err(FxError::Canceled) => {
+                    processed = i;
+                    break;
+                }
+                Err(e) => {
+                    report.add(&item.file_path, &e);
+                    continue;
+                }
+            };
+            let put = match put_as {
+                None => Ok(()),
+                Some(PutAs::New(name)) => {
+                    let to = dir.join(&name);
+                    name_set.insert(name);
+                    self.put_to(item, &to)
+                        .map(|p| put_v.push((item.file_path.clone(), p)))
+                }
+                Some(PutAs::Overwrite(name)) => {
+                    let to = dir.join(&name);
+                    self.trash_overwritten(&to)
+                        .map(|pair| trashed.extend(pair))
+                        .and_then(|_| self.put_to(item, &to))
+                        .map(|p| put_v.push((item.file_path.clone(), p)))
+                }
+                Some(PutAs::Merge(name)) => self.merge_dir(
+                    item,
+                    &dir.join(&name),
+                    &dir,
+                    &mut resolver,
+                    &mut put_v,
+                    &mut trashed,
+                ),
+            };
+            match put {
+                Ok(()) => {}
+                Err(FxError::Canceled) => {
+                    processed = i;
+                    break;
+                }
+                Err(e) => report.add(&item.file_path, &e),
             }
+        }
+        let put = put_v.iter().map(|(_, to)| to.clone()).collect();
+        //push put item information to operations
+        self.operations.push(PutFiles {
+            put: put_v,
+            trashed,
+            dir,
+        });
+
+        Ok((put, processed < targets.len()))
+    }
+
+    /// Decide the name to put the item as in `dir`, resolving the conflict by `resolver`.
+    /// Return None if the item is skipped.
+    fn put_name(
+        &self,
+        item: &ItemBuffer,
+        dir: &std::path::Path,
+        name_set: &BTreeSet<OsString>,
+        resolver: &mut Resolver,
+    ) -> Result<Option<PutAs>, FxError> {
+        let name = item.os_file_name();
+        if !name_set.contains(&name) {
+            return Ok(Some(PutAs::New(name)));
+        }
+        let rename = if item.file_type == FileType::Directory {
+            rename_dir(&name, name_set)
         } else {
-            (0, 0)
+            rename_file(&name, name_set)
         };
-        self.layout.name_max_len = name_max;
-        self.layout.time_start_pos = time_start;
+        let existing = dir.join(&name);
+        //Overwriting the item itself or its ancestor would remove the source,
+        //and merging into itself or its descendant would never end.
+        if item.file_path.starts_with(&existing) || existing.starts_with(&item.file_path) {
+            return Ok(Some(PutAs::New(rename)));
+        }
+        //Names taken by background jobs are not in the directory yet.
+        let is_dir = match fs::symlink_metadata(&existing) {
+            Ok(metadata) => metadata.is_dir(),
+            Err(_) => return Ok(Some(PutAs::New(rename))),
+        };
+        let dirs = is_dir && item.file_type == FileType::Directory;
+        match resolver.resolve(&*self.ui(), &item.file_name, dirs)? {
+            Resolution::Overwrite => Ok(Some(PutAs::Overwrite(name))),
+            Resolution::Skip => Ok(None),
+            Resolution::Rename => Ok(Some(PutAs::New(rename))),
+            Resolution::Merge => Ok(Some(PutAs::Merge(name))),
+        }
+    }
 
-        if cursor_pos > row - 1 {
-            self.layout.nums.index -= (cursor_pos - row + 1) as usize;
-            cursor_pos = row - 1;
+    /// Put single item or directory to the path.
+    fn put_to(&mut self, item: &ItemBuffer, to: &std::path::Path) -> Result<PathBuf, FxError> {
+        match item.file_type {
+            FileType::Directory => self.put_dir(item, to),
+            _ => self.put_file(item, to),
         }
+    }
 
-        self.redraw(cursor_pos);
-        Ok(())
+    /// Merge the put directory into the existing one at `to`, resolving the conflicts
+    /// inside by `resolver`. The sources and the created paths are added to `put`,
+    /// and the overwritten ones moved to the trash to `trashed`.
+    fn merge_dir(
+        &mut self,
+        item: &ItemBuffer,
+        to: &std::path::Path,
+        dir: &std::path::Path,
+        resolver: &mut Resolver,
+        put: &mut Vec<(PathBuf, PathBuf)>,
+        trashed: &mut Vec<(PathBuf, PathBuf)>,
+    ) -> Result<(), FxError> {
+        let ui = self.ui();
+        let mut progress = Progress::new(dir_size(&item.file_path), ui.clone());
+        let preserve = self.preserve_metadata;
+        merge_tree(
+            &item.file_path,
+            to,
+            preserve,
+            &mut |bytes| progress.add(bytes),
+            &mut |path, dirs| {
+                let name = path.strip_prefix(dir).unwrap_or(path);
+                resolver.resolve(&*ui, &name.display().to_string(), dirs)
+            },
+            &mut |path| {
+                trashed.extend(self.trash_overwritten(path)?);
+                Ok(())
+            },
+            put,
+        )
     }
 
-    /// Clear all and show the current directory information.
-    pub fn clear_and_show_headline(&mut self) {
-        clear_all();
-        move_to(1, 1);
+    /// Put single item to the path.
+    fn put_file(&mut self, item: &ItemBuffer, to: &std::path::Path) -> Result<PathBuf, FxError> {
+        let mut progress = Progress::new(fs::symlink_metadata(&item.file_path)?.len(), self.ui());
+        match copy_file(&item.file_path, to, self.preserve_metadata, |bytes| {
+            progress.add(bytes)
+        }) {
+            Ok(()) => {}
+            Err(FxError::Canceled) => return Err(FxError::Canceled),
+            Err(_) => return Err(FxError::PutItem(item.file_path.clone())),
+        }
+        Ok(to.to_path_buf())
+    }
 
-        let mut header_space = (self.layout.terminal_column - 1) as usize;
+    /// Put single directory recursively to the path.
+    fn put_dir(&mut self, item: &ItemBuffer, to: &std::path::Path) -> Result<PathBuf, FxError> {
+        let mut progress = Progress::new(dir_size(&item.file_path), self.ui());
+        copy_tree(&item.file_path, to, self.preserve_metadata, |bytes| {
+            progress.add(bytes)
+        })?;
+        Ok(to.to_path_buf())
+    }
 
-        // Show current directory path.
-        // crossterm's Stylize cannot be applied to PathBuf,
-        // current directory does not have any text attribute for now.
-        let current_dir = self.current_dir.display().to_string();
-        if current_dir.bytes().len() >= header_space {
-            let current_dir = shorten_str_including_wide_char(&current_dir, header_space);
-            set_color_current_dir();
-            print!(" {}", current_dir);
-            reset_color();
-            return;
+    /// Copy the item again to the path it was put to, e.g. by redo.
+    /// An item that has taken the path is not replaced.
+    pub fn put_again(
+        &mut self,
+        from: &std::path::Path,
+        to: &std::path::Path,
+    ) -> Result<(), FxError> {
+        if to.symlink_metadata().is_ok() {
+            return Err(FxError::PutItem(to.to_path_buf()));
+        }
+        if fs::symlink_metadata(from)?.is_dir() {
+            let mut progress = Progress::new(dir_size(from), self.ui());
+            copy_tree(from, to, self.preserve_metadata, |bytes| {
+                progress.add(bytes)
+            })
+        } else {
+            let mut progress = Progress::new(fs::symlink_metadata(from)?.len(), self.ui());
+            copy_file(from, to, self.preserve_metadata, |bytes| {
+                progress.add(bytes)
+            })
+        }
+    }
+
+    /// If the list got shorter than the cursor, move the cursor to the last item.
+    /// Return the row of the cursor.
+    fn keep_cursor_in_list(&mut self) -> u16 {
+        let new_len = self.list.len();
+        if new_len == 0 {
+            self.layout.nums.reset();
+            BEGINNING_ROW
+        } else if self.layout.nums.index > new_len - 1 {
+            let new_y = self.layout.y - (self.layout.nums.index - (new_len - 1)) as u16;
+            self.layout.nums.index = new_len - 1;
+            new_y
         } else {
-            set_color_current_dir();
-            print!(" {}", current_dir);
-            reset_color();
-            header_space -= current_dir.len();
-        }
-
-        // If without the write permission, print [RO].
-        if self.is_ro && header_space > 5 {
-            set_color_read_only();
-            print!(" [RO]");
-            reset_color();
-            header_space -= 5;
-        }
-
-        //If git repository exists, get the branch information and print it.
-        if let Ok(repo) = git2::Repository::open(&self.current_dir) {
-            if let Ok(head) = repo.head() {
-                if let Some(branch) = head.shorthand() {
-                    if branch.len() + 4 <= header_space {
-                        print!(" on ",);
-                        set_color_git_repo();
-                        print!("{}", branch.trim().bold());
-                        reset_color();
+            self.layout.y
+        }
+    }
+
+    /// Undo the operation, and show the result.
+    pub fn undo(&mut self, op: &dyn Invertible) -> Result<(), FxError> {
+        let mut report = ErrorReport::default();
+        op.undo(self, &mut report)?;
+        self.operations.pos += 1;
+        self.update_list()?;
+        let y = self.keep_cursor_in_list();
+        self.draw(y);
+        self.info(format!("UNDONE: {}{}", op.name(), report.summary()));
+        info!("UNDO: {} {}", op.name(), op.detail());
+        Ok(())
+    }
+
+    /// Redo the operation, and show the result.
+    pub fn redo(&mut self, op: &dyn Invertible) -> Result<(), FxError> {
+        let mut report = ErrorReport::default();
+        op.redo(self, &mut report)?;
+        self.operations.pos -= 1;
+        self.update_list()?;
+        let y = self.keep_cursor_in_list();
+        self.draw(y);
+        self.info(format!("REDONE: {}{}", op.name(), report.summary()));
+        info!("REDO: {} {}", op.name(), op.detail());
+        Ok(())
+    }
+
+    /// Change the state by the action without printing, and return what to draw.
+    /// Returns `None` for the actions the main loop handles itself,
+    /// as they read more keys, print messages or run other programs.
+    pub fn apply(&mut self, action: Action) -> Result<Option<Render>, FxError> {
+        let len = self.list.len();
+        let render = match action {
+            Action::MoveDown => {
+                let index = self.layout.nums.index;
+                let moved = self.go_down(1)?;
+                self.layout.y += moved;
+                if self.layout.nums.index == index {
+                    Render::Nothing
+                } else if self.v_start.is_none() && moved != 0 {
+                    Render::Cursor
+                } else {
+                    Render::List
+                }
+            }
+            Action::MoveUp => {
+                let index = self.layout.nums.index;
+                let moved = self.go_up(1)?;
+                self.layout.y -= moved;
+                if self.layout.nums.index == index {
+                    Render::Nothing
+                } else if self.v_start.is_none() && moved != 0 {
+                    Render::Cursor
+                } else {
+                    Render::List
+                }
+            }
+            Action::HalfPageDown => {
+                let moved = self.go_down(self.layout.terminal_row.div_ceil(2))?;
+                self.layout.y += moved;
+                Render::List
+            }
+            Action::HalfPageUp => {
+                let moved = self.go_up(self.layout.terminal_row.div_ceil(2))?;
+                self.layout.y -= moved;
+                Render::List
+            }
+            Action::GoToTop => {
+                match self.v_start {
+                    Some(_) if self.layout.nums.index == 0 => return Ok(Some(Render::Nothing)),
+                    Some(start_pos) => self.select_from_top(start_pos),
+                    None => {}
+                }
+                self.layout.nums.reset();
+                self.layout.y = BEGINNING_ROW;
+                Render::All
+            }
+            Action::GoToBottom => {
+                if len == 0 {
+                    return Ok(Some(Render::Nothing));
+                }
+                if let Some(start_pos) = self.v_start {
+                    self.select_to_bottom(start_pos);
+                }
+                self.layout.nums.go_bottom(len - 1);
+                if len > (self.layout.terminal_row - BEGINNING_ROW) as usize {
+                    self.layout.nums.skip = (len as u16) + BEGINNING_ROW - self.layout.terminal_row;
+                    self.layout.y = self.layout.terminal_row - 1;
+                    Render::All
+                } else {
+                    self.layout.y = len as u16 + BEGINNING_ROW - 1;
+                    if self.v_start.is_some() {
+                        Render::All
+                    } else {
+                        Render::Cursor
                     }
                 }
             }
+            //Switch to linewise visual mode, or return to normal mode.
+            Action::Visual | Action::Escape => {
+                if self.v_start.is_some() || action == Action::Escape {
+                    self.reset_selection();
+                } else if len == 0 {
+                    return Ok(Some(Render::Nothing));
+                } else {
+                    self.get_item_mut()?.selected = true;
+                    self.v_start = Some(self.layout.nums.index);
+                }
+                Render::All
+            }
+            //Go to the next or previous item matching the keyword of the search.
+            Action::SearchNext | Action::SearchPrev => {
+                //In visual mode, this is disabled.
+                let Some(keyword) = self.keyword.as_ref().filter(|_| self.v_start.is_none()) else {
+                    return Ok(Some(Render::Nothing));
+                };
+                let index = self.layout.nums.index;
+                let found = if action == Action::SearchNext {
+                    self.list
+                        .iter()
+                        .skip(index + 1)
+                        .position(|x| x.file_name.contains(keyword))
+                        .map(|i| i + index + 1)
+                } else {
+                    self.list
+                        .iter()
+                        .take(index)
+                        .rposition(|x| x.file_name.contains(keyword))
+                };
+                let Some(i) = found else {
+                    return Ok(Some(Render::Nothing));
+                };
+                self.layout.nums.skip = i as u16;
+                self.layout.nums.index = i;
+                self.layout.y = BEGINNING_ROW;
+                Render::All
+            }
+            Action::SelectAll | Action::InvertSelection | Action::Mark => {
+                if len == 0 {
+                    return Ok(Some(Render::Nothing));
+                }
+                match action {
+                    Action::SelectAll => self.select_all(),
+                    Action::InvertSelection => self.invert_selection(),
+                    _ => self.toggle_mark(),
+                }
+                Render::All
+            }
+            Action::ToggleHidden => {
+                //In visual mode, this is disabled.
+                if self.v_start.is_some() {
+                    return Ok(Some(Render::Nothing));
+                }
+                if self.layout.show_hidden {
+                    self.list.retain(|x| !x.is_hidden);
+                    self.layout.show_hidden = false;
+                } else {
+                    self.layout.show_hidden = true;
+                    self.update_list()?;
+                }
+                self.layout.nums.reset();
+                self.layout.y = BEGINNING_ROW;
+                Render::All
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(render))
+    }
+
+    /// Go down up to `rows` items, scrolling the list near the bottom of the screen.
+    /// In visual mode, the selection follows the cursor.
+    /// Returns the rows the cursor moves on the screen.
+    fn go_down(&mut self, rows: u16) -> Result<u16, FxError> {
+        let len = self.list.len();
+        let mut moved = 0;
+        for _ in 0..rows {
+            if len == 0 || self.layout.nums.index == len - 1 {
+                break;
+            }
+            let scroll = self.layout.y + moved >= self.layout.terminal_row - 1 - SCROLL_POINT
+                && len > (self.layout.terminal_row - BEGINNING_ROW) as usize - 1;
+            match self.v_start {
+                Some(start_pos) if self.layout.nums.index < start_pos => {
+                    self.get_item_mut()?.selected = false;
+                    self.layout.nums.go_down();
+                }
+                Some(_) => {
+                    self.layout.nums.go_down();
+                    self.get_item_mut()?.selected = true;
+                }
+                None => self.layout.nums.go_down(),
+            }
+            if scroll {
+                self.layout.nums.inc_skip();
+            } else {
+                moved += 1;
+            }
+        }
+        Ok(moved)
+    }
+
+    /// Go up up to `rows` items, scrolling the list near the top of the screen.
+    /// In visual mode, the selection follows the cursor.
+    /// Returns the rows the cursor moves on the screen.
+    fn go_up(&mut self, rows: u16) -> Result<u16, FxError> {
+        let mut moved = 0;
+        for _ in 0..rows {
+            if self.layout.nums.index == 0 {
+                break;
+            }
+            let scroll =
+                self.layout.y - moved <= BEGINNING_ROW + SCROLL_POINT && self.layout.nums.skip != 0;
+            match self.v_start {
+                Some(start_pos) if self.layout.nums.index > start_pos => {
+                    self.get_item_mut()?.selected = false;
+                    self.layout.nums.go_up();
+                }
+                Some(_) => {
+                    self.layout.nums.go_up();
+                    self.get_item_mut()?.selected = true;
+                }
+                None => self.layout.nums.go_up(),
+            }
+            if scroll {
+                self.layout.nums.dec_skip();
+            } else {
+                moved += 1;
+            }
+        }
+        Ok(moved)
+    }
+
+    /// Add the command typed after `!` to the history,
+    /// and return it with the placeholders expanded to run by the shell.
+    pub fn shell_command(&mut self, command: &str) -> Result<OsString, FxError> {
+        self.shell_history.add(command)?;
+        let item = self.get_item().ok().map(|item| item.file_path.as_path());
+        let selected: Vec<&std::path::Path> = self
+            .list
+            .iter()
+            .filter(|item| item.selected)
+            .map(|item| item.file_path.as_path())
+            .collect();
+        let expanded = expand_placeholders(command, item, &selected, &self.current_dir);
+        info!("SHELL: {:?}", expanded);
+        Ok(expanded)
+    }
+
+    /// Return true if any of the trash dirs is modified since the size was last calculated,
+    /// remembering the new time so that the size is calculated once for each change.
+    pub fn take_trash_change(&mut self) -> bool {
+        let modified = self
+            .trash_roots()
+            .iter()
+            .filter_map(|(trash_dir, _)| fs::metadata(trash_dir).and_then(|m| m.modified()).ok())
+            .max();
+        if modified == self.trash_modified {
+            return false;
         }
+        self.trash_modified = modified;
+        true
     }
 
-    /// Escape to normal mode.
-    pub fn escape(&mut self) {
-        go_to_info_line_and_reset();
-        hide_cursor();
-        self.move_cursor(self.layout.y);
+    /// Set the dir that the paths are shown relative to:
+    /// `relative_base` if under it, otherwise the root of the git repository.
+    fn update_anchor(&mut self) {
+        self.anchor = if !self.show_relative {
+            None
+        } else {
+            match &self.relative_base {
+                Some(base) if self.current_dir.starts_with(base) => Some(base.clone()),
+                _ => git::root(&self.current_dir),
+            }
+        };
+    }
+
+    /// Toggle the paths relative to the anchor, and return whether they are shown now.
+    pub fn toggle_relative(&mut self) -> Result<bool, FxError> {
+        self.show_relative = !self.show_relative;
+        self.update_list()?;
+        self.draw(self.layout.y);
+        Ok(self.show_relative)
     }
 
-    /// Print an item in the directory.
-    fn print_item(&self, item: &ItemInfo) {
-        let name = if item.file_name.bytes().len() <= self.layout.name_max_len {
-            item.file_name.clone()
+    /// The row of the item in the list, with the escape sequences of the colors.
+    pub fn render_item(&self, item: &ItemInfo) -> String {
+        //Found by the recursive search, the item is shown by the path from the anchor.
+        let relative_path = match &self.anchor {
+            Some(anchor) if self.filter.as_ref().is_some_and(|filter| filter.recursive) => item
+                .file_path
+                .strip_prefix(anchor)
+                .ok()
+                .map(|path| path.to_string_lossy()),
+            _ => None,
+        };
+        let file_name = relative_path.unwrap_or(std::borrow::Cow::Borrowed(&item.file_name));
+        let name = shorten_name(&file_name, self.layout.name_max_len);
+        let name = if self.marked.contains_key(&item.file_path) {
+            name.underlined()
+        } else if self.new_items.contains_key(&item.file_path) {
+            name.italic().bold()
+        } else if is_partial(&item.file_path) {
+            name.dim()
         } else {
-            let i = self.layout.name_max_len - 2;
-            let mut file_name = shorten_str_including_wide_char(&item.file_name, i);
-            file_name.push_str("..");
-            file_name
+            name.stylize()
         };
-        let time = format_time(&item.modified);
-        let mut color = match item.file_type {
-            FileType::Directory => &self.layout.colors.dir_fg,
-            FileType::File => &self.layout.colors.file_fg,
-            FileType::Symlink => &self.layout.colors.symlink_fg,
+        let columns = match &item.section {
+            Some(section) if !self.layout.shown_columns.is_empty() => {
+                let width = (columns_width(&self.layout.shown_columns, &self.layout.column_widths)
+                    - 1) as usize;
+                format!(
+                    " {:<width$}",
+                    shorten_str_including_wide_char(section, width)
+                )
+            }
+            _ => render_columns(
+                &self.layout.shown_columns,
+                &self.layout.column_widths,
+                &self.layout.time_format,
+                self.timezone,
+                item,
+            ),
         };
-        if item.is_dirty {
-            color = &self.layout.colors.dirty_fg;
+        let fg = color_code(&TermColor::ForeGround(self.layout.item_color(item)));
+        let theme = &self.layout.theme;
+
+        let mut row = fg.clone();
+        if item.selected {
+            row.push_str(&highlighted(
+                &name.to_string(),
+                theme.selection_fg.as_ref(),
+                theme.selection_bg.as_ref(),
+            ));
+        } else if item.matches {
+            let _ = write!(row, "{}", name.bold());
+        } else {
+            let _ = write!(row, "{}", name);
         }
-
-        if self.layout.terminal_column < PROPER_WIDTH {
+        if !columns.is_empty() {
+            let _ = write!(
+                row,
+                "{}{}",
+                MoveLeft(1000),
+                MoveRight(self.layout.column_start_pos - 1)
+            );
             if item.selected {
-                set_color(&TermColor::ForeGround(color));
-                print!("{}", name.negative(),);
-                reset_color();
+                row.push_str(&fg);
+                row.push_str(&highlighted(
+                    &columns,
+                    theme.selection_fg.as_ref(),
+                    theme.selection_bg.as_ref(),
+                ));
             } else if item.matches {
-                set_color(&TermColor::ForeGround(color));
-                print!("{}", name.bold(),);
-                reset_color();
+                row.push_str(&fg);
+                row.push_str(&columns);
             } else {
-                set_color(&TermColor::ForeGround(color));
-                print!("{}", name);
-                reset_color();
-            }
-            if self.layout.terminal_column > self.layout.time_start_pos + TIME_WIDTH {
-                clear_until_newline();
-            }
-        } else if item.selected {
-            set_color(&TermColor::ForeGround(color));
-            print!("{}", name.negative(),);
-            move_left(1000);
-            move_right(self.layout.time_start_pos - 1);
-            print!(" {}", time.negative());
-            reset_color();
-        } else if item.matches {
-            set_color(&TermColor::ForeGround(color));
-            print!("{}", name.bold(),);
-            move_left(1000);
-            move_right(self.layout.time_start_pos - 1);
-            set_color(&TermColor::ForeGround(color));
-            print!(" {}", time);
-            reset_color();
+                row.push_str(&columns);
+            }
+        }
+        let _ = write!(row, "{}", ResetColor);
+        row
+    }
+
+    /// Update state's list of items.
+    pub fn update_list(&mut self) -> Result<(), FxError> {
+        // Check before reading, so that changes while reading are caught next time.
+        self.dir_modified = fs::metadata(&self.current_dir)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        self.dir_changed = false;
+        self.update_anchor();
+        // If git repository exists, get information of changed/untracked files.
+        let dirty_paths = git::dirty_paths(&self.current_dir);
+
+        let items = if self.filter.as_ref().is_some_and(|filter| filter.recursive) {
+            self.search_recursively(&dirty_paths)?
+        } else {
+            self.read_items(&dirty_paths)?
+        };
+        self.list = self.arrange(items);
+        self.group_trash_items();
+        Ok(())
+    }
+
+    /// Sort the items, directories first if set, and drop the hidden ones
+    /// and those not matching the filter.
+    fn arrange(&self, items: Vec<ItemInfo>) -> Vec<ItemInfo> {
+        let (mut result, mut file_v): (Vec<ItemInfo>, Vec<ItemInfo>) =
+            items.into_iter().partition(|item| {
+                item.file_type == FileType::Directory || item.symlink_dir_path.is_some()
+            });
+        if self.layout.directories_first {
+            sort_items(&mut result, &self.layout);
+            sort_items(&mut file_v, &self.layout);
+            result.append(&mut file_v);
         } else {
-            set_color(&TermColor::ForeGround(color));
-            print!("{}", name);
-            move_left(1000);
-            move_right(self.layout.time_start_pos - 1);
-            print!(" {}", time);
-            reset_color();
+            result.append(&mut file_v);
+            sort_items(&mut result, &self.layout);
+        }
+
+        if !self.layout.show_hidden {
+            result.retain(|x| !x.is_hidden);
+        }
+
+        if let Some(filter) = &self.filter {
+            let now = Local::now();
+            result.retain(|x| filter.is_match(x, &now));
         }
+        result
     }
 
-    /// Print items in the directory.
-    pub fn list_up(&self) {
-        let visible = &self.list[..];
+    /// Whether the sort or the filter needs the metadata of all the items.
+    fn needs_metadata(&self) -> bool {
+        matches!(
+            self.layout.sort_by,
+            SortKey::Time | SortKey::Size | SortKey::Custom(_)
+        ) || self
+            .filter
+            .as_ref()
+            .is_some_and(|filter| filter.has_predicates())
+    }
 
-        visible.iter().enumerate().for_each(|(index, item)| {
-            if index >= self.layout.nums.skip.into()
-                && index < (self.layout.terminal_row + self.layout.nums.skip - BEGINNING_ROW).into()
-            {
-                move_to(3, (index as u16 + BEGINNING_ROW) - self.layout.nums.skip);
-                self.print_item(item);
+    /// Read the items of the current directory.
+    /// Unless the sort or the filter needs it, the metadata is not read here
+    /// but when the item is shown, which matters on network filesystems or cold caches.
+    /// Entries are read in another thread; if it takes long,
+    /// the first screen of the items read so far is shown with a spinner
+    /// until the listing completes.
+    fn read_items(&mut self, dirty_paths: &BTreeSet<PathBuf>) -> Result<Vec<ItemInfo>, FxError> {
+        // Open here so that the error is returned as is.
+        let entries = fs::read_dir(&self.current_dir)?;
+        let deferred = !self.needs_metadata();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut batch = Vec::with_capacity(LISTING_BATCH);
+            for entry in entries {
+                match entry {
+                    Ok(e) if deferred => batch.push(read_item_without_metadata(e)),
+                    Ok(e) => batch.push(read_item(e)),
+                    Err(e) => {
+                        let _ = sender.send(Err(FxError::from(e)));
+                        return;
+                    }
+                }
+                if batch.len() == LISTING_BATCH
+                    && sender.send(Ok(std::mem::take(&mut batch))).is_err()
+                {
+                    return;
+                }
             }
+            let _ = sender.send(Ok(batch));
         });
-    }
-
-    /// Update state's list of items.
-    pub fn update_list(&mut self) -> Result<(), FxError> {
-        let mut result = Vec::new();
-        let mut dir_v = Vec::new();
-        let mut file_v = Vec::new();
 
-        // If git repository exists, get information of changed/untracked files.
-        let mut dirty_paths = BTreeSet::new();
-        if let Ok(repo) = git2::Repository::discover(&self.current_dir) {
-            let mut opts = git2::DiffOptions::new();
-            // When detecting dirty files, includes untracked files.
-            opts.include_untracked(true);
-            if let Ok(diff) = repo.diff_index_to_workdir(None, Some(&mut opts)) {
-                // Current directory does not always point to the root (e.g. in the child dir),
-                // so uses repo.path() and pop() here.
-                let mut root = repo.path().to_path_buf();
-                root.pop();
-                diff.foreach(
-                    &mut |x, _| {
-                        if let Some(new_file) = x.new_file().path() {
-                            let dirty_path = root.join(new_file);
-                            // Changes color of ancestors to show a directory may contain
-                            // dirty files.
-                            for ancestor in dirty_path.ancestors() {
-                                dirty_paths.insert(ancestor.to_owned());
-                            }
-                        }
-                        true
-                    },
-                    None,
-                    None,
-                    None,
-                )
-                // Ignores error to continue the update_list process.
-                .unwrap_or(());
+        let ui = self.ui();
+        let start = Instant::now();
+        let mut items: Vec<ItemInfo> = vec![];
+        let mut frame = 0;
+        loop {
+            match receiver.recv_timeout(LISTING_REDRAW_INTERVAL) {
+                Ok(batch) => {
+                    for mut item in batch? {
+                        item.is_dirty = dirty_paths.contains(&item.file_path);
+                        items.push(item);
+                    }
+                }
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            if start.elapsed() >= LISTING_REDRAW_INTERVAL * (frame + 1) {
+                // Sorting all the items read so far would be as slow as the listing itself,
+                // so the first screen is drawn once from the first batch,
+                // and then only the spinner turns.
+                let status = format!(
+                    "{} Loading... {} items",
+                    SPINNER[frame as usize % SPINNER.len()],
+                    items.len()
+                );
+                if frame == 0 {
+                    let first = items.iter().take(LISTING_BATCH).cloned().collect();
+                    let mut partial = self.arrange(first);
+                    partial.truncate(self.layout.terminal_row as usize);
+                    self.list = partial;
+                    ui.draw_partial(self, &status);
+                } else {
+                    ui.status(&status);
+                }
+                frame += 1;
             }
         }
+        Ok(items)
+    }
 
-        for entry in fs::read_dir(&self.current_dir)? {
-            let e = entry?;
-            let mut entry = read_item(e);
-            if dirty_paths.contains(&entry.file_path) {
-                entry.is_dirty = true;
+    /// Search files matching the filter under the current directory recursively.
+    /// Items are named by the relative path, and listed progressively while searching.
+    /// <Esc> stops the search, keeping the items found so far.
+    fn search_recursively(
+        &mut self,
+        dirty_paths: &BTreeSet<PathBuf>,
+    ) -> Result<Vec<ItemInfo>, FxError> {
+        let Some(filter) = self.filter.clone() else {
+            return Ok(vec![]);
+        };
+        let now = Local::now();
+        let show_hidden = self.layout.show_hidden;
+        let mut found = vec![];
+        let ui = self.ui();
+        let mut last_drawn = Instant::now();
+
+        let walker = walkdir::WalkDir::new(&self.current_dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| show_hidden || !e.file_name().to_string_lossy().starts_with('.'));
+        for (i, entry) in walker.enumerate() {
+            // Unreadable directories are skipped.
+            let Ok(entry) = entry else {
+                continue;
+            };
+            if entry.file_type().is_dir() {
+                continue;
             }
-            match entry.file_type {
-                FileType::Directory => dir_v.push(entry),
-                FileType::File => file_v.push(entry),
-                FileType::Symlink => {
-                    if entry.symlink_dir_path.is_some() {
-                        dir_v.push(entry);
-                    } else {
-                        file_v.push(entry);
-                    }
+            let name = entry
+                .path()
+                .strip_prefix(&self.current_dir)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .into_owned();
+            let mut item = read_item_at(entry.into_path(), name);
+            if item.symlink_dir_path.is_some() || !filter.is_match(&item, &now) {
+                continue;
+            }
+            item.is_dirty = dirty_paths.contains(&item.file_path);
+            found.push(item);
+
+            if last_drawn.elapsed() >= SEARCH_REDRAW_INTERVAL {
+                self.list = found.clone();
+                sort_items(&mut self.list, &self.layout);
+                let status = format!(
+                    "Searching... {} found in {} items. <Esc> to stop.",
+                    found.len(),
+                    i + 1
+                );
+                ui.draw_partial(self, &status);
+                if ui.is_canceled() {
+                    break;
                 }
+                last_drawn = Instant::now();
+            }
+        }
+        Ok(found)
+    }
+
+    /// If the current directory is modified outside, reload the list,
+    /// keeping the cursor and the selection on the same items
+    /// and highlighting the new items for a while.
+    /// Not in the recursive search, not to search again.
+    pub fn refresh_if_modified(&mut self) -> Result<(), FxError> {
+        let before = self.new_items.len();
+        self.new_items
+            .retain(|_, appeared| appeared.elapsed() < NEW_ITEM_HIGHLIGHT);
+        let expired = self.new_items.len() != before;
+
+        let modified = fs::metadata(&self.current_dir)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if !self.auto_refresh
+            || self.filter.as_ref().is_some_and(|filter| filter.recursive)
+            || (modified == self.dir_modified && !self.dir_changed)
+            || self
+                .last_refresh
+                .is_some_and(|refreshed| refreshed.elapsed() < REFRESH_INTERVAL)
+        {
+            if expired {
+                self.draw(self.layout.y);
             }
+            return Ok(());
         }
+        self.last_refresh = Some(Instant::now());
 
-        match self.layout.sort_by {
-            SortKey::Name => {
-                dir_v.sort_by(|a, b| natord::compare_ignore_case(&a.file_name, &b.file_name));
-                file_v.sort_by(|a, b| natord::compare_ignore_case(&a.file_name, &b.file_name));
-            }
-            SortKey::Time => {
-                dir_v.sort_by(|a, b| b.modified.partial_cmp(&a.modified).unwrap());
-                file_v.sort_by(|a, b| b.modified.partial_cmp(&a.modified).unwrap());
+        let existing: BTreeSet<PathBuf> = self.list.iter().map(|x| x.file_path.clone()).collect();
+        let selected: BTreeSet<PathBuf> = self
+            .list
+            .iter()
+            .filter(|x| x.selected)
+            .map(|x| x.file_path.clone())
+            .collect();
+        let v_start = self
+            .v_start
+            .map(|i| (i, self.list.get(i).map(|x| x.file_path.clone())));
+        let current = self.get_item().ok().map(|x| x.file_path.clone());
+        self.update_list()?;
+        for item in self.list.iter_mut() {
+            item.selected = selected.contains(&item.file_path);
+        }
+        // If the item where the visual mode started is gone, start from the same position.
+        self.v_start = v_start.map(|(i, path)| {
+            path.and_then(|p| self.list.iter().position(|x| x.file_path == p))
+                .unwrap_or(i.min(self.list.len().saturating_sub(1)))
+        });
+        let now = Instant::now();
+        for item in self.list.iter() {
+            //Highlighted when the download finishes and it is renamed.
+            if !existing.contains(&item.file_path) && !is_partial(&item.file_path) {
+                self.new_items.insert(item.file_path.clone(), now);
             }
         }
 
-        result.append(&mut dir_v);
-        result.append(&mut file_v);
-
-        if !self.layout.show_hidden {
-            result.retain(|x| !x.is_hidden);
+        let position = current.and_then(|p| self.list.iter().position(|x| x.file_path == p));
+        match position {
+            Some(i) => {
+                let rows = (self.layout.terminal_row - BEGINNING_ROW) as usize;
+                let skip = self.layout.nums.skip as usize;
+                if i < skip || i >= skip + rows {
+                    self.layout.nums.skip = i.saturating_sub(3) as u16;
+                }
+                self.layout.nums.index = i;
+                self.draw(BEGINNING_ROW + (i as u16 - self.layout.nums.skip));
+            }
+            None => {
+                if self.is_out_of_bounds() {
+                    self.layout.nums.reset();
+                    self.draw(BEGINNING_ROW);
+                } else {
+                    self.draw(self.layout.y);
+                }
+            }
         }
-
-        self.list = result;
         Ok(())
     }
 
-    /// Change (only) the order of the list and print it.
+    /// Change (only) the order of the list and draw it.
     pub fn reorder(&mut self, y: u16) {
         self.change_order();
-        self.clear_and_show_headline();
-        self.list_up();
-        self.move_cursor(y);
+        self.draw(y);
     }
 
     /// Change the order of the list without re-reading all the items.
     fn change_order(&mut self) {
+        if self.needs_metadata() {
+            self.list.iter_mut().for_each(load_metadata);
+        }
         let mut dir_v = Vec::new();
         let mut file_v = Vec::new();
         let mut result = Vec::with_capacity(self.list.len());
@@ -1271,25 +2432,133 @@ impl State {
             }
         }
 
-        match self.layout.sort_by {
-            SortKey::Name => {
-                dir_v.sort_by(|a, b| natord::compare_ignore_case(&a.file_name, &b.file_name));
-                file_v.sort_by(|a, b| natord::compare_ignore_case(&a.file_name, &b.file_name));
-            }
-            SortKey::Time => {
-                dir_v.sort_by(|a, b| b.modified.partial_cmp(&a.modified).unwrap());
-                file_v.sort_by(|a, b| b.modified.partial_cmp(&a.modified).unwrap());
-            }
-        }
-
         result.append(&mut dir_v);
-        result.append(&mut file_v);
+        if self.layout.directories_first {
+            sort_items(&mut result, &self.layout);
+            sort_items(&mut file_v, &self.layout);
+            result.append(&mut file_v);
+        } else {
+            result.append(&mut file_v);
+            sort_items(&mut result, &self.layout);
+        }
 
         if !self.layout.show_hidden {
             result.retain(|x| !x.is_hidden);
         }
 
+        if let Some(filter) = &self.filter {
+            let now = Local::now();
+            result.retain(|x| filter.is_match(x, &now));
+        }
+
         self.list = result;
+        self.group_trash_items();
+    }
+
+    /// In the trash directory, sort items by the deletion date and group them
+    /// (Today, Yesterday, Last week and Older).
+    /// The first item of each group shows the header, and items of collapsed groups
+    /// are hidden except the first one.
+    fn group_trash_items(&mut self) {
+        let Some((_, info_dir)) = self.current_trash() else {
+            return;
+        };
+        let now = Local::now();
+        let mut items: Vec<(i64, TrashGroup, ItemInfo)> = std::mem::take(&mut self.list)
+            .into_iter()
+            .map(|item| {
+                let deleted_at =
+                    deleted_at(info_dir.as_deref(), &item.file_name).unwrap_or_default();
+                (deleted_at, trash_group(deleted_at, &now), item)
+            })
+            .collect();
+        items.sort_by_key(|item| std::cmp::Reverse(item.0));
+
+        let mut counts: BTreeMap<TrashGroup, usize> = BTreeMap::new();
+        for (_, group, _) in items.iter() {
+            *counts.entry(*group).or_default() += 1;
+        }
+
+        let mut previous = None;
+        for (_, group, mut item) in items {
+            let collapsed = self.collapsed_trash.contains(&group);
+            if previous != Some(group) {
+                item.section = if collapsed {
+                    Some(format!("▸ {} (+{})", group, counts[&group] - 1))
+                } else {
+                    Some(format!("▾ {}", group))
+                };
+                previous = Some(group);
+                self.list.push(item);
+            } else if !collapsed {
+                self.list.push(item);
+            }
+        }
+    }
+
+    /// Collapse or expand the group of the item under the cursor in the trash directory.
+    pub fn toggle_trash_group(&mut self) -> Result<(), FxError> {
+        let now = Local::now();
+        let info_dir = self.current_trash().and_then(|(_, info_dir)| info_dir);
+        let group = trash_group(
+            deleted_at(info_dir.as_deref(), &self.get_item()?.file_name).unwrap_or_default(),
+            &now,
+        );
+        if !self.collapsed_trash.remove(&group) {
+            self.collapsed_trash.insert(group);
+        }
+        self.update_list()?;
+
+        // Move the cursor to the header of the group.
+        let index = self
+            .list
+            .iter()
+            .position(|item| {
+                trash_group(
+                    deleted_at(info_dir.as_deref(), &item.file_name).unwrap_or_default(),
+                    &now,
+                ) == group
+            })
+            .unwrap_or_default();
+        self.layout.nums.index = index;
+        if (self.layout.nums.skip as usize) > index {
+            self.layout.nums.skip = index as u16;
+        }
+        self.draw(BEGINNING_ROW + (index as u16 - self.layout.nums.skip));
+        Ok(())
+    }
+
+    /// Create a new empty file or directory in the current directory,
+    /// and move the cursor to it.
+    pub fn create_new_item(&mut self, name: &str, is_dir: bool) -> Result<(), FxError> {
+        let path = self.current_dir.join(name);
+        create_item(&path, is_dir)?;
+        self.operations.push(CreatedItem { path, is_dir });
+        self.update_list()?;
+
+        // The new item may be hidden or filtered out.
+        match self.list.iter().position(|x| x.file_name == name) {
+            Some(new_pos) => {
+                if new_pos < 3 {
+                    self.layout.nums.skip = 0;
+                    self.layout.nums.index = new_pos;
+                    self.draw((new_pos as u16) + BEGINNING_ROW);
+                } else {
+                    self.layout.nums.skip = (new_pos - 3) as u16;
+                    self.layout.nums.index = new_pos;
+                    self.draw(BEGINNING_ROW + 3);
+                }
+            }
+            None => {
+                if self.is_out_of_bounds() {
+                    self.layout.nums.reset();
+                    self.draw(BEGINNING_ROW);
+                } else {
+                    self.draw(self.layout.y);
+                }
+            }
+        }
+        Ok(())
     }
 
     /// Rename selected items at once.
@@ -1300,9 +2569,9 @@ impl State {
 
         let mut default = Command::new(&self.default);
         let path = file.into_temp_path();
-        if let Err(e) = default
-            .arg(&path)
-            .status()
+        if let Err(e) = self
+            .ui()
+            .run(default.arg(&path))
             .map_err(|_| FxError::DefaultEditor)
         {
             Err(e)
@@ -1334,14 +2603,275 @@ impl State {
                     }
                 }
                 let len = result.len();
-                self.operations.branch();
-                self.operations.push(OpKind::Rename(result));
+                self.operations.push(Renamed(result));
 
                 Ok(len)
             }
         }
     }
 
+    /// Rename items by the substitution `s/{regex}/{replacement}/{flags}`.
+    /// Acts on the selected items in the visual mode, otherwise on all the listed items.
+    /// The new names are previewed before the confirmation.
+    /// Returns the number of renamed items.
+    pub fn rename_by_substitution(&mut self, substitution: &str) -> Result<usize, FxError> {
+        let substitution = Substitution::new(substitution)?;
+        let in_visual = self.v_start.is_some();
+
+        let mut renames: Vec<(PathBuf, PathBuf)> = vec![];
+        let mut preview: Vec<String> = vec![];
+        let mut new_names = BTreeSet::new();
+        for item in self.list.iter().filter(|item| !in_visual || item.selected) {
+            let new_name = match substitution.replace(&item.file_name) {
+                Some(new_name) if new_name != item.file_name => new_name,
+                _ => continue,
+            };
+            if new_name.is_empty() || new_name.contains(std::path::MAIN_SEPARATOR) {
+                return Err(FxError::Io(format!(
+                    "Rename failed: Invalid name -> {}",
+                    new_name
+                )));
+            }
+            let to = self.current_dir.join(&new_name);
+            if fs::symlink_metadata(&to).is_ok() || !new_names.insert(new_name.clone()) {
+                return Err(FxError::Io(format!(
+                    "Rename failed: {} already exists",
+                    new_name
+                )));
+            }
+            preview.push(format!("{} -> {}", item.file_name, new_name));
+            renames.push((item.file_path.clone(), to));
+        }
+        if renames.is_empty() {
+            return Ok(0);
+        }
+
+        // Preview the new names in the list area.
+        let rows = ((self.layout.terminal_row - BEGINNING_ROW) as usize).max(1);
+        if preview.len() > rows {
+            let rest = preview.len() - rows + 1;
+            preview.truncate(rows - 1);
+            preview.push(format!("... and {} more", rest));
+        }
+        let ui = self.ui();
+        ui.show_lines(self, &preview);
+        let answer = self.ask(format!("Rename {} items? (if yes: y)", renames.len()))?;
+        if !matches!(answer, KeyCode::Char('y') | KeyCode::Char('Y')) {
+            return Ok(0);
+        }
+
+        let mut result: Vec<(PathBuf, PathBuf)> = vec![];
+        let mut err = None;
+        for (from, to) in renames {
+            if let Err(e) = std::fs::rename(&from, &to) {
+                err = Some(e);
+                break;
+            }
+            result.push((from, to));
+        }
+        let len = result.len();
+        if len > 0 {
+            self.operations.push(Renamed(result));
+        }
+        match err {
+            Some(e) => Err(e.into()),
+            None => Ok(len),
+        }
+    }
+
+    /// Change the permissions of the selected items (or the item under the cursor).
+    /// `mode` is the octal mode such as `644`; if not given, the frontend lets the user
+    /// choose the bits to turn on and off.
+    /// Return the number of changed items.
+    pub fn change_permissions(&mut self, mode: Option<&str>) -> Result<usize, FxError> {
+        let in_visual = self.v_start.is_some();
+        let targets: Vec<(PathBuf, String, u32)> = if in_visual {
+            self.list
+                .iter()
+                .filter(|item| item.selected)
+                .filter_map(|item| {
+                    let mode = fs::metadata(&item.file_path).ok()?.permissions();
+                    Some((
+                        item.file_path.clone(),
+                        item.file_name.clone(),
+                        mode_bits(&mode)?,
+                    ))
+                })
+                .collect()
+        } else {
+            let item = self.get_item()?;
+            let mode = mode_bits(&fs::metadata(&item.file_path)?.permissions())
+                .ok_or_else(|| FxError::Io("Permissions are not supported.".to_owned()))?;
+            vec![(item.file_path.clone(), item.file_name.clone(), mode)]
+        };
+        if targets.is_empty() {
+            return Ok(0);
+        }
+
+        //Bits to turn on and off.
+        let (set, clear) = match mode {
+            Some(mode) => {
+                let mode = parse_octal_mode(mode)
+                    .ok_or_else(|| FxError::Io(format!("Invalid mode -> {}", mode)))?;
+                (mode, !mode & 0o777)
+            }
+            None => match self.ui().edit_mode_bits(self, &targets)? {
+                Some(bits) => bits,
+                None => return Ok(0),
+            },
+        };
+
+        let mut changed = vec![];
+        let mut err = None;
+        for (path, _, old) in targets {
+            let new = (old & !clear) | set;
+            if new == old {
+                continue;
+            }
+            if let Err(e) = set_mode(&path, new) {
+                err = Some(e);
+                break;
+            }
+            changed.push(ChangedMode { path, old, new });
+        }
+        let len = changed.len();
+        if len > 0 {
+            self.operations.push(ChangedModes(changed));
+        }
+        match err {
+            Some(e) => Err(e),
+            None => Ok(len),
+        }
+    }
+
+    /// Change the owner and/or the group of the selected items (or the item under the cursor),
+    /// and of everything under them if `recursive`, after confirming the number of entries.
+    /// `spec` is `user`, `user:group` or `:group`, by name or id.
+    /// If not permitted, ask whether to retry with `sudo chown`.
+    /// Entries that fail are shown in the report at the end.
+    /// Return the number of changed entries.
+    #[cfg(target_family = "unix")]
+    pub fn change_owner(&mut self, spec: &str, recursive: bool) -> Result<usize, FxError> {
+        let (uid, gid) = parse_owner(spec)?;
+        let targets: Vec<PathBuf> = if self.v_start.is_some() {
+            self.list
+                .iter()
+                .filter(|item| item.selected)
+                .map(|item| item.file_path.clone())
+                .collect()
+        } else {
+            vec![self.get_item()?.file_path.clone()]
+        };
+
+        let ui = self.ui();
+        let mut report = ErrorReport::default();
+        let entries = if recursive {
+            ui.status("Counting entries...");
+            let entries = walk_entries(&targets, &mut report);
+            let answer = self.ask(format!(
+                "Change the owner of {} entries to {}? (if yes: y)",
+                entries.len(),
+                spec
+            ))?;
+            if !matches!(answer, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                return Ok(0);
+            }
+            entries
+        } else {
+            targets
+        };
+
+        let new = (uid.map(|uid| uid.as_raw()), gid.map(|gid| gid.as_raw()));
+        let mut changed = vec![];
+        let mut denied = vec![];
+        for (i, path) in entries.into_iter().enumerate() {
+            if i % CHOWN_PROGRESS_INTERVAL == 0 {
+                ui.status(&format!("Changing the owner... {}", i));
+            }
+            let metadata = match fs::symlink_metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    report.add(&path, &e.into());
+                    continue;
+                }
+            };
+            match nix::unistd::fchownat(
+                None,
+                &path,
+                uid,
+                gid,
+                nix::fcntl::AtFlags::AT_SYMLINK_NOFOLLOW,
+            ) {
+                Ok(()) => changed.push(ChangedOwner {
+                    path,
+                    old: (metadata.uid(), metadata.gid()),
+                    new,
+                }),
+                Err(nix::errno::Errno::EPERM) => denied.push(path),
+                Err(e) => report.add(&path, &FxError::Io(format!("chown failed: {}", e))),
+            }
+        }
+        //Changes by sudo are not recorded, as undoing them needs sudo as well.
+        let mut len = changed.len();
+        if len > 0 {
+            self.operations.push(ChangedOwners(changed));
+        }
+
+        if !denied.is_empty() {
+            let answer = self.ask(format!(
+                "Permission denied for {} entries. Retry with sudo? (if yes: y)",
+                denied.len()
+            ))?;
+            if matches!(answer, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                len += self.sudo_chown(spec, &denied, &mut report)?;
+            } else {
+                for path in denied {
+                    report.add(&path, &FxError::Io("Permission denied.".to_owned()));
+                }
+            }
+        }
+        ui.show_report("CHOWN", &report)?;
+        Ok(len)
+    }
+
+    /// Run `sudo chown` for the paths, in batches not to exceed the argument limit.
+    /// Return the number of changed paths, adding the rest to `report`.
+    #[cfg(target_family = "unix")]
+    fn sudo_chown(
+        &self,
+        spec: &str,
+        paths: &[PathBuf],
+        report: &mut ErrorReport,
+    ) -> Result<usize, FxError> {
+        //sudo asks the password in the terminal.
+        let ui = self.ui();
+        let mut changed = 0;
+        for batch in paths.chunks(SUDO_CHOWN_BATCH) {
+            let status = ui.run_line_input(
+                Command::new("sudo")
+                    .args(["chown", "-h", "--", spec])
+                    .args(batch),
+            );
+            match status {
+                Ok(status) if status.success() => {
+                    info!("SUDO CHOWN: {} {:?}", spec, batch);
+                    changed += batch.len();
+                }
+                _ => {
+                    for path in batch {
+                        report.add(path, &FxError::Io("sudo chown failed.".to_owned()));
+                    }
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    pub fn change_owner(&mut self, _spec: &str, _recursive: bool) -> Result<usize, FxError> {
+        Err(FxError::Io("chown is not supported.".to_owned()))
+    }
+
     /// Reset all item's selected state and exit the select mode.
     pub fn reset_selection(&mut self) {
         for item in self.list.iter_mut() {
@@ -1363,104 +2893,328 @@ impl State {
         }
     }
 
-    /// Select items from the top to current position.
-    pub fn select_from_top(&mut self, start_pos: usize) {
-        for (i, item) in self.list.iter_mut().enumerate() {
-            item.selected = i <= start_pos;
+    /// Narrow the list to items matching the size/time predicates (e.g. `>10M <7d`),
+    /// or clear the filter if None.
+    /// Returns the number of items listed.
+    pub fn set_filter(&mut self, filter: Option<&str>) -> Result<usize, FxError> {
+        self.filter = match filter {
+            Some(filter) => Some(Filter::parse(filter, self.ignore_case.unwrap_or(false))?),
+            None => None,
+        };
+        self.filter_preset = None;
+        self.layout.nums.reset();
+        self.update_list()?;
+        self.draw(BEGINNING_ROW);
+        Ok(self.list.len())
+    }
+
+    /// Apply the filter preset, or clear it if already applied.
+    /// Returns the number of items listed.
+    pub fn toggle_filter_preset(&mut self, preset: &FilterPreset) -> Result<usize, FxError> {
+        if self.filter_preset.as_ref() == Some(&preset.name) {
+            return self.set_filter(None);
         }
+        self.filter = Some(Filter::parse(
+            &preset.filter,
+            self.ignore_case.unwrap_or(false),
+        )?);
+        self.filter_preset = Some(preset.name.clone());
+        self.layout.nums.reset();
+        self.update_list()?;
+        self.draw(BEGINNING_ROW);
+        Ok(self.list.len())
     }
 
-    /// Select items from the current position to bottom.
-    pub fn select_to_bottom(&mut self, start_pos: usize) {
-        for (i, item) in self.list.iter_mut().enumerate() {
-            item.selected = i >= start_pos;
+    /// Open the saved search: go to its directory and search by the filter.
+    /// Returns the number of items listed.
+    pub fn open_search(&mut self, search: &SavedSearch) -> Result<usize, FxError> {
+        // Check the filter first, not to move when it is invalid.
+        Filter::parse(&search.filter, self.ignore_case.unwrap_or(false))?;
+        if let Some(dir) = &search.dir {
+            let dir = expand_tilde(dir);
+            if dir != self.current_dir {
+                self.chdir(&dir, Move::Jump)?;
+            }
         }
+        self.set_filter(Some(&search.filter))
     }
 
-    /// Show help
-    pub fn show_help(&self, mut screen: &Stdout) -> Result<(), FxError> {
-        clear_all();
-        move_to(1, 1);
-        screen.flush()?;
-        let (width, height) = terminal_size()?;
-        let help = format_txt(HELP, width, true);
-        print_help(&help, 0, height);
-        screen.flush()?;
+    /// Add the predicate (e.g. `<2h`) to the current filter.
+    /// Returns the number of items listed.
+    pub fn add_filter(&mut self, predicate: &str) -> Result<usize, FxError> {
+        let filter = match &self.filter {
+            Some(filter) => format!("{} {}", filter, predicate),
+            None => predicate.to_owned(),
+        };
+        self.set_filter(Some(&filter))
+    }
 
-        let mut skip = 0;
-        loop {
-            if let Event::Key(KeyEvent {
-                code,
-                kind: KeyEventKind::Press,
-                ..
-            }) = crossterm::event::read()?
-            {
-                match code {
-                    KeyCode::Char('j') | KeyCode::Down => {
-                        clear_all();
-                        skip += 1;
-                        print_help(&help, skip, height);
-                        screen.flush()?;
-                        continue;
-                    }
-                    KeyCode::Char('k') | KeyCode::Up => {
-                        if skip == 0 {
-                            continue;
-                        } else {
-                            clear_all();
-                            skip -= 1;
-                            print_help(&help, skip, height);
-                            screen.flush()?;
-                            continue;
-                        }
-                    }
-                    _ => {
-                        break;
-                    }
+    /// Select (or deselect) items whose name matches the glob or regex pattern,
+    /// keeping the current selection of the others.
+    /// Returns the number of matched items.
+    pub fn select_matches(&mut self, pattern: &str, select: bool) -> Result<usize, FxError> {
+        let pattern = Pattern::new(pattern, self.ignore_case.unwrap_or(false))?;
+        let mut count = 0;
+        for item in self.list.iter_mut() {
+            if pattern.is_match(&item.file_name) {
+                item.selected = select;
+                count += 1;
+            }
+        }
+        self.sync_visual_mode();
+        Ok(count)
+    }
+
+    /// Select all items in the list.
+    pub fn select_all(&mut self) {
+        for item in self.list.iter_mut() {
+            item.selected = true;
+        }
+        self.sync_visual_mode();
+    }
+
+    /// Invert the selected state of all items in the list.
+    pub fn invert_selection(&mut self) {
+        for item in self.list.iter_mut() {
+            item.selected = !item.selected;
+        }
+        self.sync_visual_mode();
+    }
+
+    /// Return the number of selected items, and how many of them are out of the screen.
+    pub fn selection_scope(&self) -> (usize, usize) {
+        let top = self.layout.nums.skip as usize;
+        let bottom = top + (self.layout.terminal_row - BEGINNING_ROW) as usize;
+        self.list
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.selected)
+            .fold((0, 0), |(total, hidden), (i, _)| {
+                if i < top || i >= bottom {
+                    (total + 1, hidden + 1)
+                } else {
+                    (total + 1, hidden)
                 }
+            })
+    }
+
+    /// Toggle the mark of the item under the cursor, or mark the selected items in the visual mode.
+    /// Marks are kept across directories, so items can be collected from several places.
+    pub fn toggle_mark(&mut self) {
+        if self.v_start.is_some() {
+            for item in self.list.iter().filter(|item| item.selected) {
+                self.marked
+                    .insert(item.file_path.clone(), ItemBuffer::new(item));
+            }
+            self.reset_selection();
+        } else if let Ok(item) = self.get_item() {
+            let item = ItemBuffer::new(item);
+            if self.marked.remove(&item.file_path).is_none() {
+                self.marked.insert(item.file_path.clone(), item);
             }
         }
+    }
+
+    /// Put all marked items in the current directory, and clear the marks.
+    pub fn put_marked(&mut self) -> Result<(), FxError> {
+        let items: Vec<ItemBuffer> = self
+            .marked
+            .values()
+            .filter(|item| item.file_path.exists())
+            .cloned()
+            .collect();
+        if items.is_empty() {
+            self.info("No marked items.");
+            return Ok(());
+        }
+        self.put(items)?;
+        if !self.is_ro {
+            self.marked.clear();
+        }
         Ok(())
     }
 
-    /// Empty the trash dir.
-    pub fn empty_trash(&mut self, mut screen: &Stdout) -> Result<(), FxError> {
-        print_warning(EMPTY_WARNING, self.layout.y);
-        screen.flush()?;
+    /// Enter the visual mode if any item is selected, so that the selected items
+    /// can be yanked, deleted or renamed. Otherwise return to the normal mode.
+    fn sync_visual_mode(&mut self) {
+        if self.list.iter().any(|item| item.selected) {
+            if self.v_start.is_none() {
+                self.v_start = Some(self.layout.nums.index);
+            }
+        } else {
+            self.v_start = None;
+        }
+    }
 
-        if let Event::Key(KeyEvent {
-            code,
-            kind: KeyEventKind::Press,
-            ..
-        }) = crossterm::event::read()?
-        {
-            match code {
-                KeyCode::Char('y') | KeyCode::Char('Y') => {
-                    print_info("EMPTY: Processing...", self.layout.y);
-                    screen.flush()?;
-
-                    //Delete trash dir.
-                    if let Err(e) = std::fs::remove_dir_all(&self.trash_dir) {
-                        print_warning(e, self.layout.y);
+    /// The index of the listed item a script refers to, by the name or by the path
+    /// as `item()` and `selected()` return.
+    fn script_target(&self, name: &str) -> Option<usize> {
+        let path = self.current_dir.join(name);
+        self.list.iter().position(|x| x.file_path == path)
+    }
+
+    /// Run the user script defined in the config, and apply the actions it requests.
+    pub fn run_script(&mut self, name: &str, args: &[&str]) -> Result<(), FxError> {
+        let source = self
+            .scripts
+            .get(name)
+            .ok_or_else(|| FxError::Script(format!("Script not found: {}", name)))?;
+        let context = ScriptContext {
+            cwd: self.current_dir.clone(),
+            item: self.get_item().ok().map(|item| item.file_path.clone()),
+            selected: self
+                .list
+                .iter()
+                .filter(|item| item.selected)
+                .map(|item| item.file_path.clone())
+                .collect(),
+            items: self
+                .list
+                .iter()
+                .map(|item| item.file_name.clone())
+                .collect(),
+        };
+
+        info!("SCRIPT: {} {:?}", name, args);
+        let actions = run_script(source, &context, args)
+            .map_err(|e| FxError::Script(format!("{} failed: {}", name, e)))?;
+
+        let mut message = None;
+        self.update_list()?;
+        for action in actions {
+            match action {
+                ScriptAction::Cd(p) => {
+                    let p = if p.is_absolute() {
+                        p
+                    } else {
+                        self.current_dir.join(p)
+                    };
+                    self.chdir(&p, Move::Jump)?;
+                }
+                ScriptAction::Goto(name) => {
+                    if let Some(i) = self.script_target(&name) {
+                        self.layout.nums.skip = i as u16;
+                        self.layout.nums.index = i;
                     }
-                    //Recreate the dir.
-                    if let Err(e) = std::fs::create_dir(&self.trash_dir) {
-                        print_warning(e, self.layout.y);
+                }
+                ScriptAction::Select(name) => {
+                    if let Some(i) = self.script_target(&name) {
+                        self.list[i].selected = true;
                     }
-                    if self.current_dir == self.trash_dir {
-                        self.reload(BEGINNING_ROW)?;
+                }
+                ScriptAction::Unselect(name) => {
+                    if let Some(i) = self.script_target(&name) {
+                        self.list[i].selected = false;
                     }
-                    go_to_info_line_and_reset();
-                    print_info("Trash dir emptied", self.layout.y);
-                    self.move_cursor(self.layout.y);
-                    screen.flush()?;
                 }
-                _ => {
-                    go_to_info_line_and_reset();
-                    self.move_cursor(self.layout.y);
+                ScriptAction::Reload => {
+                    self.update_list()?;
+                }
+                ScriptAction::Info(s) => {
+                    message = Some(s);
+                }
+                ScriptAction::CreateFile(file) => {
+                    let path = self.current_dir.join(file);
+                    create_item(&path, false)?;
+                    self.operations.push(CreatedItem {
+                        path,
+                        is_dir: false,
+                    });
+                    self.update_list()?;
+                }
+                ScriptAction::CreateDir(dir) => {
+                    let path = self.current_dir.join(dir);
+                    create_item(&path, true)?;
+                    self.operations.push(CreatedItem { path, is_dir: true });
+                    self.update_list()?;
+                }
+                ScriptAction::Rename(from, to) => {
+                    let (from, to) = (self.current_dir.join(from), self.current_dir.join(to));
+                    if to.symlink_metadata().is_ok() {
+                        return Err(FxError::Script(format!(
+                            "{} failed: {} already exists.",
+                            name,
+                            to.display()
+                        )));
+                    }
+                    fs::rename(&from, &to)?;
+                    self.operations.push(Renamed(vec![(from, to)]));
+                    self.update_list()?;
+                }
+                ScriptAction::Delete(target) => {
+                    let Some(item) = self.script_target(&target).map(|i| &self.list[i]) else {
+                        continue;
+                    };
+                    let mut report = ErrorReport::default();
+                    let (src, dest, _) =
+                        self.move_to_trash(&[ItemBuffer::new(item)], &mut report)?;
+                    self.yank_after_delete(&src, &dest, None, false, self.current_dir.clone())?;
+                    if let Some(error) = report.first() {
+                        return Err(FxError::Script(format!("{} failed: {}", name, error)));
+                    }
+                    self.update_list()?;
+                }
+            }
+        }
+        if self.layout.nums.index >= self.list.len() {
+            self.layout.nums.reset();
+        }
+        self.draw(BEGINNING_ROW + self.layout.nums.index as u16 - self.layout.nums.skip);
+        if let Some(message) = message {
+            self.info(message);
+        }
+        Ok(())
+    }
+
+    /// Select items from the top to current position.
+    pub fn select_from_top(&mut self, start_pos: usize) {
+        for (i, item) in self.list.iter_mut().enumerate() {
+            item.selected = i <= start_pos;
+        }
+    }
+
+    /// Select items from the current position to bottom.
+    pub fn select_to_bottom(&mut self, start_pos: usize) {
+        for (i, item) in self.list.iter_mut().enumerate() {
+            item.selected = i >= start_pos;
+        }
+    }
+
+    /// Empty the trash dir, and those at the top of the other mounts.
+    pub fn empty_trash(&mut self) -> Result<(), FxError> {
+        let roots = self.trash_roots();
+        let question = if roots.len() > 1 {
+            let others: Vec<String> = roots[1..]
+                .iter()
+                .map(|(trash_dir, _)| trash_dir.display().to_string())
+                .collect();
+            format!("{} Also: {}", EMPTY_WARNING, others.join(", "))
+        } else {
+            EMPTY_WARNING.to_owned()
+        };
+        if !matches!(self.ask(question)?, KeyCode::Char('y') | KeyCode::Char('Y')) {
+            return Ok(());
+        }
+
+        self.ui().status("EMPTY: Processing...");
+        let _locks = lock_trash_roots(&roots, true)?;
+        let mut errors = vec![];
+        for (trash_dir, info_dir) in roots.iter() {
+            //Delete and recreate the dirs.
+            for dir in std::iter::once(trash_dir).chain(info_dir.iter()) {
+                if let Err(e) = fs::remove_dir_all(dir).and_then(|_| fs::create_dir(dir)) {
+                    errors.push(e);
                 }
             }
         }
+        if self.current_trash().is_some() {
+            self.update_list()?;
+            self.draw(BEGINNING_ROW);
+        }
+        match errors.first() {
+            Some(e) => self.warn(e),
+            None => self.info("Trash dir emptied"),
+        }
         Ok(())
     }
 
@@ -1468,6 +3222,12 @@ impl State {
     pub fn chdir(&mut self, p: &std::path::Path, mv: Move) -> Result<(), FxError> {
         std::env::set_current_dir(p)?;
 
+        // The recursive search belongs to the directory it started from.
+        if self.filter.as_ref().is_some_and(|filter| filter.recursive) {
+            self.filter = None;
+            self.filter_preset = None;
+        }
+
         self.is_ro = match has_write_permission(p) {
             Ok(b) => !b,
             Err(_) => false,
@@ -1492,7 +3252,8 @@ impl State {
                         self.keyword = None;
                         self.layout.nums.index = memo.num.index;
                         self.layout.nums.skip = memo.num.skip;
-                        self.reload(memo.cursor_pos)?;
+                        self.update_list()?;
+                        self.draw(memo.cursor_pos);
                     }
                     None => {
                         let pre = self.current_dir.clone();
@@ -1512,16 +3273,16 @@ impl State {
                                 if new_pos < 3 {
                                     self.layout.nums.skip = 0;
                                     self.layout.nums.index = new_pos;
-                                    self.redraw((new_pos as u16) + BEGINNING_ROW);
+                                    self.draw((new_pos as u16) + BEGINNING_ROW);
                                 } else {
                                     self.layout.nums.skip = (new_pos - 3) as u16;
                                     self.layout.nums.index = new_pos;
-                                    self.redraw(BEGINNING_ROW + 3);
+                                    self.draw(BEGINNING_ROW + 3);
                                 }
                             }
                             None => {
                                 self.layout.nums.reset();
-                                self.redraw(BEGINNING_ROW);
+                                self.draw(BEGINNING_ROW);
                             }
                         }
                     }
@@ -1547,15 +3308,18 @@ impl State {
                         if p == memo.path {
                             self.layout.nums.index = memo.num.index;
                             self.layout.nums.skip = memo.num.skip;
-                            self.reload(memo.cursor_pos)?;
+                            self.update_list()?;
+                            self.draw(memo.cursor_pos);
                         } else {
                             self.layout.nums.reset();
-                            self.reload(BEGINNING_ROW)?;
+                            self.update_list()?;
+                            self.draw(BEGINNING_ROW);
                         }
                     }
                     None => {
                         self.layout.nums.reset();
-                        self.reload(BEGINNING_ROW)?;
+                        self.update_list()?;
+                        self.draw(BEGINNING_ROW);
                     }
                 }
             }
@@ -1568,7 +3332,8 @@ impl State {
                 self.p_memo = Vec::new();
                 self.c_memo = Vec::new();
                 self.layout.nums.reset();
-                self.reload(BEGINNING_ROW)?;
+                self.update_list()?;
+                self.draw(BEGINNING_ROW);
             }
             Move::List => {
                 self.current_dir = p.to_owned();
@@ -1576,7 +3341,8 @@ impl State {
                 self.p_memo = Vec::new();
                 self.c_memo = Vec::new();
                 self.layout.nums.reset();
-                self.reload(BEGINNING_ROW)?;
+                self.update_list()?;
+                self.draw(BEGINNING_ROW);
             }
         }
         //if zoxide is installed, add the target or increment its rank.
@@ -1587,7 +3353,7 @@ impl State {
                     .output()
                     .is_err()
                 {
-                    print_warning("Failed to `zoxide add`.", self.layout.y);
+                    self.warn("Failed to `zoxide add`.");
                 }
             }
         }
@@ -1596,27 +3362,54 @@ impl State {
     }
 
     /// For subsequent use by cd in the parent shell
-    pub fn export_lwd(&self) -> Result<(), ()> {
+    pub fn export_lwd(&self) -> Result<(), FxError> {
         if let Some(lwd_file) = &self.lwd_file {
-            std::fs::write(lwd_file, self.current_dir.to_str().unwrap()).map_err(|_| {
-                print_warning(
-                    format!(
-                        "Couldn't write the LWD to file {0}!",
-                        lwd_file.as_path().to_string_lossy()
-                    ),
-                    self.layout.y,
-                );
+            std::fs::write(lwd_file, self.current_dir.as_os_str().as_encoded_bytes()).map_err(|e| {
+                self.warn(format!(
+                    "Couldn't write the LWD to file {0}!",
+                    lwd_file.as_path().to_string_lossy()
+                ));
+                e.into()
             })
         } else {
-            print_warning("Shell integration may not be configured.", self.layout.y);
-            Err(())
+            let message = "Shell integration may not be configured.";
+            self.warn(message);
+            Err(FxError::Io(message.to_owned()))
         }
     }
 
-    /// Change the cursor position, and print item information at the bottom.
-    /// If preview is enabled, print text preview, contents of the directory or image preview.
-    pub fn move_cursor(&mut self, y: u16) {
-        // If preview is enabled, set the preview type, read the content (if text type) and reset the scroll.
+    /// In the file-picker mode, write the selected items to the file,
+    /// or the item if given and nothing is selected.
+    /// Nothing is written if neither, to exit as canceled.
+    pub fn export_chosen(&self, item: Option<&std::path::Path>) -> Result<(), FxError> {
+        let Some(chooser) = &self.chooser else {
+            return Ok(());
+        };
+        let mut paths: Vec<&std::path::Path> = self
+            .list
+            .iter()
+            .filter(|item| item.selected)
+            .map(|item| item.file_path.as_path())
+            .collect();
+        if paths.is_empty() {
+            paths.extend(item);
+        }
+        if paths.is_empty() {
+            return Ok(());
+        }
+        chooser.write(&paths).map_err(|e| {
+            self.warn(format!(
+                "Couldn't write the chosen paths to file {}: {}",
+                chooser.path().to_string_lossy(),
+                e
+            ));
+            e
+        })
+    }
+
+    /// Change the cursor position.
+    /// If preview is enabled, set the preview type of the item and reset the scroll.
+    pub fn set_cursor(&mut self, y: u16) {
         if self.layout.is_preview() {
             if let Ok(item) = self.get_item_mut() {
                 if item.preview_type.is_none() {
@@ -1625,103 +3418,32 @@ impl State {
                 item.preview_scroll = 0;
             }
         }
-
-        delete_pointer();
-
-        if self.layout.is_reg() {
-            //Print registers by :reg
-            let reg = self.registers.prepare_reg(self.layout.preview_space.0);
-            self.layout.print_reg(&reg);
-        }
-
-        let item = self.get_item().ok();
-        //Print item information at the bottom
-        self.print_footer(item);
-        if self.layout.is_preview() {
-            //Print preview if preview is on
-            self.layout.print_preview(item, y);
-        }
-
-        move_to(1, y);
-        print_pointer();
-
         //Store cursor position when cursor moves
         self.layout.y = y;
     }
 
-    fn to_status_bar(&self) {
-        move_to(1, self.layout.terminal_row);
-    }
-
-    /// Clear status line.
-    fn clear_status_line(&self) {
-        self.to_status_bar();
-        clear_current_line();
-        reset_color();
-        print!(
-            "{}",
-            " ".repeat(self.layout.terminal_column as usize).negative(),
-        );
-        move_to(1, self.layout.terminal_row);
-    }
-
-    /// Print item information at the bottom of the terminal.
-    fn print_footer(&self, item: Option<&ItemInfo>) {
-        self.clear_status_line();
-
-        if let Some(keyword) = &self.keyword {
-            let count = self
-                .list
-                .iter()
-                .filter(|x| match self.ignore_case {
-                    Some(true) => x.file_name.to_lowercase().contains(&keyword.to_lowercase()),
-                    _ => x.file_name.contains(keyword),
-                })
-                .count();
-            let count = if count <= 1 {
-                format!("{} match", count)
-            } else {
-                format!("{} matches", count)
-            };
-            print!(
-                "{}",
-                " ".repeat(self.layout.terminal_column as usize).negative(),
-            );
-            move_to(1, self.layout.terminal_row);
-            print!(
-                "{}{}{}{}",
-                " /".negative(),
-                keyword.clone().negative(),
-                " - ".negative(),
-                count.negative()
-            );
-            return;
-        }
-
-        if let Some(item) = item {
-            let footer = self.make_footer(item);
-            print!("{}", footer.negative());
+    /// Return footer string, by `status_format` if set.
+    pub fn make_footer(&self, item: Option<&ItemInfo>) -> String {
+        let mut footer = match (&self.layout.status_format, item) {
+            (Some(template), _) => template.render(|field| self.field_value(field, item)),
+            (None, Some(item)) => self.default_footer(item),
+            (None, None) => String::new(),
+        };
+        // Show the mode so that it is clear the selection follows the cursor.
+        if self.v_start.is_some() {
+            footer.insert_str(0, " -- VISUAL --");
         }
+        footer
+            .chars()
+            .take(self.layout.terminal_column.into())
+            .collect()
     }
 
-    /// Return footer string.
-    fn make_footer(&self, item: &ItemInfo) -> String {
-        let mut footer = String::new();
+    /// `[index/len] ext size permissions` and the counts, or the target of the symlink.
+    fn default_footer(&self, item: &ItemInfo) -> String {
+        let mut footer;
         if item.file_type == FileType::Symlink {
-            footer = " linked to: ".to_owned();
-            match &item.symlink_dir_path {
-                Some(true_path) => {
-                    footer.push_str(true_path.to_str().unwrap_or("(invalid unicode path)"))
-                }
-                None => match fs::read_link(&item.file_path) {
-                    Ok(true_path) => match true_path.normalize() {
-                        Ok(p) => footer
-                            .push_str(p.as_path().to_str().unwrap_or("(invalid univode path)")),
-                        Err(_) => footer.push_str("(invalid path)"),
-                    },
-                    Err(_) => footer.push_str("(broken link)"),
-                },
-            }
+            footer = format!(" linked to: {}", link_target(item));
         } else {
             match &item.file_ext {
                 Some(ext) => {
@@ -1766,35 +3488,91 @@ impl State {
                 }
             }
         }
-        footer
-            .chars()
-            .take(self.layout.terminal_column.into())
-            .collect()
-    }
-
-    /// Scroll down previewed text.
-    pub fn scroll_down_preview(&mut self, y: u16) {
-        if let Ok(item) = self.get_item_mut() {
-            item.preview_scroll += 1;
-            self.scroll_preview(y)
+        //Shown only if SELinux is enabled.
+        if let Some(label) = context_label(&item.file_path) {
+            footer.push_str(&label);
+        }
+        let selected = self.list.iter().filter(|item| item.selected).count();
+        if selected > 0 {
+            let _ = write!(footer, " [{} selected]", selected);
+        }
+        if !self.registers.cut.is_empty() {
+            let _ = write!(footer, " [{} cut]", self.registers.cut.len());
+        }
+        if !self.marked.is_empty() {
+            let _ = write!(footer, " [{} marked]", self.marked.len());
         }
+        if !self.jobs.list.is_empty() {
+            let _ = write!(
+                footer,
+                " [{} jobs {}%]",
+                self.jobs.list.len(),
+                self.jobs.percent()
+            );
+        }
+        footer
     }
 
-    /// Scroll up previewed text.
-    pub fn scroll_up_preview(&mut self, y: u16) {
-        if let Ok(item) = self.get_item_mut() {
-            if item.preview_scroll != 0 {
-                item.preview_scroll -= 1;
-                self.scroll_preview(y)
-            }
+    /// Value of the placeholder in `header_format` and `status_format`.
+    pub fn field_value(&self, field: Field, item: Option<&ItemInfo>) -> String {
+        match field {
+            Field::Path => self.current_dir_display(),
+            Field::Name => item.map(|item| item.file_name.clone()).unwrap_or_default(),
+            Field::Index => match self.list.is_empty() {
+                true => "0".to_owned(),
+                false => (self.layout.nums.index + 1).to_string(),
+            },
+            Field::Count => self.list.len().to_string(),
+            Field::Selected => self
+                .list
+                .iter()
+                .filter(|item| item.selected)
+                .count()
+                .to_string(),
+            Field::Cut => self.registers.cut.len().to_string(),
+            Field::Marked => self.marked.len().to_string(),
+            Field::Jobs => self.jobs.list.len().to_string(),
+            Field::Ext => item
+                .and_then(|item| item.file_ext.clone())
+                .unwrap_or_default(),
+            Field::Size => item
+                .map(|item| to_proper_size(item.file_size))
+                .unwrap_or_default(),
+            Field::Permissions => item
+                .and_then(|item| item.permissions)
+                .map(convert_to_permissions)
+                .unwrap_or_default(),
+            Field::Link => item
+                .filter(|item| item.file_type == FileType::Symlink)
+                .map(link_target)
+                .unwrap_or_default(),
+            Field::Sort => self.layout.sort_by.to_string(),
+            Field::Filter => match (&self.filter, &self.filter_preset) {
+                (Some(_), Some(name)) => name.clone(),
+                (Some(filter), None) => filter.to_string(),
+                (None, _) => String::new(),
+            },
+            Field::Free => disk_space(&self.current_dir)
+                .map(|(free, _)| to_proper_size(free))
+                .unwrap_or_default(),
+            Field::Total => disk_space(&self.current_dir)
+                .map(|(_, total)| to_proper_size(total))
+                .unwrap_or_default(),
+            Field::Trash => self.trash_size.map(to_proper_size).unwrap_or_default(),
+            Field::Branch => git::branch(&self.current_dir).unwrap_or_default(),
+            Field::ReadOnly => match self.is_ro {
+                true => "RO".to_owned(),
+                false => String::new(),
+            },
         }
     }
 
-    /// Scroll preview.
-    fn scroll_preview(&self, y: u16) {
-        self.layout.print_preview(self.get_item().ok(), y);
-        move_to(1, y);
-        print_pointer();
+    /// The current directory, relative to the anchor if set.
+    pub fn current_dir_display(&self) -> String {
+        self.anchor
+            .as_ref()
+            .and_then(|anchor| relative_display(&self.current_dir, anchor))
+            .unwrap_or_else(|| self.current_dir.display().to_string())
     }
 
     /// Save the sort key and whether to show hidden items to session file.
@@ -1804,6 +3582,7 @@ impl State {
             show_hidden: self.layout.show_hidden,
             preview: Some(self.layout.is_preview()),
             split: Some(self.layout.split),
+            trash_size: self.trash_size,
         };
         let serialized = serde_yaml::to_string(&session)?;
         fs::write(session_path, serialized)?;
@@ -1811,23 +3590,66 @@ impl State {
     }
 
     /// Unpack or unarchive a file.
-    pub fn unpack(&self) -> Result<(), FxError> {
+    pub fn unpack(&mut self) -> Result<(), FxError> {
         let item = self.get_item()?;
         let p = item.file_path.clone();
 
-        let mut name_set: BTreeSet<String> = BTreeSet::new();
-        for item in self.list.iter() {
-            name_set.insert(item.file_name.clone());
+        let mut name_set: BTreeSet<OsString> = BTreeSet::new();
+        for entry in fs::read_dir(&self.current_dir)? {
+            name_set.insert(entry?.file_name());
         }
 
-        let dest_name = rename_dir(&item.file_name, &name_set);
+        let dest_name = rename_dir(&ItemBuffer::new(item).os_file_name(), &name_set);
         let mut dest = self.current_dir.clone();
         dest.push(dest_name);
 
         magic_packed::unpack(&p, &dest)?;
+        self.operations.push(Unpacked { archive: p, dest });
         Ok(())
     }
 
+    /// Compare the archive under the cursor with the directory,
+    /// which defaults to the sibling named after the archive (`foo` or `foo.tar.gz_1` for `foo.tar.gz`),
+    /// and return the difference with the directory, or None if no difference.
+    pub fn diff_archive(
+        &mut self,
+        dir: Option<&str>,
+    ) -> Result<Option<(ArchiveDiff, PathBuf)>, FxError> {
+        let item = self.get_item()?;
+        let archive = item.file_path.clone();
+        let dir = match dir {
+            Some(dir) => self.current_dir.join(dir),
+            None => {
+                let stem = archive.file_stem().unwrap_or_default().to_string_lossy();
+                let names = [
+                    stem.trim_end_matches(".tar").to_owned(),
+                    //Unpacked by felix.
+                    format!("{}_1", item.file_name),
+                ];
+                match names
+                    .iter()
+                    .map(|name| self.current_dir.join(name))
+                    .find(|path| path.is_dir())
+                {
+                    Some(dir) => dir,
+                    None => {
+                        return Err(FxError::Unpack(
+                            "No directory to compare: Use `:compare {dir}`.".to_owned(),
+                        ))
+                    }
+                }
+            }
+        };
+        self.ui().status("Comparing...");
+        let diff = magic_packed::diff_archive(&archive, &dir)?;
+        if diff.is_empty() {
+            self.info(format!("No difference in {} files.", diff.count));
+            return Ok(None);
+        }
+
+        Ok(Some((diff, dir)))
+    }
+
     /// Check if the cursor is out of bounds.
     pub fn is_out_of_bounds(&self) -> bool {
         let current = self.layout.nums.skip + self.layout.y - BEGINNING_ROW + 1;
@@ -1835,105 +3657,264 @@ impl State {
     }
 }
 
-/// Read item information from `std::fs::DirEntry`.
-fn read_item(entry: fs::DirEntry) -> ItemInfo {
-    let path = entry.path();
-    let metadata = fs::symlink_metadata(&path);
+/// All entries under the paths including themselves, not following symlinks.
+/// Entries that cannot be read are added to `report`.
+fn walk_entries(paths: &[PathBuf], report: &mut ErrorReport) -> Vec<PathBuf> {
+    let mut entries = vec![];
+    for path in paths {
+        for entry in walkdir::WalkDir::new(path) {
+            match entry {
+                Ok(entry) => entries.push(entry.into_path()),
+                Err(e) => {
+                    let path = e.path().unwrap_or(path).to_path_buf();
+                    report.add(&path, &e.into());
+                }
+            }
+        }
+    }
+    entries
+}
 
-    let name = entry
-        .file_name()
-        .into_string()
-        .unwrap_or_else(|_| "Invalid unicode name".to_string());
-
-    let hidden = matches!(name.chars().next(), Some('.'));
-
-    let ext = path.extension().map(|s| {
-        s.to_os_string()
-            .into_string()
-            .unwrap_or_default()
-            .to_ascii_lowercase()
-    });
-
-    match metadata {
-        Ok(metadata) => {
-            let time = {
-                let sometime = metadata.modified().unwrap_or(UNIX_EPOCH);
-                let chrono_time: DateTime<Local> = DateTime::from(sometime);
-                Some(chrono_time.to_rfc3339_opts(SecondsFormat::Secs, false))
-            };
+/// Create an empty file or directory. Fails if it already exists.
+pub fn create_item(path: &std::path::Path, is_dir: bool) -> Result<(), FxError> {
+    if is_dir {
+        std::fs::create_dir(path)?;
+    } else {
+        std::fs::File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+    }
+    Ok(())
+}
 
-            let filetype = {
-                let file_type = metadata.file_type();
-                if file_type.is_dir() {
-                    FileType::Directory
-                } else if file_type.is_file() {
-                    FileType::File
-                } else if file_type.is_symlink() {
-                    FileType::Symlink
-                } else {
-                    FileType::File
-                }
-            };
+/// Note on FIFOs, sockets and device files skipped by put and delete.
+fn skipped_message(items: &[ItemBuffer]) -> String {
+    match items
+        .iter()
+        .filter(|item| item.file_type == FileType::Other)
+        .count()
+    {
+        0 => "".to_owned(),
+        1 => " (1 special file skipped)".to_owned(),
+        count => format!(" ({} special files skipped)", count),
+    }
+}
 
-            let sym_dir_path = {
-                if filetype == FileType::Symlink {
-                    if let Ok(sym_meta) = fs::metadata(&path) {
-                        if sym_meta.is_dir() {
-                            path.normalize().map(|p| p.into_path_buf()).ok()
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            };
+/// Sum of the size of the items, to report the space freed by deleting.
+fn items_size(items: &[ItemBuffer]) -> u64 {
+    items.iter().map(|item| dir_size(&item.file_path)).sum()
+}
+
+/// Move the file or directory.
+/// Across filesystems `rename` fails, so copy it and then remove the original.
+pub fn move_path(from: &std::path::Path, to: &std::path::Path) -> Result<(), FxError> {
+    match std::fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if is_cross_device(&e) => {
+            copy_tree(from, to, true, |_| Ok(()))?;
+            if fs::symlink_metadata(from)?.is_dir() {
+                fs::remove_dir_all(from)?;
+            } else {
+                fs::remove_file(from)?;
+            }
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(unix)]
+fn is_cross_device(e: &std::io::Error) -> bool {
+    e.raw_os_error() == Some(nix::libc::EXDEV)
+}
 
-            #[cfg(target_family = "unix")]
-            let permissions = Some(metadata.permissions().mode());
-            #[cfg(not(target_family = "unix"))]
-            let permissions = None;
+#[cfg(not(unix))]
+fn is_cross_device(e: &std::io::Error) -> bool {
+    //ERROR_NOT_SAME_DEVICE
+    e.raw_os_error() == Some(17)
+}
 
-            let size = metadata.len();
-            ItemInfo {
-                file_type: filetype,
-                file_name: name,
-                file_path: path,
-                symlink_dir_path: sym_dir_path,
-                file_size: size,
-                file_ext: match filetype {
-                    FileType::Directory => None,
-                    _ => ext,
-                },
-                modified: time,
-                selected: false,
-                matches: false,
-                is_hidden: hidden,
-                preview_type: None,
-                preview_scroll: 0,
-                content: None,
-                permissions,
-                is_dirty: false,
-            }
-        }
-        Err(_) => ItemInfo {
-            file_type: FileType::File,
-            file_name: name,
-            file_path: path,
-            symlink_dir_path: None,
-            file_size: 0,
-            file_ext: ext,
-            modified: None,
-            selected: false,
-            matches: false,
-            is_hidden: false,
-            preview_type: None,
-            preview_scroll: 0,
-            content: None,
-            permissions: None,
-            is_dirty: false,
+/// Parse `user`, `user:group` or `:group` into the ids. Names and numeric ids are both accepted.
+#[cfg(target_family = "unix")]
+fn parse_owner(spec: &str) -> Result<(Option<Uid>, Option<Gid>), FxError> {
+    let invalid = || FxError::Io(format!("Invalid owner -> {}", spec));
+    let (user, group) = match spec.split_once(':') {
+        Some((user, group)) => (user, group),
+        None => (spec, ""),
+    };
+    if user.is_empty() && group.is_empty() {
+        return Err(invalid());
+    }
+    let uid = match user {
+        "" => None,
+        user => match user.parse() {
+            Ok(id) => Some(Uid::from_raw(id)),
+            Err(_) => Some(
+                nix::unistd::User::from_name(user)
+                    .ok()
+                    .flatten()
+                    .ok_or_else(invalid)?
+                    .uid,
+            ),
+        },
+    };
+    let gid = match group {
+        "" => None,
+        group => match group.parse() {
+            Ok(id) => Some(Gid::from_raw(id)),
+            Err(_) => Some(
+                nix::unistd::Group::from_name(group)
+                    .ok()
+                    .flatten()
+                    .ok_or_else(invalid)?
+                    .gid,
+            ),
+        },
+    };
+    Ok((uid, gid))
+}
+
+/// Parse the 3-digit octal mode such as `644`.
+pub fn parse_octal_mode(s: &str) -> Option<u32> {
+    if s.len() != 3 {
+        return None;
+    }
+    u32::from_str_radix(s, 8).ok()
+}
+
+#[cfg(target_family = "unix")]
+fn mode_bits(permissions: &fs::Permissions) -> Option<u32> {
+    Some(permissions.mode())
+}
+
+#[cfg(not(target_family = "unix"))]
+fn mode_bits(_permissions: &fs::Permissions) -> Option<u32> {
+    None
+}
+
+#[cfg(target_family = "unix")]
+pub fn set_mode(path: &std::path::Path, mode: u32) -> Result<(), FxError> {
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn set_mode(_path: &std::path::Path, _mode: u32) -> Result<(), FxError> {
+    Err(FxError::Io("Permissions are not supported.".to_owned()))
+}
+
+/// Change the owner and/or the group of the path, not following the symlink.
+#[cfg(target_family = "unix")]
+pub fn set_owner(
+    path: &std::path::Path,
+    uid: Option<u32>,
+    gid: Option<u32>,
+) -> Result<(), FxError> {
+    nix::unistd::fchownat(
+        None,
+        path,
+        uid.map(Uid::from_raw),
+        gid.map(Gid::from_raw),
+        nix::fcntl::AtFlags::AT_SYMLINK_NOFOLLOW,
+    )
+    .map_err(|e| FxError::Io(format!("chown failed: {} -> {:?}", e, path)))
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn set_owner(
+    _path: &std::path::Path,
+    _uid: Option<u32>,
+    _gid: Option<u32>,
+) -> Result<(), FxError> {
+    Err(FxError::Io("chown is not supported.".to_owned()))
+}
+
+/// Spawn the program with the arguments in the directory, not attached to the terminal.
+/// With `systemd_run`, the command runs in its own scope by `systemd-run --user --scope`,
+/// so that it is not killed with fx. Without systemd, it is spawned directly.
+#[cfg(any(target_os = "linux", target_os = "netbsd"))]
+fn spawn_detached(
+    args: &[OsString],
+    dir: &std::path::Path,
+    systemd_run: bool,
+) -> Result<(), FxError> {
+    let spawn = |program: &OsStr, args: &[&OsStr]| {
+        Command::new(program)
+            .args(args)
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    };
+    if systemd_run {
+        let mut scoped: Vec<&OsStr> = ["--user", "--scope", "--quiet", "--collect", "--"]
+            .iter()
+            .map(|x| x.as_ref())
+            .collect();
+        scoped.extend(args.iter().map(|x| x.as_os_str()));
+        match spawn("systemd-run".as_ref(), &scoped) {
+            Ok(_) => return Ok(()),
+            //Fall back to spawning directly.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(FxError::OpenItem(e.to_string())),
+        }
+    }
+    let args: Vec<&OsStr> = args.iter().map(|x| x.as_os_str()).collect();
+    spawn(args[0], &args[1..])
+        .map(|_| ())
+        .map_err(|e| FxError::OpenItem(e.to_string()))
+}
+
+/// The file name of the program in the command, e.g. `nvim` for `/usr/bin/nvim -R`.
+fn program_name(command: &str) -> &str {
+    let program = command.split_ascii_whitespace().next().unwrap_or_default();
+    std::path::Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(program)
+}
+
+/// The size limit in bytes to open a file by the program.
+/// The limit in `command_size_limits` takes precedence; 0 there disables it.
+fn size_limit(program: &str, default: Option<u64>, limits: &BTreeMap<String, u64>) -> Option<u64> {
+    match limits.get(program) {
+        Some(0) => None,
+        Some(limit) => Some(limit * 1_000_000),
+        None => default,
+    }
+}
+
+/// Sort items by the sort key.
+/// Items with the same size or extension are sorted by name.
+fn sort_items(items: &mut [ItemInfo], layout: &Layout) {
+    sort_items_by(
+        items,
+        &layout.sort_by,
+        layout.name_order,
+        &layout.sort_scripts,
+    );
+}
+
+/// Target of the symlink shown in the status line.
+fn link_target(item: &ItemInfo) -> String {
+    match &item.symlink_dir_path {
+        Some(true_path) => true_path
+            .to_str()
+            .unwrap_or("(invalid unicode path)")
+            .to_owned(),
+        None => match fs::read_link(&item.file_path) {
+            Ok(true_path) => match true_path.normalize() {
+                Ok(p) => p
+                    .as_path()
+                    .to_str()
+                    .unwrap_or("(invalid univode path)")
+                    .to_owned(),
+                Err(_) => "(invalid path)".to_owned(),
+            },
+            Err(_) => "(broken link)".to_owned(),
         },
     }
 }
@@ -1948,10 +3929,17 @@ fn check_zoxide() -> bool {
 
 /// Set content type from ItemInfo.
 fn set_preview_content_type(item: &mut ItemInfo) {
+    load_metadata(item);
     if item.file_size > MAX_SIZE_TO_PREVIEW {
         item.preview_type = Some(PreviewType::TooLargeImage);
     } else if is_supported_image(item) {
         item.preview_type = Some(PreviewType::Image);
+    } else if is_sqlite(&item.file_path) {
+        item.preview_type = Some(PreviewType::Database);
+    } else if magic_packed::is_archive(&item.file_path) {
+        item.preview_type = Some(PreviewType::Archive);
+    } else if is_font(&item.file_path) {
+        item.preview_type = Some(PreviewType::Font);
     } else if let Ok(content) = &std::fs::read(&item.file_path) {
         if content_inspector::inspect(content).is_text() {
             if item.file_size > MAX_SIZE_TO_PREVIEW_TEXT {
@@ -1959,8 +3947,12 @@ fn set_preview_content_type(item: &mut ItemInfo) {
                 return;
             }
             if let Ok(content) = String::from_utf8(content.to_vec()) {
-                let content = content.replace('\t', "    ");
-                item.content = Some(content);
+                //Tabs delimit the cells of TSV files.
+                if table_delimiter(item) == Some('\t') {
+                    item.content = Some(content);
+                } else {
+                    item.content = Some(content.replace('\t', "    "));
+                }
             }
             item.preview_type = Some(PreviewType::Text);
         } else {
@@ -1980,6 +3972,9 @@ fn set_preview_type(item: &mut ItemInfo) {
     {
         // symlink was resolved to directory already in the ItemInfo
         item.preview_type = Some(PreviewType::Directory);
+    } else if !fs::metadata(&item.file_path).is_ok_and(|metadata| metadata.is_file()) {
+        // reading FIFOs or devices (also via symlinks) would block
+        item.preview_type = Some(PreviewType::NotReadable);
     } else {
         set_preview_content_type(item);
     }
@@ -2060,7 +4055,7 @@ mod tests {
             let entry = read_item(e);
             match entry.file_type {
                 FileType::Directory => dir_v.push(entry),
-                FileType::File | FileType::Symlink => file_v.push(entry),
+                FileType::File | FileType::Symlink | FileType::Other => file_v.push(entry),
             }
         }
         Ok(())
@@ -2080,13 +4075,30 @@ mod tests {
         for entry in temp {
             match entry.file_type {
                 FileType::Directory => dir_v.push(entry),
-                FileType::File | FileType::Symlink => file_v.push(entry),
+                FileType::File | FileType::Symlink | FileType::Other => file_v.push(entry),
             }
         }
 
         Ok(())
     }
 
+    #[test]
+    fn test_size_limit() {
+        assert_eq!(program_name("/usr/bin/nvim -R"), "nvim");
+        assert_eq!(program_name("code"), "code");
+        let limits = BTreeMap::from([("nvim".to_owned(), 200), ("less".to_owned(), 0)]);
+        assert_eq!(
+            size_limit("nvim", Some(1_000_000_000), &limits),
+            Some(200_000_000)
+        );
+        assert_eq!(size_limit("less", Some(1_000_000_000), &limits), None);
+        assert_eq!(
+            size_limit("code", Some(1_000_000_000), &limits),
+            Some(1_000_000_000)
+        );
+        assert_eq!(size_limit("code", None, &limits), None);
+    }
+
     #[test]
     fn test_has_write_permission() {
         let p = std::path::PathBuf::from("./testfiles/permission_test");
@@ -2107,6 +4119,517 @@ mod tests {
         std::fs::set_permissions(&p, perms).unwrap();
     }
 
+    #[test]
+    fn test_set_config() {
+        let mut state = State::default();
+        state.set_config(Config {
+            preview_split: Some(Split::Horizontal),
+            preview_size: Some(95),
+            theme: Some("nord".to_owned()),
+            color: None,
+            timezone: Some("Asia/Tokyo".to_owned()),
+            ..Default::default()
+        });
+        assert_eq!(state.layout.split, Split::Horizontal);
+        assert_eq!(state.timezone, Timezone::Named(chrono_tz::Tz::Asia__Tokyo));
+        assert_eq!(state.layout.preview_size, 90);
+        assert_eq!(state.layout.colors.dir_fg, state.layout.theme.dir_fg);
+        assert_ne!(state.layout.colors, ConfigColor::default());
+
+        //Reloaded without them.
+        state.set_config(Config::default());
+        assert_eq!(state.layout.split, Split::Horizontal);
+        assert_eq!(state.layout.preview_size, 50);
+        assert_eq!(state.layout.colors, ConfigColor::default());
+        assert_eq!(state.timezone, Timezone::Local);
+    }
+
+    #[test]
+    fn test_status_format() {
+        let mut state = State::default();
+        state.set_config(Config {
+            status_format: Some(" {index}/{count} {name}{ext}[{selected}] {link}".to_owned()),
+            ..Default::default()
+        });
+        state.layout.terminal_column = 80;
+        assert_eq!(state.make_footer(None), " 0/0 [0] ");
+
+        state.list = vec![
+            ItemInfo {
+                file_name: "a".to_owned(),
+                selected: true,
+                ..Default::default()
+            },
+            ItemInfo {
+                file_name: "b.txt".to_owned(),
+                file_ext: Some("txt".to_owned()),
+                ..Default::default()
+            },
+        ];
+        state.layout.nums.index = 1;
+        assert_eq!(state.make_footer(state.list.get(1)), " 2/2 b.txttxt[1] ");
+        state.layout.terminal_column = 4;
+        assert_eq!(state.make_footer(state.list.get(1)), " 2/2");
+    }
+
+    #[test]
+    fn test_render_item() {
+        let mut state = State::default();
+        state.layout.name_max_len = 20;
+        let mut item = ItemInfo {
+            file_name: "foo.txt".to_owned(),
+            ..Default::default()
+        };
+        let row = state.render_item(&item);
+        assert!(row.contains("foo.txt"));
+        assert!(row.ends_with(&ResetColor.to_string()));
+        item.selected = true;
+        assert_ne!(state.render_item(&item), row);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_non_utf8_name() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let name = OsStr::from_bytes(b"caf\xe9.txt");
+        fs::write(dir.path().join(name), "a").unwrap();
+        let entry = fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap();
+        let item = read_item(entry);
+        assert_eq!(item.file_name, "caf\u{FFFD}.txt");
+        assert_eq!(item.os_name.as_deref(), Some(name));
+        assert!(item.file_path.exists());
+
+        let buffer = ItemBuffer::new(&item);
+        assert_eq!(buffer.os_file_name(), name);
+        let name_set = BTreeSet::from([name.to_owned()]);
+        assert_eq!(
+            rename_file(name, &name_set),
+            OsStr::from_bytes(b"caf\xe9_1.txt")
+        );
+    }
+
+    /// Scripts refer to the items by the paths `item()` and `selected()` return.
+    #[test]
+    fn test_script_go_to_item() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        let mut state = State {
+            current_dir: dir.path().to_path_buf(),
+            scripts: BTreeMap::from([(
+                "back".to_owned(),
+                r#"
+                    let target = item();
+                    go_to("a.txt");
+                    go_to(target);
+                    unselect(selected()[0]);
+                "#
+                .to_owned(),
+            )]),
+            ..Default::default()
+        };
+        state.layout.terminal_column = 80;
+        state.layout.terminal_row = 24;
+        state.layout.side = Side::None;
+        state.update_list().unwrap();
+        state.layout.nums.index = 2;
+        state.layout.y = BEGINNING_ROW + 2;
+        state.list[1].selected = true;
+
+        state.run_script("back", &[]).unwrap();
+        assert_eq!(state.layout.nums.index, 2);
+        assert!(!state.list[1].selected);
+    }
+
+    /// A put failing with an error is not left in the journal as incomplete.
+    #[test]
+    fn test_put_error_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "a").unwrap();
+        let journal_dir = dir.path().join("journal");
+        let mut state = State {
+            //Removed, so that listing it fails.
+            current_dir: dir.path().join("none"),
+            journal: Journal::new(journal_dir.clone()),
+            ..Default::default()
+        };
+        state.layout.y = BEGINNING_ROW;
+        let item = ItemBuffer::new(&read_item_at(file, "a.txt".to_owned()));
+        assert!(state.put(vec![item]).is_err());
+        assert!(journal_dir.exists());
+        assert!(state.journal.incomplete().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_put_non_utf8_name() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dest) = (dir.path().join("src"), dir.path().join("dest"));
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        let name = OsStr::from_bytes(b"caf\xe9.txt");
+        fs::write(src.join(name), "new").unwrap();
+        fs::write(dest.join(name), "old").unwrap();
+        //Shown the same as the put one, but another name on the disk.
+        fs::write(dest.join(OsStr::from_bytes(b"caf\xea_1.txt")), "other").unwrap();
+
+        let mut state = State {
+            current_dir: dest.clone(),
+            on_conflict: OnConflict::Rename,
+            ..Default::default()
+        };
+        let item = ItemBuffer::new(&read_item_at(src.join(name), "caf\u{FFFD}.txt".to_owned()));
+        let (put, _) = state
+            .put_item(&[item], &mut ErrorReport::default())
+            .unwrap();
+        let renamed = dest.join(OsStr::from_bytes(b"caf\xe9_1.txt"));
+        assert_eq!(put, vec![renamed.clone()]);
+        assert_eq!(fs::read_to_string(renamed).unwrap(), "new");
+        assert_eq!(fs::read_to_string(dest.join(name)).unwrap(), "old");
+    }
+
+    /// Listing, deleting and undoing without the terminal.
+    #[test]
+    fn test_headless_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cwd, trash) = (dir.path().join("cwd"), dir.path().join("Trash"));
+        fs::create_dir_all(cwd.join("b_dir")).unwrap();
+        for name in ["c.txt", "a.txt", ".hidden"] {
+            fs::write(cwd.join(name), name).unwrap();
+        }
+        fs::create_dir_all(trash.join("files")).unwrap();
+        fs::create_dir_all(trash.join("info")).unwrap();
+
+        let headless = Arc::new(Headless::default());
+        let mut state = State {
+            current_dir: cwd.clone(),
+            trash_dir: trash.join("files"),
+            trash_info_dir: Some(trash.join("info")),
+            frontend: Some(headless.clone()),
+            ..Default::default()
+        };
+        state.layout.directories_first = true;
+        state.layout.terminal_column = 80;
+        state.layout.terminal_row = 24;
+        state.layout.side = Side::None;
+        state.update_list().unwrap();
+        let names = |state: &State| {
+            state
+                .list
+                .iter()
+                .map(|item| item.file_name.clone())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(names(&state), vec!["b_dir", "a.txt", "c.txt"]);
+
+        //Delete `a.txt` under the cursor.
+        state.layout.nums.index = 1;
+        state.layout.y = BEGINNING_ROW + 1;
+        state.delete(None, false).unwrap();
+        assert_eq!(names(&state), vec!["b_dir", "c.txt"]);
+        assert!(headless
+            .last_message()
+            .is_some_and(|message| message.starts_with("1 item deleted.")));
+        assert!(trash.join("files/a.txt").exists());
+        assert_eq!(
+            state.registers.unnamed[0].file_path,
+            trash.join("files/a.txt")
+        );
+
+        let op = state.operations.to_undo().unwrap();
+        state.undo(op.as_ref()).unwrap();
+        assert_eq!(names(&state), vec!["b_dir", "a.txt", "c.txt"]);
+        assert!(!trash.join("files/a.txt").exists());
+        assert!(headless
+            .last_message()
+            .is_some_and(|message| message.starts_with("UNDONE:")));
+    }
+
+    /// Conflicts while putting are resolved by the answers of the frontend.
+    #[test]
+    fn test_headless_put_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, cwd) = (dir.path().join("src"), dir.path().join("cwd"));
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(&cwd).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(src.join(name), "new").unwrap();
+        }
+        for name in ["a.txt", "b.txt"] {
+            fs::write(cwd.join(name), "old").unwrap();
+        }
+        let trash = dir.path().join("Trash");
+        fs::create_dir_all(&trash).unwrap();
+
+        let headless = Arc::new(Headless::default());
+        let mut state = State {
+            current_dir: cwd.clone(),
+            trash_dir: trash,
+            legacy_trash: true,
+            frontend: Some(headless.clone()),
+            ..Default::default()
+        };
+        state.layout.terminal_column = 80;
+        state.layout.terminal_row = 24;
+        state.layout.y = BEGINNING_ROW;
+        let items: Vec<ItemBuffer> = ["a.txt", "b.txt", "c.txt"]
+            .iter()
+            .map(|name| ItemBuffer::new(&read_item_at(src.join(name), name.to_string())))
+            .collect();
+
+        //Rename `a.txt`, and skip `b.txt`.
+        headless.answer("rs");
+        state.put(items.clone()).unwrap();
+        assert_eq!(fs::read_to_string(cwd.join("a.txt")).unwrap(), "old");
+        assert_eq!(fs::read_to_string(cwd.join("a_1.txt")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(cwd.join("b.txt")).unwrap(), "old");
+        assert_eq!(fs::read_to_string(cwd.join("c.txt")).unwrap(), "new");
+        assert!(headless
+            .last_message()
+            .is_some_and(|message| message.starts_with("2 items inserted.")));
+
+        //Overwrite all, moving the existing ones to the trash.
+        headless.answer("O");
+        state.put(items[..2].to_vec()).unwrap();
+        assert_eq!(fs::read_to_string(cwd.join("b.txt")).unwrap(), "new");
+        assert_eq!(state.trash_dir.read_dir().unwrap().count(), 2);
+
+        //Without the answer, the put is canceled at the first conflict.
+        state.put(items[..1].to_vec()).unwrap();
+        assert!(!cwd.join("a_2.txt").exists());
+        assert!(headless
+            .last_message()
+            .is_some_and(|message| message.starts_with("Canceled: 0 items inserted.")));
+    }
+
+    /// Changing the sort key reorders the list without reading it again.
+    #[test]
+    fn test_headless_reorder() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, size) in [("a.txt", 3), ("b.txt", 1), ("c.txt", 2)] {
+            fs::write(dir.path().join(name), "x".repeat(size)).unwrap();
+        }
+        fs::create_dir(dir.path().join("d_dir")).unwrap();
+        let mut state = State {
+            current_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        state.layout.terminal_column = 80;
+        state.layout.terminal_row = 24;
+        state.layout.directories_first = true;
+        state.update_list().unwrap();
+        let names = |state: &State| {
+            state
+                .list
+                .iter()
+                .map(|item| item.file_name.clone())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(names(&state), vec!["d_dir", "a.txt", "b.txt", "c.txt"]);
+
+        state.layout.sort_by = SortKey::Size;
+        state.reorder(BEGINNING_ROW + 1);
+        //Larger first.
+        assert_eq!(names(&state), vec!["d_dir", "a.txt", "c.txt", "b.txt"]);
+        assert_eq!(state.layout.y, BEGINNING_ROW + 1);
+
+        state.layout.directories_first = false;
+        state.layout.sort_by = SortKey::Name;
+        state.reorder(BEGINNING_ROW);
+        assert_eq!(names(&state), vec!["a.txt", "b.txt", "c.txt", "d_dir"]);
+    }
+
+    /// Moving the cursor and selecting by the actions without the terminal.
+    #[test]
+    fn test_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..40 {
+            fs::write(dir.path().join(format!("{:02}.txt", i)), "").unwrap();
+        }
+        fs::write(dir.path().join(".hidden"), "").unwrap();
+        let mut state = State {
+            current_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        state.layout.terminal_column = 80;
+        state.layout.terminal_row = 24;
+        state.layout.y = BEGINNING_ROW;
+        state.update_list().unwrap();
+        assert_eq!(state.list.len(), 40);
+
+        let apply = |state: &mut State, action| state.apply(action).unwrap();
+        assert_eq!(apply(&mut state, Action::MoveUp), Some(Render::Nothing));
+        assert_eq!(apply(&mut state, Action::MoveDown), Some(Render::Cursor));
+        assert_eq!(
+            (state.layout.nums.index, state.layout.y),
+            (1, BEGINNING_ROW + 1)
+        );
+        // Scrolls near the bottom of the screen.
+        assert_eq!(apply(&mut state, Action::HalfPageDown), Some(Render::List));
+        assert_eq!(state.layout.nums.index, 13);
+        assert_eq!(apply(&mut state, Action::HalfPageDown), Some(Render::List));
+        assert_eq!(state.layout.nums.index, 25);
+        assert_eq!(state.layout.y, 24 - 1 - SCROLL_POINT);
+        assert_eq!(
+            state.layout.nums.skip,
+            25 - (state.layout.y - BEGINNING_ROW)
+        );
+        assert_eq!(apply(&mut state, Action::GoToBottom), Some(Render::All));
+        assert_eq!((state.layout.nums.index, state.layout.nums.skip), (39, 19));
+        assert_eq!(state.layout.y, 23);
+        assert_eq!(apply(&mut state, Action::MoveDown), Some(Render::Nothing));
+
+        // The selection follows the cursor in visual mode.
+        state.v_start = Some(39);
+        state.get_item_mut().unwrap().selected = true;
+        apply(&mut state, Action::MoveUp);
+        apply(&mut state, Action::MoveUp);
+        let selected = |state: &State| state.list.iter().filter(|item| item.selected).count();
+        assert_eq!(selected(&state), 3);
+        apply(&mut state, Action::MoveDown);
+        assert_eq!(selected(&state), 2);
+        // Disabled in visual mode.
+        assert_eq!(
+            apply(&mut state, Action::ToggleHidden),
+            Some(Render::Nothing)
+        );
+        // gg selects the items up to the start.
+        assert_eq!(apply(&mut state, Action::GoToTop), Some(Render::All));
+        assert_eq!(selected(&state), 40);
+        assert_eq!(
+            (state.layout.nums.index, state.layout.y),
+            (0, BEGINNING_ROW)
+        );
+        assert_eq!(apply(&mut state, Action::Escape), Some(Render::All));
+        assert_eq!((state.v_start, selected(&state)), (None, 0));
+
+        assert_eq!(
+            apply(&mut state, Action::InvertSelection),
+            Some(Render::All)
+        );
+        assert_eq!(selected(&state), 40);
+        state.reset_selection();
+        assert_eq!(apply(&mut state, Action::ToggleHidden), Some(Render::All));
+        assert_eq!(state.list.len(), 41);
+        assert_eq!(
+            (state.layout.nums.index, state.layout.y),
+            (0, BEGINNING_ROW)
+        );
+
+        state.keyword = Some("3".to_owned());
+        let current = |state: &State| state.get_item().unwrap().file_name.clone();
+        assert_eq!(apply(&mut state, Action::SearchNext), Some(Render::All));
+        assert_eq!(current(&state), "03.txt");
+        apply(&mut state, Action::SearchNext);
+        assert_eq!(current(&state), "13.txt");
+        assert_eq!(state.layout.y, BEGINNING_ROW);
+        apply(&mut state, Action::SearchPrev);
+        assert_eq!(current(&state), "03.txt");
+        assert_eq!(apply(&mut state, Action::SearchPrev), Some(Render::Nothing));
+
+        assert_eq!(apply(&mut state, Action::Visual), Some(Render::All));
+        assert_eq!(state.v_start, Some(state.layout.nums.index));
+        assert_eq!(selected(&state), 1);
+        apply(&mut state, Action::Visual);
+        assert_eq!((state.v_start, selected(&state)), (None, 0));
+        // Handled by the main loop.
+        assert_eq!(apply(&mut state, Action::Delete), None);
+    }
+
+    #[test]
+    fn test_create_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("new.txt");
+        let sub = dir.path().join("new_dir");
+
+        create_item(&file, false).unwrap();
+        create_item(&sub, true).unwrap();
+        assert!(file.is_file());
+        assert!(sub.is_dir());
+
+        // Existing items are not overwritten.
+        std::fs::write(&file, "content").unwrap();
+        assert!(create_item(&file, false).is_err());
+        assert!(create_item(&sub, true).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "content");
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_walk_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a");
+        std::fs::create_dir_all(a.join("b")).unwrap();
+        std::fs::write(a.join("b/c.txt"), "c").unwrap();
+        std::fs::write(dir.path().join("d.txt"), "d").unwrap();
+        #[cfg(target_family = "unix")]
+        std::os::unix::fs::symlink(dir.path(), a.join("link")).unwrap();
+
+        let mut report = ErrorReport::default();
+        let mut entries = walk_entries(&[a.clone(), dir.path().join("d.txt")], &mut report);
+        entries.sort();
+        let mut expected = vec![
+            a.clone(),
+            a.join("b"),
+            a.join("b/c.txt"),
+            dir.path().join("d.txt"),
+        ];
+        #[cfg(target_family = "unix")]
+        expected.push(a.join("link"));
+        expected.sort();
+        assert_eq!(entries, expected);
+        assert!(report.is_empty());
+
+        let entries = walk_entries(&[dir.path().join("none")], &mut report);
+        assert!(entries.is_empty());
+        assert!(!report.is_empty());
+    }
+
+    #[test]
+    fn test_parse_owner() {
+        assert_eq!(
+            parse_owner("0:0").unwrap(),
+            (Some(Uid::from_raw(0)), Some(Gid::from_raw(0)))
+        );
+        assert_eq!(parse_owner("root").unwrap(), (Some(Uid::from_raw(0)), None));
+        assert_eq!(parse_owner(":0").unwrap(), (None, Some(Gid::from_raw(0))));
+        assert!(parse_owner(":").is_err());
+        assert!(parse_owner("no_such_user_for_fx").is_err());
+    }
+
+    #[test]
+    fn test_parse_octal_mode() {
+        assert_eq!(parse_octal_mode("644"), Some(0o644));
+        assert_eq!(parse_octal_mode("64"), None);
+        assert_eq!(parse_octal_mode("648"), None);
+        assert_eq!(parse_octal_mode("0644"), None);
+    }
+
+    #[test]
+    fn test_move_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::write(src.join("sub/a.txt"), "a").unwrap();
+        let dest = dir.path().join("dest");
+        std::fs::create_dir(&dest).unwrap();
+
+        move_path(&src, &dest.join("src")).unwrap();
+        assert!(!src.exists());
+        assert_eq!(
+            std::fs::read_to_string(dest.join("src/sub/a.txt")).unwrap(),
+            "a"
+        );
+
+        assert!(move_path(&dir.path().join("none"), &dest.join("none")).is_err());
+    }
+
     #[test]
     fn bench_update_single() {
         let bench_result = run_benchmark(100, |_| {
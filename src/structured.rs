@@ -0,0 +1,319 @@
+use super::functions::shorten_str_including_wide_char;
+use super::item::ItemInfo;
+
+use crossterm::style::{Color, Stylize};
+use serde::Deserialize;
+use serde_yaml::Value;
+use unicode_width::UnicodeWidthStr;
+
+/// Containers nested deeper than this are folded into a summary like `{…3 keys}`.
+const MAX_DEPTH: usize = 8;
+/// Entries of a container more than this are omitted.
+const MAX_ENTRIES: usize = 100;
+/// Lines more than this are not generated, so that huge files do not slow down the preview.
+const MAX_LINES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Yaml,
+}
+
+type Line = Vec<(String, Color)>;
+
+/// Pretty-print and color JSON or YAML files for the preview, cutting lines by `width`.
+/// Return None if the item is not JSON/YAML or cannot be parsed, to show it as plain text.
+pub fn render_structured(item: &ItemInfo, content: &str, width: u16) -> Option<Vec<String>> {
+    let format = match item.file_ext.as_ref()?.to_lowercase().as_str() {
+        "json" => Format::Json,
+        "yaml" | "yml" => Format::Yaml,
+        _ => return None,
+    };
+    let lines = layout_structured(content, format)?;
+    Some(
+        lines
+            .into_iter()
+            .map(|line| cut_line(line, width.into()))
+            .collect(),
+    )
+}
+
+fn layout_structured(content: &str, format: Format) -> Option<Vec<Line>> {
+    let mut printer = Printer::default();
+    match format {
+        Format::Json => {
+            let value: Value = serde_yaml::from_str(content).ok()?;
+            printer.json(&value, 0, vec![], false);
+        }
+        Format::Yaml => {
+            //A YAML file may contain multiple documents separated by `---`.
+            for (i, document) in serde_yaml::Deserializer::from_str(content).enumerate() {
+                let value = Value::deserialize(document).ok()?;
+                if i > 0 {
+                    printer.push(vec![punct("---")]);
+                }
+                printer.yaml(&value, 0, vec![]);
+            }
+        }
+    }
+    if printer.truncated {
+        printer.lines.push(vec![punct("… (truncated)")]);
+    }
+    Some(printer.lines)
+}
+
+#[derive(Default)]
+struct Printer {
+    lines: Vec<Line>,
+    truncated: bool,
+}
+
+impl Printer {
+    fn push(&mut self, line: Line) {
+        if self.lines.len() < MAX_LINES {
+            self.lines.push(line);
+        } else {
+            self.truncated = true;
+        }
+    }
+
+    /// Print the value, where `lead` is the beginning of the first line (e.g. the key).
+    fn json(&mut self, value: &Value, depth: usize, lead: Line, comma: bool) {
+        let comma = if comma { "," } else { "" };
+        match value {
+            Value::Mapping(map) if is_block(value, depth) => {
+                let mut line = lead;
+                line.push(punct("{"));
+                self.push(line);
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i == MAX_ENTRIES {
+                        self.push_omitted(depth + 1, map.len() - i);
+                        break;
+                    }
+                    let mut line = indent(depth + 1);
+                    line.push((json_string(&key_text(key)), Color::Cyan));
+                    line.push(punct(": "));
+                    self.json(value, depth + 1, line, i + 1 < map.len());
+                }
+                let mut line = indent(depth);
+                line.push(punct(&format!("}}{}", comma)));
+                self.push(line);
+            }
+            Value::Sequence(seq) if is_block(value, depth) => {
+                let mut line = lead;
+                line.push(punct("["));
+                self.push(line);
+                for (i, value) in seq.iter().enumerate() {
+                    if i == MAX_ENTRIES {
+                        self.push_omitted(depth + 1, seq.len() - i);
+                        break;
+                    }
+                    self.json(value, depth + 1, indent(depth + 1), i + 1 < seq.len());
+                }
+                let mut line = indent(depth);
+                line.push(punct(&format!("]{}", comma)));
+                self.push(line);
+            }
+            _ => {
+                let mut line = lead;
+                line.push(inline(value, Format::Json));
+                line.push(punct(comma));
+                self.push(line);
+            }
+        }
+    }
+
+    /// Print the value, where `lead` is the beginning of the first line (e.g. `- `).
+    fn yaml(&mut self, value: &Value, depth: usize, lead: Line) {
+        let mut lead = Some(lead);
+        match value {
+            Value::Mapping(map) if is_block(value, depth) => {
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i == MAX_ENTRIES {
+                        self.push_omitted(depth, map.len() - i);
+                        break;
+                    }
+                    let mut line = lead.take().unwrap_or_else(|| indent(depth));
+                    line.push((key_text(key), Color::Cyan));
+                    line.push(punct(":"));
+                    if is_block(value, depth + 1) {
+                        self.push(line);
+                        self.yaml(value, depth + 1, indent(depth + 1));
+                    } else {
+                        line.push(punct(" "));
+                        line.push(inline(value, Format::Yaml));
+                        self.push(line);
+                    }
+                }
+            }
+            Value::Sequence(seq) if is_block(value, depth) => {
+                for (i, value) in seq.iter().enumerate() {
+                    if i == MAX_ENTRIES {
+                        self.push_omitted(depth, seq.len() - i);
+                        break;
+                    }
+                    let mut line = lead.take().unwrap_or_else(|| indent(depth));
+                    line.push(punct("- "));
+                    if is_block(value, depth + 1) {
+                        self.yaml(value, depth + 1, line);
+                    } else {
+                        line.push(inline(value, Format::Yaml));
+                        self.push(line);
+                    }
+                }
+            }
+            _ => {
+                let mut line = lead.take().unwrap_or_default();
+                line.push(inline(value, Format::Yaml));
+                self.push(line);
+            }
+        }
+    }
+
+    fn push_omitted(&mut self, depth: usize, count: usize) {
+        let mut line = indent(depth);
+        line.push(punct(&format!("… {} more", count)));
+        self.push(line);
+    }
+}
+
+/// Whether the value is printed over multiple lines, instead of inline.
+fn is_block(value: &Value, depth: usize) -> bool {
+    match value {
+        Value::Mapping(map) => !map.is_empty() && depth < MAX_DEPTH,
+        Value::Sequence(seq) => !seq.is_empty() && depth < MAX_DEPTH,
+        _ => false,
+    }
+}
+
+/// Scalars, empty containers and the folded ones.
+fn inline(value: &Value, format: Format) -> (String, Color) {
+    match value {
+        Value::Null => ("null".to_owned(), Color::Magenta),
+        Value::Bool(b) => (b.to_string(), Color::Magenta),
+        Value::Number(n) => (n.to_string(), Color::Yellow),
+        Value::String(s) => {
+            let s = if format == Format::Json || s.contains('\n') || s.is_empty() {
+                json_string(s)
+            } else {
+                s.to_owned()
+            };
+            (s, Color::Green)
+        }
+        Value::Mapping(map) if map.is_empty() => ("{}".to_owned(), Color::DarkGrey),
+        Value::Mapping(map) => (format!("{{…{} keys}}", map.len()), Color::DarkGrey),
+        Value::Sequence(seq) if seq.is_empty() => ("[]".to_owned(), Color::DarkGrey),
+        Value::Sequence(seq) => (format!("[…{} items]", seq.len()), Color::DarkGrey),
+        Value::Tagged(tagged) => {
+            let (s, color) = inline(&tagged.value, format);
+            (format!("{} {}", tagged.tag, s), color)
+        }
+    }
+}
+
+fn key_text(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.to_owned(),
+        _ => inline(key, Format::Yaml).0,
+    }
+}
+
+/// Quote the string, escaping the special characters.
+fn json_string(s: &str) -> String {
+    format!("{:?}", s)
+}
+
+fn indent(depth: usize) -> Line {
+    vec![("  ".repeat(depth), Color::DarkGrey)]
+}
+
+fn punct(s: &str) -> (String, Color) {
+    (s.to_owned(), Color::DarkGrey)
+}
+
+/// Color the spans, cutting the line by the width.
+fn cut_line(line: Line, width: usize) -> String {
+    let mut result = String::new();
+    let mut rest = width;
+    for (text, color) in line {
+        let text = shorten_str_including_wide_char(&text, rest);
+        rest -= UnicodeWidthStr::width(text.as_str());
+        result.push_str(&text.with(color).to_string());
+        if rest == 0 {
+            break;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(lines: Vec<Line>) -> Vec<String> {
+        lines
+            .into_iter()
+            .map(|line| line.into_iter().map(|(text, _)| text).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_json() {
+        let lines = layout_structured(r#"{"a":1,"b":[true,null],"c":{},"d":"x\"y"}"#, Format::Json);
+        assert_eq!(
+            plain(lines.unwrap()),
+            vec![
+                "{",
+                "  \"a\": 1,",
+                "  \"b\": [",
+                "    true,",
+                "    null",
+                "  ],",
+                "  \"c\": {},",
+                "  \"d\": \"x\\\"y\"",
+                "}",
+            ]
+        );
+        assert!(layout_structured("{", Format::Json).is_none());
+    }
+
+    #[test]
+    fn test_yaml() {
+        let lines = layout_structured(
+            "a: 1\nlist:\n- x\n- name: y\n  tags: [p, q]\n---\nb: ''",
+            Format::Yaml,
+        );
+        assert_eq!(
+            plain(lines.unwrap()),
+            vec![
+                "a: 1",
+                "list:",
+                "  - x",
+                "  - name: y",
+                "    tags:",
+                "      - p",
+                "      - q",
+                "---",
+                "b: \"\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fold() {
+        let deep = format!(
+            "{}1{}",
+            "[".repeat(MAX_DEPTH + 1),
+            "]".repeat(MAX_DEPTH + 1)
+        );
+        let lines = plain(layout_structured(&deep, Format::Json).unwrap());
+        assert_eq!(
+            lines[MAX_DEPTH],
+            format!("{}[…1 items]", "  ".repeat(MAX_DEPTH))
+        );
+
+        let long = format!("[{}]", vec!["0"; MAX_ENTRIES + 5].join(","));
+        let lines = plain(layout_structured(&long, Format::Json).unwrap());
+        assert_eq!(lines[MAX_ENTRIES + 1], "  … 5 more");
+    }
+}
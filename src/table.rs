@@ -0,0 +1,196 @@
+use super::functions::shorten_str_including_wide_char;
+use super::item::ItemInfo;
+
+use crossterm::style::{Attribute, Stylize};
+use unicode_width::UnicodeWidthStr;
+
+/// Rows more than this are not shown in the preview.
+const MAX_ROWS: usize = 200;
+/// Longer cells are cut off so that other columns fit in the preview.
+const MAX_CELL_WIDTH: usize = 24;
+const SEPARATOR: &str = " │ ";
+
+/// Return the delimiter if the item is a CSV or TSV file.
+pub fn table_delimiter(item: &ItemInfo) -> Option<char> {
+    match item.file_ext.as_ref()?.to_lowercase().as_str() {
+        "csv" => Some(','),
+        "tsv" | "tab" => Some('\t'),
+        _ => None,
+    }
+}
+
+/// Render the first rows of the delimiter-separated text as an aligned table.
+/// If the first row looks like a header, it is emphasized and underlined by a rule.
+/// Numeric columns are aligned to the right.
+pub fn render_table(content: &str, delimiter: char, width: u16) -> Vec<String> {
+    let rows = parse_rows(content, delimiter);
+    if rows.is_empty() {
+        return vec![];
+    }
+    let has_header = is_header(&rows);
+    let body = if has_header { &rows[1..] } else { &rows[..] };
+
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut widths = vec![0; columns];
+    for row in rows.iter() {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(UnicodeWidthStr::width(cell.as_str()).min(MAX_CELL_WIDTH));
+        }
+    }
+    let numeric: Vec<bool> = (0..columns)
+        .map(|i| {
+            let mut cells = body.iter().filter_map(|row| row.get(i)).peekable();
+            cells.peek().is_some() && cells.all(|cell| cell.is_empty() || is_number(cell))
+        })
+        .collect();
+
+    let width = width as usize;
+    let mut lines = vec![];
+    for (n, row) in rows.iter().enumerate() {
+        let line = (0..columns)
+            .map(|i| {
+                let cell = row.get(i).map(|cell| cell.as_str()).unwrap_or_default();
+                pad(cell, widths[i], numeric[i])
+            })
+            .collect::<Vec<String>>()
+            .join(SEPARATOR);
+        let line = shorten_str_including_wide_char(&line, width);
+        if n == 0 && has_header {
+            lines.push(line.cyan().attribute(Attribute::Bold).to_string());
+            let rule = widths
+                .iter()
+                .map(|w| "─".repeat(*w))
+                .collect::<Vec<String>>()
+                .join("─┼─");
+            lines.push(
+                shorten_str_including_wide_char(&rule, width)
+                    .dark_grey()
+                    .to_string(),
+            );
+        } else {
+            lines.push(line.dark_grey().to_string());
+        }
+    }
+    lines
+}
+
+/// Pad or cut the cell to the width.
+fn pad(cell: &str, width: usize, right: bool) -> String {
+    let mut cell = cell.to_owned();
+    if UnicodeWidthStr::width(cell.as_str()) > width {
+        cell = shorten_str_including_wide_char(&cell, width.saturating_sub(1));
+        cell.push('…');
+    }
+    let spaces = " ".repeat(width.saturating_sub(UnicodeWidthStr::width(cell.as_str())));
+    if right {
+        spaces + cell.as_str()
+    } else {
+        cell + spaces.as_str()
+    }
+}
+
+/// Split the text into rows and cells, up to `MAX_ROWS`.
+/// Quoted cells can contain the delimiter, newlines and escaped quotes ("").
+fn parse_rows(content: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    cell.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' | '\r' => cell.push(' '),
+                _ => cell.push(c),
+            }
+        } else if c == delimiter {
+            row.push(std::mem::take(&mut cell));
+        } else if c == '"' && cell.is_empty() {
+            in_quotes = true;
+        } else if c == '\n' {
+            row.push(std::mem::take(&mut cell));
+            rows.push(std::mem::take(&mut row));
+            if rows.len() == MAX_ROWS {
+                return rows;
+            }
+        } else if c != '\r' {
+            cell.push(c);
+        }
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    rows
+}
+
+/// The first row is regarded as the header if its cells are non-empty, unique and not numbers,
+/// while the next row looks like data.
+fn is_header(rows: &[Vec<String>]) -> bool {
+    let first = &rows[0];
+    if first.iter().any(|cell| cell.is_empty() || is_number(cell)) {
+        return false;
+    }
+    let mut names = first.clone();
+    names.sort();
+    names.dedup();
+    if names.len() != first.len() {
+        return false;
+    }
+    match rows.get(1) {
+        // Some cells below are numbers, or the cells differ in width a lot.
+        Some(second) => {
+            second.iter().any(|cell| is_number(cell))
+                || second.iter().zip(first).any(|(a, b)| a.len() > b.len() * 2)
+        }
+        None => true,
+    }
+}
+
+fn is_number(s: &str) -> bool {
+    let s = s.trim();
+    !s.is_empty() && s.replace([',', '_'], "").parse::<f64>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rows() {
+        assert_eq!(
+            parse_rows("a,\"b, \"\"c\"\"\"\r\n1,\"multi\nline\"\n2", ','),
+            vec![
+                vec!["a".to_owned(), "b, \"c\"".to_owned()],
+                vec!["1".to_owned(), "multi line".to_owned()],
+                vec!["2".to_owned()],
+            ]
+        );
+        assert_eq!(
+            parse_rows("x\ty\n", '\t'),
+            vec![vec!["x".to_owned(), "y".to_owned()]]
+        );
+    }
+
+    #[test]
+    fn test_is_header() {
+        let rows = parse_rows("name,size\nfoo,10\nbar,200", ',');
+        assert!(is_header(&rows));
+        let rows = parse_rows("foo,10\nbar,200", ',');
+        assert!(!is_header(&rows));
+        let rows = parse_rows("a,b\nc,d", ',');
+        assert!(!is_header(&rows));
+    }
+
+    #[test]
+    fn test_pad() {
+        assert_eq!(pad("ab", 4, false), "ab  ");
+        assert_eq!(pad("12", 4, true), "  12");
+        assert_eq!(pad("abcdef", 4, false), "abc…");
+    }
+}
@@ -0,0 +1,146 @@
+use super::errors::FxError;
+
+/// Header or status line set by `header_format` or `status_format` in the config,
+/// e.g. `{path} [{sort}] {branch}`. `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(Vec<Part>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+/// Placeholders of the template.
+/// Fields not applicable to the item (e.g. `ext` of a directory) are empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// The current directory.
+    Path,
+    /// Name of the item under the cursor.
+    Name,
+    /// Position of the cursor, from 1.
+    Index,
+    /// Number of the items.
+    Count,
+    Selected,
+    Cut,
+    Marked,
+    Jobs,
+    Ext,
+    Size,
+    Permissions,
+    /// Target of the symlink.
+    Link,
+    Sort,
+    Filter,
+    Free,
+    Total,
+    Trash,
+    Branch,
+    /// `RO` without the write permission.
+    ReadOnly,
+}
+
+const FIELDS: [(&str, Field); 19] = [
+    ("path", Field::Path),
+    ("name", Field::Name),
+    ("index", Field::Index),
+    ("count", Field::Count),
+    ("selected", Field::Selected),
+    ("cut", Field::Cut),
+    ("marked", Field::Marked),
+    ("jobs", Field::Jobs),
+    ("ext", Field::Ext),
+    ("size", Field::Size),
+    ("permissions", Field::Permissions),
+    ("link", Field::Link),
+    ("sort", Field::Sort),
+    ("filter", Field::Filter),
+    ("free", Field::Free),
+    ("total", Field::Total),
+    ("trash", Field::Trash),
+    ("branch", Field::Branch),
+    ("ro", Field::ReadOnly),
+];
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Self, FxError> {
+        let invalid = |reason: &str| FxError::Yaml(format!("Invalid format `{}`: {}", s, reason));
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(invalid("unclosed `{`.")),
+                        }
+                    }
+                    let Some((_, field)) = FIELDS.iter().find(|(key, _)| *key == name.trim())
+                    else {
+                        return Err(invalid(&format!("unknown placeholder `{{{}}}`.", name)));
+                    };
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(*field));
+                }
+                '}' => return Err(invalid("unmatched `}` (use `}}`).")),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template(parts))
+    }
+
+    /// Fill the placeholders by the values.
+    pub fn render<F>(&self, mut value: F) -> String
+    where
+        F: FnMut(Field) -> String,
+    {
+        let mut s = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => s.push_str(text),
+                Part::Field(field) => s.push_str(&value(*field)),
+            }
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        let template = Template::parse(" {path} [{ sort }] {{{count}}}").unwrap();
+        let rendered = template.render(|field| match field {
+            Field::Path => "/tmp".to_owned(),
+            Field::Sort => "Name".to_owned(),
+            Field::Count => "3".to_owned(),
+            _ => unreachable!(),
+        });
+        assert_eq!(rendered, " /tmp [Name] {3}");
+        assert_eq!(Template::parse("").unwrap().render(|_| "x".to_owned()), "");
+        assert!(Template::parse("{path").is_err());
+        assert!(Template::parse("path}").is_err());
+        assert!(Template::parse("{size} {color}").is_err());
+    }
+}
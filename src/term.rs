@@ -1,14 +1,35 @@
-use super::config::Colorname;
-use super::errors::FxError;
+use felix::color::*;
+use felix::config::Colorname;
+use felix::errors::FxError;
+use felix::functions::shorten_str_including_wide_char;
+use felix::state::BEGINNING_ROW;
 
 use crossterm::cursor::{Hide, MoveLeft, MoveRight, MoveTo, Show};
-use crossterm::style::{Color, ResetColor, SetBackgroundColor, SetForegroundColor};
-use crossterm::terminal::Clear;
+use crossterm::event::{
+    DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+};
+use crossterm::style::{ResetColor, Stylize};
+use crossterm::terminal::{
+    BeginSynchronizedUpdate, Clear, EndSynchronizedUpdate, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
 
-pub enum TermColor<'a> {
-    ForeGround(&'a Colorname),
-    BackGround(&'a Colorname),
-}
+use log::{info, warn};
+use unicode_width::UnicodeWidthStr;
+
+/// Whether the mouse events are reported, set by `mouse` in the config.
+static MOUSE_CAPTURED: AtomicBool = AtomicBool::new(false);
+/// Whether the UI is drawn in the alternate screen, set by `alternate_screen` in the config.
+static ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+/// Rows of the list as last printed, to repaint only the changed ones.
+/// None if unknown, e.g. the screen is cleared for another view.
+static DRAWN_ROWS: Mutex<Option<Vec<String>>> = Mutex::new(None);
+/// The row of the pointer last printed. 0 if not printed yet.
+static POINTER_ROW: AtomicU16 = AtomicU16::new(0);
 
 /// Puts the terminal into raw mode. Requires calling `leave_raw_mode` on program exit!
 ///
@@ -28,10 +49,77 @@ pub fn leave_raw_mode() {
     crossterm::terminal::disable_raw_mode().ok();
 }
 
+/// Give the terminal back as it was before fx started:
+/// the mouse released, raw mode and the alternate screen left.
+pub fn restore_terminal() {
+    set_mouse_capture(false);
+    leave_raw_mode();
+    set_alternate_screen(false);
+}
+
+/// Restore the terminal before the panic message is printed, if the main thread panics.
+/// Panics in the other threads (e.g. the background jobs) are only logged,
+/// so as not to break the screen still in use.
+pub fn restore_on_panic() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().name() == Some("main") {
+            restore_terminal();
+            default_hook(info);
+        } else {
+            log::error!("{}", info);
+        }
+    }));
+}
+
+/// Restore the terminal and exit on SIGTERM or SIGHUP, which would leave it in raw mode.
+/// The signals are received by a thread waiting for them, so this must be called
+/// before the other threads are spawned to block them in all the threads.
+/// The child processes get the signal mask reset by `std::process::Command`.
+#[cfg(unix)]
+pub fn restore_on_signals() {
+    use nix::sys::signal::{SigSet, Signal};
+
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGTERM);
+    signals.add(Signal::SIGHUP);
+    if signals.thread_block().is_err() {
+        return;
+    }
+    std::thread::spawn(move || {
+        if let Ok(signal) = signals.wait() {
+            restore_terminal();
+            std::process::exit(128 + signal as i32);
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn restore_on_signals() {}
+
 pub fn terminal_size() -> Result<(u16, u16), FxError> {
     crossterm::terminal::size().map_err(|_| FxError::TerminalSizeDetection)
 }
 
+/// Wait for the terminal to be resized, ignoring the other events.
+/// Returns the new size.
+pub fn wait_for_resize() -> Result<(u16, u16), FxError> {
+    loop {
+        if let crossterm::event::Event::Resize(column, row) = crossterm::event::read()? {
+            return Ok((column, row));
+        }
+    }
+}
+
+/// Shown instead of the list while the terminal is too small.
+pub fn print_too_small(column: u16) {
+    clear_all();
+    move_to(1, 1);
+    let message: String = "Terminal too small".chars().take(column as usize).collect();
+    print!("{}", message);
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+}
+
 pub fn cursor_pos() -> Result<(u16, u16), FxError> {
     Ok(crossterm::cursor::position()?)
 }
@@ -59,6 +147,57 @@ pub fn clear_until_newline() {
 
 pub fn clear_all() {
     print!("{}", Clear(crossterm::terminal::ClearType::All));
+    forget_drawn_rows();
+}
+
+/// Print all the rows of the list next time.
+pub fn forget_drawn_rows() {
+    if let Ok(mut drawn) = DRAWN_ROWS.lock() {
+        *drawn = None;
+    }
+}
+
+/// Print the rows of the list from the row `top`, at the column 3.
+/// Rows unchanged since the last time are skipped, and the changed ones are blanked
+/// by `width` columns first, leaving the preview on the right as it is.
+/// If the last rows are unknown, the whole lines are cleared,
+/// so the preview has to be printed again.
+/// Return the number of the rows printed.
+pub fn print_rows(rows: Vec<String>, top: u16, width: u16) -> usize {
+    let Ok(mut drawn) = DRAWN_ROWS.lock() else {
+        return 0;
+    };
+    let blank = " ".repeat(width.into());
+    let mut printed = 0;
+    for (i, row) in rows.iter().enumerate() {
+        let y = top + i as u16;
+        match drawn.as_ref() {
+            Some(drawn) if drawn.get(i) == Some(row) => continue,
+            Some(_) => {
+                move_to(1, y);
+                print!("{}", blank);
+            }
+            None => {
+                move_to(1, y);
+                clear_current_line();
+            }
+        }
+        move_to(3, y);
+        print!("{}", row);
+        printed += 1;
+    }
+    *drawn = Some(rows);
+    printed
+}
+
+/// Hold the output until `end_frame`, so that the terminal shows the frame at once
+/// instead of the blank screen in the middle. Ignored by the terminals not supporting it.
+pub fn begin_frame() {
+    crossterm::queue!(std::io::stdout(), BeginSynchronizedUpdate).ok();
+}
+
+pub fn end_frame() {
+    crossterm::queue!(std::io::stdout(), EndSynchronizedUpdate).ok();
 }
 
 pub fn move_left(x: u16) {
@@ -101,6 +240,73 @@ pub fn move_right_command_line(
     }
 }
 
+/// Enable or disable the mouse events.
+pub fn set_mouse_capture(enabled: bool) {
+    if enabled {
+        crossterm::execute!(std::io::stdout(), EnableMouseCapture).ok();
+    } else if MOUSE_CAPTURED.load(Ordering::Relaxed) {
+        crossterm::execute!(std::io::stdout(), DisableMouseCapture).ok();
+    }
+    MOUSE_CAPTURED.store(enabled, Ordering::Relaxed);
+}
+
+/// Give the terminal back to the shell and stop, as Ctrl-Z does in cooked mode.
+/// When resumed by `fg`, the terminal is set up again; the screen must be redrawn.
+#[cfg(unix)]
+pub fn suspend() {
+    use nix::sys::signal::{raise, Signal};
+    use std::io::Write;
+
+    let alternate_screen = ALTERNATE_SCREEN.load(Ordering::Relaxed);
+    let mouse_captured = MOUSE_CAPTURED.load(Ordering::Relaxed);
+    restore_terminal();
+    std::io::stdout().flush().ok();
+    // Returns after SIGCONT.
+    if raise(Signal::SIGTSTP).is_err() {
+        log::error!("Cannot suspend.");
+    }
+    enter_raw_mode();
+    set_alternate_screen(alternate_screen);
+    set_mouse_capture(mouse_captured);
+    forget_drawn_rows();
+}
+
+#[cfg(not(unix))]
+pub fn suspend() {}
+
+/// Enter or leave the alternate screen.
+pub fn set_alternate_screen(enabled: bool) {
+    if enabled {
+        crossterm::execute!(std::io::stdout(), EnterAlternateScreen).ok();
+    } else if ALTERNATE_SCREEN.load(Ordering::Relaxed) {
+        crossterm::execute!(std::io::stdout(), LeaveAlternateScreen).ok();
+    }
+    ALTERNATE_SCREEN.store(enabled, Ordering::Relaxed);
+}
+
+/// Enter the alternate screen again if it is used, as a program run in the terminal may leave it.
+pub fn reenter_alternate_screen() {
+    if ALTERNATE_SCREEN.load(Ordering::Relaxed) {
+        crossterm::execute!(std::io::stdout(), EnterAlternateScreen).ok();
+    }
+}
+
+/// Run the program taking over the terminal, e.g. the editor.
+/// The mouse is released meanwhile, or the program gets the escape sequences as input.
+pub fn run_in_terminal(command: &mut Command) -> std::io::Result<ExitStatus> {
+    let captured = MOUSE_CAPTURED.load(Ordering::Relaxed);
+    if captured {
+        crossterm::execute!(std::io::stdout(), DisableMouseCapture).ok();
+    }
+    let status = command.status();
+    // The program may leave anything on the screen.
+    forget_drawn_rows();
+    if captured {
+        crossterm::execute!(std::io::stdout(), EnableMouseCapture).ok();
+    }
+    status
+}
+
 pub fn hide_cursor() {
     print!("{}", Hide);
 }
@@ -109,9 +315,30 @@ pub fn show_cursor() {
     print!("{}", Show);
 }
 
-pub fn print_pointer() {
+/// Print the pointer at the row, and leave the cursor on it.
+pub fn print_pointer(y: u16) {
+    move_to(1, y);
     print!(">");
     move_left(1);
+    POINTER_ROW.store(y, Ordering::Relaxed);
+}
+
+/// Delete the pointer last printed, wherever the cursor is.
+/// As the list is not cleared before redrawn, it may be left on a row not printed again.
+pub fn remove_pointer() {
+    let y = POINTER_ROW.load(Ordering::Relaxed);
+    if y != 0 {
+        move_to(1, y);
+        delete_pointer();
+    }
+}
+
+/// The row of the pointer last printed, where the messages return the cursor to.
+pub fn pointer_row() -> u16 {
+    match POINTER_ROW.load(Ordering::Relaxed) {
+        0 => BEGINNING_ROW,
+        y => y,
+    }
 }
 
 pub fn delete_pointer() {
@@ -120,76 +347,93 @@ pub fn delete_pointer() {
 }
 
 pub fn set_color(c: &TermColor) {
-    match c {
-        TermColor::ForeGround(c) => match c {
-            Colorname::Black => print!("{}", SetForegroundColor(Color::Black)),
-            Colorname::Red => print!("{}", SetForegroundColor(Color::DarkRed)),
-            Colorname::Green => print!("{}", SetForegroundColor(Color::DarkGreen)),
-            Colorname::Yellow => print!("{}", SetForegroundColor(Color::DarkYellow)),
-            Colorname::Blue => print!("{}", SetForegroundColor(Color::DarkBlue)),
-            Colorname::Magenta => print!("{}", SetForegroundColor(Color::DarkMagenta)),
-            Colorname::Cyan => print!("{}", SetForegroundColor(Color::DarkCyan)),
-            Colorname::White => print!("{}", SetForegroundColor(Color::Grey)),
-            Colorname::LightBlack => print!("{}", SetForegroundColor(Color::DarkGrey)),
-            Colorname::LightRed => print!("{}", SetForegroundColor(Color::Red)),
-            Colorname::LightGreen => print!("{}", SetForegroundColor(Color::Green)),
-            Colorname::LightYellow => print!("{}", SetForegroundColor(Color::Yellow)),
-            Colorname::LightBlue => print!("{}", SetForegroundColor(Color::Blue)),
-            Colorname::LightMagenta => print!("{}", SetForegroundColor(Color::Magenta)),
-            Colorname::LightCyan => print!("{}", SetForegroundColor(Color::Cyan)),
-            Colorname::LightWhite => print!("{}", SetForegroundColor(Color::White)),
-            Colorname::Rgb(r, g, b) => print!(
-                "{}",
-                SetForegroundColor(Color::Rgb {
-                    r: *r,
-                    g: *g,
-                    b: *b
-                })
-            ),
-            Colorname::AnsiValue(n) => print!("{}", SetForegroundColor(Color::AnsiValue(*n))),
-        },
-        TermColor::BackGround(c) => match c {
-            Colorname::Black => print!("{}", SetBackgroundColor(Color::Black)),
-            Colorname::Red => print!("{}", SetBackgroundColor(Color::DarkRed)),
-            Colorname::Green => print!("{}", SetBackgroundColor(Color::DarkGreen)),
-            Colorname::Yellow => print!("{}", SetBackgroundColor(Color::DarkYellow)),
-            Colorname::Blue => print!("{}", SetBackgroundColor(Color::DarkBlue)),
-            Colorname::Magenta => print!("{}", SetBackgroundColor(Color::DarkMagenta)),
-            Colorname::Cyan => print!("{}", SetBackgroundColor(Color::DarkCyan)),
-            Colorname::White => print!("{}", SetBackgroundColor(Color::Grey)),
-            Colorname::LightBlack => print!("{}", SetBackgroundColor(Color::DarkGrey)),
-            Colorname::LightRed => print!("{}", SetBackgroundColor(Color::Red)),
-            Colorname::LightGreen => print!("{}", SetBackgroundColor(Color::Green)),
-            Colorname::LightYellow => print!("{}", SetBackgroundColor(Color::Yellow)),
-            Colorname::LightBlue => print!("{}", SetBackgroundColor(Color::Blue)),
-            Colorname::LightMagenta => print!("{}", SetBackgroundColor(Color::Magenta)),
-            Colorname::LightCyan => print!("{}", SetBackgroundColor(Color::Cyan)),
-            Colorname::LightWhite => print!("{}", SetBackgroundColor(Color::White)),
-            Colorname::Rgb(r, g, b) => print!(
-                "{}",
-                SetBackgroundColor(Color::Rgb {
-                    r: *r,
-                    g: *g,
-                    b: *b
-                })
-            ),
-            Colorname::AnsiValue(n) => print!("{}", SetBackgroundColor(Color::AnsiValue(*n))),
-        },
-    }
-}
-
-pub fn set_color_current_dir() {
-    set_color(&TermColor::ForeGround(&Colorname::Cyan));
-}
-
-pub fn set_color_read_only() {
-    set_color(&TermColor::ForeGround(&Colorname::Red));
-}
-
-pub fn set_color_git_repo() {
-    set_color(&TermColor::ForeGround(&Colorname::LightMagenta));
+    print!("{}", color_code(c));
+}
+
+/// Print the text in the colors, or reversed if the background is not set.
+pub fn print_highlighted(text: &str, fg: Option<&Colorname>, bg: Option<&Colorname>) {
+    print!("{}", highlighted(text, fg, bg));
 }
 
 pub fn reset_color() {
     print!("{}", ResetColor);
 }
+
+/// Print the result of operation, such as put/delete/redo/undo.
+pub fn print_info<T: std::fmt::Display>(message: T, then: u16) {
+    remove_pointer();
+    go_to_info_line_and_reset();
+    info!("{}", message);
+
+    let width = terminal_size().map_or(1, |(width, _)| width);
+    let trimmed =
+        shorten_str_including_wide_char(&message.to_string(), width.saturating_sub(1).into());
+    print!("{}", trimmed);
+
+    hide_cursor();
+    print_pointer(then);
+}
+
+/// When something goes wrong or does not work, print information about it.
+pub fn print_warning<T: std::fmt::Display>(message: T, then: u16) {
+    remove_pointer();
+    go_to_info_line_and_reset();
+    warn!("{}", message);
+
+    let width = terminal_size().map_or(1, |(width, _)| width);
+    let trimmed =
+        shorten_str_including_wide_char(&message.to_string(), width.saturating_sub(1).into());
+    set_color(&TermColor::ForeGround(&Colorname::White));
+    set_color(&TermColor::BackGround(&Colorname::LightRed));
+    print!("{}", trimmed);
+    reset_color();
+
+    hide_cursor();
+    print_pointer(then);
+}
+
+/// Print process of put/delete, and go back so that the next one overwrites it.
+pub fn print_process<T: std::fmt::Display>(message: T) {
+    let message = message.to_string();
+    print!("{}", message);
+    clear_until_newline();
+    move_left(UnicodeWidthStr::width(message.as_str()) as u16);
+}
+
+/// Print help text.
+pub fn print_help(v: &[String], skip_number: usize, row: u16) {
+    let mut row_count = 0;
+    for (i, line) in v.iter().enumerate() {
+        if i < skip_number {
+            continue;
+        }
+
+        move_to(1, (i + 1 - skip_number) as u16);
+        if row_count == row - 1 {
+            print!("{}", "...".negative());
+            break;
+        }
+        print!("{}", line);
+        row_count += 1;
+    }
+}
+
+/// Check if <Esc> or <C-c> is in the input, without waiting.
+pub fn is_cancel_pressed() -> Result<bool, FxError> {
+    while crossterm::event::poll(Duration::ZERO)? {
+        if let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = crossterm::event::read()?
+        {
+            match code {
+                KeyCode::Esc => return Ok(true),
+                KeyCode::Char('c') if modifiers == KeyModifiers::CONTROL => return Ok(true),
+                _ => {}
+            }
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,204 @@
+use super::config::{config_dirs, Colorname, ConfigColor};
+use super::errors::FxError;
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+const THEME_DIR: &str = "themes";
+const BUILTIN_THEMES: [&str; 5] = ["default", "gruvbox", "nord", "dracula", "solarized"];
+
+/// Colors of the UI, set by `theme` in the config.
+/// Fields not in the theme file are the same as the default.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Theme {
+    pub dir_fg: Colorname,
+    pub file_fg: Colorname,
+    pub symlink_fg: Colorname,
+    pub dirty_fg: Colorname,
+    pub other_fg: Colorname,
+    /// Selected items. Without the background, they are reversed.
+    pub selection_fg: Option<Colorname>,
+    pub selection_bg: Option<Colorname>,
+    /// The current directory in the header.
+    pub header_fg: Colorname,
+    /// `[RO]` in the header.
+    pub read_only_fg: Colorname,
+    /// The git branch in the header.
+    pub git_branch_fg: Colorname,
+    /// The status line at the bottom. Without the background, it is reversed.
+    pub status_fg: Option<Colorname>,
+    pub status_bg: Option<Colorname>,
+    /// Text in the preview.
+    pub preview_fg: Colorname,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let colors = ConfigColor::default();
+        Theme {
+            dir_fg: colors.dir_fg,
+            file_fg: colors.file_fg,
+            symlink_fg: colors.symlink_fg,
+            dirty_fg: colors.dirty_fg,
+            other_fg: colors.other_fg,
+            selection_fg: None,
+            selection_bg: None,
+            header_fg: Colorname::Cyan,
+            read_only_fg: Colorname::Red,
+            git_branch_fg: Colorname::LightMagenta,
+            status_fg: None,
+            status_bg: None,
+            preview_fg: Colorname::LightBlack,
+        }
+    }
+}
+
+impl Theme {
+    /// Read the user theme `themes/{name}.yaml` next to the config file,
+    /// or the built-in theme of the name.
+    pub fn load(name: Option<&str>) -> Result<Self, FxError> {
+        let Some(name) = name else {
+            return Ok(Theme::default());
+        };
+        for dir in theme_dirs() {
+            for ext in ["yaml", "yml"] {
+                let path = dir.join(format!("{}.{}", name, ext));
+                if path.exists() {
+                    let s = std::fs::read_to_string(&path)?;
+                    return serde_yaml::from_str(&s).map_err(|e| {
+                        FxError::Yaml(format!("Invalid theme {}: {}", path.display(), e))
+                    });
+                }
+            }
+        }
+        builtin(name).ok_or_else(|| {
+            FxError::Yaml(format!(
+                "Theme not found: {} (built-in: {})",
+                name,
+                BUILTIN_THEMES.join(", ")
+            ))
+        })
+    }
+
+    /// Colors of the items, used if `color` is not set in the config.
+    pub fn item_colors(&self) -> ConfigColor {
+        ConfigColor {
+            dir_fg: self.dir_fg.clone(),
+            file_fg: self.file_fg.clone(),
+            symlink_fg: self.symlink_fg.clone(),
+            dirty_fg: self.dirty_fg.clone(),
+            other_fg: self.other_fg.clone(),
+            ext: BTreeMap::new(),
+        }
+    }
+}
+
+fn theme_dirs() -> Vec<PathBuf> {
+    config_dirs()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|dir| dir.join(THEME_DIR))
+        .collect()
+}
+
+fn builtin(name: &str) -> Option<Theme> {
+    use Colorname::Rgb;
+    let theme = match name {
+        "default" => Theme::default(),
+        "gruvbox" => Theme {
+            dir_fg: Rgb(131, 165, 152),
+            file_fg: Rgb(235, 219, 178),
+            symlink_fg: Rgb(250, 189, 47),
+            dirty_fg: Rgb(251, 73, 52),
+            other_fg: Rgb(211, 134, 155),
+            selection_fg: Some(Rgb(40, 40, 40)),
+            selection_bg: Some(Rgb(215, 153, 33)),
+            header_fg: Rgb(142, 192, 124),
+            read_only_fg: Rgb(251, 73, 52),
+            git_branch_fg: Rgb(211, 134, 155),
+            status_fg: Some(Rgb(235, 219, 178)),
+            status_bg: Some(Rgb(80, 73, 69)),
+            preview_fg: Rgb(168, 153, 132),
+        },
+        "nord" => Theme {
+            dir_fg: Rgb(136, 192, 208),
+            file_fg: Rgb(229, 233, 240),
+            symlink_fg: Rgb(235, 203, 139),
+            dirty_fg: Rgb(191, 97, 106),
+            other_fg: Rgb(180, 142, 173),
+            selection_fg: Some(Rgb(46, 52, 64)),
+            selection_bg: Some(Rgb(136, 192, 208)),
+            header_fg: Rgb(143, 188, 187),
+            read_only_fg: Rgb(191, 97, 106),
+            git_branch_fg: Rgb(180, 142, 173),
+            status_fg: Some(Rgb(216, 222, 233)),
+            status_bg: Some(Rgb(67, 76, 94)),
+            preview_fg: Rgb(129, 161, 193),
+        },
+        "dracula" => Theme {
+            dir_fg: Rgb(139, 233, 253),
+            file_fg: Rgb(248, 248, 242),
+            symlink_fg: Rgb(241, 250, 140),
+            dirty_fg: Rgb(255, 85, 85),
+            other_fg: Rgb(255, 121, 198),
+            selection_fg: Some(Rgb(248, 248, 242)),
+            selection_bg: Some(Rgb(68, 71, 90)),
+            header_fg: Rgb(80, 250, 123),
+            read_only_fg: Rgb(255, 85, 85),
+            git_branch_fg: Rgb(189, 147, 249),
+            status_fg: Some(Rgb(40, 42, 54)),
+            status_bg: Some(Rgb(189, 147, 249)),
+            preview_fg: Rgb(98, 114, 164),
+        },
+        "solarized" => Theme {
+            dir_fg: Rgb(38, 139, 210),
+            file_fg: Rgb(147, 161, 161),
+            symlink_fg: Rgb(181, 137, 0),
+            dirty_fg: Rgb(220, 50, 47),
+            other_fg: Rgb(211, 54, 130),
+            selection_fg: Some(Rgb(0, 43, 54)),
+            selection_bg: Some(Rgb(42, 161, 152)),
+            header_fg: Rgb(42, 161, 152),
+            read_only_fg: Rgb(220, 50, 47),
+            git_branch_fg: Rgb(108, 113, 196),
+            status_fg: Some(Rgb(147, 161, 161)),
+            status_bg: Some(Rgb(7, 54, 66)),
+            preview_fg: Rgb(88, 110, 117),
+        },
+        _ => return None,
+    };
+    Some(theme)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_theme() {
+        assert_eq!(Theme::load(None).unwrap(), Theme::default());
+        for name in BUILTIN_THEMES {
+            assert!(Theme::load(Some(name)).is_ok());
+        }
+        assert_eq!(
+            Theme::load(Some("default")).unwrap().item_colors(),
+            ConfigColor::default()
+        );
+        assert!(Theme::load(Some("no-such-theme")).is_err());
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        let theme: Theme = serde_yaml::from_str(
+            "dir_fg: Blue\nselection_bg: !Rgb [40, 40, 40]\nstatus_fg: !AnsiValue 208\n",
+        )
+        .unwrap();
+        assert_eq!(theme.dir_fg, Colorname::Blue);
+        assert_eq!(theme.selection_bg, Some(Colorname::Rgb(40, 40, 40)));
+        assert_eq!(theme.status_fg, Some(Colorname::AnsiValue(208)));
+        assert_eq!(theme.file_fg, Theme::default().file_fg);
+        assert!(serde_yaml::from_str::<Theme>("dir_fg: Mauve").is_err());
+    }
+}
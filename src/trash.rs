@@ -0,0 +1,506 @@
+use super::errors::FxError;
+use super::functions::to_proper_size;
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The format of `DeletionDate` in `.trashinfo`, in the local time.
+const DELETION_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+const TRASH_INFO_EXTENSION: &str = "trashinfo";
+/// Next to the trash directory, locked by fx instances using the trash.
+const TRASH_LOCK: &str = ".fx_trash.lock";
+/// How long to wait for the lock held by another instance.
+const LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+const LOCK_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Groups of the trashed items by the deletion date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrashGroup {
+    Today,
+    Yesterday,
+    LastWeek,
+    Older,
+}
+
+impl std::fmt::Display for TrashGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let printable = match self {
+            TrashGroup::Today => "Today",
+            TrashGroup::Yesterday => "Yesterday",
+            TrashGroup::LastWeek => "Last week",
+            TrashGroup::Older => "Older",
+        };
+        write!(f, "{}", printable)
+    }
+}
+
+/// Advisory lock of the trash among fx instances, released when dropped.
+/// Trashing takes the shared lock, and emptying takes the exclusive one,
+/// so that the trash is not emptied while items are being copied into it.
+#[derive(Debug)]
+pub struct TrashLock {
+    #[cfg(target_family = "unix")]
+    _lock: nix::fcntl::Flock<std::fs::File>,
+}
+
+/// Lock the trash, waiting for a while if another instance holds the lock.
+#[cfg(target_family = "unix")]
+pub fn lock_trash(trash_dir: &Path, exclusive: bool) -> Result<TrashLock, FxError> {
+    use nix::fcntl::{Flock, FlockArg};
+
+    let arg = if exclusive {
+        FlockArg::LockExclusiveNonblock
+    } else {
+        FlockArg::LockSharedNonblock
+    };
+    let start = std::time::Instant::now();
+    loop {
+        let file = std::fs::File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(trash_dir.with_file_name(TRASH_LOCK))?;
+        match Flock::lock(file, arg) {
+            Ok(lock) => return Ok(TrashLock { _lock: lock }),
+            Err((_, nix::errno::Errno::EWOULDBLOCK)) if start.elapsed() < LOCK_TIMEOUT => {
+                std::thread::sleep(LOCK_RETRY_INTERVAL);
+            }
+            Err((_, nix::errno::Errno::EWOULDBLOCK)) => {
+                return Err(FxError::Io(if exclusive {
+                    "The trash is in use. Try again later.".to_owned()
+                } else {
+                    "The trash is being emptied. Try again later.".to_owned()
+                }));
+            }
+            Err((_, e)) => return Err(FxError::Io(e.to_string())),
+        }
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn lock_trash(_trash_dir: &Path, _exclusive: bool) -> Result<TrashLock, FxError> {
+    Ok(TrashLock {})
+}
+
+/// The trash dir to move the item to, and its info dir.
+/// In the freedesktop.org trash, an item on another filesystem than the home trash goes to
+/// the trash at the top of its mount, `$topdir/.Trash-$uid`, so that it can be renamed into it
+/// instead of being copied. If that cannot be created, the home trash is used.
+#[cfg(target_family = "unix")]
+pub fn trash_root(
+    path: &Path,
+    trash_dir: &Path,
+    info_dir: Option<&Path>,
+) -> (PathBuf, Option<PathBuf>) {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    let home = (trash_dir.to_path_buf(), info_dir.map(|p| p.to_path_buf()));
+    if info_dir.is_none() || is_same_device(path, trash_dir) {
+        return home;
+    }
+    let Some(dev) = path
+        .parent()
+        .and_then(|parent| std::fs::metadata(parent).ok())
+        .map(|metadata| metadata.dev())
+    else {
+        return home;
+    };
+    //The top of the mount is the last ancestor on the same device.
+    let Some(top) = path
+        .ancestors()
+        .skip(1)
+        .take_while(|ancestor| {
+            std::fs::metadata(ancestor).is_ok_and(|metadata| metadata.dev() == dev)
+        })
+        .last()
+    else {
+        return home;
+    };
+    let root = top.join(format!(".Trash-{}", nix::unistd::getuid()));
+    let (files, info) = (root.join("files"), root.join("info"));
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true).mode(0o700);
+    if builder.create(&files).is_err() || builder.create(&info).is_err() {
+        return home;
+    }
+    (files, Some(info))
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn trash_root(
+    _path: &Path,
+    trash_dir: &Path,
+    info_dir: Option<&Path>,
+) -> (PathBuf, Option<PathBuf>) {
+    (trash_dir.to_path_buf(), info_dir.map(|p| p.to_path_buf()))
+}
+
+/// The trash dirs in use with their info dirs: the home one first, and `$topdir/.Trash-$uid`
+/// at the top of each mount, where `trash_root` moves the items on that filesystem.
+/// The mounts are read from `/proc/self/mounts`, so elsewhere only the home trash is found.
+pub fn trash_roots(trash_dir: &Path, info_dir: Option<&Path>) -> Vec<(PathBuf, Option<PathBuf>)> {
+    let mut roots = vec![(trash_dir.to_path_buf(), info_dir.map(|p| p.to_path_buf()))];
+    if info_dir.is_none() {
+        return roots;
+    }
+    #[cfg(target_os = "linux")]
+    if let Ok(mounts) = std::fs::read_to_string("/proc/self/mounts") {
+        let name = format!(".Trash-{}", nix::unistd::getuid());
+        for top in mount_points(&mounts) {
+            let root = top.join(&name);
+            let files = root.join("files");
+            if files.is_dir() && roots.iter().all(|(dir, _)| *dir != files) {
+                roots.push((files, Some(root.join("info"))));
+            }
+        }
+    }
+    roots
+}
+
+/// The mount points in the format of `/proc/self/mounts`,
+/// where spaces and the like in the path are escaped as `\040`.
+#[cfg(target_os = "linux")]
+fn mount_points(mounts: &str) -> Vec<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+
+    mounts
+        .lines()
+        .filter_map(|line| line.split(' ').nth(1))
+        .map(|escaped| {
+            let escaped = escaped.as_bytes();
+            let mut bytes = Vec::new();
+            let mut i = 0;
+            while i < escaped.len() {
+                let octal = escaped
+                    .get(i + 1..i + 4)
+                    .and_then(|octal| u8::from_str_radix(std::str::from_utf8(octal).ok()?, 8).ok());
+                match octal {
+                    Some(b) if escaped[i] == b'\\' => {
+                        bytes.push(b);
+                        i += 4;
+                    }
+                    _ => {
+                        bytes.push(escaped[i]);
+                        i += 1;
+                    }
+                }
+            }
+            PathBuf::from(OsString::from_vec(bytes))
+        })
+        .collect()
+}
+
+/// Lock all the trash roots, e.g. to empty them.
+pub fn lock_trash_roots(
+    roots: &[(PathBuf, Option<PathBuf>)],
+    exclusive: bool,
+) -> Result<Vec<TrashLock>, FxError> {
+    roots
+        .iter()
+        .map(|(trash_dir, _)| lock_trash(trash_dir, exclusive))
+        .collect()
+}
+
+/// Whether the item is on the same filesystem as the trash, so that it can be renamed into it.
+#[cfg(target_family = "unix")]
+pub fn is_same_device(path: &Path, trash_dir: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    //The item itself may be a mount point, which cannot be renamed.
+    match (
+        std::fs::symlink_metadata(path),
+        std::fs::metadata(trash_dir),
+    ) {
+        (Ok(item), Ok(trash)) => item.dev() == trash.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn is_same_device(_path: &Path, _trash_dir: &Path) -> bool {
+    false
+}
+
+/// Return the path in the trash to copy the item to, reserving the name.
+/// With `info_dir` (the freedesktop.org trash), the item keeps its name unless taken,
+/// and `{name}.trashinfo` with the original path and the deletion date is created in `info_dir`.
+/// Otherwise (the legacy trash), the item is named `{unix timestamp}_{original name}`.
+pub fn trash_path(
+    trash_dir: &Path,
+    info_dir: Option<&Path>,
+    original: &Path,
+    now: &DateTime<Local>,
+) -> Result<PathBuf, FxError> {
+    let file_name = original
+        .file_name()
+        .ok_or_else(|| FxError::RemoveItem(original.to_path_buf()))?;
+    let Some(info_dir) = info_dir else {
+        let mut name = OsString::from(format!("{}_", now.timestamp()));
+        name.push(file_name);
+        return Ok(trash_dir.join(name));
+    };
+
+    let content = trash_info(original, now);
+    for i in 0.. {
+        let name = numbered_name(file_name, i);
+        if trash_dir.join(&name).symlink_metadata().is_ok() {
+            continue;
+        }
+        //The info file is created first, so that other programs do not take the name.
+        match std::fs::File::options()
+            .write(true)
+            .create_new(true)
+            .open(info_path(info_dir, &name))
+        {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())?;
+                return Ok(trash_dir.join(name));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!()
+}
+
+/// Write the info file of the item to be moved to `trash_path` again, e.g. by redo.
+pub fn write_trash_info(
+    info_dir: &Path,
+    trash_path: &Path,
+    original: &Path,
+    now: &DateTime<Local>,
+) -> Result<(), FxError> {
+    let name = trash_path
+        .file_name()
+        .ok_or_else(|| FxError::RemoveItem(original.to_path_buf()))?;
+    std::fs::write(info_path(info_dir, name), trash_info(original, now))?;
+    Ok(())
+}
+
+/// The contents of `.trashinfo`.
+fn trash_info(original: &Path, now: &DateTime<Local>) -> String {
+    format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode_path(original),
+        now.format(DELETION_DATE_FORMAT)
+    )
+}
+
+/// Remove the info file of the item not trashed after all (e.g. the copy failed).
+pub fn discard_trash_info(info_dir: Option<&Path>, trash_path: &Path) {
+    if let (Some(info_dir), Some(name)) = (info_dir, trash_path.file_name()) {
+        let _ = std::fs::remove_file(info_path(info_dir, name));
+    }
+}
+
+/// Return the timestamp when the item in the trash was deleted,
+/// from its info file, or from its name in the legacy trash.
+pub fn deleted_at(info_dir: Option<&Path>, file_name: &str) -> Option<i64> {
+    match info_dir {
+        Some(info_dir) => {
+            let info = std::fs::read_to_string(info_path(info_dir, file_name.as_ref())).ok()?;
+            let date = info
+                .lines()
+                .find_map(|line| line.strip_prefix("DeletionDate="))?;
+            let date = NaiveDateTime::parse_from_str(date.trim(), DELETION_DATE_FORMAT).ok()?;
+            Some(Local.from_local_datetime(&date).earliest()?.timestamp())
+        }
+        None => {
+            let (timestamp, _) = file_name.split_once('_')?;
+            timestamp.parse().ok()
+        }
+    }
+}
+
+fn info_path(info_dir: &Path, name: &std::ffi::OsStr) -> PathBuf {
+    let mut info_name = name.to_os_string();
+    info_name.push(".");
+    info_name.push(TRASH_INFO_EXTENSION);
+    info_dir.join(info_name)
+}
+
+/// `a.txt`, `a.2.txt`, `a.3.txt`, ... (i = 0 is the name itself)
+fn numbered_name(file_name: &std::ffi::OsStr, i: usize) -> OsString {
+    if i == 0 {
+        return file_name.to_os_string();
+    }
+    let path = Path::new(file_name);
+    let mut name = path.file_stem().unwrap_or(file_name).to_os_string();
+    name.push(format!(".{}", i + 1));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    name
+}
+
+/// Percent-encode the path as `Path` in `.trashinfo`, leaving `/` and the unreserved characters.
+fn encode_path(path: &Path) -> String {
+    #[cfg(target_family = "unix")]
+    let bytes = std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()).to_vec();
+    #[cfg(not(target_family = "unix"))]
+    let bytes = path.to_string_lossy().as_bytes().to_vec();
+    let mut encoded = String::new();
+    for b in bytes {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// Return the group of the item according to its deletion date.
+pub fn trash_group(deleted_at: i64, now: &DateTime<Local>) -> TrashGroup {
+    let deleted_at = match Local.timestamp_opt(deleted_at, 0).single() {
+        Some(t) => t.date_naive(),
+        None => return TrashGroup::Older,
+    };
+    let days = (now.date_naive() - deleted_at).num_days();
+    match days {
+        i64::MIN..=0 => TrashGroup::Today,
+        1 => TrashGroup::Yesterday,
+        2..=7 => TrashGroup::LastWeek,
+        _ => TrashGroup::Older,
+    }
+}
+
+/// Return the report if the trash grew beyond the threshold since the last run.
+pub fn growth_report(previous: Option<u64>, current: u64, threshold: u64) -> Option<String> {
+    let growth = current.checked_sub(previous?)?;
+    if growth < threshold {
+        return None;
+    }
+    Some(format!(
+        "Trash grew by {} since the last run (now {}). :empty to purge it.",
+        to_proper_size(growth),
+        to_proper_size(current)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_deleted_at() {
+        assert_eq!(deleted_at(None, "1700000000_foo_bar.txt"), Some(1700000000));
+        assert_eq!(deleted_at(None, "foo_bar.txt"), None);
+        assert_eq!(deleted_at(None, "1700000000"), None);
+    }
+
+    #[test]
+    fn test_trash_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = dir.path().join("files");
+        let info = dir.path().join("info");
+        std::fs::create_dir(&files).unwrap();
+        std::fs::create_dir(&info).unwrap();
+        let now = Local.with_ymd_and_hms(2024, 5, 1, 12, 34, 56).unwrap();
+        let original = Path::new("/home/user/a b%.txt");
+
+        assert_eq!(
+            trash_path(&files, None, original, &now).unwrap(),
+            files.join(format!("{}_a b%.txt", now.timestamp()))
+        );
+
+        let first = trash_path(&files, Some(&info), original, &now).unwrap();
+        assert_eq!(first, files.join("a b%.txt"));
+        assert_eq!(
+            std::fs::read_to_string(info.join("a b%.txt.trashinfo")).unwrap(),
+            "[Trash Info]\nPath=/home/user/a%20b%25.txt\nDeletionDate=2024-05-01T12:34:56\n"
+        );
+        assert_eq!(deleted_at(Some(&info), "a b%.txt"), Some(now.timestamp()));
+        //The name is taken by the info file even before the item is copied.
+        let second = trash_path(&files, Some(&info), original, &now).unwrap();
+        assert_eq!(second, files.join("a b%.2.txt"));
+
+        discard_trash_info(Some(&info), &second);
+        assert!(!info.join("a b%.2.txt.trashinfo").exists());
+        assert_eq!(deleted_at(Some(&info), "none"), None);
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_trash_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash_dir = dir.path().join("Trash/files");
+        let info_dir = dir.path().join("Trash/info");
+        std::fs::create_dir_all(&trash_dir).unwrap();
+        let item = dir.path().join("a");
+        std::fs::write(&item, "a").unwrap();
+        assert!(is_same_device(&item, &trash_dir));
+        assert!(!is_same_device(&dir.path().join("none"), &trash_dir));
+        assert_eq!(
+            trash_root(&item, &trash_dir, Some(&info_dir)),
+            (trash_dir.clone(), Some(info_dir.clone()))
+        );
+        assert_eq!(trash_root(&item, &trash_dir, None), (trash_dir, None));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_trash_roots() {
+        let mounts = "/dev/sda1 / ext4 rw 0 0\n/dev/sdb1 /mnt/usb\\040disk vfat rw 0 0\n";
+        assert_eq!(
+            mount_points(mounts),
+            vec![PathBuf::from("/"), PathBuf::from("/mnt/usb disk")]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let trash_dir = dir.path().join("Trash/files");
+        let roots = trash_roots(&trash_dir, None);
+        assert_eq!(roots, vec![(trash_dir.clone(), None)]);
+        let info_dir = dir.path().join("Trash/info");
+        let roots = trash_roots(&trash_dir, Some(&info_dir));
+        assert_eq!(roots[0], (trash_dir, Some(info_dir)));
+        assert!(roots[1..]
+            .iter()
+            .all(|(files, info)| files.ends_with("files") && info.is_some()));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_lock_trash() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash_dir = dir.path().join("Trash");
+        let first = lock_trash(&trash_dir, false).unwrap();
+        let second = lock_trash(&trash_dir, false).unwrap();
+        drop(first);
+        drop(second);
+        let exclusive = lock_trash(&trash_dir, true).unwrap();
+        drop(exclusive);
+        assert!(lock_trash(&trash_dir, false).is_ok());
+    }
+
+    #[test]
+    fn test_growth_report() {
+        assert_eq!(growth_report(None, 500_000_000, 100_000_000), None);
+        assert_eq!(
+            growth_report(Some(450_000_000), 500_000_000, 100_000_000),
+            None
+        );
+        assert_eq!(
+            growth_report(Some(600_000_000), 500_000_000, 100_000_000),
+            None
+        );
+        assert_eq!(
+            growth_report(Some(100_000_000), 2_500_000_000, 100_000_000),
+            Some("Trash grew by 2GB since the last run (now 2GB). :empty to purge it.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_trash_group() {
+        let now = Local::now();
+        let ago = |days: i64| (now - Duration::days(days)).timestamp();
+        assert_eq!(trash_group(ago(0), &now), TrashGroup::Today);
+        assert_eq!(trash_group(ago(1), &now), TrashGroup::Yesterday);
+        assert_eq!(trash_group(ago(5), &now), TrashGroup::LastWeek);
+        assert_eq!(trash_group(ago(30), &now), TrashGroup::Older);
+    }
+}
@@ -0,0 +1,104 @@
+use super::events::AppEvent;
+
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+
+/// Watch the current directory, to refresh the list as soon as other programs
+/// create, delete, rename or write the items.
+/// It uses inotify on Linux; elsewhere (and on filesystems inotify cannot watch,
+/// e.g. network ones), the modified time of the directory is checked on every tick.
+#[derive(Debug)]
+pub struct Watcher {
+    #[cfg(target_os = "linux")]
+    inotify: std::sync::Arc<nix::sys::inotify::Inotify>,
+    #[cfg(target_os = "linux")]
+    watch: Option<nix::sys::inotify::WatchDescriptor>,
+    #[cfg(target_os = "linux")]
+    dir: Option<PathBuf>,
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher {
+    /// Start reading the events in another thread, which sends `AppEvent::DirChanged`.
+    /// Return None if inotify is not available.
+    pub fn new(sender: Sender<AppEvent>) -> Option<Self> {
+        use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+
+        let inotify = std::sync::Arc::new(Inotify::init(InitFlags::IN_CLOEXEC).ok()?);
+        let reader = inotify.clone();
+        std::thread::spawn(move || {
+            while let Ok(events) = reader.read_events() {
+                // Removing the watch of the previous directory sends IN_IGNORED.
+                if events
+                    .iter()
+                    .all(|event| event.mask.contains(AddWatchFlags::IN_IGNORED))
+                {
+                    continue;
+                }
+                // If the main loop has finished, stop watching.
+                if sender.send(AppEvent::DirChanged).is_err() {
+                    break;
+                }
+            }
+        });
+        Some(Watcher {
+            inotify,
+            watch: None,
+            dir: None,
+        })
+    }
+
+    /// Watch the directory instead of the previous one. Nothing is done if it is the same.
+    pub fn follow(&mut self, dir: &Path) {
+        use nix::sys::inotify::AddWatchFlags;
+
+        if self.dir.as_deref() == Some(dir) {
+            return;
+        }
+        if let Some(watch) = self.watch.take() {
+            let _ = self.inotify.rm_watch(watch);
+        }
+        // IN_MODIFY is sent on every write, so the end of the writes is watched instead.
+        let mask = AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_DELETE
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_MOVED_TO
+            | AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_ATTRIB
+            | AddWatchFlags::IN_DELETE_SELF
+            | AddWatchFlags::IN_MOVE_SELF;
+        self.watch = self.inotify.add_watch(dir, mask).ok();
+        self.dir = Some(dir.to_path_buf());
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Watcher {
+    pub fn new(_sender: Sender<AppEvent>) -> Option<Self> {
+        None
+    }
+
+    pub fn follow(&mut self, _dir: &Path) {}
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_watcher() {
+        let dir = tempfile::tempdir().unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut watcher = Watcher::new(sender).unwrap();
+        watcher.follow(dir.path());
+
+        std::fs::write(dir.path().join("new"), "new").unwrap();
+        assert!(matches!(
+            receiver.recv_timeout(Duration::from_secs(5)),
+            Ok(AppEvent::DirChanged)
+        ));
+    }
+}